@@ -0,0 +1,122 @@
+//! 控制台输出。每个 CPU 先在自己的暂存区里把一整行格式化好，
+//! 再拿控制台的锁一次写出，锁只在拷贝字节时持有，各 CPU 的行也不会互相穿插。
+//!
+//! 控制台可以是串口、帧缓冲或两者都有，由命令行里的 `console=` 决定。
+
+use alloc::string::String;
+use core::cell::RefCell;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use log::warn;
+use ostd::cpu_local;
+
+use crate::fb;
+
+/// 是否往串口写
+static SERIAL: AtomicBool = AtomicBool::new(true);
+
+cpu_local! {
+    /// 本 CPU 正在格式化的一行
+    static STAGING: RefCell<String> = RefCell::new(String::new());
+}
+
+/// 把一行（会补上换行）写到控制台
+pub(crate) fn print_line(args: fmt::Arguments) {
+    stage_line(args, |line| {
+        if SERIAL.load(Ordering::Relaxed) {
+            ostd::console::early_print(format_args!("{}", line));
+        }
+        fb::write_str(line);
+    });
+}
+
+/// 按命令行里的 `console=` 选择控制台
+///
+/// 有 `console=fb` 且找到了帧缓冲时写到帧缓冲上；串口照旧保留，
+/// 除非 `console=` 只选了 `fb`。找不到帧缓冲时仍然只用串口
+pub(crate) fn select(cmdline: &str) {
+    let consoles = || {
+        cmdline
+            .split_whitespace()
+            .filter_map(|arg| arg.strip_prefix("console="))
+            .flat_map(|value| value.split(','))
+    };
+    if !consoles().any(|console| console == "fb") {
+        return;
+    }
+
+    if !fb::init() {
+        warn!("console=fb requested but no framebuffer was found");
+        return;
+    }
+    let fb_only = consoles().all(|console| console == "fb");
+    SERIAL.store(!fb_only, Ordering::Relaxed);
+}
+
+/// 在本 CPU 的暂存区里格式化一行，再整行交给 `output`
+///
+/// 格式化期间关中断，这样中断处理里打日志也不会碰到写了一半的暂存区
+fn stage_line(args: fmt::Arguments, output: impl FnOnce(&str)) {
+    let irq_guard = ostd::irq::disable_local();
+    let staging = STAGING.get_with(&irq_guard);
+    let mut line = staging.borrow_mut();
+    line.clear();
+    // 往 `String` 里写不会失败
+    let _ = writeln!(line, "{}", args);
+    output(&line);
+}
+
+#[cfg(ktest)]
+mod tests {
+    use alloc::{format, string::String, sync::Arc, vec::Vec};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use ostd::prelude::*;
+    use ostd::sync::{LocalIrqDisabled, SpinLock};
+    use ostd::task::{Task, TaskOptions};
+
+    use super::stage_line;
+
+    #[ktest]
+    fn test_lines_do_not_interleave() {
+        const LINES: usize = 1000;
+
+        // 每个 CPU 两个任务，一起往同一个“控制台”里狂写
+        let nr_tasks = 2 * ostd::cpu::num_cpus();
+        let console = Arc::new(SpinLock::<String, LocalIrqDisabled>::new(String::new()));
+        let done = Arc::new(AtomicUsize::new(0));
+        for task in 0..nr_tasks {
+            let console = console.clone();
+            let done = done.clone();
+            TaskOptions::new(move || {
+                for i in 0..LINES {
+                    stage_line(format_args!("task {} line {}", task, i), |line| {
+                        console.lock().push_str(line)
+                    });
+                }
+                done.fetch_add(1, Ordering::Release);
+            })
+            .spawn()
+            .unwrap();
+        }
+        // 没有死锁的话，所有任务都会写完
+        while done.load(Ordering::Acquire) < nr_tasks {
+            Task::yield_now();
+        }
+
+        // 每一行都是完整的，每个任务的行按顺序出现
+        let console = console.lock();
+        let lines: Vec<&str> = console.lines().collect();
+        assert_eq!(lines.len(), nr_tasks * LINES);
+        for task in 0..nr_tasks {
+            let prefix = format!("task {} line ", task);
+            let numbers: Vec<usize> = lines
+                .iter()
+                .filter_map(|line| line.strip_prefix(prefix.as_str()))
+                .map(|number| number.parse().unwrap())
+                .collect();
+            assert!(numbers.iter().copied().eq(0..LINES));
+        }
+    }
+}
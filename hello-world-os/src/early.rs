@@ -0,0 +1,100 @@
+//! 开机早期的日志。sink 注册好之前，日志先存在这里，等 [`start_logging`] 时再补写出去，
+//! 这样很早就出错时也能看到之前的诊断信息。
+
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use log::{Level, Record};
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+use crate::{Fields, sink};
+
+/// 最多缓存的早期日志条数，满了之后丢掉最旧的
+const EARLY_RECORDS: usize = 64;
+
+/// 内核自己的早期日志缓冲区
+static EARLY: SpinLock<EarlyLog, LocalIrqDisabled> = SpinLock::new(EarlyLog::new());
+
+/// 已经开始写出了，之后的日志不用再关中断拿 [`EARLY`] 的锁
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// sink 都注册好了，补写早期日志，之后的日志直接写出
+pub fn start_logging() {
+    // 补写时不持有锁：sink 可能很慢，不能一直关着中断
+    let records = EARLY.lock().start();
+    STARTED.store(true, Ordering::Release);
+    replay(records, sink::dispatch);
+}
+
+/// 还没开始写出时缓存 `record`，返回是否缓存了
+pub(crate) fn buffer(record: &Record) -> bool {
+    if STARTED.load(Ordering::Acquire) {
+        return false;
+    }
+    EARLY.lock().buffer(record)
+}
+
+/// 把取出的早期日志按顺序交给 `write`
+///
+/// 别的 CPU 这时的日志已经直接写出，可能插到补写的前面
+pub(crate) fn replay(records: VecDeque<EarlyRecord>, mut write: impl FnMut(&Record)) {
+    for early in records {
+        write(
+            &Record::builder()
+                .args(format_args!("{}", early.message))
+                .level(early.level)
+                .module_path(early.module_path.as_deref())
+                .line(early.line)
+                .build(),
+        );
+    }
+}
+
+/// 一条缓存下来的日志，消息和结构化字段已经格式化好
+pub(crate) struct EarlyRecord {
+    level: Level,
+    module_path: Option<String>,
+    line: Option<u32>,
+    message: String,
+}
+
+/// 开始写出之前的日志
+pub(crate) struct EarlyLog {
+    records: VecDeque<EarlyRecord>,
+    started: bool,
+}
+
+impl EarlyLog {
+    pub(crate) const fn new() -> Self {
+        Self {
+            records: VecDeque::new(),
+            started: false,
+        }
+    }
+
+    /// 还没开始写出时缓存 `record`，返回是否缓存了
+    pub(crate) fn buffer(&mut self, record: &Record) -> bool {
+        if self.started {
+            return false;
+        }
+
+        if self.records.len() == EARLY_RECORDS {
+            self.records.pop_front();
+        }
+        self.records.push_back(EarlyRecord {
+            level: record.level(),
+            module_path: record.module_path().map(String::from),
+            line: record.line(),
+            message: format!("{}{}", record.args(), Fields(record)),
+        });
+        true
+    }
+
+    /// 取出缓存的日志交给 [`replay`] 补写，之后不再缓存
+    pub(crate) fn start(&mut self) -> VecDeque<EarlyRecord> {
+        self.started = true;
+        core::mem::take(&mut self.records)
+    }
+}
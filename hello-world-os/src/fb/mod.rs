@@ -0,0 +1,299 @@
+//! 帧缓冲上的文本控制台。把文字用 8x8 点阵字体画成一格一格的字符，写满一屏后向上滚动。
+//!
+//! 只认 32 位像素的帧缓冲：引导程序给出的，或者设备树里的 `simple-framebuffer`。
+//! virtio-gpu 和 ramfb 要先驱动设备才有帧缓冲，这里还不支持。
+
+mod font;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use ostd::io::IoMem;
+use ostd::mm::io_util::HasVmReaderWriter;
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+use font::{GLYPH_SIZE, glyph};
+
+/// 文字的颜色，0x00RRGGBB
+const FOREGROUND: u32 = 0x00C0_C0C0;
+/// 背景色
+const BACKGROUND: u32 = 0x0000_0000;
+
+/// 能一个个设置像素的屏幕
+pub trait Screen: Send {
+    /// 宽和高，单位像素
+    fn size(&self) -> (usize, usize);
+
+    /// 把 `(x, y)` 处的像素设成 `color`（0x00RRGGBB）
+    fn put_pixel(&mut self, x: usize, y: usize, color: u32);
+}
+
+/// 一块物理内存里的帧缓冲，每个像素 32 位
+pub struct Framebuffer {
+    mem: IoMem,
+    width: usize,
+    height: usize,
+    /// 一行像素占的字节数，可能比 `width * 4` 大
+    stride: usize,
+}
+
+impl Screen for Framebuffer {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn put_pixel(&mut self, x: usize, y: usize, color: u32) {
+        let offset = y * self.stride + x * 4;
+        self.mem.writer().skip(offset).write_once(&color).unwrap();
+    }
+}
+
+/// 解析控制序列时所处的状态
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    /// 刚读到 ESC
+    Start,
+    /// 在 `ESC [` 之后，等结束字节
+    Csi,
+}
+
+/// 屏幕上的一格格字符，光标停在下一个字符要写的位置
+pub struct TextConsole<S: Screen> {
+    screen: S,
+    cols: usize,
+    rows: usize,
+    /// 每一格当前显示的字符，滚屏时据此重画
+    cells: Vec<u8>,
+    col: usize,
+    row: usize,
+    escape: Escape,
+}
+
+impl<S: Screen> TextConsole<S> {
+    pub fn new(screen: S) -> Self {
+        let (width, height) = screen.size();
+        let (cols, rows) = (width / GLYPH_SIZE, height / GLYPH_SIZE);
+        let mut console = Self {
+            screen,
+            cols,
+            rows,
+            cells: vec![b' '; cols * rows],
+            col: 0,
+            row: 0,
+            escape: Escape::None,
+        };
+        console.redraw();
+        console
+    }
+
+    /// 写出 `s`。颜色之类的控制序列会被跳过，非 ASCII 字符显示成 `?`
+    pub fn write_str(&mut self, s: &str) {
+        for c in s.chars() {
+            match (self.escape, c) {
+                (Escape::None, '\x1b') => self.escape = Escape::Start,
+                (Escape::None, '\n') => self.newline(),
+                (Escape::None, '\r') => self.col = 0,
+                (Escape::None, c) if c.is_ascii() => self.put(c as u8),
+                (Escape::None, _) => self.put(b'?'),
+                (Escape::Start, '[') => self.escape = Escape::Csi,
+                // CSI 以 0x40 到 0x7E 之间的字节结束，其余的两字节序列到此为止
+                (Escape::Csi, '\x40'..='\x7e') | (Escape::Start, _) => self.escape = Escape::None,
+                (Escape::Csi, _) => {}
+            }
+        }
+    }
+
+    fn put(&mut self, c: u8) {
+        if self.cols == 0 || self.rows == 0 {
+            return;
+        }
+        if self.col == self.cols {
+            self.newline();
+        }
+        self.cells[self.row * self.cols + self.col] = c;
+        self.draw_cell(self.row, self.col);
+        self.col += 1;
+    }
+
+    fn newline(&mut self) {
+        self.col = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+            return;
+        }
+
+        // 在最后一行，整屏上移一行
+        self.cells.copy_within(self.cols.., 0);
+        let last_row = self.cells.len() - self.cols;
+        self.cells[last_row..].fill(b' ');
+        self.redraw();
+    }
+
+    fn redraw(&mut self) {
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                self.draw_cell(row, col);
+            }
+        }
+    }
+
+    fn draw_cell(&mut self, row: usize, col: usize) {
+        let glyph = glyph(self.cells[row * self.cols + col]);
+        for (y, bits) in glyph.iter().enumerate() {
+            for x in 0..GLYPH_SIZE {
+                let color = if bits & (1 << x) != 0 {
+                    FOREGROUND
+                } else {
+                    BACKGROUND
+                };
+                self.screen
+                    .put_pixel(col * GLYPH_SIZE + x, row * GLYPH_SIZE + y, color);
+            }
+        }
+    }
+}
+
+/// 找到的帧缓冲上的文本控制台；没找到或没启用时为空
+static FB_CONSOLE: SpinLock<Option<TextConsole<Framebuffer>>, LocalIrqDisabled> =
+    SpinLock::new(None);
+
+/// 找到帧缓冲并在上面开一个文本控制台，返回是否成功
+pub(crate) fn init() -> bool {
+    let Some(framebuffer) = find_framebuffer() else {
+        return false;
+    };
+    *FB_CONSOLE.lock() = Some(TextConsole::new(framebuffer));
+    true
+}
+
+/// 帧缓冲控制台开着的话，把 `s` 写上去
+pub(crate) fn write_str(s: &str) {
+    if let Some(console) = FB_CONSOLE.lock().as_mut() {
+        console.write_str(s);
+    }
+}
+
+/// 先看引导程序有没有给出帧缓冲，再到设备树里找 `simple-framebuffer`
+fn find_framebuffer() -> Option<Framebuffer> {
+    if let Some(arg) = ostd::boot::boot_info().framebuffer_arg {
+        if arg.bpp == 32 {
+            return map_framebuffer(arg.address, arg.width, arg.height, arg.width * 4);
+        }
+    }
+
+    #[cfg(target_arch = "riscv64")]
+    {
+        let fdt = ostd::arch::boot::DEVICE_TREE.get()?;
+        let node = fdt.find_compatible(&["simple-framebuffer"])?;
+        // 只认每像素 32 位的格式
+        let format = node.property("format")?.as_str()?;
+        if format != "a8r8g8b8" && format != "x8r8g8b8" {
+            return None;
+        }
+        let address = node.reg()?.next()?.starting_address as usize;
+        let width = node.property("width")?.as_usize()?;
+        let height = node.property("height")?.as_usize()?;
+        let stride = node.property("stride")?.as_usize()?;
+        return map_framebuffer(address, width, height, stride);
+    }
+
+    #[allow(unreachable_code)]
+    None
+}
+
+fn map_framebuffer(
+    address: usize,
+    width: usize,
+    height: usize,
+    stride: usize,
+) -> Option<Framebuffer> {
+    let size = (stride * height).next_multiple_of(ostd::mm::PAGE_SIZE);
+    let mem = IoMem::acquire(address..address + size).ok()?;
+    Some(Framebuffer {
+        mem,
+        width,
+        height,
+        stride,
+    })
+}
+
+#[cfg(ktest)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use ostd::prelude::*;
+
+    use super::font::{GLYPH_SIZE, glyph};
+    use super::{BACKGROUND, FOREGROUND, Screen, TextConsole};
+
+    /// 内存里的屏幕
+    struct MemScreen {
+        width: usize,
+        height: usize,
+        pixels: Vec<u32>,
+    }
+
+    impl MemScreen {
+        fn new(width: usize, height: usize) -> Self {
+            Self {
+                width,
+                height,
+                pixels: vec![0xFFFF_FFFF; width * height],
+            }
+        }
+    }
+
+    impl Screen for MemScreen {
+        fn size(&self) -> (usize, usize) {
+            (self.width, self.height)
+        }
+
+        fn put_pixel(&mut self, x: usize, y: usize, color: u32) {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    /// 第 `row` 行第 `col` 列的格子里画的是不是 `c`
+    fn shows(console: &TextConsole<MemScreen>, row: usize, col: usize, c: u8) -> bool {
+        let screen = &console.screen;
+        glyph(c).iter().enumerate().all(|(y, bits)| {
+            (0..GLYPH_SIZE).all(|x| {
+                let pixel =
+                    screen.pixels[(row * GLYPH_SIZE + y) * screen.width + col * GLYPH_SIZE + x];
+                let expected = if bits & (1 << x) != 0 {
+                    FOREGROUND
+                } else {
+                    BACKGROUND
+                };
+                pixel == expected
+            })
+        })
+    }
+
+    #[ktest]
+    fn test_text_console() {
+        // 4 列 2 行
+        let mut console = TextConsole::new(MemScreen::new(4 * GLYPH_SIZE, 2 * GLYPH_SIZE));
+        assert!(shows(&console, 1, 3, b' '));
+
+        // 颜色控制序列不占格子
+        console.write_str("\x1b[31mHi\x1b[0m!");
+        assert!(shows(&console, 0, 0, b'H'));
+        assert!(shows(&console, 0, 1, b'i'));
+        assert!(shows(&console, 0, 2, b'!'));
+        assert!(shows(&console, 0, 3, b' '));
+
+        // 一行写满就折到下一行，最后一行写满就上滚
+        console.write_str("\nabcde");
+        assert!(shows(&console, 0, 0, b'a'));
+        assert!(shows(&console, 0, 3, b'd'));
+        assert!(shows(&console, 1, 0, b'e'));
+        assert!(shows(&console, 1, 1, b' '));
+
+        // 不是 ASCII 的字符显示成问号
+        console.write_str("é");
+        assert!(shows(&console, 1, 1, b'?'));
+    }
+}
@@ -1,55 +1,193 @@
 #![no_std]
 #![deny(unsafe_code)]
 
-use ostd::prelude::*;
+extern crate alloc;
+
+mod console;
+mod early;
+mod fb;
+mod sink;
+
+pub use early::start_logging;
+pub use sink::{KMSG, LogSink, RingBufferSink, add_sink};
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use log::kv::{self, Key, Value, VisitSource};
 use log::{Log, Metadata, Record, error, warn, info, debug, trace, Level};
-use owo_colors::OwoColorize;
+use owo_colors::{OwoColorize, Style};
+
+/// 最详细的输出等级，低于它（更啰嗦）的日志会被丢弃
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+/// 设置日志记录器输出的最详细等级
+pub fn set_max_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+    // 让 `log` 宏在等级不够时直接跳过，不再格式化参数
+    log::set_max_level(level.to_level_filter());
+}
+
+/// 为真时控制台日志不带颜色，比如输出被重定向到文件时
+static NO_COLOR: AtomicBool = AtomicBool::new(false);
+
+/// 打开或关闭控制台日志的颜色
+pub fn set_no_color(no_color: bool) {
+    NO_COLOR.store(no_color, Ordering::Relaxed);
+}
+
+/// 内核命令行里有没有非空的 `NO_COLOR`（见 <https://no-color.org>）
+fn no_color_requested(cmdline: &str) -> bool {
+    cmdline.split_whitespace().any(|arg| {
+        arg == "NO_COLOR" || arg.strip_prefix("NO_COLOR=").is_some_and(|value| !value.is_empty())
+    })
+}
+
+/// 开机以来经过的毫秒数；计时器还没初始化时返回 `None`
+fn uptime_ms() -> Option<u64> {
+    let freq = ostd::arch::tsc_freq();
+    if freq == 0 {
+        return None;
+    }
+    Some(ostd::arch::read_tsc() / (freq / 1000).max(1))
+}
+
+/// 日志行首的时间戳，形如 `[    0.123]`，宽度固定以便各列对齐
+struct Timestamp(Option<u64>);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(ms) => write!(f, "[{:>5}.{:03}]", ms / 1000, ms % 1000),
+            None => write!(f, "[{:>5}.???]", "?"),
+        }
+    }
+}
+
+/// 日志的来源，形如 `mm::fault:42 `；没有模块路径时整段省略
+struct Source<'a, 'b>(&'a Record<'b>);
+
+impl fmt::Display for Source<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(module_path) = self.0.module_path() else {
+            return Ok(());
+        };
+        match self.0.line() {
+            Some(line) => write!(f, "{}:{} ", module_path, line),
+            None => write!(f, "{} ", module_path),
+        }
+    }
+}
+
+/// 日志附带的结构化字段，形如 ` pid=3 errno=2`，接在消息后面方便机器解析
+///
+/// 字段用 `log` 的 key-value 语法写，比如 `info!(pid = 3, syscall = "write"; "syscall done")`
+struct Fields<'a, 'b>(&'a Record<'b>);
+
+impl fmt::Display for Fields<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        struct Writer<'a, 'f>(&'a mut fmt::Formatter<'f>);
+
+        impl<'kvs> VisitSource<'kvs> for Writer<'_, '_> {
+            fn visit_pair(
+                &mut self,
+                key: Key<'kvs>,
+                value: Value<'kvs>,
+            ) -> core::result::Result<(), kv::Error> {
+                write!(self.0, " {}={}", key, value).map_err(|_| kv::Error::msg("fmt error"))
+            }
+        }
+
+        self.0
+            .key_values()
+            .visit(&mut Writer(f))
+            .map_err(|_| fmt::Error)
+    }
+}
 
 struct ColoredLogger;
 
 impl Log for ColoredLogger {
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
     }
 
     fn log(&self, record: &Record) {
-        if self.enabled(record.metadata()) {
-            match record.level() {
-                Level::Error => {
-                    println!("[{}] {}", "ERROR".red(), record.args());
-                }
-                Level::Warn => {
-                    println!("[{}] {}", "WARN".yellow(), record.args());
-                }
-                Level::Info => {
-                    println!("[{}] {}", "INFO".cyan(), record.args());
-                }
-                Level::Debug => {
-                    println!("[{}] {}", "DEBUG".blue(), record.args());
-                }
-                Level::Trace => {
-                    println!("[{}] {}", "TRACE".bright_black(), record.args());
-                }
-            }
+        if self.enabled(record.metadata()) && !early::buffer(record) {
+            sink::dispatch(record);
         }
     }
 
     fn flush(&self) {}
 }
 
+/// 控制台上的一行日志（不含换行），关掉颜色时只剩纯文本
+struct ConsoleLine<'a, 'b>(&'a Record<'b>);
+
+impl fmt::Display for ConsoleLine<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let record = self.0;
+        let (tag, color) = match record.level() {
+            Level::Error => ("ERROR", Style::new().red()),
+            Level::Warn => ("WARN", Style::new().yellow()),
+            Level::Info => ("INFO", Style::new().cyan()),
+            Level::Debug => ("DEBUG", Style::new().blue()),
+            Level::Trace => ("TRACE", Style::new().bright_black()),
+        };
+        // 什么样式都没有的 `Style` 不输出转义序列
+        let (tag_style, source_style) = if NO_COLOR.load(Ordering::Relaxed) {
+            (Style::new(), Style::new())
+        } else {
+            (color, Style::new().dimmed())
+        };
+        write!(
+            f,
+            "{} [{}] {}{}{}",
+            Timestamp(uptime_ms()),
+            tag.style(tag_style),
+            Source(record).style(source_style),
+            record.args(),
+            Fields(record)
+        )
+    }
+}
+
+/// 把日志按等级着色后打印到控制台
+struct ConsoleSink;
+
+impl LogSink for ConsoleSink {
+    fn log(&self, record: &Record) {
+        console::print_line(format_args!("{}", ConsoleLine(record)));
+    }
+}
+
+static CONSOLE: ConsoleSink = ConsoleSink;
+
 static LOGGER: ColoredLogger = ColoredLogger;
 
 #[ostd::main]
 fn kernel_main() {
-    // 注册自定义彩色日志记录器
+    // 先注入自定义彩色日志记录器，sink 注册好之前的日志会先缓存起来
     ostd::logger::inject_logger(&LOGGER);
 
-    println!("Hello world from guest kernel!");
+    // 内核看不出串口那头是不是终端，只能靠命令行里的 NO_COLOR 关掉颜色
+    let cmdline = &ostd::boot::boot_info().kernel_cmdline;
+    info!("Kernel command line: {}", cmdline);
+    set_no_color(no_color_requested(cmdline));
+    console::select(cmdline);
+
+    // 日志写到控制台，同时留一份在 KMSG 里，然后补写上面缓存的日志
+    add_sink(&CONSOLE);
+    add_sink(&KMSG);
+    start_logging();
+
+    console::print_line(format_args!("Hello world from guest kernel!"));
 
     // 练习 2.1: 使用 5 种日志等级输出内容
     error!("This is an error message (Red)");
     warn!("This is a warning message (Yellow)");
     info!("This is an info message (Cyan)");
+    info!(pid = 1, syscall = "write", errno = 0; "This is an info message with fields");
     debug!("This is a debug message (Blue)");
     trace!("This is a trace message (Grey)");
 
@@ -61,6 +199,7 @@ fn kernel_main() {
 #[cfg(ktest)]
 mod tests {
     use super::*;
+    use ostd::prelude::*;
 
     #[ktest]
     fn test_example() {
@@ -73,4 +212,168 @@ mod tests {
         info!("Running kernel mode unit test...");
         assert!(true);
     }
+
+    #[ktest]
+    fn test_timestamp_format() {
+        use alloc::format;
+
+        assert_eq!(format!("{}", Timestamp(Some(123))), "[    0.123]");
+        assert_eq!(format!("{}", Timestamp(Some(61_005))), "[   61.005]");
+        // 计时器未就绪时占位，宽度不变
+        assert_eq!(format!("{}", Timestamp(None)), "[    ?.???]");
+    }
+
+    #[ktest]
+    fn test_record_source() {
+        use alloc::format;
+
+        let args = format_args!("page fault");
+        let record = Record::builder()
+            .args(args)
+            .level(Level::Info)
+            .module_path(Some("mm::fault"))
+            .line(Some(42))
+            .build();
+        assert!(format!("{}", Source(&record)).contains("mm::fault:42"));
+
+        // 没有模块路径就什么也不输出
+        let record = Record::builder().args(args).line(Some(42)).build();
+        assert_eq!(format!("{}", Source(&record)), "");
+    }
+
+    #[ktest]
+    fn test_max_level_filter() {
+        let record = |level| Metadata::builder().level(level).target("test").build();
+
+        set_max_level(Level::Warn);
+        assert!(LOGGER.enabled(&record(Level::Warn)));
+        assert!(!LOGGER.enabled(&record(Level::Debug)));
+
+        set_max_level(Level::Info);
+        assert!(LOGGER.enabled(&record(Level::Info)));
+    }
+
+    #[ktest]
+    fn test_ring_buffer_sink() {
+        use alloc::format;
+
+        static RING: RingBufferSink = RingBufferSink::new(3);
+        add_sink(&RING);
+        start_logging();
+
+        set_max_level(Level::Info);
+        for i in 0..5 {
+            LOGGER.log(
+                &Record::builder()
+                    .args(format_args!("line {}", i))
+                    .level(Level::Info)
+                    .build(),
+            );
+        }
+        // 被过滤掉的日志不会进缓冲区
+        LOGGER.log(
+            &Record::builder()
+                .args(format_args!("hidden"))
+                .level(Level::Trace)
+                .build(),
+        );
+
+        let lines = RING.lines();
+        assert_eq!(lines.len(), 3);
+        for (line, i) in lines.iter().zip(2..) {
+            assert!(line.ends_with(&format!("[INFO] line {}", i)), "{}", line);
+        }
+    }
+
+    #[ktest]
+    fn test_no_color() {
+        use alloc::format;
+
+        let args = format_args!("plain text");
+        let record = Record::builder()
+            .args(args)
+            .level(Level::Info)
+            .module_path(Some("mm::fault"))
+            .build();
+
+        set_no_color(true);
+        let line = format!("{}", ConsoleLine(&record));
+        assert!(!line.contains('\x1b'), "{:?}", line);
+        assert!(line.contains("[INFO] mm::fault plain text"), "{:?}", line);
+
+        set_no_color(false);
+        assert!(format!("{}", ConsoleLine(&record)).contains('\x1b'));
+
+        assert!(no_color_requested("console=ttyS0 NO_COLOR"));
+        assert!(no_color_requested("NO_COLOR=1"));
+        assert!(!no_color_requested("NO_COLOR="));
+        assert!(!no_color_requested("quiet"));
+    }
+
+    #[ktest]
+    fn test_record_fields() {
+        use alloc::format;
+
+        let fields = [
+            ("pid", Value::from(3)),
+            ("syscall", Value::from("write")),
+            ("errno", Value::from(-9)),
+        ];
+        let record = Record::builder()
+            .args(format_args!("syscall failed"))
+            .level(Level::Warn)
+            .key_values(&fields)
+            .build();
+        assert_eq!(format!("{}", Fields(&record)), " pid=3 syscall=write errno=-9");
+
+        // 字段跟在消息后面，控制台和环形缓冲区都一样
+        set_no_color(true);
+        let line = format!("{}", ConsoleLine(&record));
+        set_no_color(false);
+        assert!(line.ends_with("syscall failed pid=3 syscall=write errno=-9"), "{:?}", line);
+
+        let ring = RingBufferSink::new(1);
+        ring.log(&record);
+        assert!(ring.lines()[0].ends_with("syscall failed pid=3 syscall=write errno=-9"));
+
+        // 没有字段就什么也不输出
+        let record = Record::builder().args(format_args!("plain")).build();
+        assert_eq!(format!("{}", Fields(&record)), "");
+    }
+
+    #[ktest]
+    fn test_early_log() {
+        use alloc::{format, vec::Vec};
+
+        use early::{EarlyLog, replay};
+
+        let fields = [("pid", log::kv::Value::from(1))];
+        let mut early = EarlyLog::new();
+        let record = Record::builder()
+            .args(format_args!("too early"))
+            .level(Level::Warn)
+            .module_path(Some("boot"))
+            .line(Some(7))
+            .key_values(&fields)
+            .build();
+        assert!(early.buffer(&record));
+
+        // 开始写出之前的日志原样补写，包括来源和字段
+        let mut written = Vec::new();
+        replay(early.start(), |record| {
+            written.push(format!(
+                "[{}] {}{}{}",
+                record.level(),
+                Source(record),
+                record.args(),
+                Fields(record)
+            ))
+        });
+        assert_eq!(written, ["[WARN] boot:7 too early pid=1"]);
+
+        // 开始之后就不再缓存
+        let line = Record::builder().args(format_args!("late")).build();
+        assert!(!early.buffer(&line));
+        assert!(early.start().is_empty());
+    }
 }
@@ -0,0 +1,142 @@
+//! 日志的去处。`ColoredLogger` 过滤完等级后，把每条日志交给所有注册过的 sink。
+
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use log::Record;
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+use crate::{Fields, Source, Timestamp, uptime_ms};
+
+/// 日志的一个去处，比如控制台或内存里的环形缓冲区
+pub trait LogSink: Sync {
+    /// 写出一条已经通过等级过滤的日志
+    fn log(&self, record: &Record);
+}
+
+/// 最多能注册的 sink 个数
+const MAX_SINKS: usize = 8;
+
+/// 已注册的 sink，按注册顺序写出。只会往后追加，所以写日志时拷一份出来就能放锁
+static SINKS: SpinLock<[Option<&'static dyn LogSink>; MAX_SINKS], LocalIrqDisabled> =
+    SpinLock::new([None; MAX_SINKS]);
+
+/// 注册一个 sink，之后的日志也会写到它那里
+pub fn add_sink(sink: &'static dyn LogSink) {
+    let mut sinks = SINKS.lock();
+    let slot = sinks.iter_mut().find(|slot| slot.is_none());
+    *slot.expect("too many log sinks") = Some(sink);
+}
+
+/// 把一条日志交给所有 sink
+///
+/// 写的时候不拿着 `SINKS` 的锁，慢的 sink（比如帧缓冲）不会拖住别的 CPU
+pub(crate) fn dispatch(record: &Record) {
+    let sinks = *SINKS.lock();
+    for sink in sinks.iter().flatten() {
+        sink.log(record);
+    }
+}
+
+/// `KMSG` 保留的日志行数
+const KMSG_LINES: usize = 256;
+
+/// 内核最近的日志，程序崩溃后可以回头查看
+pub static KMSG: RingBufferSink = RingBufferSink::new(KMSG_LINES);
+
+/// 只保留最近 `capacity` 行日志的 sink，满了之后新的一行挤掉最旧的一行
+pub struct RingBufferSink {
+    lines: SpinLock<VecDeque<String>, LocalIrqDisabled>,
+    capacity: usize,
+}
+
+impl RingBufferSink {
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            lines: SpinLock::new(VecDeque::new()),
+            capacity,
+        }
+    }
+
+    /// 缓冲区里的日志，从旧到新，不带颜色
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().iter().cloned().collect()
+    }
+}
+
+impl LogSink for RingBufferSink {
+    fn log(&self, record: &Record) {
+        let line = format!(
+            "{} [{}] {}{}{}",
+            Timestamp(uptime_ms()),
+            record.level(),
+            Source(record),
+            record.args(),
+            Fields(record)
+        );
+
+        let mut lines = self.lines.lock();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+#[cfg(ktest)]
+mod tests {
+    use alloc::{format, sync::Arc, vec::Vec};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use log::{Level, info};
+    use ostd::prelude::*;
+    use ostd::task::{Task, TaskOptions};
+
+    use super::{RingBufferSink, add_sink};
+    use crate::{LOGGER, set_max_level, start_logging};
+
+    #[ktest]
+    fn test_log_from_all_cpus() {
+        const LINES: usize = 64;
+        static RING: RingBufferSink = RingBufferSink::new(4096);
+
+        ostd::logger::inject_logger(&LOGGER);
+        add_sink(&RING);
+        start_logging();
+        set_max_level(Level::Info);
+
+        // 每个 CPU 两个任务，一起通过 `info!` 写日志
+        let nr_tasks = 2 * ostd::cpu::num_cpus();
+        assert!(nr_tasks * LINES <= 4096);
+        let done = Arc::new(AtomicUsize::new(0));
+        for task in 0..nr_tasks {
+            let done = done.clone();
+            TaskOptions::new(move || {
+                for i in 0..LINES {
+                    info!("sink test {} line {}", task, i);
+                }
+                done.fetch_add(1, Ordering::Release);
+            })
+            .spawn()
+            .unwrap();
+        }
+        // 没有死锁的话，所有任务都会写完
+        while done.load(Ordering::Acquire) < nr_tasks {
+            Task::yield_now();
+        }
+
+        // 每个任务的每一行都完整地按顺序到了 sink 里
+        let lines = RING.lines();
+        for task in 0..nr_tasks {
+            let prefix = format!("sink test {} line ", task);
+            let numbers: Vec<usize> = lines
+                .iter()
+                .filter_map(|line| line.split_once(prefix.as_str()))
+                .map(|(_, number)| number.parse().unwrap())
+                .collect();
+            assert!(numbers.iter().copied().eq(0..LINES), "{:?}", numbers);
+        }
+    }
+}
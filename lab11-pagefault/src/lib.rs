@@ -6,12 +6,15 @@
 pub mod console;
 mod error;
 mod fs;
+#[cfg(ktest)]
+mod ktest_util;
 mod logger;
 mod mm;
 pub mod process;
 pub mod progs;
 mod sched;
 pub mod syscall;
+mod timer;
 
 extern crate alloc;
 
@@ -20,6 +23,7 @@ pub fn main() {
     logger::init();
     progs::init();
     sched::init();
+    timer::init();
     fs::init();
 
     let process = process::Process::new(progs::lookup_progs("init_proc").unwrap());
@@ -5,7 +5,10 @@ use riscv::register::scause::Exception;
 use crate::{
     mm::{
         VmMapping,
-        fault::{DefaultPageFaultHandler, PageFaultContext, PageFaultHandler},
+        fault::{
+            AllocationPageFaultHandler, CowPageFaultHandler, DefaultPageFaultHandler,
+            PageFaultContext, PageFaultHandler,
+        },
     },
     process::Process,
 };
@@ -19,6 +22,8 @@ pub struct VmArea {
     perms: PageFlags,
     mappings: LinkedList<VmMapping>,
     fault_handler: Arc<dyn PageFaultHandler>,
+    /// The lowest address a stack area may grow down to, a page at a time.
+    grows_down_to: Option<Vaddr>,
 }
 
 impl VmArea {
@@ -29,6 +34,7 @@ impl VmArea {
             perms,
             mappings: LinkedList::new(),
             fault_handler: Arc::new(DefaultPageFaultHandler),
+            grows_down_to: None,
         }
     }
 
@@ -44,6 +50,39 @@ impl VmArea {
             perms,
             mappings: LinkedList::new(),
             fault_handler,
+            grows_down_to: None,
+        }
+    }
+
+    /// Creates a stack area, with its pages allocated on fault like anonymous
+    /// memory, that a fault on the page just below it extends down to as far
+    /// as `lowest_vaddr`.
+    pub fn new_grows_down(
+        base_vaddr: Vaddr,
+        pages: usize,
+        perms: PageFlags,
+        lowest_vaddr: Vaddr,
+    ) -> Self {
+        Self {
+            base_vaddr,
+            pages,
+            perms,
+            mappings: LinkedList::new(),
+            fault_handler: Arc::new(AllocationPageFaultHandler),
+            grows_down_to: Some(lowest_vaddr),
+        }
+    }
+
+    /// An area over the same pages, with the same perms, fault handler and
+    /// growth, but nothing mapped yet.
+    pub fn empty_copy(&self) -> Self {
+        Self {
+            base_vaddr: self.base_vaddr,
+            pages: self.pages,
+            perms: self.perms,
+            mappings: LinkedList::new(),
+            fault_handler: self.fault_handler.clone(),
+            grows_down_to: self.grows_down_to,
         }
     }
 
@@ -53,12 +92,21 @@ impl VmArea {
         vaddr: Vaddr,
         fault: Exception,
     ) -> crate::error::Result<()> {
+        if self.is_growth_fault(vaddr) {
+            self.base_vaddr -= PAGE_SIZE;
+            self.pages += 1;
+        }
         debug_assert!(
             self.contains_vaddr(vaddr),
             "VmArea does not contain vaddr {:x?}",
             vaddr
         );
-        self.fault_handler.handle_page_fault(PageFaultContext::new(
+        let fault_handler: &dyn PageFaultHandler = if self.is_cow_fault(vaddr, fault) {
+            &CowPageFaultHandler
+        } else {
+            self.fault_handler.as_ref()
+        };
+        fault_handler.handle_page_fault(PageFaultContext::new(
             self.perms,
             &mut self.mappings,
             process,
@@ -67,6 +115,30 @@ impl VmArea {
         ))
     }
 
+    /// Whether `fault` is a write to a page that is only read-only because it
+    /// is shared copy-on-write.
+    pub fn is_cow_fault(&self, vaddr: Vaddr, fault: Exception) -> bool {
+        fault == Exception::StorePageFault
+            && self.perms.contains(PageFlags::W)
+            && self.mappings.iter().any(|mapping| {
+                mapping.contains_vaddr(vaddr) && !mapping.perms().contains(PageFlags::W)
+            })
+    }
+
+    /// Whether `vaddr` is on the page just below a stack area that may still
+    /// grow over it.
+    ///
+    /// A fault any further down is not the stack's, and kills the process.
+    pub fn is_growth_fault(&self, vaddr: Vaddr) -> bool {
+        let Some(lowest_vaddr) = self.grows_down_to else {
+            return false;
+        };
+        let Some(page) = self.base_vaddr.checked_sub(PAGE_SIZE) else {
+            return false;
+        };
+        page >= lowest_vaddr && (page..self.base_vaddr).contains(&vaddr)
+    }
+
     pub fn page_fault_handler(&self) -> &Arc<dyn PageFaultHandler> {
         &self.fault_handler
     }
@@ -95,14 +167,77 @@ impl VmArea {
         self.pages
     }
 
+    /// Changes the perms of the area and its pages.
+    ///
+    /// Pages still shared copy-on-write stay read-only, so that the first write
+    /// to one still takes the fault that copies it.
     pub fn set_perms(&mut self, perms: PageFlags) {
         self.perms = perms;
         for mapping in self.mappings.iter_mut() {
-            mapping.set_perms(perms);
+            if mapping.is_shared() {
+                mapping.set_perms(perms - PageFlags::W);
+            } else {
+                mapping.set_perms(perms);
+            }
         }
     }
 
     pub fn contains_vaddr(&self, vaddr: Vaddr) -> bool {
-        vaddr >= self.base_vaddr && vaddr < self.base_vaddr + self.pages * PAGE_SIZE
+        vaddr >= self.base_vaddr && vaddr < self.end_vaddr()
+    }
+
+    pub fn end_vaddr(&self) -> Vaddr {
+        self.base_vaddr + self.pages * PAGE_SIZE
+    }
+
+    /// Whether `next` starts where this area ends, with the same perms and fault
+    /// handler, so that the two could be one area.
+    pub fn is_mergeable_with(&self, next: &VmArea) -> bool {
+        self.end_vaddr() == next.base_vaddr
+            && next.grows_down_to.is_none()
+            && self.perms == next.perms
+            && Arc::ptr_eq(&self.fault_handler, &next.fault_handler)
+    }
+
+    /// Takes in `next`, which must be [mergeable](Self::is_mergeable_with) with
+    /// this area, along with its mappings.
+    pub fn merge(&mut self, mut next: VmArea) {
+        debug_assert!(self.is_mergeable_with(&next));
+        self.pages += next.pages;
+        self.mappings.append(&mut next.mappings);
+    }
+
+    /// Splits the area in two at the page-aligned `vaddr`, keeping the part
+    /// below it and returning the rest.
+    ///
+    /// Both halves keep the permissions and the fault handler, so a file
+    /// mapping still reads from the same offsets.
+    pub fn split_off(&mut self, vaddr: Vaddr) -> VmArea {
+        debug_assert!(vaddr % PAGE_SIZE == 0 && vaddr > self.base_vaddr);
+        debug_assert!(self.contains_vaddr(vaddr));
+
+        let mut lower = LinkedList::new();
+        let mut upper = LinkedList::new();
+        while let Some(mapping) = self.mappings.pop_front() {
+            if mapping.base_vaddr() < vaddr {
+                lower.push_back(mapping);
+            } else {
+                upper.push_back(mapping);
+            }
+        }
+        self.mappings = lower;
+
+        let pages = (vaddr - self.base_vaddr) / PAGE_SIZE;
+        // Only the bottom of a stack grows.
+        let upper = VmArea {
+            base_vaddr: vaddr,
+            pages: self.pages - pages,
+            perms: self.perms,
+            mappings: upper,
+            fault_handler: self.fault_handler.clone(),
+            grows_down_to: None,
+        };
+        self.pages = pages;
+        upper
     }
 }
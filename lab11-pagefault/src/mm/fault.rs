@@ -10,7 +10,10 @@ use alloc::{collections::linked_list::LinkedList, sync::Arc};
 use log::error;
 use ostd::{
     irq::disable_local,
-    mm::{CachePolicy, FrameAllocOptions, PAGE_SIZE, PageFlags, PageProperty, Vaddr},
+    mm::{
+        CachePolicy, FrameAllocOptions, PAGE_SIZE, PageFlags, PageProperty, Vaddr,
+        io_util::HasVmReaderWriter,
+    },
 };
 use riscv::register::scause::Exception;
 
@@ -83,3 +86,45 @@ impl PageFaultHandler for AllocationPageFaultHandler {
         Ok(())
     }
 }
+
+/// Handles a write to a page shared copy-on-write since a fork.
+///
+/// The writer gets a copy of the page to itself, unless no one else shares it
+/// any more, in which case it simply becomes writable again.
+#[derive(Debug)]
+pub struct CowPageFaultHandler;
+
+impl PageFaultHandler for CowPageFaultHandler {
+    fn handle_page_fault<'a>(&self, context: PageFaultContext<'a>) -> Result<()> {
+        let align_down_vaddr = context.vaddr.align_down(PAGE_SIZE);
+        let mapping = context
+            .mappings
+            .iter_mut()
+            .find(|mapping| mapping.contains_vaddr(align_down_vaddr))
+            .ok_or(Error::new(Errno::EFAULT))?;
+
+        if mapping.is_shared() {
+            let frame = FrameAllocOptions::new()
+                .zeroed(false)
+                .alloc_frame()
+                .map_err(|_| Error::new(Errno::ENOMEM))?;
+            frame.writer().write(&mut mapping.frame().reader());
+            mapping.set_frame(frame);
+        }
+        mapping.set_perms(context.perms);
+
+        let memory_space = context.process.memory_space();
+        let guard = disable_local();
+        let mut cursor_mut = memory_space
+            .vm_space()
+            .cursor_mut(&guard, &(align_down_vaddr..align_down_vaddr + PAGE_SIZE))
+            .unwrap();
+        // Mapping over the read-only page flushes it from the TLB.
+        cursor_mut.map(
+            mapping.frame().clone().into(),
+            PageProperty::new_user(context.perms, CachePolicy::Writeback),
+        );
+
+        Ok(())
+    }
+}
@@ -1,9 +1,14 @@
+use alloc::sync::Arc;
 use ostd::mm::{Frame, PAGE_SIZE, PageFlags, Vaddr};
 
+/// A page mapped to a frame.
+///
+/// Cloning a mapping shares its frame, as a forked process does until one side
+/// writes to it.
 #[derive(Debug, Clone)]
 pub struct VmMapping {
     base_vaddr: Vaddr,
-    frame: Frame<()>,
+    frame: Arc<Frame<()>>,
     perms: PageFlags,
 }
 
@@ -11,7 +16,7 @@ impl VmMapping {
     pub fn new(base_vaddr: Vaddr, perms: PageFlags, frame: Frame<()>) -> Self {
         Self {
             base_vaddr,
-            frame,
+            frame: Arc::new(frame),
             perms,
         }
     }
@@ -39,4 +44,13 @@ impl VmMapping {
     pub fn frame(&self) -> &Frame<()> {
         &self.frame
     }
+
+    /// Whether another mapping, in this or another process, shares the frame.
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.frame) > 1
+    }
+
+    pub fn set_frame(&mut self, frame: Frame<()>) {
+        self.frame = Arc::new(frame);
+    }
 }
@@ -2,17 +2,21 @@ pub mod area;
 pub mod fault;
 pub mod mapping;
 
-use alloc::{collections::linked_list::LinkedList, sync::Arc};
+use core::ops::Range;
+
+use align_ext::AlignExt;
+use alloc::{collections::linked_list::LinkedList, sync::Arc, vec::Vec};
 pub use mapping::VmMapping;
 use ostd::{
     arch::cpu::context::CpuExceptionInfo,
     mm::{
-        CachePolicy, FrameAllocOptions, MAX_USERSPACE_VADDR, PAGE_SIZE, PageProperty, Segment,
-        VmSpace, io_util::HasVmReaderWriter,
+        CachePolicy, FrameAllocOptions, MAX_USERSPACE_VADDR, PAGE_SIZE, PageFlags, PageProperty,
+        Segment, Vaddr, VmSpace, tlb::TlbFlushOp,
     },
     sync::SpinLock,
     task::disable_preempt,
 };
+use riscv::register::scause::Exception;
 
 use crate::{mm::area::VmArea, process::Process};
 
@@ -35,19 +39,56 @@ pub fn page_fault_handler(
     let page_fault_addr = cpu_exception.page_fault_addr;
 
     let mut areas = memory_space.areas.lock();
-    for area in areas.iter_mut() {
-        if !area.contains_vaddr(page_fault_addr) {
+    // The page below a stack may only be taken by the stack if no other area
+    // has it.
+    let index = areas
+        .iter()
+        .position(|area| area.contains_vaddr(page_fault_addr))
+        .or_else(|| {
+            areas
+                .iter()
+                .position(|area| area.is_growth_fault(page_fault_addr))
+        })
+        .ok_or(())?;
+
+    areas
+        .iter_mut()
+        .nth(index)
+        .unwrap()
+        .handle_page_fault(process, page_fault_addr, cpu_exception.cpu_exception())
+        .map_err(|_| ())
+}
+
+/// Takes the write faults that the kernel would run into writing the `len`
+/// bytes from `vaddr` on the user's behalf.
+///
+/// A page fault in the kernel cannot be recovered from, so a page shared
+/// copy-on-write has to be copied before the kernel writes to it.
+pub fn prepare_user_write(
+    process: &Arc<Process>,
+    vaddr: Vaddr,
+    len: usize,
+) -> crate::error::Result<()> {
+    let end = vaddr
+        .checked_add(len)
+        .ok_or(crate::error::Error::new(crate::error::Errno::EFAULT))?;
+    let mut areas = process.memory_space().areas.lock();
+    for page in (vaddr.align_down(PAGE_SIZE)..end).step_by(PAGE_SIZE) {
+        let Some(area) = areas.iter_mut().find(|area| area.contains_vaddr(page)) else {
             continue;
+        };
+        if area.is_cow_fault(page, Exception::StorePageFault) {
+            area.handle_page_fault(process, page, Exception::StorePageFault)?;
         }
-
-        return area
-            .handle_page_fault(process, page_fault_addr, cpu_exception.cpu_exception())
-            .map_err(|_| ());
     }
 
-    Err(())
+    Ok(())
 }
 
+/// The lowest address mmap picks by itself, which leaves the heap at
+/// `0x1000_0000` room to grow.
+pub const MMAP_MIN_ADDR: Vaddr = 0x2000_0000;
+
 pub struct MemorySpace {
     vm_space: Arc<VmSpace>,
     areas: SpinLock<LinkedList<VmArea>>,
@@ -96,45 +137,41 @@ impl MemorySpace {
         frames
     }
 
-    /// Duplicate self with new phyiscal frames. Also, this will copy the data inside each frame.
+    /// Duplicate self, sharing the physical frames copy-on-write.
+    ///
+    /// Both sides map the writable pages read-only from now on, and the first
+    /// to write to one gets a copy of it (see [`fault::CowPageFaultHandler`]).
     pub fn duplicate(&self) -> Self {
         let new_memory_space = MemorySpace::new();
         let mut new_mappings = new_memory_space.areas.lock();
 
         let guard = disable_preempt();
-        let areas = self.areas.lock();
-        for area in areas.iter() {
-            let mut new_area = VmArea::new_with_handler(
-                area.base_vaddr(),
-                area.pages(),
-                area.perms(),
-                area.page_fault_handler().clone(),
-            );
-
-            let old_mappings = area.mappings().iter().map(|mapping| mapping);
-            for old_mapping in old_mappings {
-                let new_frame = FrameAllocOptions::new().alloc_frame().unwrap();
-
-                // Copy data from old frame to new frame
-                new_frame.writer().write(&mut old_mapping.frame().reader());
-
-                let mut cursor_mut = new_memory_space
-                    .vm_space
-                    .cursor_mut(
-                        &guard,
-                        &(old_mapping.base_vaddr()..(old_mapping.base_vaddr() + PAGE_SIZE)),
-                    )
-                    .unwrap();
-                // Map new frame
-                cursor_mut.map(
-                    new_frame.clone().into(),
-                    PageProperty::new_user(new_area.perms(), CachePolicy::Writeback),
-                );
-
-                let mapping = VmMapping::new(old_mapping.base_vaddr(), new_area.perms(), new_frame);
-                new_area.add_mapping(mapping);
+        let mut areas = self.areas.lock();
+        for area in areas.iter_mut() {
+            let mut new_area = area.empty_copy();
+
+            let range = area.base_vaddr()..(area.base_vaddr() + area.pages() * PAGE_SIZE);
+            let mut cursor_mut = self.vm_space.cursor_mut(&guard, &range).unwrap();
+            let mut new_cursor_mut = new_memory_space
+                .vm_space
+                .cursor_mut(&guard, &range)
+                .unwrap();
+            for mapping in area.mappings_mut().iter_mut() {
+                let writable = mapping.perms().contains(PageFlags::W);
+                mapping.remove_perm(PageFlags::W);
+                let property = PageProperty::new_user(mapping.perms(), CachePolicy::Writeback);
+
+                // Take write access away from the parent too.
+                if writable {
+                    cursor_mut.jump(mapping.base_vaddr()).unwrap();
+                    cursor_mut.map(mapping.frame().clone().into(), property);
+                }
+                new_cursor_mut.jump(mapping.base_vaddr()).unwrap();
+                new_cursor_mut.map(mapping.frame().clone().into(), property);
+
+                new_area.add_mapping(mapping.clone());
             }
-            
+
             new_mappings.push_back(new_area);
         }
         drop(new_mappings);
@@ -145,38 +182,158 @@ impl MemorySpace {
         &self.vm_space
     }
 
+    /// Finds the lowest page-aligned gap of `len` bytes between the areas, at or
+    /// above [`MMAP_MIN_ADDR`], for an mmap without an address.
+    pub fn find_free_range(&self, len: usize) -> crate::error::Result<Vaddr> {
+        let len = len.align_up(PAGE_SIZE);
+        let areas = self.areas.lock();
+        let mut ranges: Vec<_> = areas
+            .iter()
+            .map(|area| area.base_vaddr()..area.end_vaddr())
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+
+        let mut start = MMAP_MIN_ADDR;
+        for range in ranges {
+            if range.end <= start {
+                continue;
+            }
+            if range.start >= start.saturating_add(len) {
+                break;
+            }
+            start = range.end;
+        }
+
+        match start.checked_add(len) {
+            Some(end) if end <= MAX_USERSPACE_VADDR => Ok(start),
+            _ => Err(crate::error::Error::new(crate::error::Errno::ENOMEM)),
+        }
+    }
+
+    /// Whether no area overlaps the `len` bytes from `vaddr`.
+    pub fn is_free(&self, vaddr: Vaddr, len: usize) -> bool {
+        let range = vaddr..vaddr.saturating_add(len);
+        !self
+            .areas
+            .lock()
+            .iter()
+            .any(|area| area.base_vaddr() < range.end && area.end_vaddr() > range.start)
+    }
+
+    /// Adds `area` at its exact address, unmapping whatever is there first.
+    ///
+    /// Unless `replace` is set, a range that is already (partly) mapped is left
+    /// alone and this fails with `EEXIST`. An empty area, or one that reaches
+    /// past user space, fails with `EINVAL`.
+    pub fn add_area_fixed(&self, area: VmArea, replace: bool) -> crate::error::Result<()> {
+        let range = area.base_vaddr()..area.end_vaddr();
+        if range.is_empty() || range.end > MAX_USERSPACE_VADDR {
+            return Err(crate::error::Error::new(crate::error::Errno::EINVAL));
+        }
+        let mut areas = self.areas.lock();
+        let overlaps = areas
+            .iter()
+            .any(|area| area.base_vaddr() < range.end && area.end_vaddr() > range.start);
+        if overlaps {
+            if !replace {
+                return Err(crate::error::Error::new(crate::error::Errno::EEXIST));
+            }
+            self.unmap_areas(&mut areas, range)?;
+        }
+        areas.push_back(area);
+        Ok(())
+    }
+
+    fn unmap_areas(
+        &self,
+        areas: &mut LinkedList<VmArea>,
+        range: Range<Vaddr>,
+    ) -> crate::error::Result<()> {
+        let guard = disable_preempt();
+        let mut cursor_mut = self
+            .vm_space
+            .cursor_mut(&guard, &range)
+            .map_err(|_| crate::error::Error::new(crate::error::Errno::EINVAL))?;
+        cursor_mut.unmap(range.len());
+
+        // Dropping the areas drops their mappings' frames.
+        split_in_range(areas, range, |_| None);
+        coalesce(areas);
+        Ok(())
+    }
+
+    /// Merges the adjacent areas that only differ in where they are, to keep
+    /// the list short for the page fault handler.
+    pub fn coalesce(&self) {
+        coalesce(&mut self.areas.lock());
+    }
+
+    /// Unmaps the pages in `[vaddr, vaddr + len)` and forgets the areas
+    /// there, splitting those that only partly overlap the range.
+    ///
+    /// Touching an unmapped page afterwards is an unhandled page fault.
+    pub fn unmap(&self, vaddr: Vaddr, len: usize) -> crate::error::Result<()> {
+        let end = len
+            .checked_add(PAGE_SIZE - 1)
+            .and_then(|len| vaddr.checked_add(len.align_down(PAGE_SIZE)))
+            .filter(|&end| end <= MAX_USERSPACE_VADDR)
+            .ok_or(crate::error::Error::new(crate::error::Errno::EINVAL))?;
+
+        let mut areas = self.areas.lock();
+        self.unmap_areas(&mut areas, vaddr..end)
+    }
+
     pub fn protect(&self, vaddr: Vaddr, len: usize, perms: PageFlags) -> crate::error::Result<()> {
+        let end = len
+            .checked_add(PAGE_SIZE - 1)
+            .and_then(|len| vaddr.checked_add(len.align_down(PAGE_SIZE)))
+            .ok_or(crate::error::Error::new(crate::error::Errno::EINVAL))?;
+        let range = vaddr..end;
         let guard = disable_preempt();
         let mut areas = self.areas.lock();
 
         // 1. Update the page table
         let mut cursor = self
             .vm_space
-            .cursor_mut(&guard, &(vaddr..vaddr + len))
-            .unwrap();
-        // RISC-V Sv48: R/W/X/U/V flags are part of the PTE. 
-        // A/D bits should ideally be preserved if possible, but cursor.protect 
-        // usually replaces the flags.
-        cursor.protect(PageProperty::new_user(perms, CachePolicy::Writeback));
+            .cursor_mut(&guard, &range)
+            .map_err(|_| crate::error::Error::new(crate::error::Errno::EINVAL))?;
+        // RISC-V Sv48: R/W/X/U/V flags are part of the PTE. Only those are
+        // replaced, the A/D bits are kept.
+        while let Some(protected) =
+            cursor.protect_next(range.end - cursor.virt_addr(), |flags, _| {
+                *flags = (*flags - PageFlags::RWX) | perms;
+            })
+        {
+            if protected.end >= range.end {
+                break;
+            }
+        }
 
-        // 2. Update the area metadata
-        for area in areas.iter_mut() {
-            let area_start = area.base_vaddr();
-            let area_end = area_start + area.pages() * PAGE_SIZE;
-            let range_start = vaddr;
-            let range_end = vaddr + len;
-
-            // Check for overlap
-            if area_start < range_end && area_end > range_start {
-                // In a complete implementation, we should split the area if the range 
-                // covers only a part of it. For this lab, we update the perms.
-                area.set_perms(perms);
+        // 2. Update the area metadata, splitting off the parts of the areas
+        // outside the range so that they keep their perms.
+        split_in_range(&mut areas, range.clone(), |mut area| {
+            area.set_perms(perms);
+            Some(area)
+        });
+        coalesce(&mut areas);
+
+        // Frames shared copy-on-write since a fork must not become writable,
+        // or the write would show through in the other process too.
+        for mapping in areas.iter().flat_map(|area| area.mappings()) {
+            if range.contains(&mapping.base_vaddr()) && mapping.is_shared() {
+                cursor.jump(mapping.base_vaddr()).unwrap();
+                cursor.map(
+                    mapping.frame().clone().into(),
+                    PageProperty::new_user(mapping.perms(), CachePolicy::Writeback),
+                );
             }
         }
 
         // 3. Flush TLB
-        // sfence.vma is handled by ostd when cursor is dropped or during mapping changes.
-        // However, we can explicitly call it if needed.
+        cursor
+            .flusher()
+            .issue_tlb_flush(TlbFlushOp::for_range(range));
+        cursor.flusher().dispatch_tlb_flush();
 
         Ok(())
     }
@@ -185,14 +342,14 @@ impl MemorySpace {
         // In a real OS, we should check if the range [vaddr, vaddr + len) is valid and mapped with R perm.
         // For Lab 10, we'll leverage ostd's VmSpace reader.
         self.vm_space
-            .reader(vaddr..vaddr + len)
+            .reader(vaddr, len)
             .map_err(|_| crate::error::Error::new(crate::error::Errno::EFAULT))
     }
 
     pub fn writer(&self, vaddr: Vaddr, len: usize) -> crate::error::Result<ostd::mm::VmWriter> {
         // In a real OS, we should check if the range [vaddr, vaddr + len) is valid and mapped with W perm.
         self.vm_space
-            .writer(vaddr..vaddr + len)
+            .writer(vaddr, len)
             .map_err(|_| crate::error::Error::new(crate::error::Errno::EFAULT))
     }
 
@@ -207,8 +364,339 @@ impl MemorySpace {
     }
 }
 
+/// Splits the areas that straddle the ends of `range`, and replaces each area
+/// inside the range with what `f` makes of it.
+fn split_in_range(
+    areas: &mut LinkedList<VmArea>,
+    range: Range<Vaddr>,
+    mut f: impl FnMut(VmArea) -> Option<VmArea>,
+) {
+    for mut area in core::mem::take(areas) {
+        if area.end_vaddr() <= range.start || area.base_vaddr() >= range.end {
+            areas.push_back(area);
+            continue;
+        }
+        if area.base_vaddr() < range.start {
+            let upper = area.split_off(range.start);
+            areas.push_back(area);
+            area = upper;
+        }
+        let upper = (area.end_vaddr() > range.end).then(|| area.split_off(range.end));
+        areas.extend(f(area));
+        areas.extend(upper);
+    }
+}
+
+/// Sorts `areas` by address, merging each into the one before it where
+/// [`VmArea::is_mergeable_with`] allows.
+fn coalesce(areas: &mut LinkedList<VmArea>) {
+    let mut sorted: Vec<VmArea> = core::mem::take(areas).into_iter().collect();
+    sorted.sort_by_key(|area| area.base_vaddr());
+    for area in sorted {
+        match areas.back_mut() {
+            Some(last) if last.is_mergeable_with(&area) => last.merge(area),
+            _ => areas.push_back(area),
+        }
+    }
+}
+
 impl Default for MemorySpace {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::mm::{HasPaddr, MAX_USERSPACE_VADDR, PAGE_SIZE, PageFlags};
+    use ostd::prelude::ktest;
+    use riscv::register::scause::Exception;
+
+    use super::{MMAP_MIN_ADDR, MemorySpace, area::VmArea, prepare_user_write};
+    use crate::error::Errno;
+    use crate::ktest_util::new_process;
+    use crate::mm::fault::{AllocationPageFaultHandler, PageFaultHandler};
+    use crate::process::Process;
+
+    #[ktest]
+    fn child_write_leaves_the_parent_page_alone() {
+        let parent = new_process("hello_world");
+        let vaddr = 0x1000_0000;
+        parent
+            .memory_space()
+            .map(VmArea::new(vaddr, 1, PageFlags::RW));
+        let write = |process: &Process, value: u64| {
+            let vm_space = process.memory_space().vm_space();
+            vm_space.activate();
+            vm_space
+                .writer(vaddr, 8)
+                .unwrap()
+                .write_val(&value)
+                .unwrap();
+        };
+        let read = |process: &Process| -> u64 {
+            let vm_space = process.memory_space().vm_space();
+            vm_space.activate();
+            vm_space.reader(vaddr, 8).unwrap().read_val().unwrap()
+        };
+        write(&parent, 0x1234);
+
+        let child = parent.fork(&UserContext::default());
+        assert_eq!(read(&child), 0x1234);
+
+        // The child's write fault gets it a copy of its own.
+        prepare_user_write(&child, vaddr, 8).unwrap();
+        write(&child, 0x5678);
+        assert_eq!(read(&child), 0x5678);
+        assert_eq!(read(&parent), 0x1234);
+
+        // The parent is the only one left with the old frame, so it gets it
+        // back writable, without a copy.
+        let frame = |process: &Process| {
+            let areas = process.memory_space().areas.lock();
+            let mapping = areas
+                .iter()
+                .flat_map(|area| area.mappings())
+                .find(|mapping| mapping.contains_vaddr(vaddr))
+                .unwrap();
+            mapping.frame().paddr()
+        };
+        let old_frame = frame(&parent);
+        prepare_user_write(&parent, vaddr + PAGE_SIZE - 8, 8).unwrap();
+        assert_eq!(frame(&parent), old_frame);
+        write(&parent, 0x9abc);
+        assert_eq!(read(&child), 0x5678);
+    }
+
+    #[ktest]
+    fn protect_after_fork_keeps_the_pages_shared_copy_on_write() {
+        let parent = new_process("hello_world");
+        let vaddr = 0x1000_0000;
+        parent
+            .memory_space()
+            .map(VmArea::new(vaddr, 1, PageFlags::RW));
+        let write = |process: &Process, value: u64| {
+            let vm_space = process.memory_space().vm_space();
+            vm_space.activate();
+            vm_space
+                .writer(vaddr, 8)
+                .unwrap()
+                .write_val(&value)
+                .unwrap();
+        };
+        let read = |process: &Process| -> u64 {
+            let vm_space = process.memory_space().vm_space();
+            vm_space.activate();
+            vm_space.reader(vaddr, 8).unwrap().read_val().unwrap()
+        };
+        write(&parent, 0x1234);
+
+        let child = parent.fork(&UserContext::default());
+        child
+            .memory_space()
+            .protect(vaddr, PAGE_SIZE, PageFlags::RW)
+            .unwrap();
+        {
+            let areas = child.memory_space().areas.lock();
+            let area = areas
+                .iter()
+                .find(|area| area.contains_vaddr(vaddr))
+                .unwrap();
+            assert_eq!(area.perms(), PageFlags::RW);
+            assert!(area.is_cow_fault(vaddr, Exception::StorePageFault));
+        }
+
+        prepare_user_write(&child, vaddr, 8).unwrap();
+        write(&child, 0x5678);
+        assert_eq!(read(&child), 0x5678);
+        assert_eq!(read(&parent), 0x1234);
+    }
+
+    #[ktest]
+    fn unmap_splits_the_area_around_the_hole() {
+        let memory_space = MemorySpace::new();
+        let vaddr = 0x1000_0000;
+        memory_space.map(VmArea::new(vaddr, 4, PageFlags::RW));
+
+        memory_space
+            .unmap(vaddr + PAGE_SIZE, 2 * PAGE_SIZE)
+            .unwrap();
+
+        let areas = memory_space.areas.lock();
+        let ranges: alloc::vec::Vec<_> = areas
+            .iter()
+            .map(|area| (area.base_vaddr(), area.pages(), area.mappings().len()))
+            .collect();
+        assert_eq!(ranges, [(vaddr, 1, 1), (vaddr + 3 * PAGE_SIZE, 1, 1)]);
+
+        // The hole is gone from the page table too.
+        let guard = ostd::task::disable_preempt();
+        let hole = vaddr + PAGE_SIZE..vaddr + 3 * PAGE_SIZE;
+        let mut cursor = memory_space.vm_space().cursor(&guard, &hole).unwrap();
+        for page in hole.step_by(PAGE_SIZE) {
+            cursor.jump(page).unwrap();
+            assert!(cursor.query().unwrap().1.is_none());
+        }
+    }
+
+    #[ktest]
+    fn protect_splits_off_the_rest_of_the_area() {
+        let vaddr = 0x1000_0000;
+        for protected in 0..3 {
+            let memory_space = MemorySpace::new();
+            memory_space.map(VmArea::new(vaddr, 3, PageFlags::RW));
+
+            let page = vaddr + protected * PAGE_SIZE;
+            memory_space.protect(page, PAGE_SIZE, PageFlags::R).unwrap();
+
+            let areas = memory_space.areas.lock();
+            assert_eq!(areas.iter().map(|area| area.pages()).sum::<usize>(), 3);
+            for area in areas.iter() {
+                let expected = if area.contains_vaddr(page) {
+                    assert_eq!(area.pages(), 1);
+                    PageFlags::R
+                } else {
+                    PageFlags::RW
+                };
+                assert_eq!(area.perms(), expected);
+                for mapping in area.mappings() {
+                    assert!(area.contains_vaddr(mapping.base_vaddr()));
+                    assert_eq!(mapping.perms(), expected);
+                }
+            }
+            // The first and last pages leave one other area, the middle page two.
+            let expected_areas = if protected == 1 { 3 } else { 2 };
+            assert_eq!(areas.len(), expected_areas);
+        }
+    }
+
+    #[ktest]
+    fn adjacent_areas_collapse_into_one() {
+        let memory_space = MemorySpace::new();
+        let vaddr = 0x1000_0000;
+        let handler: Arc<dyn PageFaultHandler> = Arc::new(AllocationPageFaultHandler);
+        // Out of order, to be sorted first.
+        for page in [2, 0, 1] {
+            memory_space.add_area(VmArea::new_with_handler(
+                vaddr + page * PAGE_SIZE,
+                1,
+                PageFlags::RWX,
+                handler.clone(),
+            ));
+        }
+        // Adjacent, but faults in another way.
+        memory_space.add_area(VmArea::new(vaddr + 3 * PAGE_SIZE, 1, PageFlags::RWX));
+
+        memory_space.coalesce();
+
+        let areas = memory_space.areas.lock();
+        let ranges: alloc::vec::Vec<_> = areas
+            .iter()
+            .map(|area| (area.base_vaddr(), area.pages()))
+            .collect();
+        assert_eq!(ranges, [(vaddr, 3), (vaddr + 3 * PAGE_SIZE, 1)]);
+    }
+
+    #[ktest]
+    fn fixed_area_replaces_what_is_there() {
+        let memory_space = MemorySpace::new();
+        let vaddr = 0x1000_0000;
+        memory_space.map(VmArea::new(vaddr, 3, PageFlags::RW));
+        let fixed = || VmArea::new(vaddr + PAGE_SIZE, 1, PageFlags::R);
+        let ranges = || {
+            let mut ranges: alloc::vec::Vec<_> = memory_space
+                .areas
+                .lock()
+                .iter()
+                .map(|area| (area.base_vaddr(), area.pages(), area.perms()))
+                .collect();
+            ranges.sort_by_key(|&(base_vaddr, ..)| base_vaddr);
+            ranges
+        };
+
+        let err = memory_space.add_area_fixed(fixed(), false).err().unwrap();
+        assert_eq!(err.code, Errno::EEXIST);
+        assert_eq!(ranges(), [(vaddr, 3, PageFlags::RW)]);
+
+        memory_space.add_area_fixed(fixed(), true).unwrap();
+        assert_eq!(
+            ranges(),
+            [
+                (vaddr, 1, PageFlags::RW),
+                (vaddr + PAGE_SIZE, 1, PageFlags::R),
+                (vaddr + 2 * PAGE_SIZE, 1, PageFlags::RW),
+            ]
+        );
+        // The new area faults its page in; the old frame is gone.
+        let guard = ostd::task::disable_preempt();
+        let page = vaddr + PAGE_SIZE..vaddr + 2 * PAGE_SIZE;
+        let mut cursor = memory_space.vm_space().cursor(&guard, &page).unwrap();
+        assert!(cursor.query().unwrap().1.is_none());
+    }
+
+    #[ktest]
+    fn fixed_area_must_be_in_user_space() {
+        let memory_space = MemorySpace::new();
+        let areas = [
+            VmArea::new(0x1000_0000, 0, PageFlags::RW),
+            VmArea::new(MAX_USERSPACE_VADDR, 1, PageFlags::RW),
+            VmArea::new(MAX_USERSPACE_VADDR - PAGE_SIZE, 2, PageFlags::RW),
+        ];
+        for area in areas {
+            let err = memory_space.add_area_fixed(area, true).err().unwrap();
+            assert_eq!(err.code, Errno::EINVAL);
+        }
+        assert!(memory_space.areas.lock().is_empty());
+    }
+
+    #[ktest]
+    fn free_range_is_the_lowest_gap_that_fits() {
+        let memory_space = MemorySpace::new();
+        let base = MMAP_MIN_ADDR;
+        // Taken: [0, 1) and [3, 4), in pages from `base`; a gap of two pages between.
+        memory_space.add_area(VmArea::new(base + 3 * PAGE_SIZE, 1, PageFlags::RW));
+        memory_space.add_area(VmArea::new(base, 1, PageFlags::RW));
+
+        let find = |pages: usize| memory_space.find_free_range(pages * PAGE_SIZE).unwrap();
+        assert_eq!(find(1), base + PAGE_SIZE);
+        assert_eq!(find(2), base + PAGE_SIZE);
+        assert_eq!(find(3), base + 4 * PAGE_SIZE);
+        assert!(memory_space.is_free(find(3), 3 * PAGE_SIZE));
+        assert!(!memory_space.is_free(base + PAGE_SIZE, 3 * PAGE_SIZE));
+
+        let err = memory_space.find_free_range(usize::MAX / 2).err().unwrap();
+        assert_eq!(err.code, Errno::ENOMEM);
+    }
+
+    #[ktest]
+    fn stack_grows_a_page_at_a_time_down_to_its_limit() {
+        let process = new_process("hello_world");
+        let mut areas = process.memory_space().areas.lock();
+        let stack = areas
+            .iter_mut()
+            .find(|area| {
+                area.base_vaddr()
+                    .checked_sub(1)
+                    .is_some_and(|vaddr| area.is_growth_fault(vaddr))
+            })
+            .unwrap();
+        let base = stack.base_vaddr();
+
+        // Two pages down is too far.
+        assert!(!stack.is_growth_fault(base - PAGE_SIZE - 8));
+        stack
+            .handle_page_fault(&process, base - 8, Exception::StorePageFault)
+            .unwrap();
+        assert_eq!(stack.base_vaddr(), base - PAGE_SIZE);
+        assert!(stack.contains_vaddr(base - PAGE_SIZE));
+        assert!(stack.is_growth_fault(base - PAGE_SIZE - 8));
+
+        // Nor does it grow past its limit.
+        let vaddr = 0x1000_0000;
+        let stack = VmArea::new_grows_down(vaddr, 1, PageFlags::RW, vaddr);
+        assert!(!stack.is_growth_fault(vaddr - 8));
+    }
+}
@@ -1,5 +1,4 @@
 use align_ext::AlignExt;
-use alloc::sync::Arc;
 use log::debug;
 use ostd::{
     arch::cpu::context::UserContext,
@@ -8,10 +7,13 @@ use ostd::{
 };
 
 use crate::{
-    mm::{MemorySpace, area::VmArea, fault::AllocationPageFaultHandler},
+    mm::{MemorySpace, area::VmArea},
     process::USER_STACK_SIZE,
 };
 
+/// The size of the user stack before it grows.
+const INITIAL_STACK_SIZE: usize = 16 * PAGE_SIZE;
+
 pub fn load_user_space(program: &[u8], memory_space: &MemorySpace) -> UserContext {
     let mut user_context = UserContext::default();
     parse_elf(program, &memory_space, &mut user_context);
@@ -79,14 +81,15 @@ fn parse_elf(input: &[u8], memory_space: &MemorySpace, user_cpu_state: &mut User
     }
 
     // Second, init the user stack with addr: 0x40_0000_0000 - 10 * PAGE_SIZE.
-    let stack_low = 0x40_0000_0000 - 10 * PAGE_SIZE - USER_STACK_SIZE;
-    memory_space.add_area(VmArea::new_with_handler(
-        stack_low,
-        USER_STACK_SIZE / PAGE_SIZE,
+    // It starts small, and grows down on faults up to `USER_STACK_SIZE`.
+    let stack_top = 0x40_0000_0000 - 10 * PAGE_SIZE;
+    memory_space.add_area(VmArea::new_grows_down(
+        stack_top - INITIAL_STACK_SIZE,
+        INITIAL_STACK_SIZE / PAGE_SIZE,
         PageFlags::RW,
-        Arc::new(AllocationPageFaultHandler),
+        stack_top - USER_STACK_SIZE,
     ));
-    user_cpu_state.set_stack_pointer(0x40_0000_0000 - 10 * PAGE_SIZE - 32);
+    user_cpu_state.set_stack_pointer(stack_top - 32);
     user_cpu_state.set_instruction_pointer(header.pt2.entry_point() as usize);
 
     // Third, map the 0 address
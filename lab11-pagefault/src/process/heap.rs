@@ -1,12 +1,11 @@
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 use align_ext::AlignExt;
-use ostd::mm::{PAGE_SIZE, PageFlags, Vaddr};
+use ostd::mm::{MAX_USERSPACE_VADDR, PAGE_SIZE, PageFlags, Vaddr};
 
+use crate::mm::MemorySpace;
 use crate::mm::area::VmArea;
 
-use super::current_process;
-
 #[derive(Debug)]
 pub struct UserHeap {
     base: Vaddr,
@@ -31,28 +30,44 @@ impl UserHeap {
         self.base
     }
 
-    pub fn brk(&self, new_end: Option<Vaddr>) -> Option<Vaddr> {
-        match new_end {
-            Some(new_end) => {
-                let process = current_process();
-                let current_end = self.current_end.load(Ordering::Acquire);
-                if new_end <= current_end {
-                    return Some(current_end);
-                }
-                let old_vaddr = current_end.align_up(PAGE_SIZE);
-                let new_vaddr = new_end.align_up(PAGE_SIZE);
-
-                if new_vaddr > old_vaddr {
-                    let pages = (new_vaddr - old_vaddr) / PAGE_SIZE;
-                    process
-                        .memory_space()
-                        .map(VmArea::new(old_vaddr, pages, PageFlags::RW));
-                }
-                self.current_end.store(new_end, Ordering::Release);
-                Some(new_end)
+    /// Moves the end of the heap to `new_end`, or just returns it if `new_end`
+    /// is `None`.
+    ///
+    /// Growing maps new pages, unless they would run into another area; shrinking
+    /// unmaps the pages past the new end. A break that can't move, like one
+    /// past user space, stays where it is, and that is what's returned.
+    pub fn brk(&self, new_end: Option<Vaddr>, memory_space: &MemorySpace) -> Vaddr {
+        let current_end = self.current_end.load(Ordering::Acquire);
+        let Some(new_end) = new_end else {
+            return current_end;
+        };
+        if new_end < self.base {
+            return current_end;
+        }
+        let Some(new_vaddr) = new_end
+            .checked_add(PAGE_SIZE - 1)
+            .map(|end| end.align_down(PAGE_SIZE))
+            .filter(|&end| end <= MAX_USERSPACE_VADDR)
+        else {
+            return current_end;
+        };
+
+        let old_vaddr = current_end.align_up(PAGE_SIZE);
+        if new_vaddr > old_vaddr {
+            let len = new_vaddr - old_vaddr;
+            if !memory_space.is_free(old_vaddr, len) {
+                return current_end;
             }
-            None => Some(self.current_end.load(Ordering::Relaxed)),
+            memory_space.map(VmArea::new(old_vaddr, len / PAGE_SIZE, PageFlags::RW));
+        } else if new_vaddr < old_vaddr
+            && memory_space
+                .unmap(new_vaddr, old_vaddr - new_vaddr)
+                .is_err()
+        {
+            return current_end;
         }
+        self.current_end.store(new_end, Ordering::Release);
+        new_end
     }
 }
 
@@ -71,3 +86,62 @@ impl Clone for UserHeap {
         }
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::mm::{MAX_USERSPACE_VADDR, PAGE_SIZE};
+    use ostd::prelude::ktest;
+
+    use super::UserHeap;
+    use crate::mm::MemorySpace;
+
+    #[ktest]
+    fn shrinking_the_heap_unmaps_the_freed_pages() {
+        let memory_space = MemorySpace::new();
+        let heap = UserHeap::new();
+        let base = heap.base();
+
+        let end = base + 3 * PAGE_SIZE;
+        assert_eq!(heap.brk(Some(end), &memory_space), end);
+        let vm_space = memory_space.vm_space();
+        vm_space.activate();
+        vm_space
+            .writer(base, 8)
+            .unwrap()
+            .write_val(&0xdead_beef_u64)
+            .unwrap();
+
+        let end = base + PAGE_SIZE;
+        assert_eq!(heap.brk(Some(end), &memory_space), end);
+        assert_eq!(heap.brk(None, &memory_space), end);
+        assert_eq!(heap.current_end(), end);
+
+        // The freed pages belong to no area, so touching them is a fatal
+        // page fault; the page that is left keeps its data.
+        assert!(memory_space.is_free(end, 2 * PAGE_SIZE));
+        let guard = ostd::task::disable_preempt();
+        let freed = end..end + 2 * PAGE_SIZE;
+        let mut cursor = vm_space.cursor(&guard, &freed).unwrap();
+        for page in freed.step_by(PAGE_SIZE) {
+            cursor.jump(page).unwrap();
+            assert!(cursor.query().unwrap().1.is_none());
+        }
+        let sentinel: u64 = vm_space.reader(base, 8).unwrap().read_val().unwrap();
+        assert_eq!(sentinel, 0xdead_beef);
+    }
+
+    #[ktest]
+    fn a_break_past_user_space_stays_put() {
+        let memory_space = MemorySpace::new();
+        let heap = UserHeap::new();
+        let base = heap.base();
+
+        assert_eq!(
+            heap.brk(Some(MAX_USERSPACE_VADDR + PAGE_SIZE), &memory_space),
+            base
+        );
+        assert_eq!(heap.brk(Some(usize::MAX), &memory_space), base);
+        assert_eq!(heap.current_end(), base);
+        assert!(memory_space.is_free(base, PAGE_SIZE));
+    }
+}
@@ -12,8 +12,14 @@ const INIT_PROC: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/init_proc");
 const MMAP_ANON_TEST: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/mmap_anon_test");
+const MMAP_NO_HINT: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/mmap_no_hint");
 const MMAP_TEST: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/mmap_test");
+const MUNMAP: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/munmap");
+const NANOSLEEP: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/nanosleep");
 const PIPE: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/pipe");
 const RAMFS: &[u8] =
@@ -26,6 +32,8 @@ const RR_TEST: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/rr_test");
 const SHELL: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/shell");
+const STACK_GROWTH: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/stack_growth");
 const WAIT: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/wait");
 
@@ -39,13 +47,17 @@ pub fn init() {
         user_progs.insert("hello_world", HELLO_WORLD);
         user_progs.insert("init_proc", INIT_PROC);
         user_progs.insert("mmap_anon_test", MMAP_ANON_TEST);
+        user_progs.insert("mmap_no_hint", MMAP_NO_HINT);
         user_progs.insert("mmap_test", MMAP_TEST);
+        user_progs.insert("munmap", MUNMAP);
+        user_progs.insert("nanosleep", NANOSLEEP);
         user_progs.insert("pipe", PIPE);
         user_progs.insert("ramfs", RAMFS);
         user_progs.insert("read_stdin", READ_STDIN);
         user_progs.insert("reparent", REPARENT);
         user_progs.insert("rr_test", RR_TEST);
         user_progs.insert("shell", SHELL);
+        user_progs.insert("stack_growth", STACK_GROWTH);
         user_progs.insert("wait", WAIT);
         user_progs
     });
@@ -4,7 +4,9 @@ use align_ext::AlignExt;
 use alloc::sync::Arc;
 use ostd::irq::disable_local;
 use ostd::mm::io_util::HasVmReaderWriter;
-use ostd::mm::{CachePolicy, FrameAllocOptions, PAGE_SIZE, PageFlags, PageProperty, Vaddr};
+use ostd::mm::{
+    CachePolicy, FrameAllocOptions, MAX_USERSPACE_VADDR, PAGE_SIZE, PageFlags, PageProperty, Vaddr,
+};
 
 use crate::error::{Errno, Error, Result};
 use crate::fs::Inode;
@@ -55,7 +57,28 @@ pub fn sys_mmap(
     let mmap_flags = MMapFlags::from_bits_truncate(flags);
     let page_flags = PageFlags::from_bits_truncate(perms as _);
     let memory_space = current_process.memory_space();
-    let pages = length.align_up(PAGE_SIZE as _) as usize / PAGE_SIZE;
+    if length == 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let len = (length as usize)
+        .checked_add(PAGE_SIZE - 1)
+        .ok_or(Error::new(Errno::ENOMEM))?
+        .align_down(PAGE_SIZE);
+    let pages = len / PAGE_SIZE;
+
+    // Without MAP_FIXED the address is only a hint, taken if it is free.
+    let fixed = mmap_flags.intersects(MMapFlags::MAP_FIXED | MMapFlags::MAP_FIXED_NOREPLACE);
+    let in_user_space = (vaddr as usize)
+        .checked_add(len)
+        .is_some_and(|end| end <= MAX_USERSPACE_VADDR);
+    if fixed && !in_user_space {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let vaddr = if fixed || (vaddr != 0 && in_user_space && memory_space.is_free(vaddr as _, len)) {
+        vaddr
+    } else {
+        memory_space.find_free_range(len)? as u64
+    };
 
     let handler: Arc<dyn PageFaultHandler> = if mmap_flags.contains(MMapFlags::MAP_ANONYMOUS) {
         Arc::new(AllocationPageFaultHandler)
@@ -74,12 +97,13 @@ pub fn sys_mmap(
         })
     };
 
-    memory_space.add_area(VmArea::new_with_handler(
-        vaddr as _,
-        pages,
-        page_flags,
-        handler,
-    ));
+    let area = VmArea::new_with_handler(vaddr as _, pages, page_flags, handler);
+    if fixed {
+        let replace = !mmap_flags.contains(MMapFlags::MAP_FIXED_NOREPLACE);
+        memory_space.add_area_fixed(area, replace)?;
+    } else {
+        memory_space.add_area(area);
+    }
 
     Ok(SyscallReturn(vaddr as _))
 }
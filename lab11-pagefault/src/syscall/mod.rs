@@ -4,6 +4,8 @@ mod exec;
 mod exit;
 mod mmap;
 mod mprotect;
+mod munmap;
+mod nanosleep;
 mod open;
 mod pipe;
 mod prlimit;
@@ -14,10 +16,11 @@ mod wait4;
 mod write;
 
 use alloc::sync::Arc;
-use log::{debug, info};
+use log::{debug, info, warn};
 use ostd::arch::cpu::context::UserContext;
 use ostd::arch::qemu::exit_qemu;
 use ostd::task::Task;
+use syscall_names::syscall_name;
 
 use crate::error::{Errno, Error, Result};
 use crate::process::Process;
@@ -27,6 +30,8 @@ use crate::syscall::exec::sys_execve;
 use crate::syscall::exit::sys_exit;
 use crate::syscall::mmap::sys_mmap;
 use crate::syscall::mprotect::sys_mprotect;
+use crate::syscall::munmap::sys_munmap;
+use crate::syscall::nanosleep::sys_nanosleep;
 use crate::syscall::pipe::sys_pipe2;
 use crate::syscall::prlimit::sys_prlimit64;
 use crate::syscall::read::sys_read;
@@ -46,6 +51,7 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
     const SYS_WRITEV: usize = 66;
     const SYS_EXIT: usize = 93;
     const SYS_EXIT_GROUP: usize = 94;
+    const SYS_NANOSLEEP: usize = 101;
 
     const SYS_CLOCK_GETTIME: usize = 113;
     const SYS_SCHED_YIELD: usize = 124;
@@ -54,6 +60,7 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
     const SYS_GETPID: usize = 172;
     const SYS_GETPPID: usize = 173;
     const SYS_BRK: usize = 214;
+    const SYS_MUNMAP: usize = 215;
     const SYS_CLONE: usize = 220;
     const SYS_EXECVE: usize = 221;
     const SYS_MMAP: usize = 222;
@@ -83,6 +90,7 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
         SYS_WRITEV => sys_writev(args[0] as _, args[1] as _, args[2] as _, current_process),
         SYS_NEWUNAME => sys_uname(args[0] as _, current_process),
         SYS_BRK => sys_brk(args[0] as _, current_process),
+        SYS_MUNMAP => sys_munmap(args[0] as _, args[1] as _, current_process),
         SYS_MPROTECT => sys_mprotect(args[0] as _, args[1] as _, args[2] as _, current_process),
         SYS_GETPID => Ok(SyscallReturn(current_process.pid() as _)),
         SYS_GETPPID => {
@@ -123,6 +131,7 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
             args[3] as _,
             current_process,
         ),
+        SYS_NANOSLEEP => sys_nanosleep(args[0] as _, args[1] as _, current_process),
         SYS_CLOCK_GETTIME => sys_clock_gettime(args[0] as _, args[1] as _, current_process),
         SYS_REBOOT => exit_qemu(ostd::arch::qemu::QemuExitCode::Success),
         SYS_READ => sys_read(args[0] as _, args[1] as _, args[2] as _, current_process),
@@ -149,7 +158,15 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
             args[5] as _,
             current_process,
         ),
-        _ => Err(Error::new(Errno::ENOSYS)),
+        _ => {
+            warn!(
+                "[pid: {}] unimplemented syscall num: {} ({})",
+                current_process.pid(),
+                user_context.a7(),
+                syscall_name(user_context.a7()).unwrap_or("unknown")
+            );
+            Err(Error::new(Errno::ENOSYS))
+        }
     };
 
     match ret {
@@ -165,3 +182,27 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
         }
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::prelude::ktest;
+    use syscall_names::syscall_name;
+
+    use crate::error::Errno;
+    use crate::ktest_util::new_process;
+
+    #[ktest]
+    fn unimplemented_syscall_returns_enosys() {
+        let process = new_process("hello_world");
+
+        // Files can't be listed here, so `getdents64` hits the fallback arm.
+        assert_eq!(syscall_name(61), Some("getdents64"));
+        let mut user_context = UserContext::default();
+        user_context.set_a7(61);
+        super::handle_syscall(&mut user_context, &process);
+
+        assert_eq!(user_context.a0() as isize, -(Errno::ENOSYS as isize));
+        assert!(process.exit_code().is_none());
+    }
+}
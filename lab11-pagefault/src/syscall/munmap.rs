@@ -0,0 +1,24 @@
+use align_ext::AlignExt;
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::{PAGE_SIZE, Vaddr};
+
+use crate::error::{Errno, Error, Result};
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+pub fn sys_munmap(
+    addr: Vaddr,
+    len: usize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!("[SYS_MUNMAP] addr: {:#x}, len: {:#x}", addr, len);
+
+    if addr.align_down(PAGE_SIZE) != addr || len == 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    current_process.memory_space().unmap(addr, len)?;
+
+    Ok(SyscallReturn(0))
+}
@@ -0,0 +1,36 @@
+use core::time::Duration;
+
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use super::SyscallReturn;
+use super::time::timespec_t;
+use crate::error::{Errno, Error, Result};
+use crate::process::Process;
+
+pub fn sys_nanosleep(
+    req_addr: Vaddr,
+    rem_addr: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let req: timespec_t = current_process
+        .memory_space()
+        .vm_space()
+        .reader(req_addr, size_of::<timespec_t>())
+        .map_err(|_| Error::new(Errno::EFAULT))?
+        .read_val()
+        .map_err(|_| Error::new(Errno::EFAULT))?;
+    debug!("[SYS_NANOSLEEP] req: {:?}, rem_addr: {:#x}", req, rem_addr);
+
+    if req.sec < 0 || !(0..1_000_000_000).contains(&req.nsec) {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    // Nothing interrupts a sleep yet, so it always runs to the end and `rem`
+    // is left alone. A signal would end it early with `EINTR`, and the time
+    // left written to `rem`.
+    crate::timer::sleep(Duration::new(req.sec as u64, req.nsec as u32));
+
+    Ok(SyscallReturn(0))
+}
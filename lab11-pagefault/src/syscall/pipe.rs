@@ -32,6 +32,7 @@ pub fn sys_pipe2(
     let read_fd = file_table.insert(FileEntry::new(reader));
     let write_fd = file_table.insert(FileEntry::new(writer));
 
+    crate::mm::prepare_user_write(current_process, pipe_address, size_of::<PipeFds>())?;
     let vm_space = current_process.memory_space().vm_space();
     let mut writer = vm_space.writer(pipe_address, size_of::<PipeFds>()).unwrap();
 
@@ -38,6 +38,11 @@ pub fn sys_prlimit64(
     };
 
     if old_limit != 0 {
+        crate::mm::prepare_user_write(
+            current_process,
+            old_limit,
+            core::mem::size_of::<RLimit64>(),
+        )?;
         current_process
             .memory_space()
             .vm_space()
@@ -20,6 +20,7 @@ pub fn sys_read(
         fd, user_buf_addr, buf_len
     );
 
+    crate::mm::prepare_user_write(current_process, user_buf_addr, buf_len)?;
     let writer = current_process
         .memory_space()
         .vm_space()
@@ -18,6 +18,7 @@ pub fn sys_clock_gettime(
 
     let clock = ClockId::try_from(clockid).unwrap();
 
+    crate::mm::prepare_user_write(current_process, timespec_addr, size_of::<timespec_t>())?;
     let vm_space = current_process.memory_space().vm_space();
     let mut writer = vm_space
         .writer(timespec_addr, size_of::<timespec_t>())
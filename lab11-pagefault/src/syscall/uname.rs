@@ -26,6 +26,11 @@ pub fn sys_uname(utsname_addr: usize, current_process: &Arc<Process>) -> Result<
     uts.version[..6].copy_from_slice(b"#1 SMP");
     uts.machine[..7].copy_from_slice(b"riscv64");
 
+    crate::mm::prepare_user_write(
+        current_process,
+        utsname_addr,
+        core::mem::size_of::<UtsName>(),
+    )?;
     current_process
         .memory_space()
         .vm_space()
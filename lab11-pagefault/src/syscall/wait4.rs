@@ -23,6 +23,7 @@ pub fn sys_wait4(
     // Write the exit code to the user space
     if exit_status_ptr != 0 {
         current_process.memory_space().vm_space().activate();
+        crate::mm::prepare_user_write(current_process, exit_status_ptr, 4)?;
         current_process
             .memory_space()
             .vm_space()
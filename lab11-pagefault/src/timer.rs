@@ -0,0 +1,107 @@
+//! Sleeping until a point in time.
+//!
+//! The timer interrupt checks the sleepers on every tick, and wakes the ones
+//! whose time has come.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use ostd::sync::{LocalIrqDisabled, SpinLock, WaitQueue};
+use ostd::timer::{Jiffies, TIMER_FREQ};
+
+/// The tasks sleeping until some tick.
+static SLEEPERS: SpinLock<Vec<Arc<Sleeper>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+struct Sleeper {
+    /// The tick to wake up on.
+    deadline: u64,
+    fired: AtomicBool,
+    wait_queue: WaitQueue,
+}
+
+pub fn init() {
+    ostd::timer::register_callback_on_cpu(wake_sleepers);
+}
+
+/// Blocks the current task for at least `duration`.
+///
+/// The timer ticks [`TIMER_FREQ`] times a second, so the sleep is rounded up
+/// to a whole number of ticks. A sleep too long to count in ticks lasts until
+/// the last one.
+pub fn sleep(duration: Duration) {
+    let ticks = (duration.as_nanos() * TIMER_FREQ as u128).div_ceil(1_000_000_000);
+    let ticks = u64::try_from(ticks).unwrap_or(u64::MAX);
+    if ticks == 0 {
+        return;
+    }
+
+    let sleeper = Arc::new(Sleeper {
+        deadline: Jiffies::elapsed().as_u64().saturating_add(ticks),
+        fired: AtomicBool::new(false),
+        wait_queue: WaitQueue::new(),
+    });
+    SLEEPERS.lock().push(sleeper.clone());
+
+    sleeper
+        .wait_queue
+        .wait_until(|| sleeper.fired.load(Ordering::Acquire).then_some(()));
+}
+
+fn wake_sleepers() {
+    let now = Jiffies::elapsed().as_u64();
+    SLEEPERS.lock().retain(|sleeper| {
+        if sleeper.deadline > now {
+            return true;
+        }
+        sleeper.fired.store(true, Ordering::Release);
+        sleeper.wait_queue.wake_all();
+        false
+    });
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use core::time::Duration;
+
+    use alloc::sync::Arc;
+    use ostd::prelude::ktest;
+    use ostd::task::{Task, TaskOptions};
+
+    use super::{SLEEPERS, sleep, wake_sleepers};
+
+    #[ktest]
+    fn huge_sleep_is_not_cut_short() {
+        let woke = Arc::new(AtomicBool::new(false));
+        {
+            let woke = woke.clone();
+            TaskOptions::new(move || {
+                // The longest sleep `nanosleep` accepts.
+                sleep(Duration::new(i64::MAX as u64, 999_999_999));
+                woke.store(true, Ordering::Release);
+            })
+            .data(())
+            .spawn()
+            .unwrap();
+        }
+        while SLEEPERS.lock().is_empty() {
+            Task::yield_now();
+        }
+
+        // More ticks than a `u64` holds, so it sleeps until the last one.
+        wake_sleepers();
+        let sleeper = SLEEPERS.lock()[0].clone();
+        assert_eq!(sleeper.deadline, u64::MAX);
+        assert!(!woke.load(Ordering::Acquire));
+
+        // Wake it by hand, so the task doesn't outlive the test.
+        SLEEPERS.lock().clear();
+        sleeper.fired.store(true, Ordering::Release);
+        sleeper.wait_queue.wake_all();
+        while !woke.load(Ordering::Acquire) {
+            Task::yield_now();
+        }
+    }
+}
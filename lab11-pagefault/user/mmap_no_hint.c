@@ -0,0 +1,45 @@
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+
+#define PAGE_SIZE 4096
+#define MAP_PAGES 4
+
+int main()
+{
+    // Disable buffer in STDOUT
+    setvbuf(stdout, NULL, _IONBF, 0);
+
+    // Without an address hint, the kernel picks a free range for each mapping.
+    char *first = mmap(NULL, MAP_PAGES * PAGE_SIZE, PROT_READ | PROT_WRITE,
+                       MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    char *second = mmap(NULL, MAP_PAGES * PAGE_SIZE, PROT_READ | PROT_WRITE,
+                        MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    if (first == MAP_FAILED || second == MAP_FAILED)
+    {
+        perror("mmap failed");
+        return 1;
+    }
+    printf("Mapped %d pages at %p and %p\n", MAP_PAGES, first, second);
+
+    if (first == NULL || second == NULL ||
+        (first < second + MAP_PAGES * PAGE_SIZE && second < first + MAP_PAGES * PAGE_SIZE))
+    {
+        printf("The mappings are at NULL or overlap\n");
+        return 1;
+    }
+
+    memset(first, 'a', MAP_PAGES * PAGE_SIZE);
+    memset(second, 'b', MAP_PAGES * PAGE_SIZE);
+    for (int i = 0; i < MAP_PAGES * PAGE_SIZE; i++)
+    {
+        if (first[i] != 'a' || second[i] != 'b')
+        {
+            printf("Byte %d was overwritten\n", i);
+            return 1;
+        }
+    }
+
+    printf("Both mappings hold their own data\n");
+    return 0;
+}
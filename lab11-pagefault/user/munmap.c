@@ -0,0 +1,44 @@
+#include <stdio.h>
+#include <sys/mman.h>
+
+#define PAGE_SIZE 4096
+#define MAP_PAGES 3
+
+int main()
+{
+    // Disable buffer in STDOUT
+    setvbuf(stdout, NULL, _IONBF, 0);
+
+    char *ptr = mmap(NULL, MAP_PAGES * PAGE_SIZE, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    if (ptr == MAP_FAILED)
+    {
+        perror("mmap failed");
+        return 1;
+    }
+    for (int i = 0; i < MAP_PAGES; i++)
+    {
+        ptr[i * PAGE_SIZE] = 'a' + i;
+    }
+
+    if (munmap(ptr + 1, PAGE_SIZE) == 0)
+    {
+        printf("munmap of an unaligned address should fail\n");
+        return 1;
+    }
+
+    // Punch out the middle page; the pages around it stay.
+    if (munmap(ptr + PAGE_SIZE, PAGE_SIZE) != 0)
+    {
+        perror("munmap failed");
+        return 1;
+    }
+    printf("First page: %c, last page: %c\n", ptr[0], ptr[2 * PAGE_SIZE]);
+
+    // This should kill the process with an unhandled page fault.
+    printf("Touching the unmapped page at %p...\n", ptr + PAGE_SIZE);
+    ptr[PAGE_SIZE] = 'x';
+
+    printf("Still alive after touching an unmapped page\n");
+    return 1;
+}
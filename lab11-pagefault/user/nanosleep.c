@@ -0,0 +1,30 @@
+#include <stdio.h>
+#include <time.h>
+
+#define SLEEP_MS 100
+
+long long get_current_time_us() {
+    struct timespec ts = {0, 0};
+    clock_gettime(CLOCK_MONOTONIC, &ts);
+    return (long long)ts.tv_sec * 1000000 + ts.tv_nsec / 1000;
+}
+
+int main() {
+    // Disable buffer in STDOUT
+    setvbuf(stdout, NULL, _IONBF, 0);
+
+    struct timespec req = {0, SLEEP_MS * 1000000L};
+    long long start_time = get_current_time_us();
+    if (nanosleep(&req, NULL) != 0) {
+        perror("nanosleep failed");
+        return 1;
+    }
+    long long elapsed = get_current_time_us() - start_time;
+
+    printf("Slept for %lld microseconds (asked for %d ms)\n", elapsed, SLEEP_MS);
+    if (elapsed < SLEEP_MS * 1000) {
+        printf("Woke up too early\n");
+        return 1;
+    }
+    return 0;
+}
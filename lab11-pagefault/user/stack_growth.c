@@ -0,0 +1,40 @@
+#include <stdio.h>
+
+#define FRAME_SIZE 1024
+#define DEPTH 512
+
+// Each call takes a bit over FRAME_SIZE bytes of stack, so DEPTH calls need
+// far more than the stack starts with, but stay well inside its 8MB limit.
+int recurse(int depth)
+{
+    volatile char frame[FRAME_SIZE];
+    for (int i = 0; i < FRAME_SIZE; i++)
+    {
+        frame[i] = (char)depth;
+    }
+    if (depth == 0)
+    {
+        return frame[0];
+    }
+    return recurse(depth - 1) + frame[FRAME_SIZE - 1];
+}
+
+int main()
+{
+    // Disable buffer in STDOUT
+    setvbuf(stdout, NULL, _IONBF, 0);
+
+    int sum = recurse(DEPTH);
+    int expected = 0;
+    for (int depth = 0; depth <= DEPTH; depth++)
+    {
+        expected += (char)depth;
+    }
+    if (sum != expected)
+    {
+        printf("Stack held %d, expected %d\n", sum, expected);
+        return 1;
+    }
+    printf("Recursed %d frames deep on a growing stack\n", DEPTH);
+    return 0;
+}
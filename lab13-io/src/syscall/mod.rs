@@ -13,10 +13,11 @@ mod wait4;
 mod write;
 
 use alloc::sync::Arc;
-use log::{debug, info};
+use log::{debug, info, warn};
 use ostd::arch::cpu::context::UserContext;
 use ostd::arch::qemu::exit_qemu;
 use ostd::task::Task;
+use syscall_names::syscall_name;
 
 use crate::error::{Errno, Error, Result};
 use crate::process::Process;
@@ -147,7 +148,15 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
             args[5] as _,
             current_process,
         ),
-        _ => Err(Error::new(Errno::ENOSYS)),
+        _ => {
+            warn!(
+                "[pid: {}] unimplemented syscall num: {} ({})",
+                current_process.pid(),
+                user_context.a7(),
+                syscall_name(user_context.a7()).unwrap_or("unknown")
+            );
+            Err(Error::new(Errno::ENOSYS))
+        }
     };
 
     match ret {
@@ -163,3 +172,28 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
         }
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::prelude::ktest;
+    use syscall_names::syscall_name;
+
+    use crate::error::Errno;
+    use crate::process::Process;
+
+    #[ktest]
+    fn unimplemented_syscall_returns_enosys() {
+        crate::progs::init();
+        let process = Process::new(crate::progs::lookup_progs("hello_world").unwrap());
+
+        // Mappings are never taken down here, so `munmap` hits the fallback arm.
+        assert_eq!(syscall_name(215), Some("munmap"));
+        let mut user_context = UserContext::default();
+        user_context.set_a7(215);
+        super::handle_syscall(&mut user_context, &process);
+
+        assert_eq!(user_context.a0() as isize, -(Errno::ENOSYS as isize));
+        assert!(process.exit_code().is_none());
+    }
+}
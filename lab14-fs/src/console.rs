@@ -1,40 +1,185 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use ostd::early_println;
 use ostd::mm::{
-    Fallible, Frame, FrameAllocOptions, HasPaddr, PAGE_SIZE, VmReader, io_util::HasVmReaderWriter,
+    Frame, FrameAllocOptions, HasPaddr, PAGE_SIZE, VmWriter, io_util::HasVmReaderWriter,
 };
+use ostd::sync::{SpinLock, WaitQueue};
+use ostd::timer::Jiffies;
 use sbi_rt::Physical;
 use spin::Once;
 
+use crate::error::Result;
+use crate::fs::POLL_QUEUE;
+use crate::process::INIT_PROCESS_ID;
+use crate::process::signal::{self, SIGINT};
+
+/// The size of the input buffer. Once it is full, the oldest input is dropped.
+const INPUT_BUFFER_SIZE: usize = 4096;
+/// How often the console is polled for input, in jiffies.
+const POLL_INTERVAL: u64 = 10;
+
+/// "End of Text", sent by Ctrl-C.
+const ETX: u8 = 3;
+
 static RECEIVE_BUFFER: Once<Frame<()>> = Once::new();
 
-pub fn receive_str<F>(mut callback: F) -> usize
-where
-    F: FnMut(VmReader<Fallible>),
-{
-    if !RECEIVE_BUFFER.is_completed() {
-        RECEIVE_BUFFER.call_once(|| FrameAllocOptions::new().alloc_frame().unwrap());
-    }
+/// Input that has been typed but not read yet.
+static INPUT_BUFFER: SpinLock<InputBuffer> = SpinLock::new(InputBuffer::new());
+/// The WaitQueue for the input buffer to become non-empty.
+static INPUT_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+/// Whether Ctrl-C has been typed since the foreground process group was last interrupted.
+static INTERRUPT_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// The session the console is the controlling terminal of, or 0 if there is
+/// none. It starts out as init's, which every process inherits until it calls
+/// `setsid`; another session leader takes it over with `TIOCSCTTY`.
+static SESSION: AtomicUsize = AtomicUsize::new(INIT_PROCESS_ID);
+
+/// The process group that receives the signals typed on the console, or 0 if
+/// there is none. The shell sets it with `tcsetpgrp`.
+static FOREGROUND_PGID: AtomicUsize = AtomicUsize::new(0);
+
+pub fn session() -> usize {
+    SESSION.load(Ordering::Acquire)
+}
+
+/// Makes the console the controlling terminal of the session `sid`, which
+/// starts with no foreground process group.
+pub fn set_session(sid: usize) {
+    SESSION.store(sid, Ordering::Release);
+    FOREGROUND_PGID.store(0, Ordering::Release);
+}
+
+pub fn foreground_pgid() -> usize {
+    FOREGROUND_PGID.load(Ordering::Acquire)
+}
+
+pub fn set_foreground_pgid(pgid: usize) {
+    FOREGROUND_PGID.store(pgid, Ordering::Release);
+}
 
-    let paddr = RECEIVE_BUFFER.get().unwrap().paddr();
+/// Starts capturing console input.
+///
+/// The SBI console has no interrupt of its own, so the timer interrupt polls it
+/// and moves whatever has been typed into the input buffer.
+pub fn init() {
+    RECEIVE_BUFFER.call_once(|| FrameAllocOptions::new().alloc_frame().unwrap());
+
+    ostd::timer::register_callback_on_cpu(|| {
+        if Jiffies::elapsed().as_u64() % POLL_INTERVAL == 0 {
+            poll_input();
+        }
+    });
+}
+
+/// Moves the input pending on the console into the input buffer.
+fn poll_input() {
+    let receive_buffer = RECEIVE_BUFFER.get().unwrap();
+    let paddr = receive_buffer.paddr();
     let ret = sbi_rt::console_read(Physical::new(
         PAGE_SIZE,
         paddr & 0xFFFF_FFFF,
         (paddr >> 32) & 0xFFFF_FFFF,
     ));
 
-    if ret.is_err() {
-        return 0;
+    if ret.is_err() || ret.value == 0 {
+        return;
     }
 
-    let read_bytes = ret.value;
+    let mut reader = receive_buffer.reader();
+    reader.limit(ret.value);
+    let mut chunk = [0u8; 64];
+    while reader.has_remain() {
+        let len = reader.read(&mut VmWriter::from(&mut chunk as &mut [u8]));
+        push_input(&chunk[..len]);
+    }
+}
 
-    let reader = RECEIVE_BUFFER
-        .get()
-        .unwrap()
-        .reader()
-        .limit(read_bytes)
-        .clone()
-        .to_fallible();
+/// Appends typed characters to the input buffer and wakes up the readers.
+///
+/// Ctrl-C is not buffered. It marks an interrupt for the foreground process
+/// group instead, which is delivered by [`handle_interrupt`].
+pub fn push_input(input: &[u8]) {
+    let mut input_buffer = INPUT_BUFFER.disable_irq().lock();
+    for &byte in input {
+        if byte == ETX {
+            early_println!("^C");
+            INTERRUPT_PENDING.store(true, Ordering::Release);
+        } else {
+            input_buffer.push(byte);
+        }
+    }
+    drop(input_buffer);
+
+    INPUT_WAIT_QUEUE.wake_all();
+    POLL_QUEUE.wake_all();
+}
+
+/// Whether there is input that has not been read yet.
+pub fn has_input() -> bool {
+    INPUT_BUFFER.disable_irq().lock().len > 0
+}
 
-    callback.call_mut((reader,));
-    read_bytes
+/// Reads one character of input, waiting until there is one.
+///
+/// Gives up with `ERESTARTSYS` if a signal arrives first, Ctrl-C included.
+pub fn read_byte() -> Result<u8> {
+    signal::wait_until_interruptible(&INPUT_WAIT_QUEUE, || {
+        // Ctrl-C wakes the readers up, but the `SIGINT` is only sent from here.
+        handle_interrupt();
+        try_read_byte()
+    })
+}
+
+/// Reads one character of input if one has been typed.
+pub fn try_read_byte() -> Option<u8> {
+    INPUT_BUFFER.disable_irq().lock().pop()
+}
+
+/// Sends `SIGINT` to the foreground process group if Ctrl-C has been typed.
+///
+/// Signals cannot be sent from the timer interrupt that captures the input, so
+/// this is called on the way back to user mode instead.
+pub fn handle_interrupt() {
+    if INTERRUPT_PENDING.swap(false, Ordering::AcqRel) {
+        signal::kill_group(foreground_pgid(), SIGINT);
+    }
+}
+
+/// A ring buffer of typed characters.
+struct InputBuffer {
+    buf: [u8; INPUT_BUFFER_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl InputBuffer {
+    const fn new() -> Self {
+        Self {
+            buf: [0; INPUT_BUFFER_SIZE],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.len == INPUT_BUFFER_SIZE {
+            // Drop the oldest character to make room.
+            self.head = (self.head + 1) % INPUT_BUFFER_SIZE;
+            self.len -= 1;
+        }
+        self.buf[(self.head + self.len) % INPUT_BUFFER_SIZE] = byte;
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.buf[self.head];
+        self.head = (self.head + 1) % INPUT_BUFFER_SIZE;
+        self.len -= 1;
+        Some(byte)
+    }
 }
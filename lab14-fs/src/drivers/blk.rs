@@ -15,6 +15,12 @@ pub trait BlockDevice: Send + Sync {
     fn read_block(&self, req: &mut BioRequest);
 
     fn write_block(&self, req: &BioRequest);
+
+    /// Returns once every write that completed before the call is on stable storage,
+    /// so a crash after it loses none of them.
+    ///
+    /// A device without a write cache has nothing to do.
+    fn flush(&self) {}
 }
 
 impl dyn BlockDevice {
@@ -53,6 +59,16 @@ impl dyn BlockDevice {
         self.write_block(&request);
     }
 
+    /// Writes `bytes`, which span whole sectors, starting at sector `index`.
+    pub fn write_bytes(&self, index: usize, bytes: &[u8]) {
+        assert!(bytes.len() % SECTOR_SIZE == 0);
+        let request = BioRequest::new(index, bytes.len() / SECTOR_SIZE);
+        for (data, sector) in request.data.iter().zip(bytes.chunks(SECTOR_SIZE)) {
+            data.write_bytes(0, sector).unwrap();
+        }
+        self.write_block(&request);
+    }
+
     pub fn read_val<T: ostd::Pod>(&self, index: usize) -> T {
         assert!(core::mem::size_of::<T>() <= SECTOR_SIZE);
         let mut request = BioRequest::new(index, 1);
@@ -97,11 +113,12 @@ impl BioRequest {
     }
 }
 
-pub(super) fn init() {
+pub(crate) fn init() {
     const POOL_SIZE: usize = 128;
-    let segment = FrameAllocOptions::new().alloc_segment(POOL_SIZE).unwrap();
-    let dma_allocator = DmaSliceAlloc::<[u8; SECTOR_SIZE], DmaStream>::new(
-        DmaStream::map(segment.into(), ostd::mm::DmaDirection::Bidirectional, false).unwrap(),
-    );
-    DMA_ALLOCATOR.call_once(|| Mutex::new(dma_allocator));
+    DMA_ALLOCATOR.call_once(|| {
+        let segment = FrameAllocOptions::new().alloc_segment(POOL_SIZE).unwrap();
+        Mutex::new(DmaSliceAlloc::<[u8; SECTOR_SIZE], DmaStream>::new(
+            DmaStream::map(segment.into(), ostd::mm::DmaDirection::Bidirectional, false).unwrap(),
+        ))
+    });
 }
@@ -3,7 +3,7 @@ use alloc::vec::Vec;
 use log::{debug, error};
 use ostd::{
     Pod,
-    mm::{DmaCoherent, FrameAllocOptions, VmIo},
+    mm::{DmaCoherent, DmaStream, FrameAllocOptions, VmIo},
     sync::{LocalIrqDisabled, SpinLock},
 };
 
@@ -13,13 +13,18 @@ use crate::drivers::{
 };
 use crate::drivers::{
     blk::BlockDevice,
-    utils::DmaSliceAlloc,
+    utils::{DmaSlice, DmaSliceAlloc},
     virtio::{mmio::VirtioMmioTransport, queue::Virtqueue},
 };
 
+/// The device takes `ReqType::Flush` requests.
+pub const VIRTIO_BLK_F_FLUSH: u64 = 1 << 9;
+
 pub struct VirtioBlkDevice {
     transport: VirtioMmioTransport,
     config: VirtioBlkConfig,
+    /// Whether the device has a write cache that `ReqType::Flush` writes back.
+    has_flush: bool,
     request_queue: SpinLock<Virtqueue, LocalIrqDisabled>,
 
     request_alloc: SpinLock<DmaSliceAlloc<BlockReq, DmaCoherent>, LocalIrqDisabled>,
@@ -44,6 +49,7 @@ impl VirtioBlkDevice {
         let blk_config: VirtioBlkConfig = config_io_mem.read_val(0).unwrap();
 
         debug!("Virtio Block Device config: {:#?}", blk_config);
+        let has_flush = transport.device_features() & VIRTIO_BLK_F_FLUSH != 0;
 
         transport.finish_init();
 
@@ -53,19 +59,22 @@ impl VirtioBlkDevice {
             request_alloc: SpinLock::new(DmaSliceAlloc::new(request_dma)),
             resp_alloc: SpinLock::new(DmaSliceAlloc::new(resp_dma)),
             config: blk_config,
+            has_flush,
         }
     }
 }
 
-impl BlockDevice for VirtioBlkDevice {
-    fn read_block(&self, bio_request: &mut BioRequest) {
+impl VirtioBlkDevice {
+    /// Sends a request with `data` between its header and its status, and waits
+    /// for the device to finish it.
+    fn submit(&self, type_: ReqType, sector: usize, data: &[DmaSlice<DmaStream>]) {
         let req_dma = self.request_alloc.lock().alloc().unwrap();
         let resp_dma = self.resp_alloc.lock().alloc().unwrap();
 
         let req = BlockReq {
-            type_: ReqType::In as _,
+            type_: type_ as _,
             reserved: 0,
-            sector: bio_request.index() as u64,
+            sector: sector as u64,
         };
         req_dma.write_no_offset_val(&req).unwrap();
 
@@ -73,13 +82,13 @@ impl BlockDevice for VirtioBlkDevice {
         resp_dma.write_no_offset_val(&resp).unwrap();
 
         // Construct Requests
-        let mut requests: Vec<Box<dyn VirtqueueRequest>> =
-            Vec::with_capacity(bio_request.num_sectors() + 2);
+        let device_writable = matches!(type_, ReqType::In);
+        let mut requests: Vec<Box<dyn VirtqueueRequest>> = Vec::with_capacity(data.len() + 2);
         requests.push(Box::new(VirtqueueCoherentRequest::from_dma_slice(
             &req_dma, false,
         )));
-        for data in bio_request.data_slices_mut().iter_mut() {
-            let stream_req = VirtqueueStreamRequest::from_dma_slice(data, true);
+        for data in data.iter() {
+            let stream_req = VirtqueueStreamRequest::from_dma_slice(data, device_writable);
             requests.push(Box::new(stream_req));
         }
         requests.push(Box::new(VirtqueueCoherentRequest::from_dma_slice(
@@ -106,11 +115,31 @@ impl BlockDevice for VirtioBlkDevice {
         // Read response
         let resp_read: BlockResp = resp_dma.read_no_offset_val().unwrap();
         if resp_read.status != RespStatus::Ok as u8 {
-            error!("Block device read error: {:?}", resp_read.status);
+            error!("Block device {:?} error: {:?}", type_, resp_read.status);
         }
     }
+}
 
-    fn write_block(&self, bio_request: &BioRequest) {}
+impl BlockDevice for VirtioBlkDevice {
+    fn read_block(&self, bio_request: &mut BioRequest) {
+        self.submit(
+            ReqType::In,
+            bio_request.index(),
+            bio_request.data_slices_mut(),
+        );
+    }
+
+    fn write_block(&self, bio_request: &BioRequest) {
+        self.submit(ReqType::Out, bio_request.index(), &bio_request.data);
+    }
+
+    fn flush(&self) {
+        // Without the feature the device has no write cache to empty: a write
+        // is on the disk once it completes.
+        if self.has_flush {
+            self.submit(ReqType::Flush, 0, &[]);
+        }
+    }
 }
 
 #[repr(C)]
@@ -57,6 +57,8 @@ pub enum Errno {
     EUNATCH = 49,      // Protocol driver not attached
     ENOCSI = 50,       // No CSI structure available
     EL2HLT = 51,       // Level 2 halted
+    ELIBBAD = 80,      // Accessing a corrupted shared library
+    ERESTARTSYS = 512, // Interrupted by a signal, restart the syscall (never seen by user space)
 }
 
 #[derive(Debug)]
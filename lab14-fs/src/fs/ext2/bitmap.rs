@@ -0,0 +1,134 @@
+use core::ops::Range;
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use ostd::mm::VmWriter;
+
+use crate::drivers::blk::{BlockDevice, SECTOR_SIZE};
+
+/// A block of bits that say which blocks or inodes of a group are in use, loaded
+/// from the device. Changes stay in memory until written back.
+pub(super) struct Bitmap {
+    blk_device: Arc<dyn BlockDevice>,
+    /// The sector the bitmap block starts at.
+    sector: usize,
+    bits: Vec<u8>,
+    /// Only the first bits stand for something; the last group may be short.
+    nr_bits: usize,
+    /// The sectors changed since the bitmap was loaded or last written back.
+    dirty: Option<Range<usize>>,
+}
+
+impl Bitmap {
+    /// Loads the `block_size` bytes from `sector`, of which the first `nr_bits`
+    /// bits are used.
+    pub(super) fn load(
+        blk_device: &Arc<dyn BlockDevice>,
+        sector: usize,
+        block_size: usize,
+        nr_bits: usize,
+    ) -> Self {
+        let mut bits = vec![0u8; block_size];
+        blk_device.read_to_vm_writer(
+            sector,
+            block_size / SECTOR_SIZE,
+            &mut VmWriter::from(bits.as_mut_slice()).to_fallible(),
+        );
+        Self {
+            blk_device: blk_device.clone(),
+            sector,
+            bits,
+            nr_bits: nr_bits.min(block_size * 8),
+            dirty: None,
+        }
+    }
+
+    pub(super) fn is_set(&self, idx: usize) -> bool {
+        self.bits[idx / 8] & (1 << (idx % 8)) != 0
+    }
+
+    /// Sets the first clear bit and returns it, or `None` if they are all set.
+    pub(super) fn alloc_first_free(&mut self) -> Option<usize> {
+        let idx = (0..self.nr_bits).find(|&idx| !self.is_set(idx))?;
+        self.bits[idx / 8] |= 1 << (idx % 8);
+        self.mark_dirty(idx);
+        Some(idx)
+    }
+
+    /// Clears a bit that is set.
+    pub(super) fn free(&mut self, idx: usize) {
+        assert!(
+            idx < self.nr_bits && self.is_set(idx),
+            "bit {} is not in use",
+            idx
+        );
+        self.bits[idx / 8] &= !(1 << (idx % 8));
+        self.mark_dirty(idx);
+    }
+
+    fn mark_dirty(&mut self, idx: usize) {
+        let sector = idx / 8 / SECTOR_SIZE;
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(sector)..dirty.end.max(sector + 1),
+            None => sector..sector + 1,
+        });
+    }
+
+    /// Writes the changed sectors back to the device.
+    pub(super) fn write_back(&mut self) {
+        if let Some(dirty) = self.dirty.take() {
+            self.blk_device.write_bytes(
+                self.sector + dirty.start,
+                &self.bits[dirty.start * SECTOR_SIZE..dirty.end * SECTOR_SIZE],
+            );
+        }
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::{sync::Arc, vec};
+    use ostd::prelude::ktest;
+
+    use super::Bitmap;
+    use crate::drivers::blk::{BlockDevice, SECTOR_SIZE};
+    use crate::fs::ext2::inode::test::MemBlockDevice;
+
+    const BLOCK_SIZE: usize = 1024;
+    const SECTOR: usize = 4;
+
+    #[ktest]
+    fn alloc_and_free_round_trip() {
+        crate::drivers::blk::init();
+        let mut disk = vec![0u8; (SECTOR + 4) * SECTOR_SIZE];
+        // The first ten bits are taken.
+        disk[SECTOR * SECTOR_SIZE] = 0xff;
+        disk[SECTOR * SECTOR_SIZE + 1] = 0b11;
+        let device: Arc<dyn BlockDevice> = Arc::new(MemBlockDevice::new(disk));
+        let load = |nr_bits| Bitmap::load(&device, SECTOR, BLOCK_SIZE, nr_bits);
+
+        let mut bitmap = load(12);
+        assert_eq!(bitmap.alloc_first_free(), Some(10));
+        assert_eq!(bitmap.alloc_first_free(), Some(11));
+        // The bits past the end don't count.
+        assert_eq!(bitmap.alloc_first_free(), None);
+        bitmap.free(3);
+        assert_eq!(bitmap.alloc_first_free(), Some(3));
+        bitmap.free(11);
+
+        // Nothing is on the device until written back.
+        assert!(!load(12).is_set(10));
+        bitmap.write_back();
+        let mut bitmap = load(12);
+        assert!((0..11).all(|idx| bitmap.is_set(idx)));
+        assert!(!bitmap.is_set(11));
+        assert_eq!(bitmap.alloc_first_free(), Some(11));
+
+        // Bits in the second sector of the block are written back too.
+        let mut bitmap = load(BLOCK_SIZE * 8);
+        while bitmap.alloc_first_free().unwrap() < SECTOR_SIZE * 8 {}
+        bitmap.write_back();
+        let bitmap = load(BLOCK_SIZE * 8);
+        assert!((0..=SECTOR_SIZE * 8).all(|idx| bitmap.is_set(idx)));
+        assert!(!bitmap.is_set(SECTOR_SIZE * 8 + 1));
+    }
+}
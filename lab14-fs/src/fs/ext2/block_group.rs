@@ -1,6 +1,6 @@
 use ostd::Pod;
 
-use crate::fs::ext2::Ext2Bid;
+use crate::fs::ext2::{Ext2Bid, Ext2Fs, bitmap::Bitmap};
 
 #[expect(unused)]
 pub struct BlockGroup {
@@ -27,6 +27,26 @@ impl BlockGroup {
     pub fn inode_table_start_bid(&self) -> Ext2Bid {
         self.inode_table_start_bid.into()
     }
+
+    pub fn block_bitmap_bid(&self) -> Ext2Bid {
+        self.bitmap_start_bid.into()
+    }
+
+    pub fn inode_bitmap_bid(&self) -> Ext2Bid {
+        self.inode_start_bid.into()
+    }
+
+    /// Loads the bitmap of the group's `nr_blocks` blocks.
+    pub(super) fn block_bitmap(&self, fs: &Ext2Fs, nr_blocks: usize) -> Bitmap {
+        let sector = fs.bid_to_sector(self.block_bitmap_bid());
+        Bitmap::load(&fs.blk_device, sector, fs.block_size, nr_blocks)
+    }
+
+    /// Loads the bitmap of the group's `nr_inodes` inodes.
+    pub(super) fn inode_bitmap(&self, fs: &Ext2Fs, nr_inodes: usize) -> Bitmap {
+        let sector = fs.bid_to_sector(self.inode_bitmap_bid());
+        Bitmap::load(&fs.blk_device, sector, fs.block_size, nr_inodes)
+    }
 }
 
 #[repr(C)]
@@ -35,9 +55,9 @@ pub(super) struct RawGroupDescriptor {
     block_bitmap: u32,
     inode_bitmap: u32,
     inode_table: u32,
-    free_blocks_count: u16,
-    free_inodes_count: u16,
-    dirs_count: u16,
+    pub free_blocks_count: u16,
+    pub free_inodes_count: u16,
+    pub dirs_count: u16,
     pad: u16,
     reserved: [u32; 3],
 }
@@ -1,8 +1,7 @@
-use core::ffi::CStr;
-
-use alloc::string::{String, ToString};
 use ostd::Pod;
 
+use crate::fs::InodeType;
+
 const MAX_NAME_LEN: usize = 256;
 
 #[repr(C)]
@@ -16,6 +15,23 @@ pub struct Ext2DirEntry {
 }
 
 impl Ext2DirEntry {
+    /// Creates an entry that takes up no more than it needs.
+    pub fn new(ino: u32, name: &[u8], type_: InodeType) -> Self {
+        let mut entry = Self {
+            ino,
+            name_len: name.len() as u8,
+            type_: match type_ {
+                InodeType::File => 1,
+                InodeType::Directory => 2,
+                InodeType::SymbolLink => 7,
+            },
+            ..Default::default()
+        };
+        entry.name[..name.len()].copy_from_slice(name);
+        entry.record_len = entry.needed_length();
+        entry
+    }
+
     pub fn inode(&self) -> u32 {
         self.ino
     }
@@ -28,11 +44,29 @@ impl Ext2DirEntry {
         self.name_len
     }
 
-    pub fn name(&self) -> String {
-        CStr::from_bytes_until_nul(&self.name)
-            .unwrap()
-            .to_string_lossy()
-            .to_string()
+    /// Returns the bytes the entry needs on disk: the header and the name, padded
+    /// to 4 bytes. Any more of its length is free for a new entry.
+    pub fn needed_length(&self) -> u16 {
+        (8 + self.name_len as u16).next_multiple_of(4)
+    }
+
+    pub fn set_length(&mut self, record_len: u16) {
+        self.record_len = record_len;
+    }
+
+    /// Returns the type of the inode, which is kept in the entry as well.
+    pub fn inode_type(&self) -> InodeType {
+        match self.type_ {
+            2 => InodeType::Directory,
+            7 => InodeType::SymbolLink,
+            _ => InodeType::File,
+        }
+    }
+
+    /// Returns the name as stored on disk. It is any bytes but `/` and NUL, so it need
+    /// not be UTF-8, and it is not NUL-terminated if it fills its space.
+    pub fn name(&self) -> &[u8] {
+        &self.name[..self.name_len as usize]
     }
 }
 
@@ -1,23 +1,33 @@
 #![expect(unused_variables)]
 
 use alloc::{
+    collections::btree_map::BTreeMap,
+    string::String,
     sync::{Arc, Weak},
+    vec,
     vec::Vec,
 };
 use log::debug;
-use ostd::Pod;
+use ostd::{
+    Pod,
+    mm::{FallibleVmRead, FallibleVmWrite, VmReader, VmWriter},
+    sync::Mutex,
+};
 
 use crate::{
     drivers::blk::SECTOR_SIZE,
     fs::{
-        InodeType,
+        DirEntry, InodeType,
         ext2::{Ext2Bid, Ext2Fs, dir_entry::Ext2DirEntry},
         util::sector_ptr::SectorPtr,
     },
 };
 
+use core::ops::Range;
+use core::sync::atomic::{AtomicBool, Ordering};
 use core::time::Duration;
 use crate::fs::InodeMeta;
+use crate::process::credentials::Id;
 
 #[expect(unused)]
 pub struct Inode {
@@ -29,11 +39,25 @@ pub struct Inode {
     inner: Inner,
     fs: Weak<Ext2Fs>,
     meta: InodeMeta,
+    /// Keeps writers from racing on the block pointers and size on disk.
+    write_lock: Mutex<()>,
+    /// The data blocks written since they were last written back, by their index
+    /// in the file, along with the block they go to.
+    dirty_blocks: Mutex<DirtyBlocks>,
+    /// Whether the timestamps in `meta` changed since they were last written to
+    /// the disk. They only get there on a full sync.
+    times_dirty: AtomicBool,
 }
 
+type DirtyBlocks = BTreeMap<usize, (Ext2Bid, Vec<u8>)>;
+
+/// Past this many dirty blocks, a write writes them all back rather than keep more.
+const MAX_DIRTY_BLOCKS: usize = 64;
+
 enum Inner {
     File,
-    Directory(Vec<Ext2DirEntry>),
+    /// The entries in the order they were read, then created.
+    Directory(Mutex<Vec<Ext2DirEntry>>),
 }
 
 impl Inode {
@@ -55,25 +79,24 @@ impl Inode {
         debug!("Inode {} type: {:?}", inode_id, type_);
         debug!("Raw inode data: {:#x?}", raw_inode);
 
-        let inner = match type_ {
+        let (inner, size) = match type_ {
             InodeType::Directory => {
-                Inner::Directory(read_directory(type_, &raw_inode, fs.clone()).unwrap())
+                let fs = fs.upgrade().expect("Filesystem has been dropped");
+                let (entries, nr_blocks) = read_directory(&raw_inode, &fs);
+                (Inner::Directory(Mutex::new(entries)), nr_blocks * fs.block_size)
             }
-            InodeType::File | InodeType::SymbolLink => Inner::File,
-        };
-
-        let size = if type_ == InodeType::File {
-            ((raw_inode.size_high as usize) << 32) | (raw_inode.size_low as usize)
-        } else {
-            raw_inode.size_low as usize
+            InodeType::File | InodeType::SymbolLink => (Inner::File, file_size(&raw_inode)),
         };
 
-        let meta = InodeMeta {
+        let meta = InodeMeta::new(
             size,
-            atime: Duration::from_secs(raw_inode.atime as u64),
-            mtime: Duration::from_secs(raw_inode.mtime as u64),
-            ctime: Duration::from_secs(raw_inode.ctime as u64),
-        };
+            raw_inode.mode,
+            ((raw_inode.os_dependent_2.uid_high as u32) << 16) | raw_inode.uid as u32,
+            ((raw_inode.os_dependent_2.gid_high as u32) << 16) | raw_inode.gid as u32,
+            Duration::from_secs(raw_inode.atime as u64),
+            Duration::from_secs(raw_inode.mtime as u64),
+            Duration::from_secs(raw_inode.ctime as u64),
+        );
 
         let inode = Arc::new(Inode {
             inode_id,
@@ -83,42 +106,54 @@ impl Inode {
             fs,
             sector_ptr,
             meta,
+            write_lock: Mutex::new(()),
+            dirty_blocks: Mutex::new(BTreeMap::new()),
+            times_dirty: AtomicBool::new(false),
         });
         inode
     }
 }
 
-fn read_directory(
-    type_: InodeType,
-    raw_inode: &RawInode,
-    fs: Weak<Ext2Fs>,
-) -> Option<Vec<Ext2DirEntry>> {
-    if type_ != InodeType::Directory {
-        return None;
-    }
+/// Returns the size of a regular file or symbolic link.
+fn file_size(raw_inode: &RawInode) -> usize {
+    ((raw_inode.size_high as usize) << 32) | (raw_inode.size_low as usize)
+}
+
+/// Reads all entries of a directory, along with the number of blocks they span.
+///
+/// A directory always fills its blocks, and its size is that of the blocks.
+fn read_directory(raw_inode: &RawInode, fs: &Ext2Fs) -> (Vec<Ext2DirEntry>, usize) {
+    let block_size = fs.block_size;
+    let nr_blocks = (raw_inode.size_low as usize).div_ceil(block_size);
 
-    // Read directory entries
     let mut dir_entries = Vec::new();
-    for &block_ptr in &raw_inode.block_ptrs.direct_pointers {
-        if block_ptr.0 == 0 {
+    let mut blocks = BlockMap::new(fs, &raw_inode.block_ptrs);
+    let mut block = vec![0u8; block_size];
+    for block_index in 0..nr_blocks {
+        let Some(bid) = blocks.get(block_index) else {
             continue;
-        }
-
-        let fs = fs.upgrade().expect("Filesystem has been dropped");
-        let block_size = fs.block_size as usize;
-        let sector = fs.bid_to_sector(block_ptr);
+        };
+        fs.blk_device.read_to_vm_writer(
+            fs.bid_to_sector(bid),
+            block_size / SECTOR_SIZE,
+            &mut VmWriter::from(block.as_mut_slice()).to_fallible(),
+        );
 
         let mut offset = 0;
         while offset < block_size {
-            let dir_entry: Ext2DirEntry = fs
-                .blk_device
-                .read_val_offset(sector + offset / SECTOR_SIZE, offset % SECTOR_SIZE);
-
-            if dir_entry.inode() == 0 {
+            // An entry at the end of a block is shorter than the whole struct.
+            let mut dir_entry = Ext2DirEntry::default();
+            let len = core::cmp::min(size_of::<Ext2DirEntry>(), block_size - offset);
+            dir_entry.as_bytes_mut()[..len].copy_from_slice(&block[offset..offset + len]);
+            if dir_entry.length() == 0 {
                 break;
             }
-
             offset += dir_entry.length() as usize;
+
+            // Removed entries keep their space, but point to no inode.
+            if dir_entry.inode() == 0 {
+                continue;
+            }
             dir_entries.push(dir_entry);
 
             debug!(
@@ -126,22 +161,120 @@ fn read_directory(
                 dir_entry.inode(),
                 dir_entry.length(),
                 dir_entry.name_length(),
-                dir_entry.name()
+                String::from_utf8_lossy(dir_entry.name())
             );
         }
     }
 
-    Some(dir_entries)
+    (dir_entries, nr_blocks)
 }
 
-impl super::super::Inode for Inode {
-    fn lookup(&self, name: &str) -> crate::error::Result<alloc::sync::Arc<dyn crate::fs::Inode>> {
+/// Writes the dirty blocks that hold any of the bytes in `range` to the device,
+/// which then no longer keeps them dirty.
+fn write_back(fs: &Ext2Fs, dirty_blocks: &mut DirtyBlocks, range: Range<usize>) {
+    let first = range.start / fs.block_size;
+    let end = range.end.div_ceil(fs.block_size);
+    let mut in_range = dirty_blocks.split_off(&first);
+    dirty_blocks.append(&mut in_range.split_off(&end));
+    for (bid, block) in in_range.into_values() {
+        fs.blk_device.write_bytes(fs.bid_to_sector(bid), &block);
+    }
+}
+
+/// The number of block pointers in an inode that point straight to data.
+const NR_DIRECT: usize = 12;
+
+/// Maps the blocks of some data to the blocks on disk that hold them, following the
+/// indirect pointers past the direct ones.
+///
+/// Neighbouring data blocks share their pointer blocks, so the last pointer block read
+/// at each level of indirection is kept rather than read again.
+struct BlockMap<'a> {
+    fs: &'a Ext2Fs,
+    block_ptrs: &'a BlockPointers,
+    /// The last pointer block read at each depth below the inode, with its block id.
+    pointer_blocks: [Option<(Ext2Bid, Vec<u8>)>; 3],
+}
+
+impl<'a> BlockMap<'a> {
+    fn new(fs: &'a Ext2Fs, block_ptrs: &'a BlockPointers) -> Self {
+        Self {
+            fs,
+            block_ptrs,
+            pointer_blocks: [None, None, None],
+        }
+    }
+
+    /// Finds the block that holds the `block_index`-th block of the data. Returns
+    /// `None` for a hole.
+    fn get(&mut self, block_index: usize) -> Option<Ext2Bid> {
+        if block_index < NR_DIRECT {
+            let bid = self.block_ptrs.direct_pointers[block_index];
+            return (bid.0 != 0).then_some(bid);
+        }
+
+        let ptrs_per_block = self.fs.block_size / size_of::<Ext2Bid>();
+        let mut index = block_index - NR_DIRECT;
+        // The number of data blocks under one pointer in the inode.
+        let mut span = 1;
+        for root in [
+            self.block_ptrs.single_indirect_pointer,
+            self.block_ptrs.double_indirect_pointer,
+            self.block_ptrs.triple_indirect_pointer,
+        ] {
+            span *= ptrs_per_block;
+            if index >= span {
+                index -= span;
+                continue;
+            }
+
+            let mut bid = root;
+            let mut depth = 0;
+            while bid.0 != 0 && span > 1 {
+                span /= ptrs_per_block;
+                bid = self.pointer(depth, bid, index / span);
+                index %= span;
+                depth += 1;
+            }
+            return (bid.0 != 0).then_some(bid);
+        }
+
+        None
+    }
+
+    /// Reads the `index`-th pointer in the pointer block `bid`, which is `depth`
+    /// levels below the inode.
+    fn pointer(&mut self, depth: usize, bid: Ext2Bid, index: usize) -> Ext2Bid {
+        let cached = &mut self.pointer_blocks[depth];
+        if !matches!(cached, Some((cached_bid, _)) if *cached_bid == bid) {
+            let fs = self.fs;
+            let mut block = vec![0u8; fs.block_size];
+            fs.blk_device.read_to_vm_writer(
+                fs.bid_to_sector(bid),
+                fs.block_size / SECTOR_SIZE,
+                &mut VmWriter::from(block.as_mut_slice()).to_fallible(),
+            );
+            *cached = Some((bid, block));
+        }
+
+        let (_, block) = cached.as_ref().unwrap();
+        let offset = index * size_of::<Ext2Bid>();
+        Ext2Bid::from_bytes(&block[offset..offset + size_of::<Ext2Bid>()])
+    }
+}
+
+impl Inode {
+    /// Looks up the entry whose name is exactly `name`, which need not be UTF-8.
+    pub fn lookup_bytes(
+        &self,
+        name: &[u8],
+    ) -> crate::error::Result<alloc::sync::Arc<dyn crate::fs::Inode>> {
         if self.type_ != InodeType::Directory {
             return Err(crate::error::Error::new(crate::error::Errno::ENOTDIR));
         }
 
         if let Inner::Directory(ref entries) = self.inner {
-            for entry in entries {
+            for entry in entries.lock().iter() {
                 if entry.name() == name {
                     let fs = self.fs.upgrade().expect("Filesystem has been dropped");
                     let inode = fs.lookup_inode(entry.inode())?;
@@ -151,17 +284,254 @@ impl super::super::Inode for Inode {
         }
         Err(crate::error::Error::new(crate::error::Errno::ENOENT))
     }
+}
+
+impl Inode {
+    /// Writes `entry` into the first gap in the directory that fits it, or into a new
+    /// block at the end. The caller holds `write_lock`.
+    fn add_entry(&self, fs: &Ext2Fs, mut entry: Ext2DirEntry) -> crate::error::Result<()> {
+        let block_size = fs.block_size;
+        let needed = entry.needed_length() as usize;
+        let mut raw_inode: RawInode = self.sector_ptr.read();
+        let nr_blocks = (raw_inode.size_low as usize).div_ceil(block_size);
+
+        let block_ptrs = raw_inode.block_ptrs;
+        let mut blocks = BlockMap::new(fs, &block_ptrs);
+        let mut block = vec![0u8; block_size];
+        let mut slot = None;
+        'blocks: for block_index in 0..nr_blocks {
+            let Some(bid) = blocks.get(block_index) else {
+                continue;
+            };
+            fs.blk_device.read_to_vm_writer(
+                fs.bid_to_sector(bid),
+                block_size / SECTOR_SIZE,
+                &mut VmWriter::from(block.as_mut_slice()).to_fallible(),
+            );
+
+            let mut offset = 0;
+            while offset < block_size {
+                let mut existing = Ext2DirEntry::default();
+                let len = core::cmp::min(size_of::<Ext2DirEntry>(), block_size - offset);
+                existing.as_bytes_mut()[..len].copy_from_slice(&block[offset..offset + len]);
+                let record_len = existing.length() as usize;
+                if record_len == 0 {
+                    break;
+                }
+
+                // A removed entry is free as a whole, a live one past what it needs.
+                let used = match existing.inode() {
+                    0 => 0,
+                    _ => existing.needed_length() as usize,
+                };
+                if record_len - used >= needed {
+                    if used > 0 {
+                        // The entry before gives up its padding.
+                        existing.set_length(used as u16);
+                        block[offset..offset + 8].copy_from_slice(&existing.as_bytes()[..8]);
+                    }
+                    entry.set_length((record_len - used) as u16);
+                    slot = Some((bid, offset + used));
+                    break 'blocks;
+                }
+                offset += record_len;
+            }
+        }
+
+        let (bid, offset) = match slot {
+            Some(slot) => slot,
+            None if nr_blocks < NR_DIRECT => {
+                let bid = fs.alloc_block()?;
+                raw_inode.block_ptrs.direct_pointers[nr_blocks] = bid;
+                raw_inode.blocks_count += (block_size / SECTOR_SIZE) as u32;
+                raw_inode.size_low += block_size as u32;
+                block.fill(0);
+                entry.set_length(block_size as u16);
+                (bid, 0)
+            }
+            // Only the direct pointers get new blocks so far.
+            None => return Err(crate::error::Error::new(crate::error::Errno::ENOSPC)),
+        };
+        block[offset..offset + needed].copy_from_slice(&entry.as_bytes()[..needed]);
+        fs.blk_device.write_bytes(fs.bid_to_sector(bid), &block);
+        self.sector_ptr.write(&raw_inode);
+
+        let now = crate::fs::now();
+        self.meta.set_times(None, Some(now), now);
+        self.times_dirty.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Writes the timestamps in `meta` into the on-disk inode, if they changed.
+    fn write_times(&self) {
+        if !self.times_dirty.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let _guard = self.write_lock.lock();
+        let mut raw_inode: RawInode = self.sector_ptr.read();
+        // The on-disk inode keeps whole seconds only.
+        raw_inode.atime = self.meta.atime().as_secs() as u32;
+        raw_inode.mtime = self.meta.mtime().as_secs() as u32;
+        raw_inode.ctime = self.meta.ctime().as_secs() as u32;
+        self.sector_ptr.write(&raw_inode);
+    }
+
+    /// Allocates the first block of a new directory `ino` inside this one and
+    /// writes its `.` and `..` entries there.
+    fn new_directory_block(&self, fs: &Ext2Fs, ino: u32) -> crate::error::Result<Ext2Bid> {
+        let bid = fs.alloc_block()?;
+        let mut block = vec![0u8; fs.block_size];
+        let dot = Ext2DirEntry::new(ino, b".", InodeType::Directory);
+        let mut dot_dot = Ext2DirEntry::new(self.inode_id, b"..", InodeType::Directory);
+        // `..` takes the rest of the block.
+        let offset = dot.length() as usize;
+        dot_dot.set_length((fs.block_size - offset) as u16);
+        block[..offset].copy_from_slice(&dot.as_bytes()[..offset]);
+        let len = dot_dot.needed_length() as usize;
+        block[offset..offset + len].copy_from_slice(&dot_dot.as_bytes()[..len]);
+        fs.blk_device.write_bytes(fs.bid_to_sector(bid), &block);
+        Ok(bid)
+    }
+}
+
+impl super::super::Inode for Inode {
+    fn lookup(&self, name: &str) -> crate::error::Result<alloc::sync::Arc<dyn crate::fs::Inode>> {
+        self.lookup_bytes(name.as_bytes())
+    }
+
+    fn readdir(&self, after: Option<&str>) -> crate::error::Result<Vec<DirEntry>> {
+        let Inner::Directory(ref entries) = self.inner else {
+            return Err(crate::error::Error::new(crate::error::Errno::ENOTDIR));
+        };
+
+        // New entries go to the end of the list, so its order is stable, though it
+        // need not be the order on disk. Names are handed out lossily, so they are
+        // matched the same way.
+        let entries = entries.lock();
+        let start = match after {
+            Some(name) => entries
+                .iter()
+                .position(|entry| String::from_utf8_lossy(entry.name()) == name)
+                .map_or(entries.len(), |index| index + 1),
+            None => 0,
+        };
+        Ok(entries[start..]
+            .iter()
+            .map(|entry| DirEntry {
+                name: String::from_utf8_lossy(entry.name()).into_owned(),
+                typ: entry.inode_type(),
+            })
+            .collect())
+    }
 
     fn create(
         &self,
         name: &str,
         type_: InodeType,
+        mode: u16,
+        uid: Id,
+        gid: Id,
     ) -> crate::error::Result<alloc::sync::Arc<dyn crate::fs::Inode>> {
-        todo!()
+        let Inner::Directory(ref entries) = self.inner else {
+            return Err(crate::error::Error::new(crate::error::Errno::ENOTDIR));
+        };
+        // Only regular files and directories can be created so far.
+        if type_ == InodeType::SymbolLink {
+            return Err(crate::error::Error::new(crate::error::Errno::EINVAL));
+        }
+        if name.len() > u8::MAX as usize {
+            return Err(crate::error::Error::new(crate::error::Errno::ENAMETOOLONG));
+        }
+
+        let _guard = self.write_lock.lock();
+        let mut entries = entries.lock();
+        if entries.iter().any(|entry| entry.name() == name.as_bytes()) {
+            return Err(crate::error::Error::new(crate::error::Errno::EEXIST));
+        }
+
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        let ino = fs.alloc_inode()?;
+        let now = crate::fs::now().as_secs() as u32;
+        let mut raw_inode = RawInode {
+            // A regular file.
+            mode: 0x8000 | (mode & 0o7777),
+            uid: uid as u16,
+            gid: gid as u16,
+            atime: now,
+            ctime: now,
+            mtime: now,
+            hard_links: 1,
+            ..Default::default()
+        };
+        raw_inode.os_dependent_2.uid_high = (uid >> 16) as u16;
+        raw_inode.os_dependent_2.gid_high = (gid >> 16) as u16;
+        if type_ == InodeType::Directory {
+            let bid = match self.new_directory_block(&fs, ino) {
+                Ok(bid) => bid,
+                Err(err) => {
+                    fs.free_inode(ino);
+                    return Err(err);
+                }
+            };
+            // A directory, linked from here and from its own `.`.
+            raw_inode.mode = 0x4000 | (mode & 0o7777);
+            raw_inode.hard_links = 2;
+            raw_inode.size_low = fs.block_size as u32;
+            raw_inode.blocks_count = (fs.block_size / SECTOR_SIZE) as u32;
+            raw_inode.block_ptrs.direct_pointers[0] = bid;
+        }
+        fs.inode_ptr(ino).write(&raw_inode);
+
+        let entry = Ext2DirEntry::new(ino, name.as_bytes(), type_);
+        if let Err(err) = self.add_entry(&fs, entry) {
+            if type_ == InodeType::Directory {
+                fs.free_block(raw_inode.block_ptrs.direct_pointers[0]);
+            }
+            fs.free_inode(ino);
+            return Err(err);
+        }
+        entries.push(entry);
+
+        if type_ == InodeType::Directory {
+            // The `..` of the new directory links back here.
+            let mut parent: RawInode = self.sector_ptr.read();
+            parent.hard_links += 1;
+            self.sector_ptr.write(&parent);
+            fs.add_dir(ino);
+        }
+
+        Ok(fs.lookup_inode(ino)?)
     }
 
+    /// Reads the target of a symlink. One shorter than the block pointers is kept
+    /// in them (a "fast" symlink), and a longer one in data blocks.
     fn read_link(&self) -> crate::error::Result<alloc::string::String> {
-        todo!()
+        if self.type_ != InodeType::SymbolLink {
+            return Err(crate::error::Error::new(crate::error::Errno::EINVAL));
+        }
+
+        let raw_inode: RawInode = self.sector_ptr.read();
+        let len = file_size(&raw_inode);
+        let target = if len < size_of::<BlockPointers>() {
+            raw_inode.block_ptrs.as_bytes()[..len].to_vec()
+        } else {
+            let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+            let mut target = vec![0u8; len.next_multiple_of(fs.block_size)];
+            let mut blocks = BlockMap::new(&fs, &raw_inode.block_ptrs);
+            for (block_index, block) in target.chunks_mut(fs.block_size).enumerate() {
+                let bid = blocks
+                    .get(block_index)
+                    .ok_or(crate::error::Error::new(crate::error::Errno::EIO))?;
+                fs.blk_device.read_to_vm_writer(
+                    fs.bid_to_sector(bid),
+                    fs.block_size / SECTOR_SIZE,
+                    &mut VmWriter::from(block).to_fallible(),
+                );
+            }
+            target.truncate(len);
+            target
+        };
+        String::from_utf8(target).map_err(|_| crate::error::Error::new(crate::error::Errno::EINVAL))
     }
 
     fn write_link(&self, target: &str) -> crate::error::Result<()> {
@@ -196,30 +566,56 @@ impl super::super::Inode for Inode {
         let mut offset_in_block = current_offset % block_size;
 
         // Read data block by block
+        let mut blocks = BlockMap::new(&fs, &raw_inode.block_ptrs);
+        let mut block = vec![0u8; block_size];
+        let dirty_blocks = self.dirty_blocks.lock();
         while bytes_read < max_to_read {
-            let block_ptr = if block_index < 12 {
-                raw_inode.block_ptrs.direct_pointers[block_index as usize]
-            } else {
-                // For simplicity, we only handle direct pointers here
-                break;
-            };
-            if block_ptr.0 == 0 {
-                break;
-            }
-            let sector = fs.bid_to_sector(block_ptr);
             let remaining_in_file = max_to_read - bytes_read;
             let remaining_in_block = block_size - offset_in_block;
             let to_read = core::cmp::min(remaining_in_block, remaining_in_file);
+            // A block not yet written back is newer than the one on the device.
+            if let Some((_, dirty)) = dirty_blocks.get(&block_index) {
+                writer
+                    .write_fallible(&mut VmReader::from(
+                        &dirty[offset_in_block..offset_in_block + to_read],
+                    ))
+                    .map_err(|_| crate::error::Error::new(crate::error::Errno::EFAULT))?;
+                bytes_read += to_read;
+                current_offset += to_read;
+                offset_in_block = 0;
+                block_index += 1;
+                continue;
+            }
+            // A hole, left by a write past the end, reads as zeros.
+            let Some(block_ptr) = blocks.get(block_index) else {
+                writer
+                    .fill_zeros(to_read)
+                    .map_err(|_| crate::error::Error::new(crate::error::Errno::EFAULT))?;
+                bytes_read += to_read;
+                current_offset += to_read;
+                offset_in_block = 0;
+                block_index += 1;
+                continue;
+            };
+            let sector = fs.bid_to_sector(block_ptr);
 
             debug!(
                 "Reading block_index: {}, block_ptr: {:?}, sector: {}, offset_in_block: {}, to_read: {}",
                 block_index, block_ptr, sector, offset_in_block, to_read
             );
+            // The device reads whole sectors, of which only the asked for bytes are handed out.
+            let first_sector = offset_in_block / SECTOR_SIZE;
+            let nr_sectors = (offset_in_block + to_read).div_ceil(SECTOR_SIZE) - first_sector;
+            let sectors = &mut block[..nr_sectors * SECTOR_SIZE];
             fs.blk_device.read_to_vm_writer(
-                sector + offset_in_block / SECTOR_SIZE,
-                (to_read + SECTOR_SIZE - 1) / SECTOR_SIZE,
-                &mut writer,
+                sector + first_sector,
+                nr_sectors,
+                &mut VmWriter::from(&mut *sectors).to_fallible(),
             );
+            let start = offset_in_block % SECTOR_SIZE;
+            writer
+                .write_fallible(&mut VmReader::from(&sectors[start..start + to_read]))
+                .map_err(|_| crate::error::Error::new(crate::error::Errno::EFAULT))?;
 
             bytes_read += to_read;
             current_offset += to_read;
@@ -230,20 +626,158 @@ impl super::super::Inode for Inode {
         Ok(bytes_read)
     }
 
-    fn write_at(&self, offset: usize, reader: ostd::mm::VmReader) -> crate::error::Result<usize> {
-        todo!()
+    fn write_at(
+        &self,
+        offset: usize,
+        mut reader: ostd::mm::VmReader,
+    ) -> crate::error::Result<usize> {
+        if self.type_ != InodeType::File {
+            return Err(crate::error::Error::new(crate::error::Errno::EISDIR));
+        }
+
+        let _guard = self.write_lock.lock();
+        let mut raw_inode: RawInode = self.sector_ptr.read();
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        let block_size = fs.block_size;
+        let len = reader.remain();
+
+        // New blocks go into `raw_inode`, while the map looks up the old ones.
+        let block_ptrs = raw_inode.block_ptrs;
+        let mut blocks = BlockMap::new(&fs, &block_ptrs);
+        let mut buf = vec![0u8; block_size];
+        let mut dirty_blocks = self.dirty_blocks.lock();
+        let mut written = 0;
+        let mut error = None;
+        while written < len {
+            let block_index = (offset + written) / block_size;
+            let offset_in_block = (offset + written) % block_size;
+            let to_write = core::cmp::min(block_size - offset_in_block, len - written);
+
+            let mut new_block = false;
+            let bid = match blocks.get(block_index) {
+                Some(bid) => bid,
+                None if block_index < NR_DIRECT => match fs.alloc_block() {
+                    Ok(bid) => {
+                        raw_inode.block_ptrs.direct_pointers[block_index] = bid;
+                        raw_inode.blocks_count += (block_size / SECTOR_SIZE) as u32;
+                        new_block = true;
+                        bid
+                    }
+                    Err(err) => {
+                        error = Some(err);
+                        break;
+                    }
+                },
+                // Only the direct pointers get new blocks so far.
+                None => {
+                    error = Some(crate::error::Error::new(crate::error::Errno::EFBIG));
+                    break;
+                }
+            };
+
+            let chunk = &mut buf[..to_write];
+            if reader
+                .read_fallible(&mut VmWriter::from(&mut *chunk))
+                .is_err()
+            {
+                // Nothing went into the new block, so the file does not get it.
+                if new_block {
+                    raw_inode.block_ptrs.direct_pointers[block_index] = Ext2Bid(0);
+                    raw_inode.blocks_count -= (block_size / SECTOR_SIZE) as u32;
+                    fs.free_block(bid);
+                }
+                error = Some(crate::error::Error::new(crate::error::Errno::EFAULT));
+                break;
+            }
+
+            // The block stays in memory until it is written back, whole, so it starts
+            // out as what is on the device, or as zeros if new.
+            let (_, block) = dirty_blocks.entry(block_index).or_insert_with(|| {
+                let mut block = vec![0u8; block_size];
+                if !new_block {
+                    fs.blk_device.read_to_vm_writer(
+                        fs.bid_to_sector(bid),
+                        block_size / SECTOR_SIZE,
+                        &mut VmWriter::from(block.as_mut_slice()).to_fallible(),
+                    );
+                }
+                (bid, block)
+            });
+            block[offset_in_block..offset_in_block + to_write].copy_from_slice(chunk);
+
+            written += to_write;
+        }
+        if dirty_blocks.len() > MAX_DIRTY_BLOCKS {
+            write_back(&fs, &mut dirty_blocks, 0..usize::MAX);
+        }
+
+        // A write that wrote nothing leaves the file as it was, even past its end.
+        if written == 0 {
+            return match error {
+                Some(err) => Err(err),
+                None => Ok(0),
+            };
+        }
+
+        let size = file_size(&raw_inode).max(offset + written);
+        raw_inode.size_low = size as u32;
+        raw_inode.size_high = (size >> 32) as u32;
+        self.sector_ptr.write(&raw_inode);
+        let now = crate::fs::now();
+        self.meta.set_times(None, Some(now), now);
+        self.times_dirty.store(true, Ordering::Relaxed);
+
+        Ok(written)
     }
 
     fn metadata(&self) -> &crate::fs::InodeMeta {
         &self.meta
     }
 
+    fn set_mode(&self, mode: u16) -> crate::error::Result<()> {
+        let mut raw_inode: RawInode = self.sector_ptr.read();
+        raw_inode.mode = (raw_inode.mode & 0xF000) | (mode & 0o7777);
+        self.sector_ptr.write(&raw_inode);
+
+        self.meta.set_mode(mode, crate::fs::now());
+        self.times_dirty.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_times(
+        &self,
+        atime: Option<Duration>,
+        mtime: Option<Duration>,
+    ) -> crate::error::Result<()> {
+        self.meta.set_times(atime, mtime, crate::fs::now());
+        self.times_dirty.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Writes back the dirty blocks with bytes in `range`, then flushes the device's
+    /// write cache.
+    ///
+    /// The device can only flush all of its cache, so whatever else sits there
+    /// reaches the disk along with the range.
+    fn sync_range(&self, range: Range<usize>) -> crate::error::Result<()> {
+        let fs = self.fs.upgrade().expect("Filesystem has been dropped");
+        write_back(&fs, &mut self.dirty_blocks.lock(), range);
+        fs.blk_device.flush();
+        Ok(())
+    }
+
+    /// Writes the timestamps along with the data. Without this, they wait in
+    /// memory, as `sync_data` leaves them out.
+    fn sync_all(&self) -> crate::error::Result<()> {
+        self.write_times();
+        self.sync_data()
+    }
+
     fn size(&self) -> usize {
-        let raw_inode: RawInode = self.sector_ptr.read();
-        if self.type_ == InodeType::File {
-            ((raw_inode.size_high as usize) << 32) | (raw_inode.size_low as usize)
-        } else {
-            raw_inode.size_low as usize
+        match self.type_ {
+            // A directory always fills its blocks.
+            InodeType::Directory => self.sector_ptr.read().size_low as usize,
+            InodeType::File | InodeType::SymbolLink => file_size(&self.sector_ptr.read()),
         }
     }
 
@@ -316,3 +850,963 @@ pub struct OsDependent2 {
     pub gid_high: u16,
     _reserved: u32,
 }
+
+#[cfg(ktest)]
+pub(super) mod test {
+    use alloc::{format, string::ToString, sync::Arc, vec, vec::Vec};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::time::Duration;
+    use ostd::{Pod, prelude::ktest, sync::Mutex};
+
+    use super::{BlockPointers, NR_DIRECT, RawInode};
+    use crate::drivers::blk::{BioRequest, BlockDevice, SECTOR_SIZE};
+    use crate::fs::ext2::block_group::RawGroupDescriptor;
+    use crate::fs::ext2::super_block::{EXT2_FIRST_SUPERBLOCK_OFFSET, RawSuperBlock};
+    use crate::fs::ext2::{EXT2_MAGIC, Ext2Bid, Ext2Fs, ROOT_INO};
+    use crate::error::Errno;
+    use crate::fs::util::PathString;
+    use crate::fs::{FileSystem, Inode as _, InodeType};
+    use crate::process::credentials::ROOT_ID;
+    use ostd::mm::{VmIo, VmReader, VmWriter};
+
+    const BLOCK_SIZE: usize = 4096;
+    const INODE_SIZE: usize = 128;
+    const NR_INODES: u32 = 32;
+    /// The first block that is free for data, with 4KB blocks.
+    const FIRST_DATA_BID: u32 = 5;
+    /// A regular file that all directory entries point to.
+    const FILE_INO: u32 = 12;
+
+    /// A disk held in memory, behind a write cache: writes only survive a crash
+    /// once flushed.
+    pub(in crate::fs::ext2) struct MemBlockDevice {
+        disk: Mutex<Vec<u8>>,
+        durable: Mutex<Vec<u8>>,
+        /// The number of sectors written so far.
+        nr_written: AtomicUsize,
+    }
+
+    impl MemBlockDevice {
+        pub(in crate::fs::ext2) fn new(disk: Vec<u8>) -> Self {
+            Self {
+                durable: Mutex::new(disk.clone()),
+                disk: Mutex::new(disk),
+                nr_written: AtomicUsize::new(0),
+            }
+        }
+
+        fn nr_written(&self) -> usize {
+            self.nr_written.load(Ordering::Relaxed)
+        }
+
+        /// The disk as a crash would leave it.
+        fn crash(&self) -> Vec<u8> {
+            self.durable.lock().clone()
+        }
+    }
+
+    impl BlockDevice for MemBlockDevice {
+        fn read_block(&self, req: &mut BioRequest) {
+            let disk = self.disk.lock();
+            let index = req.index();
+            for (i, data) in req.data_slices_mut().iter().enumerate() {
+                let start = (index + i) * SECTOR_SIZE;
+                data.write_bytes(0, &disk[start..start + SECTOR_SIZE])
+                    .unwrap();
+            }
+        }
+
+        fn write_block(&self, req: &BioRequest) {
+            let mut disk = self.disk.lock();
+            for (i, data) in req.data.iter().enumerate() {
+                let start = (req.index() + i) * SECTOR_SIZE;
+                data.read_bytes(0, &mut disk[start..start + SECTOR_SIZE])
+                    .unwrap();
+            }
+            self.nr_written.fetch_add(req.data.len(), Ordering::Relaxed);
+        }
+
+        fn flush(&self) {
+            self.durable.lock().clone_from(&self.disk.lock());
+        }
+    }
+
+    /// Builds an ext2 image with a single block group, unless split.
+    ///
+    /// After the super block come the group descriptor table, the inode table, the
+    /// block bitmap, the inode bitmap, and then data.
+    struct Image {
+        disk: Vec<u8>,
+        block_size: usize,
+        /// The block holding the super block, which is 1 for 1KB blocks and 0 otherwise.
+        first_data_block: u32,
+        inode_table_bid: u32,
+        block_bitmap_bid: u32,
+        inode_bitmap_bid: u32,
+    }
+
+    impl Image {
+        fn new(nr_blocks: u32) -> Self {
+            Self::with_block_size(nr_blocks, BLOCK_SIZE)
+        }
+
+        fn with_block_size(nr_blocks: u32, block_size: usize) -> Self {
+            let first_data_block = if block_size == 1024 { 1 } else { 0 };
+            let inode_table_bid = first_data_block + 2;
+            let block_bitmap_bid =
+                inode_table_bid + (NR_INODES as usize * INODE_SIZE).div_ceil(block_size) as u32;
+            let mut image = Self {
+                disk: vec![0; nr_blocks as usize * block_size],
+                block_size,
+                first_data_block,
+                inode_table_bid,
+                block_bitmap_bid,
+                inode_bitmap_bid: block_bitmap_bid + 1,
+            };
+
+            let super_block = RawSuperBlock {
+                inodes_count: NR_INODES,
+                blocks_count: nr_blocks,
+                first_data_block,
+                log_block_size: (block_size / 1024).ilog2(),
+                blocks_per_group: nr_blocks - first_data_block,
+                inodes_per_group: NR_INODES,
+                magic: EXT2_MAGIC,
+                rev_level: 1,
+                inode_size: INODE_SIZE as u16,
+                ..Default::default()
+            };
+            image.write(EXT2_FIRST_SUPERBLOCK_OFFSET, super_block.as_bytes());
+            // The two bitmap pointers come first, then the inode table pointer.
+            let descriptor = image.descriptor_offset(0);
+            image.write(descriptor, &image.block_bitmap_bid.to_le_bytes());
+            image.write(descriptor + 4, &image.inode_bitmap_bid.to_le_bytes());
+            image.write(descriptor + 8, &image.inode_table_bid.to_le_bytes());
+            for bid in first_data_block..=image.inode_bitmap_bid {
+                image.use_block(bid);
+            }
+            // Every inode up to the file is taken.
+            for ino in 1..=FILE_INO {
+                let offset = image.inode_bitmap_bid as usize * block_size + (ino as usize - 1) / 8;
+                image.disk[offset] |= 1 << ((ino - 1) % 8);
+            }
+            image.count_free();
+
+            image.write_inode(
+                FILE_INO,
+                RawInode {
+                    mode: 0x8000,
+                    ..Default::default()
+                },
+            );
+            image
+        }
+
+        /// The first block that is free for data.
+        fn first_free_bid(&self) -> u32 {
+            self.inode_bitmap_bid + 1
+        }
+
+        /// Where the descriptor of `group` is, in the block after the super block.
+        fn descriptor_offset(&self, group: usize) -> usize {
+            (self.first_data_block as usize + 1) * self.block_size
+                + group * size_of::<RawGroupDescriptor>()
+        }
+
+        /// Splits the blocks and inodes evenly into two groups. The second group's
+        /// bitmaps and inode table start at `bitmaps_bid`.
+        fn split_into_two_groups(&mut self, bitmaps_bid: u32) {
+            let offset = EXT2_FIRST_SUPERBLOCK_OFFSET;
+            let mut super_block =
+                RawSuperBlock::from_bytes(&self.disk[offset..offset + size_of::<RawSuperBlock>()]);
+            super_block.blocks_per_group = super_block.blocks_count.div_ceil(2);
+            super_block.inodes_per_group = NR_INODES / 2;
+            self.write(offset, super_block.as_bytes());
+
+            let descriptor = self.descriptor_offset(1);
+            self.write(descriptor, &bitmaps_bid.to_le_bytes());
+            self.write(descriptor + 4, &(bitmaps_bid + 1).to_le_bytes());
+            self.write(descriptor + 8, &(bitmaps_bid + 2).to_le_bytes());
+            // The bitmaps and the inode table are the first blocks of the group.
+            let offset = bitmaps_bid as usize * self.block_size;
+            self.disk[offset] |= 0b111;
+            self.count_free();
+        }
+
+        /// Sets the free counts in the descriptor of each group from its bitmaps, as
+        /// `mkfs` would.
+        fn count_free(&mut self) {
+            let offset = EXT2_FIRST_SUPERBLOCK_OFFSET;
+            let super_block =
+                RawSuperBlock::from_bytes(&self.disk[offset..offset + size_of::<RawSuperBlock>()]);
+            let nr_free = |bitmap_bid: u32, nr_bits: u32| {
+                let bitmap = &self.disk[bitmap_bid as usize * self.block_size..];
+                (0..nr_bits as usize)
+                    .filter(|&bit| bitmap[bit / 8] & (1 << (bit % 8)) == 0)
+                    .count() as u16
+            };
+            let nr_groups = (super_block.blocks_count - super_block.first_data_block)
+                .div_ceil(super_block.blocks_per_group) as usize;
+            let mut counts = Vec::new();
+            for group in 0..nr_groups {
+                let descriptor = self.descriptor_offset(group);
+                let bitmap_bid = |offset: usize| {
+                    u32::from_le_bytes(self.disk[offset..offset + 4].try_into().unwrap())
+                };
+                let group_start =
+                    super_block.first_data_block + group as u32 * super_block.blocks_per_group;
+                let nr_blocks = super_block
+                    .blocks_per_group
+                    .min(super_block.blocks_count - group_start);
+                let nr_inodes = super_block
+                    .inodes_per_group
+                    .min(super_block.inodes_count - group as u32 * super_block.inodes_per_group);
+                counts.push((
+                    descriptor,
+                    nr_free(bitmap_bid(descriptor), nr_blocks),
+                    nr_free(bitmap_bid(descriptor + 4), nr_inodes),
+                ));
+            }
+            // The free counts come after the two bitmap and the inode table pointers.
+            for (descriptor, free_blocks, free_inodes) in counts {
+                self.write(descriptor + 12, &free_blocks.to_le_bytes());
+                self.write(descriptor + 14, &free_inodes.to_le_bytes());
+            }
+        }
+
+        fn write(&mut self, offset: usize, bytes: &[u8]) {
+            self.disk[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        /// Marks a block used in the block bitmap, which starts from the first data block.
+        fn use_block(&mut self, bid: u32) {
+            let bit = (bid - self.first_data_block) as usize;
+            let offset = self.block_bitmap_bid as usize * self.block_size + bit / 8;
+            self.disk[offset] |= 1 << (bit % 8);
+            self.count_free();
+        }
+
+        fn write_inode(&mut self, ino: u32, raw_inode: RawInode) {
+            let offset =
+                self.inode_table_bid as usize * self.block_size + (ino as usize - 1) * INODE_SIZE;
+            self.write(offset, raw_inode.as_bytes());
+        }
+
+        /// Writes a block of directory entries for the file, each taking an equal share
+        /// of the block.
+        fn write_dir_block(&mut self, bid: u32, names: &[&[u8]]) {
+            let entries: Vec<_> = names.iter().map(|name| (*name, FILE_INO, 1)).collect();
+            self.write_dir_entries(bid, &entries);
+        }
+
+        /// Writes a block of directory entries, given as name, inode number and file
+        /// type, each taking an equal share of the block.
+        fn write_dir_entries(&mut self, bid: u32, entries: &[(&[u8], u32, u8)]) {
+            let rec_len = self.block_size / entries.len();
+            for (i, (name, ino, file_type)) in entries.iter().enumerate() {
+                let offset = bid as usize * self.block_size + i * rec_len;
+                self.write(offset, &ino.to_le_bytes());
+                self.write(offset + 4, &(rec_len as u16).to_le_bytes());
+                self.write(offset + 6, &[name.len() as u8, *file_type]);
+                self.write(offset + 8, name);
+            }
+        }
+
+        fn mount(self) -> Arc<Ext2Fs> {
+            crate::drivers::blk::init();
+            Ext2Fs::new(Arc::new(MemBlockDevice::new(self.disk))).unwrap()
+        }
+    }
+
+    #[ktest]
+    fn directory_entries_past_direct_blocks() {
+        const NR_DIR_BLOCKS: u32 = 13;
+        const ENTRIES_PER_BLOCK: usize = 16;
+        const INDIRECT_BID: u32 = FIRST_DATA_BID + NR_DIR_BLOCKS;
+
+        let mut image = Image::new(INDIRECT_BID + 1);
+        let mut block_ptrs = BlockPointers::default();
+        for block_index in 0..NR_DIR_BLOCKS {
+            let bid = FIRST_DATA_BID + block_index;
+            let names: Vec<_> = (0..ENTRIES_PER_BLOCK)
+                .map(|i| format!("file-{}-{}", block_index, i))
+                .collect();
+            let names: Vec<_> = names.iter().map(|name| name.as_bytes()).collect();
+            image.write_dir_block(bid, &names);
+
+            match block_ptrs.direct_pointers.get_mut(block_index as usize) {
+                Some(ptr) => *ptr = Ext2Bid(bid),
+                None => {
+                    let offset = INDIRECT_BID as usize * BLOCK_SIZE
+                        + (block_index as usize - 12) * size_of::<Ext2Bid>();
+                    image.write(offset, &bid.to_le_bytes());
+                }
+            }
+        }
+        block_ptrs.single_indirect_pointer = Ext2Bid(INDIRECT_BID);
+        image.write_inode(
+            ROOT_INO,
+            RawInode {
+                mode: 0x4000,
+                size_low: NR_DIR_BLOCKS * BLOCK_SIZE as u32,
+                block_ptrs,
+                ..Default::default()
+            },
+        );
+
+        let root = image.mount().root_inode();
+        assert_eq!(root.size(), NR_DIR_BLOCKS as usize * BLOCK_SIZE);
+        for block_index in 0..NR_DIR_BLOCKS {
+            for i in 0..ENTRIES_PER_BLOCK {
+                let name = format!("file-{}-{}", block_index, i);
+                assert!(root.lookup(&name).is_ok(), "{} is missing", name);
+            }
+        }
+    }
+
+    #[ktest]
+    fn names_are_matched_as_bytes() {
+        const NON_UTF8: &[u8] = b"caf\xe9";
+        const FULL: &[u8] = b"abcd";
+        const DIR_BID: u32 = FIRST_DATA_BID;
+
+        let mut image = Image::new(DIR_BID + 1);
+        image.write_dir_block(DIR_BID, &[NON_UTF8, FULL]);
+        // Nothing but the length ends a name, so let garbage follow the second one.
+        let full_end = DIR_BID as usize * BLOCK_SIZE + BLOCK_SIZE / 2 + 8 + FULL.len();
+        image.write(full_end, &[0xff; 4]);
+
+        let mut block_ptrs = BlockPointers::default();
+        block_ptrs.direct_pointers[0] = Ext2Bid(DIR_BID);
+        image.write_inode(
+            ROOT_INO,
+            RawInode {
+                mode: 0x4000,
+                size_low: BLOCK_SIZE as u32,
+                block_ptrs,
+                ..Default::default()
+            },
+        );
+
+        let fs = image.mount();
+        let root = fs.lookup_inode(ROOT_INO).unwrap();
+        assert!(root.lookup_bytes(NON_UTF8).is_ok());
+        assert!(root.lookup_bytes(b"caf").is_err());
+        assert!(root.lookup("abcd").is_ok());
+        assert!(root.lookup("abc").is_err());
+    }
+
+    #[ktest]
+    fn flushed_writes_survive_a_crash() {
+        let image = Image::new(FIRST_DATA_BID);
+        crate::drivers::blk::init();
+        let device = Arc::new(MemBlockDevice::new(image.disk));
+        let fs = Ext2Fs::new(device.clone()).unwrap();
+        let file = fs.lookup_inode(FILE_INO).unwrap();
+
+        file.set_times(None, Some(Duration::from_secs(1000))).unwrap();
+        file.sync_all().unwrap();
+        // The crash comes before this one is flushed.
+        file.set_times(None, Some(Duration::from_secs(2000))).unwrap();
+        assert_eq!(file.metadata().mtime(), Duration::from_secs(2000));
+
+        let fs = Ext2Fs::new(Arc::new(MemBlockDevice::new(device.crash()))).unwrap();
+        let file = fs.lookup_inode(FILE_INO).unwrap();
+        assert_eq!(file.metadata().mtime(), Duration::from_secs(1000));
+    }
+
+    #[ktest]
+    fn sync_data_leaves_the_timestamps_behind() {
+        let image = Image::new(FIRST_DATA_BID);
+        crate::drivers::blk::init();
+        let device = Arc::new(MemBlockDevice::new(image.disk));
+        let fs = Ext2Fs::new(device.clone()).unwrap();
+        let file = fs.lookup_inode(FILE_INO).unwrap();
+        let mtime_after_crash = || {
+            let fs = Ext2Fs::new(Arc::new(MemBlockDevice::new(device.crash()))).unwrap();
+            fs.lookup_inode(FILE_INO).unwrap().metadata().mtime()
+        };
+
+        file.set_times(None, Some(Duration::from_secs(1000)))
+            .unwrap();
+        file.sync_all().unwrap();
+        // Only the timestamp changed, which `fdatasync` has no need to write.
+        file.set_times(None, Some(Duration::from_secs(2000)))
+            .unwrap();
+        file.sync_data().unwrap();
+        assert_eq!(mtime_after_crash(), Duration::from_secs(1000));
+
+        file.sync_all().unwrap();
+        assert_eq!(mtime_after_crash(), Duration::from_secs(2000));
+    }
+
+    #[ktest]
+    fn sync_range_writes_back_only_the_range() {
+        const DIR_BID: u32 = FIRST_DATA_BID;
+
+        let mut image = Image::new(DIR_BID + 4);
+        image.write_dir_block(DIR_BID, &[b"hello.txt"]);
+        image.use_block(DIR_BID);
+        let mut block_ptrs = BlockPointers::default();
+        block_ptrs.direct_pointers[0] = Ext2Bid(DIR_BID);
+        image.write_inode(
+            ROOT_INO,
+            RawInode {
+                mode: 0x4000,
+                size_low: BLOCK_SIZE as u32,
+                block_ptrs,
+                ..Default::default()
+            },
+        );
+        crate::drivers::blk::init();
+        let device = Arc::new(MemBlockDevice::new(image.disk));
+        let fs = Ext2Fs::new(device.clone()).unwrap();
+        let file = fs.root_inode().lookup("hello.txt").unwrap();
+
+        // The first and the third block, with a hole in between.
+        for (block_index, byte) in [(0, 1u8), (2, 2u8)] {
+            let data = [byte; BLOCK_SIZE];
+            file.write_at(
+                block_index * BLOCK_SIZE,
+                VmReader::from(&data[..]).to_fallible(),
+            )
+            .unwrap();
+        }
+        // A range that ends partway into the first block still takes all of it, and
+        // only it.
+        let nr_written = device.nr_written();
+        file.sync_range(10..100).unwrap();
+        assert_eq!(device.nr_written() - nr_written, BLOCK_SIZE / SECTOR_SIZE);
+
+        let crashed = Ext2Fs::new(Arc::new(MemBlockDevice::new(device.crash()))).unwrap();
+        let crashed = crashed.root_inode().lookup("hello.txt").unwrap();
+        let mut buf = vec![0xffu8; 3 * BLOCK_SIZE];
+        let len = crashed
+            .read_at(0, VmWriter::from(buf.as_mut_slice()).to_fallible())
+            .unwrap();
+        assert_eq!(len, buf.len());
+        assert!(buf[..BLOCK_SIZE].iter().all(|&byte| byte == 1));
+        assert!(buf[2 * BLOCK_SIZE..].iter().all(|&byte| byte == 0));
+
+        // The rest is left for a later sync, which does not write the first again.
+        let nr_written = device.nr_written();
+        file.sync_range(0..usize::MAX).unwrap();
+        assert_eq!(device.nr_written() - nr_written, BLOCK_SIZE / SECTOR_SIZE);
+    }
+
+    #[ktest]
+    fn read_file_past_direct_blocks() {
+        const NR_FILE_BLOCKS: u32 = 14;
+        const INDIRECT_BID: u32 = FIRST_DATA_BID + NR_FILE_BLOCKS;
+        // Every block is filled with its own index.
+        let byte_at = |offset: usize| (offset / BLOCK_SIZE) as u8;
+
+        let mut image = Image::new(INDIRECT_BID + 1);
+        let mut block_ptrs = BlockPointers::default();
+        for block_index in 0..NR_FILE_BLOCKS {
+            let bid = FIRST_DATA_BID + block_index;
+            image.write(bid as usize * BLOCK_SIZE, &[block_index as u8; BLOCK_SIZE]);
+            match block_ptrs.direct_pointers.get_mut(block_index as usize) {
+                Some(ptr) => *ptr = Ext2Bid(bid),
+                None => {
+                    let offset = INDIRECT_BID as usize * BLOCK_SIZE
+                        + (block_index as usize - 12) * size_of::<Ext2Bid>();
+                    image.write(offset, &bid.to_le_bytes());
+                }
+            }
+        }
+        block_ptrs.single_indirect_pointer = Ext2Bid(INDIRECT_BID);
+        // The file ends partway into its last block.
+        let file_size = NR_FILE_BLOCKS as usize * BLOCK_SIZE - 100;
+        image.write_inode(
+            FILE_INO,
+            RawInode {
+                mode: 0x8000,
+                size_low: file_size as u32,
+                block_ptrs,
+                ..Default::default()
+            },
+        );
+
+        let file = image.mount().lookup_inode(FILE_INO).unwrap();
+        let mut buf = vec![0u8; file_size + BLOCK_SIZE];
+        let len = file
+            .read_at(0, VmWriter::from(buf.as_mut_slice()).to_fallible())
+            .unwrap();
+        assert_eq!(len, file_size);
+        assert!((0..file_size).all(|offset| buf[offset] == byte_at(offset)));
+
+        // A read from the middle of a sector, across the first indirect block.
+        let offset = 12 * BLOCK_SIZE - 1000;
+        let mut buf = vec![0u8; 2 * BLOCK_SIZE];
+        let len = file
+            .read_at(offset, VmWriter::from(buf.as_mut_slice()).to_fallible())
+            .unwrap();
+        assert_eq!(len, buf.len());
+        assert!((0..len).all(|i| buf[i] == byte_at(offset + i)));
+    }
+
+    #[ktest]
+    fn write_then_read_back() {
+        const DIR_BID: u32 = FIRST_DATA_BID;
+
+        let mut image = Image::new(DIR_BID + 4);
+        image.write_dir_block(DIR_BID, &[b"hello.txt"]);
+        image.use_block(DIR_BID);
+        let mut block_ptrs = BlockPointers::default();
+        block_ptrs.direct_pointers[0] = Ext2Bid(DIR_BID);
+        image.write_inode(
+            ROOT_INO,
+            RawInode {
+                mode: 0x4000,
+                size_low: BLOCK_SIZE as u32,
+                block_ptrs,
+                ..Default::default()
+            },
+        );
+        crate::drivers::blk::init();
+        let device = Arc::new(MemBlockDevice::new(image.disk));
+        let fs = Ext2Fs::new(device.clone()).unwrap();
+        let file = fs.root_inode().lookup("hello.txt").unwrap();
+
+        let read = |file: &dyn crate::fs::Inode, len| {
+            let mut buf = vec![0xffu8; len];
+            let len = file
+                .read_at(0, VmWriter::from(buf.as_mut_slice()).to_fallible())
+                .unwrap();
+            buf.truncate(len);
+            buf
+        };
+        // The empty file gets a block for its first write.
+        let write = |offset, bytes: &[u8]| {
+            file.write_at(offset, VmReader::from(bytes).to_fallible())
+                .unwrap()
+        };
+        assert_eq!(write(0, b"Hello, ext2!"), 12);
+        assert_eq!(write(7, b"world"), 5);
+        assert_eq!(file.size(), 12);
+        assert_eq!(read(&*file, 64), b"Hello, world");
+
+        // A write past the end leaves a hole of zeros, with a block only for the end.
+        let far = 2 * BLOCK_SIZE + 10;
+        assert_eq!(write(far, b"end"), 3);
+        assert_eq!(file.size(), far + 3);
+        let buf = read(&*file, far + 64);
+        assert_eq!(buf.len(), far + 3);
+        assert!(buf[12..far].iter().all(|&byte| byte == 0));
+        assert_eq!(&buf[far..], b"end");
+
+        // Once synced, it is all on the device.
+        file.sync_data().unwrap();
+        let fs = Ext2Fs::new(device).unwrap();
+        let file = fs.root_inode().lookup("hello.txt").unwrap();
+        assert_eq!(read(&*file, 12), b"Hello, world");
+    }
+
+    #[ktest]
+    fn failed_write_past_the_end_keeps_the_size() {
+        const DIR_BID: u32 = FIRST_DATA_BID;
+
+        let mut image = Image::new(DIR_BID + 4);
+        image.write_dir_block(DIR_BID, &[b"hello.txt"]);
+        image.use_block(DIR_BID);
+        let mut block_ptrs = BlockPointers::default();
+        block_ptrs.direct_pointers[0] = Ext2Bid(DIR_BID);
+        image.write_inode(
+            ROOT_INO,
+            RawInode {
+                mode: 0x4000,
+                size_low: BLOCK_SIZE as u32,
+                block_ptrs,
+                ..Default::default()
+            },
+        );
+        let fs = image.mount();
+        let file = fs.root_inode().lookup("hello.txt").unwrap();
+        file.write_at(0, VmReader::from(&b"data"[..]).to_fallible())
+            .unwrap();
+
+        // Past the direct blocks, the first block already fails.
+        let far = NR_DIRECT * BLOCK_SIZE;
+        let err = file
+            .write_at(far, VmReader::from(&b"end"[..]).to_fallible())
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::EFBIG);
+        assert_eq!(file.size(), 4);
+
+        // Neither does writing nothing make the file longer.
+        let written = file
+            .write_at(far, VmReader::from(&b""[..]).to_fallible())
+            .unwrap();
+        assert_eq!(written, 0);
+        assert_eq!(file.size(), 4);
+    }
+
+    #[ktest]
+    fn create_then_look_up() {
+        const DIR_BID: u32 = FIRST_DATA_BID;
+        // With long names, enough to spill out of the first directory block, but not
+        // to run out of inodes.
+        const NR_FILES: usize = 16;
+        let name = |i| format!("{:x<250}", i);
+
+        let mut image = Image::new(DIR_BID + 4);
+        image.write_dir_block(DIR_BID, &[b"hello.txt"]);
+        image.use_block(DIR_BID);
+        let mut block_ptrs = BlockPointers::default();
+        block_ptrs.direct_pointers[0] = Ext2Bid(DIR_BID);
+        image.write_inode(
+            ROOT_INO,
+            RawInode {
+                mode: 0x4000,
+                size_low: BLOCK_SIZE as u32,
+                block_ptrs,
+                ..Default::default()
+            },
+        );
+        crate::drivers::blk::init();
+        let device = Arc::new(MemBlockDevice::new(image.disk));
+        let fs = Ext2Fs::new(device.clone()).unwrap();
+        let root = fs.root_inode();
+
+        // An owner with ids past 16 bits, which are split on disk.
+        let (mode, uid, gid) = (0o640, 0x1_0003, 100);
+        let file = root
+            .create("new.txt", InodeType::File, mode, uid, gid)
+            .unwrap();
+        assert_eq!((file.typ(), file.size()), (InodeType::File, 0));
+        // The new inode is the one that lookups give out.
+        let found = root.lookup("new.txt").unwrap();
+        assert!(core::ptr::addr_eq(Arc::as_ptr(&file), Arc::as_ptr(&found)));
+        assert_eq!(
+            root.create("new.txt", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+                .err()
+                .unwrap()
+                .code,
+            Errno::EEXIST
+        );
+
+        for i in 0..NR_FILES {
+            root.create(&name(i), InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+                .unwrap();
+        }
+        assert_eq!(root.size(), 2 * BLOCK_SIZE);
+
+        // It is all on the device, and the old entry kept its name.
+        let fs = Ext2Fs::new(device).unwrap();
+        let root = fs.root_inode();
+        assert!(root.lookup("hello.txt").is_ok());
+        let file = root.lookup("new.txt").unwrap();
+        assert_eq!(file.typ(), InodeType::File);
+        let metadata = file.metadata();
+        assert_eq!(metadata.mode(), mode);
+        assert_eq!((metadata.uid(), metadata.gid()), (uid, gid));
+        for i in 0..NR_FILES {
+            assert!(root.lookup(&name(i)).is_ok());
+        }
+    }
+
+    #[ktest]
+    fn create_directory() {
+        const DIR_BID: u32 = FIRST_DATA_BID;
+        let same = |a: &Arc<dyn crate::fs::Inode>, b: &Arc<dyn crate::fs::Inode>| {
+            core::ptr::addr_eq(Arc::as_ptr(a), Arc::as_ptr(b))
+        };
+
+        let mut image = Image::new(DIR_BID + 4);
+        image.write_dir_entries(DIR_BID, &[(b".", ROOT_INO, 2), (b"..", ROOT_INO, 2)]);
+        image.use_block(DIR_BID);
+        let mut block_ptrs = BlockPointers::default();
+        block_ptrs.direct_pointers[0] = Ext2Bid(DIR_BID);
+        image.write_inode(
+            ROOT_INO,
+            RawInode {
+                mode: 0x4000 | 0o755,
+                size_low: BLOCK_SIZE as u32,
+                hard_links: 2,
+                block_ptrs,
+                ..Default::default()
+            },
+        );
+        crate::drivers::blk::init();
+        let device = Arc::new(MemBlockDevice::new(image.disk));
+        let fs = Ext2Fs::new(device.clone()).unwrap();
+        let root = fs.root_inode();
+
+        let subdir = root
+            .create("subdir", InodeType::Directory, 0o755, ROOT_ID, ROOT_ID)
+            .unwrap();
+        assert_eq!(subdir.typ(), InodeType::Directory);
+        assert_eq!(subdir.size(), BLOCK_SIZE);
+        assert_eq!(subdir.metadata().mode(), 0o755);
+        assert!(same(&root.lookup("subdir").unwrap(), &subdir));
+        assert!(same(&subdir.lookup(".").unwrap(), &subdir));
+        assert!(same(&subdir.lookup("..").unwrap(), &root));
+        let names: Vec<_> = subdir
+            .readdir(None)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.name, entry.typ))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (".".to_string(), InodeType::Directory),
+                ("..".to_string(), InodeType::Directory)
+            ]
+        );
+        // The new `..` is another link to the root.
+        assert_eq!(fs.inode_ptr(ROOT_INO).read().hard_links, 3);
+        assert_eq!(fs.descriptor_ptr(0).read().dirs_count, 1);
+
+        // The new directory takes entries of its own.
+        let file = subdir
+            .create("file", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        assert!(same(&subdir.lookup("file").unwrap(), &file));
+        assert_eq!(subdir.size(), BLOCK_SIZE);
+
+        // It is all on the device.
+        let fs = Ext2Fs::new(device).unwrap();
+        let root = fs.root_inode();
+        let subdir = root.lookup("subdir").unwrap();
+        assert!(same(&subdir.lookup("..").unwrap(), &root));
+        assert!(same(&subdir.lookup(".").unwrap(), &subdir));
+        assert_eq!(subdir.lookup("file").unwrap().typ(), InodeType::File);
+    }
+
+    #[ktest]
+    fn inode_in_second_group() {
+        const NR_BLOCKS: u32 = 16;
+        // The second group starts halfway, with its bitmaps and then its inode table.
+        const SECOND_GROUP_BID: u32 = NR_BLOCKS / 2;
+        const SECOND_INODE_TABLE_BID: u32 = SECOND_GROUP_BID + 2;
+        // The fourth inode of the second group, and of the first.
+        const SECOND_GROUP_INO: u32 = NR_INODES / 2 + 4;
+        const FIRST_GROUP_INO: u32 = 4;
+
+        let mut image = Image::new(NR_BLOCKS);
+        image.split_into_two_groups(SECOND_GROUP_BID);
+        let raw_inode = |size_low| RawInode {
+            mode: 0x8000,
+            size_low,
+            ..Default::default()
+        };
+        image.write_inode(FIRST_GROUP_INO, raw_inode(100));
+        let offset = SECOND_INODE_TABLE_BID as usize * BLOCK_SIZE + 3 * INODE_SIZE;
+        image.write(offset, raw_inode(200).as_bytes());
+
+        let fs = image.mount();
+        assert_eq!(fs.block_groups.len(), 2);
+        assert_eq!(fs.lookup_inode(FIRST_GROUP_INO).unwrap().size(), 100);
+        assert_eq!(fs.lookup_inode(SECOND_GROUP_INO).unwrap().size(), 200);
+        assert_eq!(
+            fs.lookup_inode(NR_INODES + 1).err().unwrap().code,
+            Errno::ENOENT
+        );
+        assert_eq!(fs.lookup_inode(0).err().unwrap().code, Errno::EINVAL);
+
+        // Once the first group is out of inodes, they come from the second.
+        let free_in_first_group = NR_INODES / 2 - FILE_INO;
+        for _ in 0..free_in_first_group {
+            assert!(fs.alloc_inode().unwrap() <= NR_INODES / 2);
+        }
+        assert_eq!(fs.alloc_inode().unwrap(), NR_INODES / 2 + 1);
+    }
+
+    #[ktest]
+    fn full_groups_are_skipped() {
+        const NR_BLOCKS: u32 = 16;
+        const SECOND_GROUP_BID: u32 = NR_BLOCKS / 2;
+
+        // The first group has free blocks and inodes in its bitmaps, but its
+        // descriptor counts none.
+        let mut image = Image::new(NR_BLOCKS);
+        image.split_into_two_groups(SECOND_GROUP_BID);
+        let descriptor = image.descriptor_offset(0);
+        image.write(descriptor + 12, &0u16.to_le_bytes());
+        image.write(descriptor + 14, &0u16.to_le_bytes());
+
+        let fs = image.mount();
+        assert_eq!(fs.descriptor_ptr(1).read().free_blocks_count, 5);
+        // Past the second group's bitmaps and inode table.
+        assert_eq!(fs.alloc_block().unwrap(), Ext2Bid(SECOND_GROUP_BID + 3));
+        assert_eq!(fs.alloc_inode().unwrap(), NR_INODES / 2 + 1);
+        assert_eq!(fs.descriptor_ptr(1).read().free_blocks_count, 4);
+    }
+
+    /// Reads a file through an indirect block, then writes a new one, on an image
+    /// with `block_size` blocks.
+    fn read_and_write_with_block_size(block_size: usize) {
+        const NR_FILE_BLOCKS: u32 = 14;
+        // Every block of the file is filled with its own index.
+        let byte_at = |offset: usize| (offset / block_size) as u8;
+
+        let mut image = Image::with_block_size(64, block_size);
+        let dir_bid = image.first_free_bid();
+        let indirect_bid = dir_bid + 1 + NR_FILE_BLOCKS;
+        image.write_dir_block(dir_bid, &[b"hello.txt"]);
+        let mut block_ptrs = BlockPointers::default();
+        block_ptrs.direct_pointers[0] = Ext2Bid(dir_bid);
+        image.write_inode(
+            ROOT_INO,
+            RawInode {
+                mode: 0x4000,
+                size_low: block_size as u32,
+                block_ptrs,
+                ..Default::default()
+            },
+        );
+        let mut block_ptrs = BlockPointers::default();
+        for block_index in 0..NR_FILE_BLOCKS {
+            let bid = dir_bid + 1 + block_index;
+            image.write(
+                bid as usize * block_size,
+                &vec![block_index as u8; block_size],
+            );
+            match block_ptrs.direct_pointers.get_mut(block_index as usize) {
+                Some(ptr) => *ptr = Ext2Bid(bid),
+                None => {
+                    let offset = indirect_bid as usize * block_size
+                        + (block_index as usize - 12) * size_of::<Ext2Bid>();
+                    image.write(offset, &bid.to_le_bytes());
+                }
+            }
+        }
+        block_ptrs.single_indirect_pointer = Ext2Bid(indirect_bid);
+        let file_size = NR_FILE_BLOCKS as usize * block_size;
+        image.write_inode(
+            FILE_INO,
+            RawInode {
+                mode: 0x8000,
+                size_low: file_size as u32,
+                block_ptrs,
+                ..Default::default()
+            },
+        );
+        for bid in dir_bid..=indirect_bid {
+            image.use_block(bid);
+        }
+        crate::drivers::blk::init();
+        let device = Arc::new(MemBlockDevice::new(image.disk));
+        let fs = Ext2Fs::new(device.clone()).unwrap();
+        let root = fs.root_inode();
+
+        let read = |file: &dyn crate::fs::Inode, len| {
+            let mut buf = vec![0xffu8; len];
+            let len = file
+                .read_at(0, VmWriter::from(buf.as_mut_slice()).to_fallible())
+                .unwrap();
+            buf.truncate(len);
+            buf
+        };
+        let buf = read(&*root.lookup("hello.txt").unwrap(), file_size + 64);
+        assert_eq!(buf.len(), file_size);
+        assert!((0..file_size).all(|offset| buf[offset] == byte_at(offset)));
+
+        // A new file spanning a few blocks, which must not land on the old ones.
+        let data: Vec<u8> = (0..3 * block_size + 5).map(|i| i as u8).collect();
+        let file = root
+            .create("new.txt", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        let len = file
+            .write_at(0, VmReader::from(data.as_slice()).to_fallible())
+            .unwrap();
+        assert_eq!(len, data.len());
+
+        file.sync_data().unwrap();
+        let fs = Ext2Fs::new(device).unwrap();
+        let root = fs.root_inode();
+        assert_eq!(
+            read(&*root.lookup("new.txt").unwrap(), data.len() + 64),
+            data
+        );
+        let buf = read(&*root.lookup("hello.txt").unwrap(), file_size);
+        assert!((0..file_size).all(|offset| buf[offset] == byte_at(offset)));
+    }
+
+    #[ktest]
+    fn block_size_1k() {
+        read_and_write_with_block_size(1024);
+    }
+
+    #[ktest]
+    fn block_size_2k() {
+        read_and_write_with_block_size(2048);
+    }
+
+    #[ktest]
+    fn symlinks_inline_and_in_a_block() {
+        const DIR_BID: u32 = FIRST_DATA_BID;
+        const TARGET_BID: u32 = DIR_BID + 1;
+        const FAST_INO: u32 = FILE_INO + 1;
+        const SLOW_INO: u32 = FILE_INO + 2;
+        const LOOP_INO: u32 = FILE_INO + 3;
+        // The directory entry file type of a symlink.
+        const SYMLINK: u8 = 7;
+        let symlink = |target: &[u8], block_ptrs| RawInode {
+            mode: 0xA1FF,
+            size_low: target.len() as u32,
+            block_ptrs,
+            ..Default::default()
+        };
+
+        let mut image = Image::new(TARGET_BID + 1);
+        image.write_dir_entries(
+            DIR_BID,
+            &[
+                (b"hello.txt", FILE_INO, 1),
+                (b"fast", FAST_INO, SYMLINK),
+                (b"slow", SLOW_INO, SYMLINK),
+                (b"loop", LOOP_INO, SYMLINK),
+            ],
+        );
+        let mut block_ptrs = BlockPointers::default();
+        block_ptrs.direct_pointers[0] = Ext2Bid(DIR_BID);
+        image.write_inode(
+            ROOT_INO,
+            RawInode {
+                mode: 0x4000,
+                size_low: BLOCK_SIZE as u32,
+                block_ptrs,
+                ..Default::default()
+            },
+        );
+
+        // A short target sits in the block pointers themselves.
+        let inline = |target: &[u8]| {
+            let mut bytes = [0u8; size_of::<BlockPointers>()];
+            bytes[..target.len()].copy_from_slice(target);
+            BlockPointers::from_bytes(&bytes)
+        };
+        image.write_inode(FAST_INO, symlink(b"hello.txt", inline(b"hello.txt")));
+        image.write_inode(LOOP_INO, symlink(b"loop", inline(b"loop")));
+        // One too long for them goes in a block.
+        let long_target = format!("{}hello.txt", "./".repeat(40));
+        image.write(TARGET_BID as usize * BLOCK_SIZE, long_target.as_bytes());
+        let mut block_ptrs = BlockPointers::default();
+        block_ptrs.direct_pointers[0] = Ext2Bid(TARGET_BID);
+        image.write_inode(SLOW_INO, symlink(long_target.as_bytes(), block_ptrs));
+
+        let fs = image.mount();
+        let root = fs.root_inode();
+        let file = fs.lookup_inode(FILE_INO).unwrap();
+        let fast = root.lookup("fast").unwrap();
+        assert_eq!(fast.typ(), InodeType::SymbolLink);
+        assert_eq!(fast.read_link().unwrap(), "hello.txt");
+        assert_eq!(
+            root.lookup("slow").unwrap().read_link().unwrap(),
+            long_target
+        );
+        assert_eq!(file.read_link().err().unwrap().code, Errno::EINVAL);
+
+        // Path lookups follow them, up to a point.
+        let resolve = |path: &str| PathString::new(path.to_string()).lookup(root.as_ref());
+        for path in ["fast", "slow"] {
+            let found = resolve(path).unwrap();
+            assert!(core::ptr::addr_eq(Arc::as_ptr(&found), Arc::as_ptr(&file)));
+        }
+        assert_eq!(resolve("loop").err().unwrap().code, Errno::ELOOP);
+    }
+}
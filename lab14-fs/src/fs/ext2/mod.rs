@@ -6,7 +6,7 @@ use core::fmt::Debug;
 use core::ops::Add;
 
 use alloc::sync::Weak;
-use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec, vec::Vec};
 use log::{debug, info};
 use ostd::Pod;
 use ostd::{early_println, sync::Mutex};
@@ -20,13 +20,14 @@ use crate::{
     fs::{
         FileSystem,
         ext2::{
-            block_group::BlockGroup,
+            block_group::{BlockGroup, RawGroupDescriptor},
             inode::Inode,
             super_block::{RawSuperBlock, SuperBlock},
         },
     },
 };
 
+mod bitmap;
 mod block_group;
 mod dir_entry;
 mod inode;
@@ -42,6 +43,8 @@ pub struct Ext2Fs {
     block_groups: Vec<BlockGroup>,
 
     inode_cache: Mutex<BTreeMap<u32, Arc<Inode>>>,
+    /// Held while a bitmap and the free counts that go with it are updated.
+    alloc_lock: Mutex<()>,
     inodes_per_group: u32,
     blocks_per_group: u32,
     inode_size: usize,
@@ -63,19 +66,20 @@ impl Ext2Fs {
 
         let super_block = SuperBlock::from(raw_super_block);
 
-        // We currently only support exactly one block group.
-        assert!(super_block.inodes_per_group == super_block.inodes_count);
-        assert!(super_block.blocks_per_group == super_block.blocks_count);
-        // We currently only support 4KB block size.
-        assert!(super_block.block_size == 4096);
-
-        let first_group_bid = super_block.group_descriptor_table_bid();
-
-        let raw_descriptor: block_group::RawGroupDescriptor = blk_device
-            .read_val(first_group_bid.0 as usize * super_block.block_size as usize / SECTOR_SIZE);
-
-        let mut blk_groups = Vec::new();
-        blk_groups.push(BlockGroup::new(raw_descriptor));
+        // The group descriptor table follows the super block, one descriptor per group.
+        // The groups cover the blocks from the first data block on.
+        let nr_groups = (super_block.blocks_count - super_block.first_data_block)
+            .div_ceil(super_block.blocks_per_group) as usize;
+        let table_offset =
+            super_block.group_descriptor_table_bid().0 as usize * super_block.block_size as usize;
+        let blk_groups = (0..nr_groups)
+            .map(|group| {
+                let offset = table_offset + group * size_of::<RawGroupDescriptor>();
+                let raw_descriptor: RawGroupDescriptor =
+                    blk_device.read_val_offset(offset / SECTOR_SIZE, offset % SECTOR_SIZE);
+                BlockGroup::new(raw_descriptor)
+            })
+            .collect();
 
         let fs = Arc::new_cyclic(|fs| Ext2Fs {
             blk_device,
@@ -85,6 +89,7 @@ impl Ext2Fs {
             inode_size: super_block.inode_size as usize,
             super_block,
             inode_cache: Mutex::new(BTreeMap::new()),
+            alloc_lock: Mutex::new(()),
             block_groups: blk_groups,
             self_ref: fs.clone(),
         });
@@ -93,7 +98,10 @@ impl Ext2Fs {
     }
 
     fn lookup_inode(&self, inode_number: u32) -> Result<Arc<Inode>> {
-        let idx = inode_number - 1;
+        // Inode numbers start from 1, and 0 stands for no inode at all.
+        let idx = inode_number
+            .checked_sub(1)
+            .ok_or(Error::new(crate::error::Errno::EINVAL))?;
         if let Some(inode) = self.inode_cache.lock().get(&inode_number) {
             return Ok(inode.clone());
         }
@@ -102,11 +110,31 @@ impl Ext2Fs {
             return Err(Error::new(crate::error::Errno::ENOENT));
         }
 
+        let inode = Inode::new(
+            self.inode_ptr(inode_number),
+            inode_number,
+            (idx / self.inodes_per_group) as usize,
+            self.self_ref.clone(),
+        );
+
+        // Someone else may have read it in the meantime; there must only be one.
+        Ok(self
+            .inode_cache
+            .lock()
+            .entry(inode_number)
+            .or_insert(inode)
+            .clone())
+    }
+
+    /// Points to where the inode is in the inode table of its group.
+    fn inode_ptr(&self, inode_number: u32) -> SectorPtr<RawInode> {
+        let idx = inode_number - 1;
         let inode_table_block =
             self.block_groups[(idx / self.inodes_per_group) as usize].inode_table_start_bid();
+        let idx_in_group = idx % self.inodes_per_group;
         let inodes_per_block = (self.block_size / self.inode_size) as u32;
-        let bid_offset = Ext2Bid::from(idx / inodes_per_block);
-        let offset_in_block = idx % inodes_per_block;
+        let bid_offset = Ext2Bid::from(idx_in_group / inodes_per_block);
+        let offset_in_block = idx_in_group % inodes_per_block;
         let bid_num = inode_table_block + bid_offset;
 
         debug!(
@@ -119,17 +147,150 @@ impl Ext2Fs {
             self.bid_to_sector(bid_num) + offset_in_block as usize * self.inode_size / SECTOR_SIZE;
         let sector_offset = (offset_in_block as usize * self.inode_size) % SECTOR_SIZE;
 
-        let sector_ptr: SectorPtr<RawInode> =
-            SectorPtr::new(sector, sector_offset, &self.blk_device);
+        SectorPtr::new(sector, sector_offset, &self.blk_device)
+    }
 
-        let inode = Inode::new(
-            sector_ptr,
-            inode_number,
-            (idx / self.inodes_per_group) as usize,
-            self.self_ref.clone(),
+    /// Allocates a free block from the first group that has one, and zeroes it.
+    fn alloc_block(&self) -> Result<Ext2Bid> {
+        let _guard = self.alloc_lock.lock();
+
+        // Bit `i` of a group stands for the `i`-th block from the start of the group,
+        // and the groups start from the first data block.
+        let first_data_block = self.super_block.first_data_block;
+        for (group, block_group) in self.block_groups.iter().enumerate() {
+            // A full group is skipped without reading its bitmap.
+            if self.descriptor_ptr(group).read().free_blocks_count == 0 {
+                continue;
+            }
+            let group_start = first_data_block + group as u32 * self.blocks_per_group;
+            let mut bitmap = block_group.block_bitmap(self, self.nr_blocks_in_group(group));
+            let Some(bit) = bitmap.alloc_first_free() else {
+                continue;
+            };
+            bitmap.write_back();
+            self.add_free(group, -1, 0);
+
+            let bid = Ext2Bid(group_start + bit as u32);
+            self.blk_device
+                .write_bytes(self.bid_to_sector(bid), &vec![0u8; self.block_size]);
+            return Ok(bid);
+        }
+        Err(Error::new(crate::error::Errno::ENOSPC))
+    }
+
+    /// Allocates a free inode from the first group that has one. Filling it in is up
+    /// to the caller.
+    fn alloc_inode(&self) -> Result<u32> {
+        let _guard = self.alloc_lock.lock();
+
+        // Bit `i` of a group stands for the `i`-th inode of the group, and inode
+        // numbers start from 1.
+        for (group, block_group) in self.block_groups.iter().enumerate() {
+            if self.descriptor_ptr(group).read().free_inodes_count == 0 {
+                continue;
+            }
+            let group_start = group as u32 * self.inodes_per_group;
+            let mut bitmap = block_group.inode_bitmap(self, self.nr_inodes_in_group(group));
+            let Some(bit) = bitmap.alloc_first_free() else {
+                continue;
+            };
+            bitmap.write_back();
+            self.add_free(group, 0, -1);
+
+            return Ok(group_start + bit as u32 + 1);
+        }
+        Err(Error::new(crate::error::Errno::ENOSPC))
+    }
+
+    /// Gives back an inode from [`Self::alloc_inode`] that never made it into a
+    /// directory.
+    fn free_inode(&self, inode_number: u32) {
+        let _guard = self.alloc_lock.lock();
+
+        let idx = inode_number - 1;
+        let group = (idx / self.inodes_per_group) as usize;
+        let mut bitmap =
+            self.block_groups[group].inode_bitmap(self, self.nr_inodes_in_group(group));
+        bitmap.free((idx % self.inodes_per_group) as usize);
+        bitmap.write_back();
+        self.add_free(group, 0, 1);
+    }
+
+    /// Gives back a block from [`Self::alloc_block`] that never made it into a file.
+    fn free_block(&self, bid: Ext2Bid) {
+        let _guard = self.alloc_lock.lock();
+
+        let idx = bid.0 - self.super_block.first_data_block;
+        let group = (idx / self.blocks_per_group) as usize;
+        let mut bitmap =
+            self.block_groups[group].block_bitmap(self, self.nr_blocks_in_group(group));
+        bitmap.free((idx % self.blocks_per_group) as usize);
+        bitmap.write_back();
+        self.add_free(group, 1, 0);
+    }
+
+    /// How many blocks `group` has, which is fewer than the others for the last group
+    /// when the blocks do not divide evenly.
+    fn nr_blocks_in_group(&self, group: usize) -> usize {
+        let group_start = self.super_block.first_data_block + group as u32 * self.blocks_per_group;
+        self.blocks_per_group
+            .min(self.super_block.blocks_count - group_start) as usize
+    }
+
+    /// How many inodes `group` has, which is fewer than the others for the last group
+    /// when the inodes do not divide evenly.
+    fn nr_inodes_in_group(&self, group: usize) -> usize {
+        let group_start = group as u32 * self.inodes_per_group;
+        self.inodes_per_group
+            .min(self.super_block.inodes_count - group_start) as usize
+    }
+
+    /// Counts the inode `inode_number` as a directory in its group's descriptor.
+    fn add_dir(&self, inode_number: u32) {
+        let _guard = self.alloc_lock.lock();
+
+        let group = ((inode_number - 1) / self.inodes_per_group) as usize;
+        let descriptor_ptr = self.descriptor_ptr(group);
+        let mut raw_descriptor = descriptor_ptr.read();
+        raw_descriptor.dirs_count += 1;
+        descriptor_ptr.write(&raw_descriptor);
+    }
+
+    /// Adds what was freed in `group`, or takes off what was allocated, to the free
+    /// counts in the super block and the group's descriptor. The caller holds
+    /// `alloc_lock`.
+    fn add_free(&self, group: usize, blocks: i32, inodes: i32) {
+        let super_block_ptr: SectorPtr<RawSuperBlock> = SectorPtr::new(
+            EXT2_FIRST_SUPERBLOCK_OFFSET / SECTOR_SIZE,
+            EXT2_FIRST_SUPERBLOCK_OFFSET % SECTOR_SIZE,
+            &self.blk_device,
         );
+        let mut raw_super_block = super_block_ptr.read();
+        raw_super_block.free_blocks_count = raw_super_block
+            .free_blocks_count
+            .saturating_add_signed(blocks);
+        raw_super_block.free_inodes_count = raw_super_block
+            .free_inodes_count
+            .saturating_add_signed(inodes);
+        super_block_ptr.write(&raw_super_block);
+
+        let descriptor_ptr = self.descriptor_ptr(group);
+        let mut raw_descriptor = descriptor_ptr.read();
+        raw_descriptor.free_blocks_count = raw_descriptor
+            .free_blocks_count
+            .saturating_add_signed(blocks as i16);
+        raw_descriptor.free_inodes_count = raw_descriptor
+            .free_inodes_count
+            .saturating_add_signed(inodes as i16);
+        descriptor_ptr.write(&raw_descriptor);
+    }
 
-        Ok(inode)
+    /// Points to the descriptor of `group` in the group descriptor table.
+    fn descriptor_ptr(&self, group: usize) -> SectorPtr<RawGroupDescriptor> {
+        let offset = self.bid_to_sector(self.super_block.group_descriptor_table_bid())
+            * SECTOR_SIZE
+            + group * size_of::<RawGroupDescriptor>();
+        SectorPtr::new(offset / SECTOR_SIZE, offset % SECTOR_SIZE, &self.blk_device)
     }
 
     pub fn bid_to_sector(&self, bid: Ext2Bid) -> usize {
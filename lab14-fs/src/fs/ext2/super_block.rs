@@ -32,8 +32,11 @@ pub struct SuperBlock {
 }
 
 impl SuperBlock {
+    /// The group descriptor table is in the block right after the super block. The
+    /// super block is at byte 1024, which is block 1 with 1KB blocks and block 0
+    /// with larger ones; `first_data_block` says which.
     pub fn group_descriptor_table_bid(&self) -> Ext2Bid {
-        (self.idx * self.blocks_per_group + 1).into()
+        (self.first_data_block + self.idx * self.blocks_per_group + 1).into()
     }
 }
 
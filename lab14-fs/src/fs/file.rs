@@ -1,60 +1,194 @@
 use alloc::{sync::Arc, vec};
 use ostd::{
     early_print,
-    mm::{Fallible, FallibleVmRead, VmReader, VmWriter},
+    mm::{FallibleVmRead, VmReader, VmWriter},
+    sync::WaitQueue,
 };
 
 use crate::{
-    console::receive_str,
+    console,
     error::{Errno, Error, Result},
-    fs::Inode,
+    fs::{DirEntry, Inode, pipe::Pipe},
 };
 use core::str;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+bitflags::bitflags! {
+    /// The events that `poll` waits for and reports.
+    pub struct PollEvents: u16 {
+        const POLLIN = 0x0001;
+        const POLLOUT = 0x0004;
+    }
+}
+
+/// Woken whenever a file may have become ready for an event, so that `poll` checks
+/// its files again. Files have no queue in common otherwise.
+pub static POLL_QUEUE: WaitQueue = WaitQueue::new();
 
 pub trait FileLike: Sync + Send {
     fn read(&self, writer: VmWriter) -> Result<usize>;
     fn write(&self, reader: VmReader) -> Result<usize>;
 
+    /// Hands up to `len` bytes of the data to `consume`, and takes only as many as it
+    /// returns out of the file. No other read gets in between, but the file is not
+    /// locked while `consume` runs, so it may sleep. It waits for data like
+    /// [`Self::read`], but with `nonblocking`, it fails with `EAGAIN` instead, as if the
+    /// file were nonblocking.
+    ///
+    /// Only pipes keep data around to be read again.
+    fn read_with(
+        &self,
+        _len: usize,
+        _nonblocking: bool,
+        _consume: &mut dyn FnMut(&[u8]) -> Result<usize>,
+    ) -> Result<usize> {
+        Err(Error::new(Errno::EINVAL))
+    }
+
+    /// The pipe that this file is either end of.
+    fn as_pipe(&self) -> Option<&Pipe> {
+        None
+    }
+
     fn as_inode(&self) -> Option<Arc<dyn Inode>> {
         None
     }
+
+    /// Whether this file is the console, which accepts the terminal `ioctl`s.
+    fn is_terminal(&self) -> bool {
+        false
+    }
+
+    /// Returns the subset of `events` that the file is ready for right now.
+    ///
+    /// Files that never block are always ready. The others wake [`POLL_QUEUE`]
+    /// whenever they may have become ready.
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        events
+    }
+
+    /// Switches the file between blocking and nonblocking (`O_NONBLOCK`) reads.
+    ///
+    /// Files that never block ignore this.
+    fn set_nonblocking(&self, _nonblocking: bool) {}
+
+    fn is_nonblocking(&self) -> bool {
+        false
+    }
+
+    /// Passes the next entries of the directory to `fill` until it returns `false`, and
+    /// returns how many it took. The following call picks up after the last one taken.
+    fn read_dir(&self, _fill: &mut dyn FnMut(&DirEntry) -> bool) -> Result<usize> {
+        Err(Error::new(Errno::ENOTDIR))
+    }
+}
+
+pub struct Stdin {
+    nonblocking: AtomicBool,
+}
+
+/// "End of Transmission", sent by Ctrl-D.
+const EOT: u8 = 4;
+
+impl Stdin {
+    pub fn new() -> Self {
+        Stdin {
+            nonblocking: AtomicBool::new(false),
+        }
+    }
+
+    /// Reads one line of input, pulling characters from `next_byte` until a return or a Ctrl-D,
+    /// or until it has none left to give.
+    ///
+    /// A Ctrl-D is taken from the input but not passed on. What has been typed before it
+    /// is handed over without a trailing newline, so on an empty line the read returns 0,
+    /// which the reader sees as EOF. Running out of input ends the line early the same way.
+    /// Once `buf` is full, the rest of the line stays in the input for the next read.
+    fn read_line<R>(mut buf: VmWriter, mut next_byte: R) -> usize
+    where
+        R: FnMut() -> Option<u8>,
+    {
+        let mut read_len = 0;
+
+        while buf.has_avail() {
+            let Some(byte) = next_byte() else {
+                break;
+            };
+            let Some(ascii_char) = core::ascii::Char::from_u8(byte) else {
+                continue;
+            };
+            // Ctrl-D: end the read, dropping the character.
+            if ascii_char.to_u8() == EOT {
+                break;
+            }
+            read_len += 1;
+            // Return.
+            if ascii_char.to_u8() == 13 {
+                early_print!("{}", ascii_char);
+                // We convert "Return" to "New Line" (Ascii 10)
+                buf.write_val::<u8>(&10).unwrap();
+                break;
+            }
+            // Output the character, although we cannot use backspace and other special char :)
+            early_print!("{}", ascii_char);
+            buf.write_val(&ascii_char.to_u8()).unwrap();
+        }
+        read_len
+    }
 }
 
-pub struct Stdin;
+impl Default for Stdin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl FileLike for Stdin {
-    fn read(&self, mut buf: VmWriter) -> Result<usize> {
-        let mut read_len = 0;
-        let mut need_return = false;
-
-        while !need_return {
-            let mut callback = |mut reader: VmReader<Fallible>| {
-                while reader.has_remain() {
-                    if let Some(ascii_char) =
-                        core::ascii::Char::from_u8(reader.read_val::<u8>().unwrap())
-                    {
-                        read_len += 1;
-                        // Return.
-                        if ascii_char.to_u8() == 13 {
-                            need_return = true;
-                            // We convert "Return" to "New Line" (Ascii 10)
-                            buf.write_val::<u8>(&10).unwrap();
-                        }
-                        // Output the character, although we cannot use backspace and other special char :)
-                        early_print!("{}", ascii_char);
-                        buf.write_val(&ascii_char.to_u8()).unwrap();
-                    }
-                }
+    fn read(&self, buf: VmWriter) -> Result<usize> {
+        if !self.is_nonblocking() {
+            // What was read before a signal arrived is still handed over.
+            let mut interrupted = None;
+            let read_len = Self::read_line(buf, || {
+                console::read_byte()
+                    .map_err(|err| interrupted = Some(err))
+                    .ok()
+            });
+            return match interrupted {
+                Some(err) if read_len == 0 => Err(err),
+                _ => Ok(read_len),
             };
+        }
 
-            receive_str(&mut callback);
+        // Hand over only what has been typed so far, even if it is not a whole line.
+        if !console::has_input() {
+            return Err(Error::new(Errno::EAGAIN));
         }
-        Ok(read_len)
+        Ok(Self::read_line(buf, console::try_read_byte))
     }
 
     fn write(&self, _buf: VmReader) -> Result<usize> {
         Err(Error::new(Errno::ENOSYS))
     }
+
+    fn is_terminal(&self) -> bool {
+        true
+    }
+
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        if console::has_input() {
+            events & PollEvents::POLLIN
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Relaxed)
+    }
 }
 
 pub struct Stdout;
@@ -73,6 +207,10 @@ impl FileLike for Stdout {
 
         Ok(buffer.len())
     }
+
+    fn is_terminal(&self) -> bool {
+        true
+    }
 }
 
 pub struct Stderr;
@@ -91,4 +229,113 @@ impl FileLike for Stderr {
 
         Ok(buffer.len())
     }
+
+    fn is_terminal(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::mm::VmWriter;
+    use ostd::prelude::ktest;
+
+    use super::{FileLike, Stdin};
+    use crate::console;
+    use crate::error::Errno;
+    use crate::ktest_util::new_process;
+    use crate::process::Process;
+
+    fn read_stdin(buf: &mut [u8]) -> usize {
+        Stdin::new()
+            .read(VmWriter::from(buf).to_fallible())
+            .unwrap()
+    }
+
+    #[ktest]
+    fn ctrl_d_on_empty_line_is_eof() {
+        let mut buf = [0u8; 16];
+        console::push_input(b"hello\r");
+        assert_eq!(read_stdin(&mut buf), 6);
+        assert_eq!(&buf[..6], b"hello\n");
+
+        console::push_input(b"\x04");
+        assert_eq!(read_stdin(&mut buf), 0);
+    }
+
+    #[ktest]
+    fn ctrl_d_on_non_empty_line_flushes_input() {
+        let mut buf = [0u8; 16];
+        console::push_input(b"hello\x04");
+        assert_eq!(read_stdin(&mut buf), 5);
+        assert_eq!(&buf[..5], b"hello");
+    }
+
+    #[ktest]
+    fn typed_ahead_input_is_kept() {
+        // Everything is typed before anyone reads.
+        console::push_input(b"ab");
+        console::push_input(b"c\rde");
+        console::push_input(b"f\r");
+
+        let mut buf = [0u8; 16];
+        assert_eq!(read_stdin(&mut buf), 4);
+        assert_eq!(&buf[..4], b"abc\n");
+        assert_eq!(read_stdin(&mut buf), 4);
+        assert_eq!(&buf[..4], b"def\n");
+    }
+
+    #[ktest]
+    fn short_read_leaves_rest_of_line() {
+        console::push_input(b"ab\r");
+
+        let mut buf = [0u8; 1];
+        assert_eq!(read_stdin(&mut buf), 1);
+        assert_eq!(buf[0], b'a');
+
+        let mut buf = [0u8; 16];
+        assert_eq!(read_stdin(&mut buf), 2);
+        assert_eq!(&buf[..2], b"b\n");
+    }
+
+    #[ktest]
+    fn nonblocking_read_takes_partial_line() {
+        let stdin = Stdin::new();
+        stdin.set_nonblocking(true);
+        let mut buf = [0u8; 16];
+
+        // Half a line has been typed, and the read does not wait for the rest.
+        console::push_input(b"ab");
+        let read_len = stdin.read(VmWriter::from(&mut buf as &mut [u8]).to_fallible());
+        assert_eq!(read_len.unwrap(), 2);
+        assert_eq!(&buf[..2], b"ab");
+
+        let err = stdin
+            .read(VmWriter::from(&mut buf as &mut [u8]).to_fallible())
+            .unwrap_err();
+        assert_eq!(err.code, Errno::EAGAIN);
+    }
+
+    #[ktest]
+    fn ctrl_c_interrupts_foreground_group() {
+        let shell = new_process("hello_world");
+        let job = shell.fork(&UserContext::default()).unwrap();
+        shell.set_pgid(job.pid(), 0).unwrap();
+        console::set_foreground_pgid(job.pgid());
+
+        // Ctrl-C goes to the job and is not passed through as input.
+        console::push_input(b"\x03\r");
+        console::handle_interrupt();
+        let mut buf = [0u8; 16];
+        assert_eq!(read_stdin(&mut buf), 1);
+        assert_eq!(buf[0], b'\n');
+
+        job.handle_pending_signals(&mut UserContext::default());
+        shell.handle_pending_signals(&mut UserContext::default());
+        assert_eq!(job.killed_by(), Some(crate::process::signal::SIGINT));
+        assert!(!shell.is_zombie());
+
+        console::set_foreground_pgid(0);
+    }
 }
@@ -7,16 +7,29 @@ pub type FileDescriptor = i32;
 /// Represents an open file entry
 pub struct FileEntry {
     file: Arc<dyn FileLike>,
+    /// Whether the descriptor is closed when the process calls `execve`.
+    close_on_exec: bool,
 }
 
 impl FileEntry {
     pub fn new(file: Arc<dyn FileLike>) -> Self {
-        FileEntry { file }
+        FileEntry {
+            file,
+            close_on_exec: false,
+        }
     }
 
     pub fn file(&self) -> &Arc<dyn FileLike> {
         &self.file
     }
+
+    pub fn close_on_exec(&self) -> bool {
+        self.close_on_exec
+    }
+
+    pub fn set_close_on_exec(&mut self, close_on_exec: bool) {
+        self.close_on_exec = close_on_exec;
+    }
 }
 
 /// File table structure
@@ -44,6 +57,7 @@ impl FileTable {
             if let Some(e) = entry {
                 new_table.push(Some(FileEntry {
                     file: e.file.clone(),
+                    close_on_exec: e.close_on_exec,
                 }));
             } else {
                 new_table.push(None);
@@ -57,15 +71,9 @@ impl FileTable {
 
     pub fn new_with_standard_io() -> Self {
         let mut table = Vec::new();
-        table.push(Some(FileEntry {
-            file: Arc::new(Stdin),
-        }));
-        table.push(Some(FileEntry {
-            file: Arc::new(Stdout),
-        }));
-        table.push(Some(FileEntry {
-            file: Arc::new(Stderr),
-        }));
+        table.push(Some(FileEntry::new(Arc::new(Stdin::new()))));
+        table.push(Some(FileEntry::new(Arc::new(Stdout))));
+        table.push(Some(FileEntry::new(Arc::new(Stderr))));
         FileTable {
             table,
             fds_in_use: 3,
@@ -89,10 +97,25 @@ impl FileTable {
         self.table.get(fd as usize)?.as_ref()
     }
 
+    pub fn get_mut(&mut self, fd: FileDescriptor) -> Option<&mut FileEntry> {
+        self.table.get_mut(fd as usize)?.as_mut()
+    }
+
     /// Closes a file descriptor
     pub fn close(&mut self, fd: FileDescriptor) -> Option<FileEntry> {
         let entry = self.table.get_mut(fd as usize)?.take()?;
         self.fds_in_use -= 1;
         Some(entry)
     }
+
+    /// Closes every descriptor marked close-on-exec. The others are inherited
+    /// by the new program as they are.
+    pub fn close_files_on_exec(&mut self) {
+        for entry in self.table.iter_mut() {
+            if entry.as_ref().is_some_and(|e| e.close_on_exec) {
+                *entry = None;
+                self.fds_in_use -= 1;
+            }
+        }
+    }
 }
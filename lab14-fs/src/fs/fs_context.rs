@@ -0,0 +1,108 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::util::PathString;
+use crate::fs::{Inode, InodeType, Permission, ROOT};
+use crate::process::credentials::Credentials;
+
+/// The umask a process starts with.
+const DEFAULT_UMASK: u16 = 0o022;
+
+/// The working directory and umask of a process.
+///
+/// Processes cloned with `CLONE_FS` share one, so a `chdir` in one is seen by
+/// the others; forked ones get a copy.
+#[derive(Clone)]
+pub struct FsContext {
+    /// The working directory, or `None` for the root directory.
+    cwd: Option<Arc<dyn Inode>>,
+    /// The absolute path of the working directory, with no `.` or `..` in it.
+    cwd_path: String,
+    umask: u16,
+}
+
+impl FsContext {
+    pub fn new() -> Self {
+        Self {
+            cwd: None,
+            cwd_path: String::from("/"),
+            umask: DEFAULT_UMASK,
+        }
+    }
+
+    pub fn cwd(&self) -> Arc<dyn Inode> {
+        match &self.cwd {
+            Some(cwd) => cwd.clone(),
+            None => ROOT.get().unwrap().root_inode(),
+        }
+    }
+
+    pub fn cwd_path(&self) -> &str {
+        &self.cwd_path
+    }
+
+    /// Makes `path` the working directory. A relative `path` starts from the
+    /// current one. Every directory on the way, the last one included, must be
+    /// searchable with `credentials`.
+    pub fn chdir(&mut self, path: &str, credentials: Credentials) -> Result<()> {
+        let cwd_path = self.absolute_path(path);
+        let root = ROOT.get().ok_or(Error::new(Errno::ENOENT))?.root_inode();
+        let cwd = if cwd_path == "/" {
+            root
+        } else {
+            PathString::new(cwd_path.clone())
+                .with_credentials(credentials)
+                .lookup(root.as_ref())?
+        };
+        if cwd.typ() != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+        cwd.metadata()
+            .check_permission(&credentials, Permission::EXEC)?;
+
+        self.cwd = Some(cwd);
+        self.cwd_path = cwd_path;
+        Ok(())
+    }
+
+    pub fn umask(&self) -> u16 {
+        self.umask
+    }
+
+    /// Sets the umask to the permission bits of `umask` and returns the old one.
+    pub fn set_umask(&mut self, umask: u16) -> u16 {
+        core::mem::replace(&mut self.umask, umask & 0o777)
+    }
+
+    /// Returns `path` as an absolute path, with `.` and `..` worked out by name.
+    fn absolute_path(&self, path: &str) -> String {
+        let base = if path.starts_with('/') {
+            ""
+        } else {
+            self.cwd_path.as_str()
+        };
+
+        let mut components = Vec::new();
+        for component in base.split('/').chain(path.split('/')) {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    components.pop();
+                }
+                name => components.push(name),
+            }
+        }
+
+        if components.is_empty() {
+            return String::from("/");
+        }
+        let mut absolute = String::new();
+        for component in components {
+            absolute.push('/');
+            absolute.push_str(component);
+        }
+        absolute
+    }
+}
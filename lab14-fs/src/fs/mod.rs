@@ -3,18 +3,23 @@
 pub mod ext2;
 mod file;
 pub mod file_table;
+mod fs_context;
 pub mod pipe;
 pub mod ramfs;
 pub mod util;
 
-use crate::error::Result;
-use core::{ffi::CStr, time::Duration};
+use crate::error::{Errno, Error, Result};
+use crate::process::credentials::{Credentials, Id, ROOT_ID};
+use core::{ffi::CStr, ops::Range, time::Duration};
 
-use alloc::{boxed::Box, string::String, sync::Arc};
-pub use file::{FileLike, Stderr, Stdin, Stdout};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+pub use file::{FileLike, POLL_QUEUE, PollEvents, Stderr, Stdin, Stdout};
+pub use fs_context::FsContext;
 use ostd::{
     early_println,
     mm::{VmReader, VmWriter},
+    sync::Mutex,
+    timer::Jiffies,
 };
 use spin::Once;
 
@@ -159,14 +164,52 @@ pub trait FileSystem: Send + Sync {
 
 pub trait Inode: Send + Sync {
     fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>>;
-    fn create(&self, name: &str, type_: InodeType) -> Result<Arc<dyn Inode>>;
+    /// Creates `name` in this directory with the permission bits of `mode`, owned by
+    /// the user `uid` and the group `gid`.
+    fn create(
+        &self,
+        name: &str,
+        type_: InodeType,
+        mode: u16,
+        uid: Id,
+        gid: Id,
+    ) -> Result<Arc<dyn Inode>>;
 
     fn read_link(&self) -> Result<String>;
     fn write_link(&self, target: &str) -> Result<()>;
 
+    /// Lists the entries of a directory that come after the one named `after`, or all
+    /// of them if it is `None`.
+    ///
+    /// Entries come in a fixed order, so a listing resumed after the last name it saw
+    /// neither repeats nor skips the entries that are still there, whatever was created
+    /// or removed in between.
+    fn readdir(&self, after: Option<&str>) -> Result<Vec<DirEntry>>;
+
     fn read_at(&self, offset: usize, writer: VmWriter) -> Result<usize>;
     fn write_at(&self, offset: usize, reader: VmReader) -> Result<usize>;
     fn metadata(&self) -> &InodeMeta;
+    /// Sets the permission bits of the mode.
+    fn set_mode(&self, mode: u16) -> Result<()>;
+    /// Sets the access and modification times, leaving those that are `None` alone.
+    fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> Result<()>;
+
+    /// Writes the changes to the bytes in `range` that are only in memory back to
+    /// the device.
+    ///
+    /// Writes go straight to the device for now, so by default there is nothing to do.
+    fn sync_range(&self, _range: Range<usize>) -> Result<()> {
+        Ok(())
+    }
+    /// Writes back the data, and the metadata needed to read it back (the size and
+    /// where the blocks are), but not the timestamps.
+    fn sync_data(&self) -> Result<()> {
+        self.sync_range(0..usize::MAX)
+    }
+    /// Writes back the data and all of the metadata, timestamps included.
+    fn sync_all(&self) -> Result<()> {
+        self.sync_data()
+    }
     fn size(&self) -> usize;
 
     fn typ(&self) -> InodeType;
@@ -179,13 +222,131 @@ pub enum InodeType {
     SymbolLink,
 }
 
+/// An entry of a directory, as listed by [`Inode::readdir`].
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub name: String,
+    pub typ: InodeType,
+}
+
+/// The current time, to stamp files with.
+///
+/// There is no real-time clock, so it counts from boot.
+pub fn now() -> Duration {
+    Jiffies::elapsed().as_duration()
+}
+
+bitflags::bitflags! {
+    /// What a process asks to do with an inode. The bits line up with those of
+    /// each class in the mode.
+    pub struct Permission: u16 {
+        const READ = 0o4;
+        const WRITE = 0o2;
+        const EXEC = 0o1;
+    }
+}
+
 pub struct InodeMeta {
     /// File size
     size: usize,
+    /// Permission bits, without the file type
+    mode: Mutex<u16>,
+    /// Owner user id
+    uid: Id,
+    /// Owner group id
+    gid: Id,
     /// Last access time
-    atime: Duration,
+    atime: Mutex<Duration>,
     /// Last modification time
-    mtime: Duration,
+    mtime: Mutex<Duration>,
     /// Last status change time
-    ctime: Duration,
+    ctime: Mutex<Duration>,
+}
+
+impl InodeMeta {
+    pub fn new(
+        size: usize,
+        mode: u16,
+        uid: Id,
+        gid: Id,
+        atime: Duration,
+        mtime: Duration,
+        ctime: Duration,
+    ) -> Self {
+        Self {
+            size,
+            mode: Mutex::new(mode & 0o7777),
+            uid,
+            gid,
+            atime: Mutex::new(atime),
+            mtime: Mutex::new(mtime),
+            ctime: Mutex::new(ctime),
+        }
+    }
+
+    pub fn mode(&self) -> u16 {
+        *self.mode.lock()
+    }
+
+    pub fn uid(&self) -> Id {
+        self.uid
+    }
+
+    pub fn gid(&self) -> Id {
+        self.gid
+    }
+
+    /// Sets the permission bits and marks the status as changed at `now`.
+    pub fn set_mode(&self, mode: u16, now: Duration) {
+        *self.mode.lock() = mode & 0o7777;
+        *self.ctime.lock() = now;
+    }
+
+    /// Checks that a process with `credentials` may do all of `wanted`.
+    ///
+    /// The owner bits apply to the owner, the group bits to a member of the
+    /// group, and the other bits to everyone else. Root may do anything.
+    pub fn check_permission(&self, credentials: &Credentials, wanted: Permission) -> Result<()> {
+        if credentials.euid() == ROOT_ID {
+            return Ok(());
+        }
+
+        let mode = self.mode();
+        let granted = if credentials.euid() == self.uid {
+            mode >> 6
+        } else if credentials.egid() == self.gid {
+            mode >> 3
+        } else {
+            mode
+        };
+        if Permission::from_bits_truncate(granted).contains(wanted) {
+            Ok(())
+        } else {
+            Err(Error::new(Errno::EACCES))
+        }
+    }
+
+    pub fn atime(&self) -> Duration {
+        *self.atime.lock()
+    }
+
+    pub fn mtime(&self) -> Duration {
+        *self.mtime.lock()
+    }
+
+    pub fn ctime(&self) -> Duration {
+        *self.ctime.lock()
+    }
+
+    /// Sets the access and modification times, leaving those that are `None`
+    /// alone, and marks the status as changed at `now`.
+    pub fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>, now: Duration) {
+        if let Some(atime) = atime {
+            *self.atime.lock() = atime;
+        }
+        if let Some(mtime) = mtime {
+            *self.mtime.lock() = mtime;
+        }
+        *self.ctime.lock() = now;
+    }
 }
@@ -1,135 +1,585 @@
 use crate::error::{Errno, Error, Result};
-use crate::fs::FileLike;
+use crate::fs::{FileLike, POLL_QUEUE, PollEvents};
+use alloc::collections::VecDeque;
 use alloc::sync::Arc;
-use ostd::mm::{FrameAllocOptions, PAGE_SIZE, Segment, VmIo, VmReader, VmWriter};
-use spin::Mutex;
+use alloc::vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use ostd::mm::{
+    FallibleVmRead, FallibleVmWrite, Frame, FrameAllocOptions, PAGE_SIZE, VmReader, VmWriter,
+    io_util::HasVmReaderWriter,
+};
+use ostd::sync::WaitQueue;
+use spin::{Mutex, MutexGuard};
+
+use crate::process::signal::wait_until_interruptible;
 
 pub struct PipeReader {
     pipe: Arc<Pipe>,
+    nonblocking: AtomicBool,
 }
 
 pub struct PipeWriter {
     pipe: Arc<Pipe>,
+    nonblocking: AtomicBool,
 }
 
 pub struct Pipe {
-    buffer: Segment<()>,
     inner: Mutex<Inner>,
+    /// Readers waiting for data or for the write end to close.
+    read_queue: WaitQueue,
+    /// Held by every read that takes data out, from looking at the data until it is
+    /// taken, even where `inner` is unlocked in between.
+    read_lock: ostd::sync::Mutex<()>,
 }
 
 struct Inner {
-    pos: usize,
+    /// The data, oldest first.
+    buffers: VecDeque<PipeBuffer>,
     current_size: usize,
+    /// Whether the read end is gone, so that nothing written can ever be read.
+    reader_closed: bool,
+    /// Whether the write end is gone, so that a read of an empty pipe is at EOF.
+    writer_closed: bool,
+}
+
+/// Some of the bytes in a page of a pipe.
+///
+/// `splice` and `tee` hand pages from one pipe to another instead of copying them,
+/// so a page may be in more than one pipe. Only the pipe that allocated it appends
+/// to it, and only after the last byte it has, where no other pipe has any bytes.
+#[derive(Clone)]
+struct PipeBuffer {
+    frame: Frame<()>,
+    offset: usize,
+    len: usize,
+    /// Whether a write may fill up the rest of the page.
+    can_merge: bool,
 }
 
-const DEFAULT_PIPE_BUF_SIZE: usize = 65536;
+/// How many pages a pipe holds, which is 64KB when they are full.
+const PIPE_BUFFERS: usize = 16;
 
 impl Pipe {
     pub fn new_pair() -> (Arc<PipeReader>, Arc<PipeWriter>) {
-        let buffer = FrameAllocOptions::new()
-            .alloc_segment(DEFAULT_PIPE_BUF_SIZE / PAGE_SIZE)
-            .unwrap();
-
         let pipe = Arc::new(Self {
-            buffer,
             inner: Mutex::new(Inner {
-                pos: 0,
+                buffers: VecDeque::new(),
                 current_size: 0,
+                reader_closed: false,
+                writer_closed: false,
             }),
+            read_queue: WaitQueue::new(),
+            read_lock: ostd::sync::Mutex::new(()),
         });
 
-        let reader = Arc::new(PipeReader { pipe: pipe.clone() });
-        let writer = Arc::new(PipeWriter { pipe });
+        let reader = Arc::new(PipeReader {
+            pipe: pipe.clone(),
+            nonblocking: AtomicBool::new(false),
+        });
+        let writer = Arc::new(PipeWriter {
+            pipe,
+            nonblocking: AtomicBool::new(false),
+        });
 
         (reader, writer)
     }
-}
 
-impl FileLike for PipeWriter {
-    fn read(&self, _writer: VmWriter) -> Result<usize> {
-        Err(Error::new(Errno::EBADF))
+    /// Locks the pipe once a read of up to `len` bytes would not come back empty-handed
+    /// for want of data, i.e. there is data, the write end is closed, or `len` is 0.
+    ///
+    /// If that takes waiting and `nonblocking`, it fails with `EAGAIN` instead.
+    fn lock_readable(&self, len: usize, nonblocking: bool) -> Result<MutexGuard<'_, Inner>> {
+        let can_read = || {
+            let inner = self.inner.lock();
+            inner.is_readable(len).then_some(inner)
+        };
+        match can_read() {
+            Some(inner) => Ok(inner),
+            None if nonblocking => Err(Error::new(Errno::EAGAIN)),
+            None => wait_until_interruptible(&self.read_queue, can_read),
+        }
     }
 
-    fn write(&self, mut reader: VmReader) -> Result<usize> {
-        let mut total_written = 0;
-        let mut inner = self.pipe.inner.lock();
-
+    /// Locks the pipe like [`Self::lock_readable`], for a read that takes data out.
+    ///
+    /// `read_lock` is taken too, and no other such read gets in until its guard is
+    /// dropped, even once the pipe is unlocked.
+    fn lock_for_read(
+        &self,
+        len: usize,
+        nonblocking: bool,
+    ) -> Result<(ostd::sync::MutexGuard<'_, ()>, MutexGuard<'_, Inner>)> {
         loop {
-            let current_size = inner.current_size;
-            if current_size >= DEFAULT_PIPE_BUF_SIZE {
-                break;
+            // Waited for without `read_lock`, so that a signal can still interrupt it.
+            drop(self.lock_readable(len, nonblocking)?);
+            let read_guard = self.read_lock.lock();
+            let inner = self.inner.lock();
+            // Another reader may have emptied the pipe while it was unlocked.
+            if inner.is_readable(len) {
+                return Ok((read_guard, inner));
             }
+        }
+    }
 
-            let write_pos = (inner.pos + current_size) % DEFAULT_PIPE_BUF_SIZE;
+    /// How many more bytes a write can put in the pipe right now.
+    pub fn room(&self) -> usize {
+        self.inner.lock().room()
+    }
 
-            let to_write = core::cmp::min(reader.remain(), DEFAULT_PIPE_BUF_SIZE - current_size);
-            if to_write == 0 {
-                break;
+    /// Locks the pipe like [`Self::lock_readable`], and `other` along with it.
+    ///
+    /// The two are always locked in the same order, so that two transfers the
+    /// opposite way between the same pipes cannot deadlock.
+    fn lock_readable_with<'a>(
+        &'a self,
+        other: &'a Pipe,
+        len: usize,
+        nonblocking: bool,
+    ) -> Result<(MutexGuard<'a, Inner>, MutexGuard<'a, Inner>)> {
+        loop {
+            drop(self.lock_readable(len, nonblocking)?);
+            let (inner, other_inner) = if core::ptr::from_ref(self) < core::ptr::from_ref(other) {
+                let inner = self.inner.lock();
+                (inner, other.inner.lock())
+            } else {
+                let other_inner = other.inner.lock();
+                (self.inner.lock(), other_inner)
+            };
+            // Another reader may have emptied the pipe while it was unlocked.
+            if inner.is_readable(len) {
+                return Ok((inner, other_inner));
             }
+        }
+    }
+
+    /// Moves up to `len` bytes to the end of `other`, handing over the pages they
+    /// are in rather than copying them.
+    ///
+    /// An empty pipe is waited on like by `read`, unless `nonblocking`. A full `other`
+    /// takes nothing, and fails with `EAGAIN` if `other_nonblocking`.
+    pub fn splice_to(
+        &self,
+        other: &Pipe,
+        len: usize,
+        nonblocking: bool,
+        other_nonblocking: bool,
+    ) -> Result<usize> {
+        self.transfer_to(other, len, nonblocking, other_nonblocking, true)
+    }
 
-            let buffer_offset = write_pos % DEFAULT_PIPE_BUF_SIZE;
-            let first_chunk = core::cmp::min(to_write, DEFAULT_PIPE_BUF_SIZE - buffer_offset);
-            let second_chunk = to_write - first_chunk;
+    /// Like [`Self::splice_to`], but leaves the bytes in this pipe too.
+    pub fn tee_to(
+        &self,
+        other: &Pipe,
+        len: usize,
+        nonblocking: bool,
+        other_nonblocking: bool,
+    ) -> Result<usize> {
+        self.transfer_to(other, len, nonblocking, other_nonblocking, false)
+    }
 
-            // Write first chunk
-            self.pipe.buffer.write(buffer_offset, &mut reader).unwrap();
-            total_written += first_chunk;
+    fn transfer_to(
+        &self,
+        other: &Pipe,
+        len: usize,
+        nonblocking: bool,
+        other_nonblocking: bool,
+        consume: bool,
+    ) -> Result<usize> {
+        // Bytes that a `read_with` has yet to take out must not be moved meanwhile.
+        let _read_guard = consume.then(|| self.read_lock.lock());
+        let (mut inner, mut other_inner) = self.lock_readable_with(other, len, nonblocking)?;
+        if other_inner.reader_closed {
+            return Err(Error::new(Errno::EPIPE));
+        }
+        let has_data = len > 0 && inner.current_size > 0;
+        if has_data && other_inner.room() == 0 && other_nonblocking {
+            return Err(Error::new(Errno::EAGAIN));
+        }
 
-            // Write second chunk if needed
-            if second_chunk > 0 {
-                self.pipe.buffer.write(0, &mut reader).unwrap();
-                total_written += second_chunk;
+        let mut transferred = 0;
+        for buffer in inner.buffers.iter() {
+            if transferred == len {
+                break;
+            }
+            let to_transfer = buffer.len.min(len - transferred);
+            let appended = other_inner.append_shared(buffer, to_transfer)?;
+            transferred += appended;
+            if appended < to_transfer {
+                break;
             }
-
-            inner.current_size += to_write;
         }
+        if consume {
+            inner.consume(transferred);
+        }
+        drop(other_inner);
+        drop(inner);
 
-        Ok(total_written)
+        if transferred > 0 {
+            other.read_queue.wake_all();
+            POLL_QUEUE.wake_all();
+        }
+        Ok(transferred)
     }
 }
 
-impl FileLike for PipeReader {
-    fn read(&self, mut writer: VmWriter) -> Result<usize> {
-        let mut total_read = 0;
-        let mut inner = Some(self.pipe.inner.lock());
+impl Inner {
+    fn is_readable(&self, len: usize) -> bool {
+        self.current_size > 0 || self.writer_closed || len == 0
+    }
 
-        let mut inner = inner.take().unwrap();
+    /// How many more bytes fit in the last page.
+    fn tail_room(&self) -> usize {
+        match self.buffers.back() {
+            Some(buffer) if buffer.can_merge => PAGE_SIZE - buffer.offset - buffer.len,
+            _ => 0,
+        }
+    }
 
-        loop {
-            let current_size = inner.current_size;
+    /// How many more bytes a write can put in the pipe.
+    fn room(&self) -> usize {
+        self.tail_room() + (PIPE_BUFFERS - self.buffers.len()) * PAGE_SIZE
+    }
 
-            if current_size == 0 {
+    /// Copies as much of the data as fits into `writer`, oldest first, and leaves
+    /// it in the pipe.
+    fn copy_to(&self, writer: &mut VmWriter) -> Result<usize> {
+        let mut copied = 0;
+        for buffer in self.buffers.iter() {
+            if writer.avail() == 0 {
                 break;
             }
+            let mut page = buffer.frame.reader();
+            page.skip(buffer.offset).limit(buffer.len);
+            copied += writer
+                .write_fallible(&mut page)
+                .map_err(|_| Error::new(Errno::EFAULT))?;
+        }
 
-            let read_pos = inner.pos % DEFAULT_PIPE_BUF_SIZE;
+        Ok(copied)
+    }
 
-            let to_read = core::cmp::min(writer.avail(), current_size);
-            if to_read == 0 {
-                break;
+    /// Copies as much from `reader` as there is room for to the end of the pipe.
+    fn write_from(&mut self, reader: &mut VmReader) -> Result<usize> {
+        let mut written = 0;
+        while reader.has_remain() {
+            if self.tail_room() == 0 {
+                if self.buffers.len() == PIPE_BUFFERS {
+                    break;
+                }
+                let frame = match FrameAllocOptions::new().zeroed(false).alloc_frame() {
+                    Ok(frame) => frame,
+                    Err(_) if written > 0 => break,
+                    Err(_) => return Err(Error::new(Errno::ENOMEM)),
+                };
+                self.buffers.push_back(PipeBuffer {
+                    frame,
+                    offset: 0,
+                    len: 0,
+                    can_merge: true,
+                });
             }
 
-            let buffer_offset = read_pos % DEFAULT_PIPE_BUF_SIZE;
-            let first_chunk = core::cmp::min(to_read, DEFAULT_PIPE_BUF_SIZE - buffer_offset);
-            let second_chunk = to_read - first_chunk;
+            let tail = self.buffers.back_mut().unwrap();
+            let mut page = tail.frame.writer();
+            page.skip(tail.offset + tail.len);
+            let chunk = reader
+                .read_fallible(&mut page)
+                .map_err(|_| Error::new(Errno::EFAULT))?;
+            tail.len += chunk;
+            self.current_size += chunk;
+            written += chunk;
+        }
 
-            // Read first chunk
-            self.pipe.buffer.read(buffer_offset, &mut writer).unwrap();
-            total_read += first_chunk;
+        Ok(written)
+    }
 
-            // Read second chunk if needed
-            if second_chunk > 0 {
-                self.pipe.buffer.read(0, &mut writer).unwrap();
-                total_read += second_chunk;
+    /// Appends the first `len` bytes of `buffer` from another pipe, and returns how
+    /// many fit.
+    ///
+    /// The page itself is shared if there is a free slot for it. Otherwise the bytes
+    /// are copied into what is left of the last page.
+    fn append_shared(&mut self, buffer: &PipeBuffer, len: usize) -> Result<usize> {
+        if self.buffers.len() < PIPE_BUFFERS {
+            self.buffers.push_back(PipeBuffer {
+                len,
+                can_merge: false,
+                ..buffer.clone()
+            });
+            self.current_size += len;
+            return Ok(len);
+        }
+
+        let mut page = buffer.frame.reader();
+        page.skip(buffer.offset).limit(len);
+        self.write_from(&mut page.to_fallible())
+    }
+
+    /// Drops the `len` oldest bytes, which have been read.
+    fn consume(&mut self, mut len: usize) {
+        self.current_size -= len;
+        while len > 0 {
+            let buffer = self.buffers.front_mut().unwrap();
+            let chunk = len.min(buffer.len);
+            buffer.offset += chunk;
+            buffer.len -= chunk;
+            len -= chunk;
+            if buffer.len == 0 {
+                self.buffers.pop_front();
             }
+        }
+    }
+}
 
-            inner.current_size -= to_read;
+impl FileLike for PipeWriter {
+    fn read(&self, _writer: VmWriter) -> Result<usize> {
+        Err(Error::new(Errno::EBADF))
+    }
+
+    fn read_with(
+        &self,
+        _len: usize,
+        _nonblocking: bool,
+        _consume: &mut dyn FnMut(&[u8]) -> Result<usize>,
+    ) -> Result<usize> {
+        Err(Error::new(Errno::EBADF))
+    }
+
+    fn as_pipe(&self) -> Option<&Pipe> {
+        Some(&self.pipe)
+    }
+
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        // A write to a pipe without a reader does not block either, it fails.
+        let inner = self.pipe.inner.lock();
+        if inner.room() > 0 || inner.reader_closed {
+            events & PollEvents::POLLOUT
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    fn write(&self, mut reader: VmReader) -> Result<usize> {
+        let mut inner = self.pipe.inner.lock();
+        if inner.reader_closed {
+            return Err(Error::new(Errno::EPIPE));
+        }
+        if inner.room() == 0 && reader.has_remain() && self.is_nonblocking() {
+            return Err(Error::new(Errno::EAGAIN));
+        }
+
+        let total_written = inner.write_from(&mut reader)?;
+        drop(inner);
+
+        if total_written > 0 {
+            self.pipe.read_queue.wake_all();
+            POLL_QUEUE.wake_all();
         }
+        Ok(total_written)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Relaxed)
+    }
+}
+
+impl PipeReader {
+    /// Locks the pipe like [`Pipe::lock_for_read`], where it fails rather than waits if
+    /// either `nonblocking` or the pipe is.
+    fn lock_for_read(
+        &self,
+        len: usize,
+        nonblocking: bool,
+    ) -> Result<(ostd::sync::MutexGuard<'_, ()>, MutexGuard<'_, Inner>)> {
+        self.pipe
+            .lock_for_read(len, nonblocking || self.is_nonblocking())
+    }
+}
+
+impl FileLike for PipeReader {
+    fn read(&self, mut writer: VmWriter) -> Result<usize> {
+        let (_read_guard, mut inner) = self.lock_for_read(writer.avail(), false)?;
+
+        let read_len = inner.copy_to(&mut writer)?;
+        inner.consume(read_len);
+        drop(inner);
+
+        if read_len > 0 {
+            POLL_QUEUE.wake_all();
+        }
+        Ok(read_len)
+    }
+
+    fn read_with(
+        &self,
+        len: usize,
+        nonblocking: bool,
+        consume: &mut dyn FnMut(&[u8]) -> Result<usize>,
+    ) -> Result<usize> {
+        let (read_guard, inner) = self.lock_for_read(len, nonblocking)?;
+        let mut buf = vec![0u8; len.min(inner.current_size)];
+        let read_len = inner.copy_to(&mut VmWriter::from(buf.as_mut_slice()).to_fallible())?;
+        // `consume` may sleep, so it runs with the pipe unlocked. Writers only add
+        // after the bytes copied, and `read_guard` keeps other reads out.
+        drop(inner);
+        let consumed = consume(&buf[..read_len])?.min(read_len);
+        self.pipe.inner.lock().consume(consumed);
+        drop(read_guard);
+
+        if consumed > 0 {
+            POLL_QUEUE.wake_all();
+        }
+        Ok(consumed)
+    }
 
-        Ok(total_read)
+    fn as_pipe(&self) -> Option<&Pipe> {
+        Some(&self.pipe)
     }
 
     fn write(&self, _reader: VmReader) -> Result<usize> {
         Err(Error::new(Errno::EBADF))
     }
+
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        // Without a writer, a read returns EOF right away.
+        let inner = self.pipe.inner.lock();
+        if inner.current_size > 0 || inner.writer_closed {
+            events & PollEvents::POLLIN
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.pipe.inner.lock().reader_closed = true;
+        POLL_QUEUE.wake_all();
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.pipe.inner.lock().writer_closed = true;
+        self.pipe.read_queue.wake_all();
+        POLL_QUEUE.wake_all();
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::vec::Vec;
+    use ostd::mm::{HasPaddr, VmReader, VmWriter};
+    use ostd::prelude::ktest;
+
+    use super::Pipe;
+    use crate::error::Errno;
+    use crate::fs::{FileLike, PollEvents};
+
+    #[ktest]
+    fn poll_through_lifecycle() {
+        let all = PollEvents::POLLIN | PollEvents::POLLOUT;
+        let (reader, writer) = Pipe::new_pair();
+        assert!(reader.poll(all).is_empty());
+        assert_eq!(writer.poll(all), PollEvents::POLLOUT);
+
+        writer
+            .write(VmReader::from(&b"data"[..]).to_fallible())
+            .unwrap();
+        assert_eq!(reader.poll(all), PollEvents::POLLIN);
+
+        let mut buf = [0u8; 8];
+        assert_eq!(
+            reader
+                .read(VmWriter::from(&mut buf[..]).to_fallible())
+                .unwrap(),
+            4
+        );
+        assert!(reader.poll(all).is_empty());
+
+        // With the writer gone, the reader is at EOF, even when nonblocking.
+        drop(writer);
+        reader.set_nonblocking(true);
+        assert_eq!(reader.poll(all), PollEvents::POLLIN);
+        assert_eq!(
+            reader
+                .read(VmWriter::from(&mut buf[..]).to_fallible())
+                .unwrap(),
+            0
+        );
+    }
+
+    #[ktest]
+    fn write_without_reader() {
+        let (reader, writer) = Pipe::new_pair();
+        drop(reader);
+
+        assert_eq!(writer.poll(PollEvents::POLLOUT), PollEvents::POLLOUT);
+        let err = writer
+            .write(VmReader::from(&b"data"[..]).to_fallible())
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::EPIPE);
+    }
+
+    #[ktest]
+    fn splice_and_tee_hand_over_pages() {
+        let (first_reader, first_writer) = Pipe::new_pair();
+        let (second_reader, second_writer) = Pipe::new_pair();
+        let (third_reader, third_writer) = Pipe::new_pair();
+        let write = |writer: &dyn FileLike, bytes: &[u8]| {
+            writer.write(VmReader::from(bytes).to_fallible()).unwrap()
+        };
+        let read = |reader: &dyn FileLike| {
+            let mut buf = [0u8; 64];
+            let len = reader
+                .read(VmWriter::from(&mut buf[..]).to_fallible())
+                .unwrap();
+            buf[..len].to_vec()
+        };
+        let paddrs = |pipe: &Pipe| -> Vec<_> {
+            let inner = pipe.inner.lock();
+            inner
+                .buffers
+                .iter()
+                .map(|buffer| buffer.frame.paddr())
+                .collect()
+        };
+
+        write(&*first_writer, b"abc");
+        let first_paddrs = paddrs(&first_reader.pipe);
+        let teed = first_reader
+            .pipe
+            .tee_to(&second_writer.pipe, 8, false, false);
+        assert_eq!(teed.unwrap(), 3);
+        assert_eq!(paddrs(&second_reader.pipe), first_paddrs);
+
+        // The first pipe still fills up its page after the shared bytes, but the second
+        // starts a page of its own.
+        write(&*first_writer, b"def");
+        write(&*second_writer, b"ghi");
+        assert_eq!(paddrs(&first_reader.pipe), first_paddrs);
+        assert_eq!(read(&*second_reader), b"abcghi");
+
+        let spliced = first_reader
+            .pipe
+            .splice_to(&third_writer.pipe, 8, false, false);
+        assert_eq!(spliced.unwrap(), 6);
+        assert_eq!(paddrs(&third_reader.pipe), first_paddrs);
+        assert!(paddrs(&first_reader.pipe).is_empty());
+        write(&*third_writer, b"jkl");
+        assert_eq!(read(&*third_reader), b"abcdefjkl");
+    }
 }
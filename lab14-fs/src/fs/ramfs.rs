@@ -1,3 +1,5 @@
+use core::ops::Bound;
+
 use alloc::{
     collections::btree_map::BTreeMap,
     string::{String, ToString},
@@ -10,7 +12,8 @@ use ostd::{
 };
 
 use crate::error::{Errno, Error, Result};
-use crate::fs::{Inode, InodeMeta, InodeType};
+use crate::fs::{DirEntry, Inode, InodeMeta, InodeType};
+use crate::process::credentials::{Id, ROOT_ID};
 
 pub struct RamInode {
     inner: Inner,
@@ -20,30 +23,53 @@ pub struct RamInode {
 enum Inner {
     File(Mutex<Vec<u8>>),
     Directory(RwMutex<BTreeMap<String, Arc<RamInode>>>),
+    /// A symbolic link and the path it points to.
+    SymbolLink(Mutex<String>),
 }
 
 impl RamInode {
-    fn new_file() -> Arc<Self> {
+    fn new_file(mode: u16, uid: Id, gid: Id) -> Arc<Self> {
         Arc::new(RamInode {
             inner: Inner::File(Mutex::new(Vec::new())),
-            metadata: InodeMeta {
-                size: 0,
-                atime: core::time::Duration::new(0, 0),
-                mtime: core::time::Duration::new(0, 0),
-                ctime: core::time::Duration::new(0, 0),
-            },
+            metadata: InodeMeta::new(
+                0,
+                mode,
+                uid,
+                gid,
+                core::time::Duration::new(0, 0),
+                core::time::Duration::new(0, 0),
+                core::time::Duration::new(0, 0),
+            ),
         })
     }
 
-    fn new_directory() -> Arc<Self> {
+    fn new_directory(mode: u16, uid: Id, gid: Id) -> Arc<Self> {
         Arc::new(RamInode {
             inner: Inner::Directory(RwMutex::new(BTreeMap::new())),
-            metadata: InodeMeta {
-                size: 0,
-                atime: core::time::Duration::new(0, 0),
-                mtime: core::time::Duration::new(0, 0),
-                ctime: core::time::Duration::new(0, 0),
-            },
+            metadata: InodeMeta::new(
+                0,
+                mode,
+                uid,
+                gid,
+                core::time::Duration::new(0, 0),
+                core::time::Duration::new(0, 0),
+                core::time::Duration::new(0, 0),
+            ),
+        })
+    }
+
+    fn new_symlink(uid: Id, gid: Id) -> Arc<Self> {
+        Arc::new(RamInode {
+            inner: Inner::SymbolLink(Mutex::new(String::new())),
+            metadata: InodeMeta::new(
+                0,
+                0o777,
+                uid,
+                gid,
+                core::time::Duration::new(0, 0),
+                core::time::Duration::new(0, 0),
+                core::time::Duration::new(0, 0),
+            ),
         })
     }
 }
@@ -97,6 +123,7 @@ impl Inode for RamInode {
         match &self.inner {
             Inner::File(data) => data.lock().len(),
             Inner::Directory(_) => 12,
+            Inner::SymbolLink(target) => target.lock().len(),
         }
     }
 
@@ -104,6 +131,20 @@ impl Inode for RamInode {
         &self.metadata
     }
 
+    fn set_mode(&self, mode: u16) -> Result<()> {
+        self.metadata.set_mode(mode, crate::fs::now());
+        Ok(())
+    }
+
+    fn set_times(
+        &self,
+        atime: Option<core::time::Duration>,
+        mtime: Option<core::time::Duration>,
+    ) -> Result<()> {
+        self.metadata.set_times(atime, mtime, crate::fs::now());
+        Ok(())
+    }
+
     fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
         let Inner::Directory(ref entries) = self.inner else {
             return Err(Error::new(Errno::ENOTDIR));
@@ -115,15 +156,42 @@ impl Inode for RamInode {
         Ok(inode.clone())
     }
 
-    fn create(&self, name: &str, type_: InodeType) -> Result<Arc<dyn Inode>> {
+    fn readdir(&self, after: Option<&str>) -> Result<Vec<DirEntry>> {
+        let Inner::Directory(ref entries) = self.inner else {
+            return Err(Error::new(Errno::ENOTDIR));
+        };
+
+        let entries = entries.read();
+        let start = match after {
+            Some(name) => Bound::Excluded(name),
+            None => Bound::Unbounded,
+        };
+        Ok(entries
+            .range::<str, _>((start, Bound::Unbounded))
+            .map(|(name, inode)| DirEntry {
+                name: name.clone(),
+                typ: inode.typ(),
+            })
+            .collect())
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        type_: InodeType,
+        mode: u16,
+        uid: Id,
+        gid: Id,
+    ) -> Result<Arc<dyn Inode>> {
         let Inner::Directory(ref entries) = self.inner else {
             return Err(Error::new(Errno::ENOTDIR));
         };
 
         let inode = match type_ {
-            InodeType::File => RamInode::new_file(),
-            InodeType::Directory => RamInode::new_directory(),
-            InodeType::SymbolLink => todo!(),
+            InodeType::File => RamInode::new_file(mode, uid, gid),
+            InodeType::Directory => RamInode::new_directory(mode, uid, gid),
+            // The permissions of a symlink are never looked at.
+            InodeType::SymbolLink => RamInode::new_symlink(uid, gid),
         };
 
         entries.write().insert(name.to_string(), inode.clone());
@@ -132,17 +200,27 @@ impl Inode for RamInode {
     }
 
     fn read_link(&self) -> Result<String> {
-        todo!()
+        let Inner::SymbolLink(ref link) = self.inner else {
+            return Err(Error::new(Errno::EINVAL));
+        };
+
+        Ok(link.lock().clone())
     }
 
-    fn write_link(&self, _target: &str) -> Result<()> {
-        todo!()
+    fn write_link(&self, target: &str) -> Result<()> {
+        let Inner::SymbolLink(ref link) = self.inner else {
+            return Err(Error::new(Errno::EINVAL));
+        };
+
+        *link.lock() = target.to_string();
+        Ok(())
     }
 
     fn typ(&self) -> InodeType {
         match &self.inner {
             Inner::Directory(_) => InodeType::Directory,
             Inner::File(_) => InodeType::File,
+            Inner::SymbolLink(_) => InodeType::SymbolLink,
         }
     }
 }
@@ -154,7 +232,7 @@ pub struct RamFS {
 impl RamFS {
     pub fn new() -> Self {
         RamFS {
-            root: RamInode::new_directory(),
+            root: RamInode::new_directory(0o755, ROOT_ID, ROOT_ID),
         }
     }
 }
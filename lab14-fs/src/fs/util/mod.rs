@@ -1,17 +1,24 @@
 pub mod sector_ptr;
 
 use alloc::{string::String, sync::Arc};
+use ostd::sync::Mutex;
 
-use crate::error::Result;
-use crate::fs::{FileLike, Inode, InodeType};
+use crate::error::{Errno, Error, Result};
+use crate::fs::{DirEntry, FileLike, Inode, InodeType, Permission};
+use crate::process::credentials::{Credentials, ROOT_ID};
 
 pub struct FileInode {
     inode: Arc<dyn Inode>,
+    /// The name of the last directory entry handed out by `read_dir`, to resume from.
+    dir_cursor: Mutex<Option<String>>,
 }
 
 impl FileInode {
     pub fn new(inode: Arc<dyn Inode>) -> Self {
-        Self { inode }
+        Self {
+            inode,
+            dir_cursor: Mutex::new(None),
+        }
     }
 }
 
@@ -27,12 +34,35 @@ impl FileLike for FileInode {
     fn as_inode(&self) -> Option<Arc<dyn Inode>> {
         Some(self.inode.clone())
     }
+
+    fn read_dir(&self, fill: &mut dyn FnMut(&DirEntry) -> bool) -> Result<usize> {
+        let mut cursor = self.dir_cursor.lock();
+        let entries = self.inode.readdir(cursor.as_deref())?;
+
+        let mut taken = 0;
+        for entry in entries.iter() {
+            if !fill(entry) {
+                break;
+            }
+            *cursor = Some(entry.name.clone());
+            taken += 1;
+        }
+        Ok(taken)
+    }
 }
 
+/// How many symlinks a lookup follows before it gives up with `ELOOP`.
+const MAX_SYMLINKS: usize = 40;
+
 #[derive(Debug)]
 pub struct PathString {
     inner: String,
     location: usize,
+    /// Whether the path ended with a slash, which only a directory can satisfy.
+    trailing_slash: bool,
+    /// Who the lookup is for, if it is to check they may search each directory
+    /// on the way.
+    credentials: Option<Credentials>,
 }
 
 impl PathString {
@@ -41,6 +71,7 @@ impl PathString {
             s.remove(0);
         }
 
+        let trailing_slash = s.ends_with('/');
         while s.ends_with('/') {
             s.pop();
         }
@@ -48,24 +79,86 @@ impl PathString {
         Self {
             inner: s,
             location: 0,
+            trailing_slash,
+            credentials: None,
         }
     }
 
-    pub fn lookup<'a>(&mut self, start: &'a dyn Inode) -> Result<Arc<dyn Inode>> {
-        let mut current = if self.peek().is_none() {
-            return Ok(start.lookup("")?);
+    /// Makes lookups fail with `EACCES` where a directory on the way may not be
+    /// searched by a process with `credentials`.
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Checks that `dir` may be searched, or also written to if `write` is set.
+    fn check_dir(&self, dir: &dyn Inode, write: bool) -> Result<()> {
+        let Some(credentials) = &self.credentials else {
+            return Ok(());
+        };
+        let wanted = if write {
+            Permission::EXEC | Permission::WRITE
         } else {
-            start.lookup(&self.next().unwrap())?
+            Permission::EXEC
         };
+        dir.metadata().check_permission(credentials, wanted)
+    }
+
+    /// Looks the path up from `start`, following symlinks all the way.
+    pub fn lookup(&mut self, start: &dyn Inode) -> Result<Arc<dyn Inode>> {
+        self.resolve(start, true, &mut 0)
+    }
 
+    /// Looks the path up from `start` like [`Self::lookup`], but stops at the
+    /// last component if it is a symlink rather than following it.
+    pub fn lookup_no_follow(&mut self, start: &dyn Inode) -> Result<Arc<dyn Inode>> {
+        self.resolve(start, false, &mut 0)
+    }
+
+    /// Walks the path from `start`. `links` counts the symlinks followed so far,
+    /// including those of any path that led here.
+    fn resolve(
+        &mut self,
+        start: &dyn Inode,
+        follow_last: bool,
+        links: &mut usize,
+    ) -> Result<Arc<dyn Inode>> {
+        if self.peek().is_none() {
+            return start.lookup("");
+        }
+        // A trailing slash asks for a directory, so a link there is followed too.
+        let follow_last = follow_last || self.trailing_slash;
+
+        let mut current: Option<Arc<dyn Inode>> = None;
         while let Some(name) = self.next() {
-            let next_inode = current.lookup(&name)?;
-            current = next_inode;
+            let dir = current.as_deref().unwrap_or(start);
+            self.check_dir(dir, false)?;
+            let mut next_inode = dir.lookup(&name)?;
+            if next_inode.typ() == InodeType::SymbolLink && (self.peek().is_some() || follow_last) {
+                next_inode = follow_link(dir, next_inode.as_ref(), self.credentials, links)?;
+            }
+            current = Some(next_inode);
+        }
+        let current = current.unwrap();
+
+        if self.trailing_slash && current.typ() != InodeType::Directory {
+            return Err(Error::new(Errno::ENOTDIR));
         }
         Ok(current)
     }
 
-    pub fn create<'a>(&mut self, start: &'a dyn Inode, type_: InodeType) -> Result<Arc<dyn Inode>> {
+    /// Creates the last component of the path, from `start`, with the permission
+    /// bits of `mode`.
+    pub fn create<'a>(
+        &mut self,
+        start: &'a dyn Inode,
+        type_: InodeType,
+        mode: u16,
+    ) -> Result<Arc<dyn Inode>> {
+        if self.trailing_slash && type_ != InodeType::Directory {
+            return Err(Error::new(Errno::EISDIR));
+        }
+
         let mut last_name = String::new();
         let mut current = start;
         let mut next_inode;
@@ -74,11 +167,17 @@ impl PathString {
                 last_name = name;
                 break;
             }
+            self.check_dir(current, false)?;
             next_inode = current.lookup(&name)?;
             current = next_inode.as_ref();
         }
 
-        let new_inode = current.create(&last_name, type_)?;
+        self.check_dir(current, true)?;
+        // The new inode belongs to whoever creates it.
+        let (uid, gid) = self.credentials.map_or((ROOT_ID, ROOT_ID), |credentials| {
+            (credentials.euid(), credentials.egid())
+        });
+        let new_inode = current.create(&last_name, type_, mode, uid, gid)?;
         Ok(new_inode)
     }
 
@@ -87,34 +186,65 @@ impl PathString {
     }
 
     pub fn peek(&self) -> Option<String> {
-        if self.location >= self.inner.len() {
+        self.next_component()
+            .map(|(component, _)| String::from(component))
+    }
+
+    /// Finds the component from the current location on, skipping the empty ones that
+    /// repeated slashes leave. Returns it along with where it ends.
+    fn next_component(&self) -> Option<(&str, usize)> {
+        let rest = self.inner.get(self.location..).unwrap_or("");
+        let start = self.inner.len() - rest.trim_start_matches('/').len();
+        if start >= self.inner.len() {
             return None;
         }
-        let bytes = self.inner.as_bytes();
-        let mut next_location = self.location;
-        while next_location < bytes.len() && bytes[next_location] != b'/' {
-            next_location += 1;
-        }
-        let part = String::from(&self.inner[self.location..next_location]);
-        Some(part)
+        let end = self.inner[start..]
+            .find('/')
+            .map_or(self.inner.len(), |len| start + len);
+        Some((&self.inner[start..end], end))
     }
 }
 
+/// Resolves the symlink `link`, found in `dir`, to what it points to, checking
+/// the directories on the way against `credentials` if there are any.
+fn follow_link(
+    dir: &dyn Inode,
+    link: &dyn Inode,
+    credentials: Option<Credentials>,
+    links: &mut usize,
+) -> Result<Arc<dyn Inode>> {
+    *links += 1;
+    if *links > MAX_SYMLINKS {
+        return Err(Error::new(Errno::ELOOP));
+    }
+
+    let target = link.read_link()?;
+    let root;
+    let start = if target.starts_with('/') {
+        root = crate::fs::ROOT
+            .get()
+            .ok_or(Error::new(Errno::ENOENT))?
+            .root_inode();
+        if target.trim_start_matches('/').is_empty() {
+            return Ok(root);
+        }
+        root.as_ref()
+    } else {
+        dir
+    };
+    let mut target = PathString::new(target);
+    target.credentials = credentials;
+    target.resolve(start, true, links)
+}
+
 impl Iterator for PathString {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.location >= self.inner.len() {
-            return None;
-        }
-        let bytes = self.inner.as_bytes();
-        let mut next_location = self.location;
-        while next_location < bytes.len() && bytes[next_location] != b'/' {
-            next_location += 1;
-        }
-        let part = String::from(&self.inner[self.location..next_location]);
-        self.location = next_location + 1; // Skip the '/'
-        Some(part)
+        let (component, end) = self.next_component()?;
+        let component = String::from(component);
+        self.location = end;
+        Some(component)
     }
 }
 
@@ -123,3 +253,176 @@ impl From<String> for PathString {
         PathString::new(s)
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use alloc::string::{String, ToString};
+    use alloc::sync::Arc;
+    use ostd::prelude::ktest;
+
+    use alloc::vec::Vec;
+
+    use super::{FileInode, PathString};
+    use crate::error::{Errno, Result};
+    use crate::fs::ramfs::RamFS;
+    use crate::fs::{FileLike, FileSystem, Inode, InodeType, Permission};
+    use crate::process::credentials::{Credentials, ROOT_ID};
+
+    fn resolve(root: &dyn Inode, path: &str) -> Result<Arc<dyn Inode>> {
+        PathString::new(path.to_string()).lookup(root)
+    }
+
+    #[ktest]
+    fn trailing_slash_and_empty_components() {
+        let ramfs = RamFS::new();
+        let root = ramfs.root_inode();
+        let dir = root
+            .create("a", InodeType::Directory, 0o755, ROOT_ID, ROOT_ID)
+            .unwrap();
+        let file = dir
+            .create("b", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        root.create("file", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+
+        assert!(Arc::ptr_eq(&resolve(root.as_ref(), "a/").unwrap(), &dir));
+        let err = resolve(root.as_ref(), "file/").err().unwrap();
+        assert_eq!(err.code, Errno::ENOTDIR);
+        assert!(Arc::ptr_eq(&resolve(root.as_ref(), "a//b").unwrap(), &file));
+        assert!(Arc::ptr_eq(
+            &resolve(root.as_ref(), "//a///b").unwrap(),
+            &file
+        ));
+
+        // Names are matched exactly.
+        let err = resolve(root.as_ref(), "A/b").err().unwrap();
+        assert_eq!(err.code, Errno::ENOENT);
+    }
+
+    #[ktest]
+    fn symlinks_are_followed_but_not_forever() {
+        let ramfs = RamFS::new();
+        let root = ramfs.root_inode();
+        let dir = root
+            .create("a", InodeType::Directory, 0o755, ROOT_ID, ROOT_ID)
+            .unwrap();
+        let file = dir
+            .create("b", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        let link = root
+            .create("to_a", InodeType::SymbolLink, 0o777, ROOT_ID, ROOT_ID)
+            .unwrap();
+        link.write_link("a").unwrap();
+        root.create("loop", InodeType::SymbolLink, 0o777, ROOT_ID, ROOT_ID)
+            .unwrap()
+            .write_link("loop")
+            .unwrap();
+
+        assert!(Arc::ptr_eq(
+            &resolve(root.as_ref(), "to_a/b").unwrap(),
+            &file
+        ));
+        assert!(Arc::ptr_eq(&resolve(root.as_ref(), "to_a").unwrap(), &dir));
+        let no_follow = PathString::new("to_a".to_string())
+            .lookup_no_follow(root.as_ref())
+            .unwrap();
+        assert!(Arc::ptr_eq(&no_follow, &link));
+
+        let err = resolve(root.as_ref(), "loop").err().unwrap();
+        assert_eq!(err.code, Errno::ELOOP);
+    }
+
+    #[ktest]
+    fn searching_a_directory_needs_execute() {
+        let ramfs = RamFS::new();
+        let root = ramfs.root_inode();
+        let dir = root
+            .create("a", InodeType::Directory, 0o755, ROOT_ID, ROOT_ID)
+            .unwrap();
+        let file = dir
+            .create("b", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        root.create("to_b", InodeType::SymbolLink, 0o777, ROOT_ID, ROOT_ID)
+            .unwrap()
+            .write_link("a/b")
+            .unwrap();
+        // Both are owned by root, so another user falls in the "other" class.
+        dir.set_mode(0o754).unwrap();
+        let mut user = Credentials::root();
+        user.set_uid(1000).unwrap();
+        let lookup = |path: &str, credentials| {
+            PathString::new(path.to_string())
+                .with_credentials(credentials)
+                .lookup(root.as_ref())
+        };
+
+        for path in ["a/b", "to_b"] {
+            let err = lookup(path, user).err().unwrap();
+            assert_eq!(err.code, Errno::EACCES);
+            assert!(Arc::ptr_eq(
+                &lookup(path, Credentials::root()).unwrap(),
+                &file
+            ));
+        }
+        // Finding the directory itself only needs the root searched.
+        assert!(Arc::ptr_eq(&lookup("a", user).unwrap(), &dir));
+        let err = PathString::new("a/c".to_string())
+            .with_credentials(user)
+            .create(root.as_ref(), InodeType::File, 0o644)
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::EACCES);
+
+        // What the user creates belongs to them.
+        dir.set_mode(0o777).unwrap();
+        let created = PathString::new("a/c".to_string())
+            .with_credentials(user)
+            .create(root.as_ref(), InodeType::File, 0o644)
+            .unwrap();
+        assert_eq!(created.metadata().uid(), user.euid());
+        assert_eq!(created.metadata().gid(), user.egid());
+        dir.set_mode(0o755).unwrap();
+        assert!(Arc::ptr_eq(&lookup("a/b", user).unwrap(), &file));
+        // Reading is allowed to others, writing is not.
+        let meta = file.metadata();
+        assert!(meta.check_permission(&user, Permission::READ).is_ok());
+        let err = meta
+            .check_permission(&user, Permission::WRITE)
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::EACCES);
+    }
+
+    #[ktest]
+    fn listing_survives_create() {
+        let ramfs = RamFS::new();
+        let root = ramfs.root_inode();
+        for name in ["b", "c", "d", "e"] {
+            root.create(name, InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+                .unwrap();
+        }
+        let dir = FileInode::new(root.clone());
+
+        let mut names = Vec::<String>::new();
+        let mut read_some = |max: usize| {
+            dir.read_dir(&mut |entry| {
+                if names.len() >= max {
+                    return false;
+                }
+                names.push(entry.name.clone());
+                true
+            })
+            .unwrap()
+        };
+        assert_eq!(read_some(2), 2);
+
+        // One before the cursor and one after it.
+        root.create("a", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        root.create("f", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        while read_some(usize::MAX) > 0 {}
+
+        assert_eq!(names, ["b", "c", "d", "e", "f"]);
+    }
+}
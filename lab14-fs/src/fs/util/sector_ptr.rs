@@ -1,7 +1,8 @@
 use alloc::sync::{Arc, Weak};
 use ostd::Pod;
+use ostd::mm::VmIo;
 
-use crate::drivers::blk::BlockDevice;
+use crate::drivers::blk::{BlockDevice, SECTOR_SIZE};
 
 pub struct SectorPtr<T: Pod> {
     sector: usize,
@@ -27,4 +28,19 @@ impl<T: Pod> SectorPtr<T> {
             .expect("Block device has been dropped");
         blk_device.read_val_offset::<T>(self.sector, self.offset)
     }
+
+    /// Writes `val` in place, leaving the rest of the sector as it is.
+    pub fn write(&self, val: &T) {
+        let blk_device = self
+            .blk_device
+            .upgrade()
+            .expect("Block device has been dropped");
+        let mut sector = [0u8; SECTOR_SIZE];
+        blk_device
+            .read_one(self.sector)
+            .read_bytes(0, &mut sector)
+            .unwrap();
+        sector[self.offset..self.offset + size_of::<T>()].copy_from_slice(val.as_bytes());
+        blk_device.write_one(self.sector, &sector);
+    }
 }
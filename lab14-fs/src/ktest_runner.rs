@@ -0,0 +1,145 @@
+//! A kernel entry point that runs every `#[ktest]` and reports the results.
+//!
+//! `cargo osdk test` links OSDK's own test kernel, which owns the entry point,
+//! so this one is only built with the `ktest-runner` feature (see `make ktest`).
+//! It prints a line per test and a final summary, and exits QEMU with success
+//! only if every test passed.
+
+use alloc::boxed::Box;
+use core::any::Any;
+
+use ostd::arch::qemu::QemuExitCode;
+use ostd::early_println;
+use ostd::ktest::{KtestError, KtestItem};
+
+/// The pass/fail counts of a test run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+impl Summary {
+    /// The QEMU exit code reporting this run: success only if nothing failed.
+    pub fn exit_code(&self) -> QemuExitCode {
+        if self.failed == 0 {
+            QemuExitCode::Success
+        } else {
+            QemuExitCode::Failed
+        }
+    }
+}
+
+/// Runs `tests` one after another, printing a pass/fail line for each.
+pub fn run_tests(tests: impl Iterator<Item = KtestItem>) -> Summary {
+    let catch_unwind: fn(fn()) -> core::result::Result<(), Box<dyn Any + Send>> =
+        ostd::panic::catch_unwind::<(), fn()>;
+
+    let mut summary = Summary::default();
+    for test in tests {
+        let info = test.info();
+        match test.run(&catch_unwind) {
+            Ok(()) => {
+                early_println!("test {}::{} ... ok", info.module_path, info.fn_name);
+                summary.passed += 1;
+            }
+            Err(err) => {
+                early_println!("test {}::{} ... FAILED", info.module_path, info.fn_name);
+                match err {
+                    KtestError::Panic(info) => early_println!("    {}", info),
+                    KtestError::ShouldPanicButNoPanic => {
+                        early_println!("    expected a panic, but none occurred")
+                    }
+                    KtestError::ExpectedPanicNotMatch(expected, info) => {
+                        early_println!("    expected panic message {:?}, got {}", expected, info)
+                    }
+                    KtestError::Unknown => early_println!("    panicked with an unknown payload"),
+                }
+                summary.failed += 1;
+            }
+        }
+    }
+    summary
+}
+
+#[cfg(feature = "ktest-runner")]
+#[ostd::ktest::main]
+fn main() {
+    use ostd::task::TaskOptions;
+
+    crate::logger::init();
+
+    // Tests may block and yield, so they need a task of their own.
+    TaskOptions::new(|| {
+        let summary = run_tests(ostd::ktest::KtestIter::new());
+        early_println!("{} passed, {} failed", summary.passed, summary.failed);
+        ostd::arch::qemu::exit_qemu(summary.exit_code());
+    })
+    .data(())
+    .spawn()
+    .unwrap();
+}
+
+/// Turns a panic into an unwind carrying the panic's location and message, for
+/// `run_tests` to catch.
+#[cfg(feature = "ktest-runner")]
+#[ostd::ktest::panic_handler]
+fn panic_handler(info: &core::panic::PanicInfo) -> ! {
+    use alloc::string::ToString;
+
+    let location = info.location().unwrap();
+    let payload = ostd::ktest::PanicInfo {
+        message: info.message().to_string(),
+        file: location.file().to_string(),
+        line: location.line() as usize,
+        col: location.column() as usize,
+    };
+    ostd::panic::begin_panic(Box::new(payload.clone()));
+
+    // Only reached if nothing is there to catch the unwind.
+    early_println!("Uncaught panic: {}", payload);
+    ostd::panic::abort();
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::arch::qemu::QemuExitCode;
+    use ostd::ktest::{KtestItem, KtestItemInfo};
+    use ostd::prelude::ktest;
+
+    use super::{Summary, run_tests};
+
+    fn passes() {}
+
+    fn fails() {
+        panic!("deliberate failure");
+    }
+
+    fn item(f: fn(), fn_name: &'static str) -> KtestItem {
+        let info = KtestItemInfo {
+            module_path: module_path!(),
+            fn_name,
+            package: "lab14-fs",
+            source: file!(),
+            line: line!() as usize,
+            col: column!() as usize,
+        };
+        KtestItem::new(f, (false, None), info)
+    }
+
+    #[ktest]
+    fn failing_test_fails_the_run() {
+        let summary = run_tests([item(passes, "passes"), item(fails, "fails")].into_iter());
+        assert_eq!(
+            summary,
+            Summary {
+                passed: 1,
+                failed: 1
+            }
+        );
+        assert_eq!(summary.exit_code(), QemuExitCode::Failed);
+
+        let summary = run_tests([item(passes, "passes")].into_iter());
+        assert_eq!(summary.exit_code(), QemuExitCode::Success);
+    }
+}
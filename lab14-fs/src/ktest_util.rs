@@ -0,0 +1,25 @@
+//! What the `#[ktest]`s have in common: processes to run syscalls in, and the
+//! `mmap` arguments that user programs would take from the C headers.
+
+use alloc::sync::Arc;
+
+use crate::process::Process;
+
+pub const PROT_NONE: u64 = 0x0;
+pub const PROT_READ: u64 = 0x1;
+pub const PROT_WRITE: u64 = 0x2;
+pub const PROT_EXEC: u64 = 0x4;
+pub const PROT_READ_WRITE: u64 = PROT_READ | PROT_WRITE;
+pub const MAP_SHARED: u32 = 0x1;
+pub const MAP_PRIVATE: u32 = 0x2;
+
+/// The binary of the user program `name`.
+pub fn prog(name: &str) -> &'static [u8] {
+    crate::progs::init();
+    crate::progs::lookup_progs(name).unwrap()
+}
+
+/// A new process that would run the user program `name`.
+pub fn new_process(name: &str) -> Arc<Process> {
+    Process::new(prog(name))
+}
@@ -7,8 +7,13 @@ pub mod console;
 mod drivers;
 mod error;
 mod fs;
+#[cfg(ktest)]
+mod ktest_runner;
+#[cfg(ktest)]
+mod ktest_util;
 mod logger;
 mod mm;
+mod panic;
 pub mod process;
 pub mod progs;
 mod sched;
@@ -21,6 +26,7 @@ pub fn main() {
     logger::init();
     progs::init();
     drivers::init();
+    console::init();
     sched::init();
     fs::init();
 
@@ -3,9 +3,10 @@ use ostd::mm::{PAGE_SIZE, PageFlags, Vaddr};
 use riscv::register::scause::Exception;
 
 use crate::{
+    error::{Errno, Error},
     mm::{
         VmMapping,
-        fault::{DefaultPageFaultHandler, PageFaultContext, PageFaultHandler},
+        fault::{DefaultPageFaultHandler, FaultKind, PageFaultContext, PageFaultHandler},
     },
     process::Process,
 };
@@ -58,13 +59,18 @@ impl VmArea {
             "VmArea does not contain vaddr {:x?}",
             vaddr
         );
-        self.fault_handler.handle_page_fault(PageFaultContext::new(
-            self.perms,
-            &mut self.mappings,
-            process,
-            vaddr,
-            fault,
-        ))
+        let context = PageFaultContext::new(self.perms, &mut self.mappings, process, vaddr, fault);
+        // An access the area does not allow is never resolved by mapping a page, and
+        // would fault again forever if the page is already there.
+        let required = match context.fault_kind() {
+            FaultKind::Read => PageFlags::R,
+            FaultKind::Write => PageFlags::W,
+            FaultKind::Execute => PageFlags::X,
+        };
+        if !self.perms.contains(required) {
+            return Err(Error::new(Errno::EACCES));
+        }
+        self.fault_handler.handle_page_fault(context)
     }
 
     pub fn page_fault_handler(&self) -> &Arc<dyn PageFaultHandler> {
@@ -75,6 +81,15 @@ impl VmArea {
         self.perms
     }
 
+    /// Sets the permissions of the area and of the pages already mapped in it. The
+    /// page table is left to the caller.
+    pub fn set_perms(&mut self, perms: PageFlags) {
+        self.perms = perms;
+        for mapping in self.mappings.iter_mut() {
+            mapping.set_perms(perms);
+        }
+    }
+
     pub fn add_mapping(&mut self, mapping: VmMapping) {
         self.mappings.push_back(mapping);
     }
@@ -98,4 +113,46 @@ impl VmArea {
     pub fn contains_vaddr(&self, vaddr: Vaddr) -> bool {
         vaddr >= self.base_vaddr && vaddr < self.base_vaddr + self.pages * PAGE_SIZE
     }
+
+    pub fn end_vaddr(&self) -> Vaddr {
+        self.base_vaddr + self.pages * PAGE_SIZE
+    }
+
+    /// Merges `next` into the end of self if it starts right where self ends and
+    /// behaves the same. Otherwise gives `next` back.
+    pub fn merge(&mut self, mut next: VmArea) -> Result<(), VmArea> {
+        if self.end_vaddr() != next.base_vaddr
+            || self.perms != next.perms
+            || !self.fault_handler.can_merge(next.fault_handler.as_ref())
+        {
+            return Err(next);
+        }
+
+        self.pages += next.pages;
+        self.mappings.append(&mut next.mappings);
+        Ok(())
+    }
+
+    /// Splits the area in two at `vaddr`, which must be a page boundary inside it.
+    /// Self keeps the part below `vaddr` and the rest is returned, with the mappings
+    /// going along with their pages. Both halves share the page fault handler.
+    pub fn split_off(&mut self, vaddr: Vaddr) -> VmArea {
+        debug_assert!(vaddr % PAGE_SIZE == 0 && vaddr > self.base_vaddr);
+        debug_assert!(vaddr < self.end_vaddr());
+
+        let (mappings, upper_mappings) = core::mem::take(&mut self.mappings)
+            .into_iter()
+            .partition(|mapping| mapping.base_vaddr() < vaddr);
+        self.mappings = mappings;
+
+        let upper_pages = (self.end_vaddr() - vaddr) / PAGE_SIZE;
+        self.pages -= upper_pages;
+        VmArea {
+            base_vaddr: vaddr,
+            pages: upper_pages,
+            perms: self.perms,
+            mappings: upper_mappings,
+            fault_handler: self.fault_handler.clone(),
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use core::any::{Any, TypeId};
 use core::fmt::Debug;
 
 use crate::{
@@ -14,6 +15,14 @@ use ostd::{
 };
 use riscv::register::scause::Exception;
 
+/// The kind of access that caused a page fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    Read,
+    Write,
+    Execute,
+}
+
 pub struct PageFaultContext<'a> {
     pub perms: PageFlags,
     pub mappings: &'a mut LinkedList<VmMapping>,
@@ -38,10 +47,28 @@ impl PageFaultContext<'_> {
             fault,
         }
     }
+
+    pub fn fault_kind(&self) -> FaultKind {
+        match self.fault {
+            Exception::StorePageFault => FaultKind::Write,
+            Exception::InstructionPageFault => FaultKind::Execute,
+            // Everything else that reaches a handler is a load.
+            _ => FaultKind::Read,
+        }
+    }
 }
 
-pub trait PageFaultHandler: Send + Sync + Debug {
+pub trait PageFaultHandler: Send + Sync + Debug + Any {
     fn handle_page_fault<'a>(&self, context: PageFaultContext<'a>) -> Result<()>;
+
+    /// Whether an area handled by `self` can absorb the adjacent area that follows it,
+    /// which is handled by `next`.
+    ///
+    /// Handlers that depend on where their area starts must not be merged, which is the
+    /// default.
+    fn can_merge(&self, _next: &dyn PageFaultHandler) -> bool {
+        false
+    }
 }
 
 #[derive(Debug)]
@@ -55,6 +82,10 @@ impl PageFaultHandler for DefaultPageFaultHandler {
         );
         Err(Error::new(Errno::EACCES))
     }
+
+    fn can_merge(&self, next: &dyn PageFaultHandler) -> bool {
+        next.type_id() == TypeId::of::<Self>()
+    }
 }
 
 #[derive(Debug)]
@@ -64,13 +95,22 @@ impl PageFaultHandler for AllocationPageFaultHandler {
     fn handle_page_fault<'a>(&self, context: PageFaultContext<'a>) -> Result<()> {
         let memory_space = context.process.memory_space();
         let vm_space = memory_space.vm_space();
-        let frame = FrameAllocOptions::new().alloc_frame().unwrap();
+        // The frame may have belonged to another process, never hand out its old contents.
+        let frame = FrameAllocOptions::new()
+            .zeroed(true)
+            .alloc_frame()
+            .map_err(|_| Error::new(Errno::ENOMEM))?;
         let align_down_vaddr = context.vaddr.align_down(PAGE_SIZE);
 
         let guard = disable_local();
         let mut cursor_mut = vm_space
             .cursor_mut(&guard, &(align_down_vaddr..align_down_vaddr + PAGE_SIZE))
             .unwrap();
+        // Another thread sharing the address space may have faulted the page in while we
+        // were allocating. Keep its frame, ours is freed when dropped.
+        if let Ok((_, Some(_))) = cursor_mut.query() {
+            return Ok(());
+        }
         cursor_mut.map(
             frame.clone().into(),
             PageProperty::new_user(context.perms, CachePolicy::Writeback),
@@ -82,4 +122,58 @@ impl PageFaultHandler for AllocationPageFaultHandler {
 
         Ok(())
     }
+
+    fn can_merge(&self, next: &dyn PageFaultHandler) -> bool {
+        next.type_id() == TypeId::of::<Self>()
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use ostd::mm::{PAGE_SIZE, PageFlags};
+    use ostd::prelude::ktest;
+    use riscv::register::scause::Exception;
+    use spin::Mutex;
+
+    use super::{FaultKind, PageFaultContext, PageFaultHandler};
+    use crate::error::Result;
+    use crate::ktest_util::new_process;
+    use crate::mm::area::VmArea;
+
+    #[derive(Debug, Default)]
+    struct RecordingPageFaultHandler {
+        kinds: Mutex<Vec<FaultKind>>,
+    }
+
+    impl PageFaultHandler for RecordingPageFaultHandler {
+        fn handle_page_fault<'a>(&self, context: PageFaultContext<'a>) -> Result<()> {
+            self.kinds.lock().push(context.fault_kind());
+            Ok(())
+        }
+    }
+
+    #[ktest]
+    fn handler_sees_fault_kind() {
+        const BASE: usize = 0x6000_0000;
+
+        let process = new_process("hello_world");
+        let handler = Arc::new(RecordingPageFaultHandler::default());
+        let mut area = VmArea::new_with_handler(BASE, 1, PageFlags::RWX, handler.clone());
+
+        for fault in [
+            Exception::LoadPageFault,
+            Exception::StorePageFault,
+            Exception::InstructionPageFault,
+        ] {
+            area.handle_page_fault(&process, BASE + PAGE_SIZE / 2, fault)
+                .unwrap();
+        }
+
+        assert_eq!(
+            *handler.kinds.lock(),
+            [FaultKind::Read, FaultKind::Write, FaultKind::Execute]
+        );
+    }
 }
@@ -28,6 +28,10 @@ impl VmMapping {
         self.perms
     }
 
+    pub fn set_perms(&mut self, perms: PageFlags) {
+        self.perms = perms;
+    }
+
     pub fn remove_perm(&mut self, flag: PageFlags) {
         self.perms.remove(flag);
     }
@@ -2,19 +2,25 @@ pub mod area;
 pub mod fault;
 pub mod mapping;
 
-use alloc::{collections::linked_list::LinkedList, sync::Arc};
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
 pub use mapping::VmMapping;
 use ostd::{
     arch::cpu::context::CpuExceptionInfo,
     mm::{
-        CachePolicy, FrameAllocOptions, MAX_USERSPACE_VADDR, PAGE_SIZE, PageProperty, Segment,
-        VmSpace, io_util::HasVmReaderWriter,
+        CachePolicy, FrameAllocOptions, MAX_USERSPACE_VADDR, PAGE_SIZE, PageFlags, PageProperty,
+        Segment, Vaddr, VmSpace, io_util::HasVmReaderWriter, tlb::TlbFlushOp,
     },
     sync::SpinLock,
     task::disable_preempt,
 };
 
-use crate::{mm::area::VmArea, process::Process};
+use riscv::register::scause::Exception;
+
+use crate::{
+    error::{Errno, Error, Result},
+    mm::area::VmArea,
+    process::Process,
+};
 
 pub fn page_fault_handler(
     process: &Arc<Process>,
@@ -23,36 +29,64 @@ pub fn page_fault_handler(
     let memory_space = process.memory_space();
     let page_fault_addr = cpu_exception.page_fault_addr;
 
-    let mut areas = memory_space.areas.lock();
-    for area in areas.iter_mut() {
-        if !area.contains_vaddr(page_fault_addr) {
-            continue;
-        }
+    memory_space
+        .with_area_mut(page_fault_addr, |area| {
+            area.handle_page_fault(process, page_fault_addr, cpu_exception.code)
+        })
+        .ok_or(())?
+        .map_err(|_| ())
+}
 
-        return area
-            .handle_page_fault(process, page_fault_addr, cpu_exception.code)
-            .map_err(|_| ());
-    }
+/// The lowest address that is picked for a mapping when the user does not ask for
+/// one. It keeps such mappings well clear of the heap, which grows upwards from the
+/// end of the program.
+const MMAP_BASE: Vaddr = 0x10_0000_0000;
 
-    Err(())
-}
+/// Counts the areas looked at by [`MemorySpace::with_area_mut`].
+#[cfg(ktest)]
+static AREA_PROBES: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
 
 pub struct MemorySpace {
     vm_space: Arc<VmSpace>,
-    areas: SpinLock<LinkedList<VmArea>>,
+    /// The areas, keyed by their base address. Areas never overlap.
+    areas: SpinLock<BTreeMap<Vaddr, VmArea>>,
 }
 
 impl MemorySpace {
     pub fn new() -> Self {
         Self {
             vm_space: Arc::new(VmSpace::new()),
-            areas: SpinLock::new(LinkedList::new()),
+            areas: SpinLock::new(BTreeMap::new()),
         }
     }
 
     /// Add a virtual memory area without initializing the frames.
-    pub fn add_area(&self, area: VmArea) {
-        self.areas.lock().push_back(area);
+    ///
+    /// Fails with `EINVAL` for an empty area, and with `EEXIST` if the area overlaps
+    /// one that is already there.
+    pub fn add_area(&self, area: VmArea) -> Result<()> {
+        if area.pages() == 0 {
+            return Err(Error::new(Errno::EINVAL));
+        }
+        let mut areas = self.areas.lock();
+        if !range_is_free(&areas, area.base_vaddr(), area.pages() * PAGE_SIZE) {
+            return Err(Error::new(Errno::EEXIST));
+        }
+        areas.insert(area.base_vaddr(), area);
+        Ok(())
+    }
+
+    /// Calls `f` on the area that contains `vaddr`, if there is one.
+    pub fn with_area_mut<R>(&self, vaddr: Vaddr, f: impl FnOnce(&mut VmArea) -> R) -> Option<R> {
+        let mut areas = self.areas.lock();
+        // Only the last area starting at or below `vaddr` can contain it.
+        let (_, area) = areas.range_mut(..=vaddr).next_back()?;
+        #[cfg(ktest)]
+        AREA_PROBES.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+        if !area.contains_vaddr(vaddr) {
+            return None;
+        }
+        Some(f(area))
     }
 
     pub fn map(&self, mut area: VmArea) -> Segment<()> {
@@ -80,19 +114,22 @@ impl MemorySpace {
             area.add_mapping(mapping);
         }
 
-        self.areas.lock().push_back(area);
+        self.areas.lock().insert(area.base_vaddr(), area);
 
         frames
     }
 
     /// Duplicate self with new phyiscal frames. Also, this will copy the data inside each frame.
+    ///
+    /// Frames of read-only areas, such as code and rodata, can never diverge, so they are
+    /// shared with the new memory space instead of copied.
     pub fn duplicate(&self) -> Self {
         let new_memory_space = MemorySpace::new();
         let mut new_mappings = new_memory_space.areas.lock();
 
         let guard = disable_preempt();
         let areas = self.areas.lock();
-        for area in areas.iter() {
+        for area in areas.values() {
             let mut new_area = VmArea::new_with_handler(
                 area.base_vaddr(),
                 area.pages(),
@@ -102,10 +139,15 @@ impl MemorySpace {
 
             let old_mappings = area.mappings().iter().map(|mapping| mapping);
             for old_mapping in old_mappings {
-                let new_frame = FrameAllocOptions::new().alloc_frame().unwrap();
-
-                // Copy data from old frame to new frame
-                new_frame.writer().write(&mut old_mapping.frame().reader());
+                let new_frame = if area.perms().contains(PageFlags::W) {
+                    let new_frame = FrameAllocOptions::new().alloc_frame().unwrap();
+                    // Copy data from old frame to new frame
+                    new_frame.writer().write(&mut old_mapping.frame().reader());
+                    new_frame
+                } else {
+                    // Share the frame, which takes another reference to it.
+                    old_mapping.frame().clone()
+                };
 
                 let mut cursor_mut = new_memory_space
                     .vm_space
@@ -124,12 +166,239 @@ impl MemorySpace {
                 new_area.add_mapping(mapping);
             }
 
-            new_mappings.push_back(new_area);
+            new_mappings.insert(new_area.base_vaddr(), new_area);
         }
         drop(new_mappings);
         new_memory_space
     }
 
+    /// Merges adjacent areas with the same permissions and page fault behavior, so
+    /// that the area list does not fragment after many small mappings.
+    pub fn coalesce(&self) {
+        let mut areas = self.areas.lock();
+        let sorted = core::mem::take(&mut *areas);
+
+        for (base_vaddr, area) in sorted {
+            let area = match areas.last_entry() {
+                Some(mut last) => match last.get_mut().merge(area) {
+                    Ok(()) => continue,
+                    Err(area) => area,
+                },
+                None => area,
+            };
+            areas.insert(base_vaddr, area);
+        }
+    }
+
+    /// Whether no area overlaps the `len` bytes from `base_vaddr`.
+    pub fn is_free(&self, base_vaddr: Vaddr, len: usize) -> bool {
+        range_is_free(&self.areas.lock(), base_vaddr, len)
+    }
+
+    /// Finds the lowest free range of `len` bytes at or above [`MMAP_BASE`].
+    pub fn find_free_region(&self, len: usize) -> Option<Vaddr> {
+        let areas = self.areas.lock();
+        let mut candidate = MMAP_BASE;
+        for area in areas.values() {
+            if area.end_vaddr() <= candidate {
+                continue;
+            }
+            if area.base_vaddr() >= candidate.checked_add(len)? {
+                break;
+            }
+            candidate = area.end_vaddr();
+        }
+
+        (candidate.checked_add(len)? <= MAX_USERSPACE_VADDR).then_some(candidate)
+    }
+
+    /// Removes the area starting at `base_vaddr` and unmaps its pages.
+    pub fn remove_area(&self, base_vaddr: Vaddr) -> Option<VmArea> {
+        let area = self.areas.lock().remove(&base_vaddr)?;
+
+        let guard = disable_preempt();
+        let mut cursor = self
+            .vm_space
+            .cursor_mut(&guard, &(area.base_vaddr()..area.end_vaddr()))
+            .unwrap();
+        cursor.unmap(area.pages() * PAGE_SIZE);
+
+        Some(area)
+    }
+
+    /// Removes the `len` bytes from `base_vaddr` from whatever areas cover them and
+    /// unmaps their pages. Areas reaching out of the range are split so that the part
+    /// outside of it stays. Parts of the range that no area covers are fine.
+    pub fn unmap(&self, base_vaddr: Vaddr, len: usize) -> Result<()> {
+        let end_vaddr = base_vaddr
+            .checked_add(len)
+            .filter(|&end_vaddr| end_vaddr <= MAX_USERSPACE_VADDR)
+            .ok_or(Error::new(Errno::EINVAL))?;
+        let mut areas = self.areas.lock();
+
+        for vaddr in [base_vaddr, end_vaddr] {
+            let Some((_, area)) = areas.range_mut(..vaddr).next_back() else {
+                continue;
+            };
+            if area.contains_vaddr(vaddr) {
+                let upper = area.split_off(vaddr);
+                areas.insert(vaddr, upper);
+            }
+        }
+        let inside: Vec<Vaddr> = areas
+            .range(base_vaddr..end_vaddr)
+            .map(|(&vaddr, _)| vaddr)
+            .collect();
+        for vaddr in inside {
+            areas.remove(&vaddr);
+        }
+
+        let guard = disable_preempt();
+        let mut cursor = self
+            .vm_space
+            .cursor_mut(&guard, &(base_vaddr..end_vaddr))
+            .unwrap();
+        cursor.unmap(len);
+
+        Ok(())
+    }
+
+    /// Changes the permissions of the `len` bytes from `base_vaddr` to `perms`, for the
+    /// area bookkeeping and for pages that are already mapped. Areas reaching out of
+    /// the range are split so that the permissions outside of it stay as they are.
+    ///
+    /// Fails with `ENOMEM` without changing anything if part of the range is not
+    /// covered by an area.
+    pub fn protect(&self, base_vaddr: Vaddr, len: usize, perms: PageFlags) -> Result<()> {
+        let end_vaddr = base_vaddr
+            .checked_add(len)
+            .ok_or(Error::new(Errno::ENOMEM))?;
+        let mut areas = self.areas.lock();
+
+        let mut vaddr = base_vaddr;
+        while vaddr < end_vaddr {
+            let (_, area) = areas
+                .range(..=vaddr)
+                .next_back()
+                .filter(|(_, area)| area.contains_vaddr(vaddr))
+                .ok_or(Error::new(Errno::ENOMEM))?;
+            vaddr = area.end_vaddr();
+        }
+
+        for vaddr in [base_vaddr, end_vaddr] {
+            let Some((_, area)) = areas.range_mut(..vaddr).next_back() else {
+                continue;
+            };
+            if area.contains_vaddr(vaddr) {
+                let upper = area.split_off(vaddr);
+                areas.insert(vaddr, upper);
+            }
+        }
+
+        let guard = disable_preempt();
+        let range = base_vaddr..end_vaddr;
+        let mut cursor = self.vm_space.cursor_mut(&guard, &range).unwrap();
+        for area in areas.range_mut(base_vaddr..end_vaddr).map(|(_, area)| area) {
+            // Frames of read-only areas may be shared with other memory spaces, see
+            // `duplicate`. Each gets a private copy before it can be written to.
+            if perms.contains(PageFlags::W) && !area.perms().contains(PageFlags::W) {
+                for mapping in area.mappings_mut().iter_mut() {
+                    let frame = FrameAllocOptions::new()
+                        .zeroed(false)
+                        .alloc_frame()
+                        .map_err(|_| Error::new(Errno::ENOMEM))?;
+                    frame.writer().write(&mut mapping.frame().reader());
+                    cursor.jump(mapping.base_vaddr()).unwrap();
+                    cursor.map(
+                        frame.clone().into(),
+                        PageProperty::new_user(perms, CachePolicy::Writeback),
+                    );
+                    *mapping = VmMapping::new(mapping.base_vaddr(), perms, frame);
+                }
+            }
+            area.set_perms(perms);
+        }
+
+        cursor.jump(base_vaddr).unwrap();
+        while let Some(protected) =
+            cursor.protect_next(end_vaddr - cursor.virt_addr(), |flags, _| {
+                *flags = (*flags - PageFlags::RWX) | perms;
+            })
+        {
+            if protected.end >= end_vaddr {
+                break;
+            }
+        }
+        cursor
+            .flusher()
+            .issue_tlb_flush(TlbFlushOp::for_range(range));
+        cursor.flusher().dispatch_tlb_flush();
+
+        Ok(())
+    }
+
+    /// Faults in every page in the `len` bytes from `base_vaddr` that is not resident
+    /// yet, so that the first access to them does not fault.
+    pub fn populate(&self, process: &Arc<Process>, base_vaddr: Vaddr, len: usize) -> Result<()> {
+        self.fault_in(process, base_vaddr, len, Exception::LoadPageFault)
+    }
+
+    /// Like [`Self::populate`], but fails unless every page allows the access that
+    /// `fault` stands for, resident or not.
+    pub fn fault_in(
+        &self,
+        process: &Arc<Process>,
+        base_vaddr: Vaddr,
+        len: usize,
+        fault: Exception,
+    ) -> Result<()> {
+        let required = match fault {
+            Exception::StorePageFault => PageFlags::W,
+            Exception::InstructionPageFault => PageFlags::X,
+            _ => PageFlags::R,
+        };
+        for vaddr in (base_vaddr..base_vaddr + len).step_by(PAGE_SIZE) {
+            self.with_area_mut(vaddr, |area| {
+                if !area.perms().contains(required) {
+                    return Err(Error::new(Errno::EACCES));
+                }
+                if area
+                    .mappings()
+                    .iter()
+                    .any(|mapping| mapping.contains_vaddr(vaddr))
+                {
+                    return Ok(());
+                }
+                area.handle_page_fault(process, vaddr, fault)
+            })
+            .ok_or(Error::new(Errno::ENOMEM))??;
+        }
+        Ok(())
+    }
+
+    /// Whether the page containing `vaddr` is backed by a frame.
+    pub fn is_resident(&self, vaddr: Vaddr) -> bool {
+        self.with_area_mut(vaddr, |area| {
+            area.mappings()
+                .iter()
+                .any(|mapping| mapping.contains_vaddr(vaddr))
+        })
+        .unwrap_or(false)
+    }
+
+    pub fn area_count(&self) -> usize {
+        self.areas.lock().len()
+    }
+
+    /// Returns the number of pages that are backed by a frame.
+    pub fn rss_pages(&self) -> usize {
+        self.areas
+            .lock()
+            .values()
+            .map(|area| area.mappings().len())
+            .sum()
+    }
+
     pub fn vm_space(&self) -> &Arc<VmSpace> {
         &self.vm_space
     }
@@ -145,8 +414,221 @@ impl MemorySpace {
     }
 }
 
+/// Whether none of `areas` overlaps the `len` bytes from `base_vaddr`.
+fn range_is_free(areas: &BTreeMap<Vaddr, VmArea>, base_vaddr: Vaddr, len: usize) -> bool {
+    let Some(end_vaddr) = base_vaddr.checked_add(len) else {
+        return false;
+    };
+    if end_vaddr > MAX_USERSPACE_VADDR {
+        return false;
+    }
+
+    // Areas never overlap, so only the last one starting below the end can reach
+    // into the range.
+    match areas.range(..end_vaddr).next_back() {
+        Some((_, area)) => area.end_vaddr() <= base_vaddr,
+        None => true,
+    }
+}
+
 impl Default for MemorySpace {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use alloc::sync::Arc;
+    use ostd::mm::{HasPaddr, PAGE_SIZE, PageFlags};
+    use ostd::prelude::ktest;
+    use ostd::task::{Task, TaskOptions};
+    use riscv::register::scause::Exception;
+
+    use super::{AREA_PROBES, MemorySpace};
+    use crate::error::Errno;
+    use crate::ktest_util::new_process;
+    use crate::mm::area::VmArea;
+    use crate::mm::fault::AllocationPageFaultHandler;
+
+    #[ktest]
+    fn coalesce_adjacent_areas() {
+        const BASE: usize = 0x1000_0000;
+
+        let memory_space = MemorySpace::new();
+        let add_area = |page: usize, pages: usize, perms: PageFlags| {
+            memory_space
+                .add_area(VmArea::new_with_handler(
+                    BASE + page * PAGE_SIZE,
+                    pages,
+                    perms,
+                    Arc::new(AllocationPageFaultHandler),
+                ))
+                .unwrap();
+        };
+
+        // Out of order, as a series of mmaps could leave them.
+        add_area(3, 2, PageFlags::RW);
+        add_area(0, 1, PageFlags::RW);
+        add_area(1, 2, PageFlags::RW);
+        // Adjacent, but with other permissions.
+        add_area(5, 1, PageFlags::R);
+        // Same permissions, but not adjacent.
+        add_area(7, 1, PageFlags::RW);
+        assert_eq!(memory_space.area_count(), 5);
+
+        memory_space.coalesce();
+        assert_eq!(memory_space.area_count(), 3);
+
+        let areas = memory_space.areas.lock();
+        let merged = areas.values().next().unwrap();
+        assert_eq!(merged.base_vaddr(), BASE);
+        assert_eq!(merged.pages(), 5);
+    }
+
+    #[ktest]
+    fn add_area_rejects_overlaps() {
+        const BASE: usize = 0x1800_0000;
+
+        let memory_space = MemorySpace::new();
+        memory_space
+            .add_area(VmArea::new(BASE + PAGE_SIZE, 2, PageFlags::RW))
+            .unwrap();
+
+        // The same base, a tail and a head all overlap the area.
+        for (page, pages) in [(1, 1), (2, 2), (0, 2)] {
+            let area = VmArea::new(BASE + page * PAGE_SIZE, pages, PageFlags::R);
+            assert_eq!(
+                memory_space.add_area(area).err().unwrap().code,
+                Errno::EEXIST
+            );
+        }
+        let area = VmArea::new(BASE + PAGE_SIZE, 0, PageFlags::R);
+        assert_eq!(
+            memory_space.add_area(area).err().unwrap().code,
+            Errno::EINVAL
+        );
+
+        // The area was not replaced, and the pages around it are still free.
+        assert_eq!(memory_space.area_count(), 1);
+        let perms = memory_space.with_area_mut(BASE + PAGE_SIZE, |area| area.perms());
+        assert_eq!(perms, Some(PageFlags::RW));
+        memory_space
+            .add_area(VmArea::new(BASE, 1, PageFlags::R))
+            .unwrap();
+        memory_space
+            .add_area(VmArea::new(BASE + 3 * PAGE_SIZE, 1, PageFlags::R))
+            .unwrap();
+    }
+
+    #[ktest]
+    fn area_lookup_does_not_scan() {
+        const BASE: usize = 0x2000_0000;
+        const NR_AREAS: usize = 512;
+
+        // Leave a page between areas so that they never merge.
+        let memory_space = MemorySpace::new();
+        for i in 0..NR_AREAS {
+            memory_space
+                .add_area(VmArea::new(BASE + 2 * i * PAGE_SIZE, 1, PageFlags::RW))
+                .unwrap();
+        }
+
+        for i in [0, NR_AREAS / 2, NR_AREAS - 1] {
+            let vaddr = BASE + 2 * i * PAGE_SIZE + 8;
+            let probes = AREA_PROBES.load(Ordering::Relaxed);
+            let base_vaddr = memory_space.with_area_mut(vaddr, |area| area.base_vaddr());
+            assert_eq!(base_vaddr, Some(BASE + 2 * i * PAGE_SIZE));
+            // Only one area is looked at, however many come before it.
+            assert_eq!(AREA_PROBES.load(Ordering::Relaxed) - probes, 1);
+        }
+
+        // The gap after the last area is not covered.
+        let gap = BASE + (2 * NR_AREAS - 1) * PAGE_SIZE;
+        assert!(memory_space.with_area_mut(gap, |_| ()).is_none());
+    }
+
+    #[ktest]
+    fn duplicate_shares_read_only_frames() {
+        const CODE_BASE: usize = 0x3000_0000;
+        const DATA_BASE: usize = 0x4000_0000;
+        const CODE_PAGES: usize = 64;
+        const DATA_PAGES: usize = 4;
+
+        let memory_space = MemorySpace::new();
+        memory_space.map(VmArea::new(CODE_BASE, CODE_PAGES, PageFlags::RX));
+        memory_space.map(VmArea::new(DATA_BASE, DATA_PAGES, PageFlags::RW));
+
+        let child = memory_space.duplicate();
+        assert_eq!(child.rss_pages(), memory_space.rss_pages());
+
+        let parent_areas = memory_space.areas.lock();
+        let child_areas = child.areas.lock();
+
+        // A private frame is referenced by its mapping and the page table of one memory
+        // space. A shared one is referenced from both memory spaces.
+        let private_refs = child_areas[&DATA_BASE]
+            .mappings()
+            .front()
+            .unwrap()
+            .frame()
+            .reference_count();
+
+        let code = parent_areas[&CODE_BASE].mappings().iter();
+        let child_code = child_areas[&CODE_BASE].mappings().iter();
+        for (mapping, child_mapping) in code.zip(child_code) {
+            assert_eq!(mapping.frame().paddr(), child_mapping.frame().paddr());
+            assert_eq!(mapping.frame().reference_count(), 2 * private_refs);
+        }
+
+        let data = parent_areas[&DATA_BASE].mappings().iter();
+        let child_data = child_areas[&DATA_BASE].mappings().iter();
+        for (mapping, child_mapping) in data.zip(child_data) {
+            assert_ne!(mapping.frame().paddr(), child_mapping.frame().paddr());
+        }
+    }
+
+    #[ktest]
+    fn racing_faults_map_one_frame() {
+        const BASE: usize = 0x5000_0000;
+        const NR_THREADS: usize = 2;
+
+        let process = new_process("hello_world");
+        process
+            .memory_space()
+            .add_area(VmArea::new_with_handler(
+                BASE,
+                1,
+                PageFlags::RW,
+                Arc::new(AllocationPageFaultHandler),
+            ))
+            .unwrap();
+
+        let done = Arc::new(AtomicUsize::new(0));
+        for i in 0..NR_THREADS {
+            let process = process.clone();
+            let done = done.clone();
+            TaskOptions::new(move || {
+                let vaddr = BASE + i * 8;
+                process
+                    .memory_space()
+                    .with_area_mut(vaddr, |area| {
+                        area.handle_page_fault(&process, vaddr, Exception::StorePageFault)
+                    })
+                    .unwrap()
+                    .unwrap();
+                done.fetch_add(1, Ordering::Release);
+            })
+            .data(())
+            .spawn()
+            .unwrap();
+        }
+        while done.load(Ordering::Acquire) < NR_THREADS {
+            Task::yield_now();
+        }
+
+        assert_eq!(process.memory_space().rss_pages(), 1);
+    }
+}
@@ -0,0 +1,73 @@
+//! The kernel panic handler.
+//!
+//! A kernel panic is fatal: the handler prints the message, the location and a
+//! backtrace, then exits QEMU with [`QemuExitCode::Failed`] so that scripted runs
+//! see the panic as a failure. The SBI shutdown call only tells a failure from a
+//! normal shutdown, so that is the most specific exit code available.
+
+use core::fmt::Display;
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use ostd::arch::qemu::{QemuExitCode, exit_qemu};
+use ostd::early_println;
+
+/// Whether a panic is already being reported.
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+#[ostd::panic_handler]
+fn panic_handler(info: &core::panic::PanicInfo) -> ! {
+    exit_qemu(report(&info.message(), info.location()));
+}
+
+/// Prints a panic and returns the code to exit QEMU with.
+///
+/// Only the first panic is reported in full. A panic raised while reporting,
+/// say by the backtrace walker, or one raised on another CPU meanwhile, gets a
+/// single line so it cannot recurse.
+fn report(message: &dyn Display, location: Option<&Location>) -> QemuExitCode {
+    // The panic may come from an IRQ handler, and an interrupt taken while
+    // printing would interleave with, or deadlock on, the console.
+    let _irq_guard = ostd::irq::disable_local();
+
+    if PANICKING.swap(true, Ordering::AcqRel) {
+        early_println!("Kernel panicked while panicking: {}", message);
+        return QemuExitCode::Failed;
+    }
+
+    match location {
+        Some(location) => early_println!(
+            "Kernel panic at {}:{}:{}: {}",
+            location.file(),
+            location.line(),
+            location.column(),
+            message
+        ),
+        None => early_println!("Kernel panic: {}", message),
+    }
+    ostd::panic::print_stack_trace();
+    QemuExitCode::Failed
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::panic::Location;
+    use core::sync::atomic::Ordering;
+
+    use ostd::arch::qemu::QemuExitCode;
+    use ostd::prelude::ktest;
+
+    use super::{PANICKING, report};
+
+    #[ktest]
+    fn panic_exits_with_failure() {
+        PANICKING.store(false, Ordering::Release);
+        let code = report(&"deliberate panic", Some(Location::caller()));
+        assert_eq!(code, QemuExitCode::Failed);
+
+        // A nested panic is cut short but still fails the run.
+        assert!(PANICKING.load(Ordering::Acquire));
+        assert_eq!(report(&"nested panic", None), QemuExitCode::Failed);
+        PANICKING.store(false, Ordering::Release);
+    }
+}
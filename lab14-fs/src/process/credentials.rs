@@ -0,0 +1,138 @@
+use crate::error::{Errno, Error, Result};
+
+/// A user or group id.
+pub type Id = u32;
+
+/// The id of the superuser.
+pub const ROOT_ID: Id = 0;
+
+/// The user and group ids a process runs as. A forked process inherits a copy.
+///
+/// A process whose effective user id is root may set any id. Any other may only
+/// switch between the real, effective and saved ids it already has, which lets it
+/// drop privileges and take them back from the saved id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Credentials {
+    /// The real user id, of whoever started the process.
+    uid: Id,
+    /// The effective user id, which permission checks go by.
+    euid: Id,
+    /// The saved user id, which the effective one can be set back to.
+    suid: Id,
+    /// The real group id.
+    gid: Id,
+    /// The effective group id.
+    egid: Id,
+    /// The saved group id.
+    sgid: Id,
+}
+
+impl Credentials {
+    /// The credentials of a process running as root.
+    pub const fn root() -> Self {
+        Self {
+            uid: ROOT_ID,
+            euid: ROOT_ID,
+            suid: ROOT_ID,
+            gid: ROOT_ID,
+            egid: ROOT_ID,
+            sgid: ROOT_ID,
+        }
+    }
+
+    pub fn uid(&self) -> Id {
+        self.uid
+    }
+
+    pub fn euid(&self) -> Id {
+        self.euid
+    }
+
+    pub fn suid(&self) -> Id {
+        self.suid
+    }
+
+    pub fn gid(&self) -> Id {
+        self.gid
+    }
+
+    pub fn egid(&self) -> Id {
+        self.egid
+    }
+
+    pub fn sgid(&self) -> Id {
+        self.sgid
+    }
+
+    fn is_privileged(&self) -> bool {
+        self.euid == ROOT_ID
+    }
+
+    /// Sets all three user ids as root, or else just the effective one, which must
+    /// then be the real or saved id.
+    pub fn set_uid(&mut self, uid: Id) -> Result<()> {
+        if self.is_privileged() {
+            (self.uid, self.euid, self.suid) = (uid, uid, uid);
+        } else if uid == self.uid || uid == self.suid {
+            self.euid = uid;
+        } else {
+            return Err(Error::new(Errno::EPERM));
+        }
+        Ok(())
+    }
+
+    /// Sets all three group ids as root, or else just the effective one, which must
+    /// then be the real or saved id.
+    pub fn set_gid(&mut self, gid: Id) -> Result<()> {
+        if self.is_privileged() {
+            (self.gid, self.egid, self.sgid) = (gid, gid, gid);
+        } else if gid == self.gid || gid == self.sgid {
+            self.egid = gid;
+        } else {
+            return Err(Error::new(Errno::EPERM));
+        }
+        Ok(())
+    }
+
+    /// Sets the real, effective and saved user ids, leaving those that are `None`
+    /// as they are. Nothing is set unless all of them may be.
+    pub fn set_resuid(
+        &mut self,
+        uid: Option<Id>,
+        euid: Option<Id>,
+        suid: Option<Id>,
+    ) -> Result<()> {
+        let current = [self.uid, self.euid, self.suid];
+        check_ids(self.is_privileged(), &current, [uid, euid, suid])?;
+        self.uid = uid.unwrap_or(self.uid);
+        self.euid = euid.unwrap_or(self.euid);
+        self.suid = suid.unwrap_or(self.suid);
+        Ok(())
+    }
+
+    /// Sets the real, effective and saved group ids, leaving those that are `None`
+    /// as they are. Nothing is set unless all of them may be.
+    pub fn set_resgid(
+        &mut self,
+        gid: Option<Id>,
+        egid: Option<Id>,
+        sgid: Option<Id>,
+    ) -> Result<()> {
+        let current = [self.gid, self.egid, self.sgid];
+        check_ids(self.is_privileged(), &current, [gid, egid, sgid])?;
+        self.gid = gid.unwrap_or(self.gid);
+        self.egid = egid.unwrap_or(self.egid);
+        self.sgid = sgid.unwrap_or(self.sgid);
+        Ok(())
+    }
+}
+
+/// Fails with `EPERM` unless the process is privileged or each of the `new` ids
+/// is one of the `current` ones.
+fn check_ids(privileged: bool, current: &[Id; 3], new: [Option<Id>; 3]) -> Result<()> {
+    if privileged || new.into_iter().flatten().all(|id| current.contains(&id)) {
+        Ok(())
+    } else {
+        Err(Error::new(Errno::EPERM))
+    }
+}
@@ -1,20 +1,76 @@
+use core::ffi::CStr;
+use core::ops::Range;
+
 use align_ext::AlignExt;
-use alloc::sync::Arc;
+use alloc::{string::String, sync::Arc, vec::Vec};
 use log::debug;
 use ostd::{
     arch::cpu::context::UserContext,
-    mm::{PAGE_SIZE, PageFlags, VmIo},
+    mm::{MAX_USERSPACE_VADDR, PAGE_SIZE, PageFlags, Vaddr, VmIo},
     user::UserContextApi,
 };
+use xmas_elf::{
+    ElfFile,
+    header::{self, Class, Data, Machine},
+    program::{self, ProgramHeader, ProgramHeader64},
+};
 
 use crate::{
+    error::{Errno, Error, Result},
     mm::{MemorySpace, area::VmArea, fault::AllocationPageFaultHandler},
     process::USER_STACK_SIZE,
 };
 
-pub fn load_user_space(program: &[u8], memory_space: &MemorySpace) -> UserContext {
+/// The arguments are placed in the top page of the user stack, strings and
+/// pointers together, so this is all the room they get.
+pub const ARGS_MAX: usize = PAGE_SIZE;
+
+const USER_STACK_TOP: Vaddr = 0x40_0000_0000 - 10 * PAGE_SIZE;
+/// The largest stack a program gets, what an unlimited `RLIMIT_STACK` means. It
+/// keeps the stack clear of the mmap area below.
+const USER_STACK_MAX: usize = 1 << 30;
+/// Where signal handlers return to, in the page right above the user stack.
+pub const SIGRETURN_TRAMPOLINE: Vaddr = USER_STACK_TOP;
+/// `li a7, 139` (`rt_sigreturn`), then `ecall`.
+const SIGRETURN_CODE: [u32; 2] = [0x08b0_0893, 0x0000_0073];
+
+/// Where position-independent executables are loaded, clear of the heap below
+/// and the mmap area above.
+const ELF_ET_DYN_BASE: Vaddr = 0x2_0000_0000;
+/// Where the dynamic loader of a program is loaded.
+const ELF_INTERP_BASE: Vaddr = 0x3_0000_0000;
+
+// The auxiliary vector entries passed to every program.
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_BASE: u64 = 7;
+const AT_ENTRY: u64 = 9;
+const NR_AUXV: usize = 6;
+
+/// Loads `program` into `memory_space`, with a stack that grows to at most
+/// `stack_limit` bytes.
+///
+/// A dynamically-linked program is started through its `interpreter`, which
+/// the caller has to find from `interpreter_path`.
+pub fn load_user_space(
+    program: &[u8],
+    interpreter: Option<&[u8]>,
+    args: &[String],
+    memory_space: &MemorySpace,
+    stack_limit: u64,
+) -> UserContext {
     let mut user_context = UserContext::default();
-    parse_elf(program, &memory_space, &mut user_context);
+    parse_elf(
+        program,
+        interpreter,
+        args,
+        stack_size(stack_limit),
+        &memory_space,
+        &mut user_context,
+    );
     user_context
 }
 
@@ -22,17 +78,256 @@ pub fn create_user_space(program: &[u8]) -> (MemorySpace, UserContext) {
     let memory_space = MemorySpace::new();
     let mut user_context = UserContext::default();
 
-    parse_elf(program, &memory_space, &mut user_context);
+    parse_elf(
+        program,
+        None,
+        &[],
+        USER_STACK_SIZE,
+        &memory_space,
+        &mut user_context,
+    );
     (memory_space, user_context)
 }
 
-fn parse_elf(input: &[u8], memory_space: &MemorySpace, user_cpu_state: &mut UserContext) {
+/// Checks that `program` is a RISC-V executable that `load_user_space` can
+/// load, failing with `ENOEXEC` otherwise.
+///
+/// The loader trusts what it is given, so anything it would trip over has to
+/// be caught here.
+pub fn check_elf(program: &[u8]) -> Result<()> {
+    let enoexec = |_| Error::new(Errno::ENOEXEC);
+
+    // The parser reads the headers in place and insists that they are aligned.
+    if program.as_ptr().align_offset(align_of::<ProgramHeader64>()) != 0 {
+        return Err(Error::new(Errno::ENOEXEC));
+    }
+    let elf = ElfFile::new(program).map_err(enoexec)?;
+    header::sanity_check(&elf).map_err(enoexec)?;
+
+    let header = elf.header;
+    let pt2 = &header.pt2;
+    let is_executable = matches!(
+        pt2.type_().as_type(),
+        header::Type::Executable | header::Type::SharedObject
+    );
+    if header.pt1.class() != Class::SixtyFour
+        || header.pt1.data() != Data::LittleEndian
+        || pt2.machine().as_machine() != Machine::RISC_V
+        || !is_executable
+        || pt2.ph_entry_size() as usize != size_of::<ProgramHeader64>()
+        || pt2.ph_offset() as usize % align_of::<ProgramHeader64>() != 0
+    {
+        return Err(Error::new(Errno::ENOEXEC));
+    }
+
+    // Every segment has to fit, and none may land on another one or on what
+    // the loader maps around the program.
+    let mut ranges = segment_ranges(program, load_bias(header))?;
+    ranges.extend(reserved_ranges());
+    check_disjoint(&mut ranges)
+}
+
+/// Checks that `interpreter` can be loaded as the dynamic loader of `program`,
+/// which must have passed `check_elf`.
+pub fn check_interpreter(interpreter: &[u8], program: &[u8]) -> Result<()> {
+    check_elf(interpreter)?;
+    // It goes at its own base, so it has to be position-independent. Nor can it
+    // ask for an interpreter of its own.
+    let header = header::parse_header(interpreter).map_err(|_| Error::new(Errno::ENOEXEC))?;
+    if load_bias(header) == 0 || interpreter_path(interpreter)?.is_some() {
+        return Err(Error::new(Errno::ELIBBAD));
+    }
+
+    // Where it goes must be left free by the program.
+    let program_header = header::parse_header(program).map_err(|_| Error::new(Errno::ENOEXEC))?;
+    let mut ranges = segment_ranges(program, load_bias(program_header))?;
+    ranges.extend(segment_ranges(interpreter, ELF_INTERP_BASE)?);
+    ranges.extend(reserved_ranges());
+    check_disjoint(&mut ranges)
+}
+
+/// Returns the pages the loadable segments of `program` take up once moved up
+/// by `load_bias`, failing with `ENOEXEC` if a segment doesn't fit in the file
+/// or in user space.
+fn segment_ranges(program: &[u8], load_bias: Vaddr) -> Result<Vec<Range<Vaddr>>> {
+    let enoexec = |_| Error::new(Errno::ENOEXEC);
+    let header = header::parse_header(program).map_err(enoexec)?;
+
+    let mut ranges = Vec::new();
+    for index in 0..header.pt2.ph_count() {
+        let ProgramHeader::Ph64(ph64) =
+            program::parse_program_header(program, header, index).map_err(enoexec)?
+        else {
+            return Err(Error::new(Errno::ENOEXEC));
+        };
+        let is_load = match ph64.get_type() {
+            Ok(program::Type::Load) => true,
+            Ok(program::Type::Tls) => false,
+            _ => continue,
+        };
+
+        let file_end = ph64.offset.checked_add(ph64.file_size);
+        let mem_end = (ph64.virtual_addr.checked_add(ph64.mem_size))
+            .and_then(|end| end.checked_add(load_bias as u64));
+        let fits = file_end.is_some_and(|end| end <= program.len() as u64)
+            && mem_end.is_some_and(|end| end <= MAX_USERSPACE_VADDR as u64)
+            && ph64.file_size <= ph64.mem_size;
+        if !fits {
+            return Err(Error::new(Errno::ENOEXEC));
+        }
+
+        // The TLS segment is only a template, copied to wherever there is room.
+        let start = (load_bias + ph64.virtual_addr as usize).align_down(PAGE_SIZE);
+        let end = (load_bias + (ph64.virtual_addr + ph64.mem_size) as usize).align_up(PAGE_SIZE);
+        if is_load && start < end {
+            ranges.push(start..end);
+        }
+    }
+    Ok(ranges)
+}
+
+/// Returns what the loader maps besides the segments: the page at 0, and the
+/// stack with the trampoline right above it, as large as any `RLIMIT_STACK`
+/// makes it.
+fn reserved_ranges() -> [Range<Vaddr>; 2] {
+    [
+        0..PAGE_SIZE,
+        USER_STACK_TOP - USER_STACK_MAX..SIGRETURN_TRAMPOLINE + PAGE_SIZE,
+    ]
+}
+
+/// Fails with `ENOEXEC` if any two of `ranges` overlap.
+fn check_disjoint(ranges: &mut [Range<Vaddr>]) -> Result<()> {
+    ranges.sort_unstable_by_key(|range| range.start);
+    if ranges.windows(2).any(|pair| pair[0].end > pair[1].start) {
+        return Err(Error::new(Errno::ENOEXEC));
+    }
+    Ok(())
+}
+
+/// Returns the path of the dynamic loader `program` asks for with `PT_INTERP`,
+/// if any.
+///
+/// `program` must have passed `check_elf`.
+pub fn interpreter_path(program: &[u8]) -> Result<Option<&str>> {
+    let header = header::parse_header(program).map_err(|_| Error::new(Errno::ENOEXEC))?;
+    for index in 0..header.pt2.ph_count() {
+        let Ok(ProgramHeader::Ph64(ph64)) = program::parse_program_header(program, header, index)
+        else {
+            continue;
+        };
+        if ph64.get_type() != Ok(program::Type::Interp) {
+            continue;
+        }
+
+        let path = program
+            .get(ph64.offset as usize..(ph64.offset + ph64.file_size) as usize)
+            .ok_or(Error::new(Errno::ENOEXEC))?;
+        let path = CStr::from_bytes_until_nul(path)
+            .map_err(|_| Error::new(Errno::ENOEXEC))?
+            .to_str()
+            .map_err(|_| Error::new(Errno::ENOEXEC))?;
+        return Ok(Some(path));
+    }
+    Ok(None)
+}
+
+/// Returns the offset added to every address of `header`'s program.
+///
+/// `ET_EXEC` programs are linked at their final address, `ET_DYN` ones are
+/// linked at 0 and can go anywhere.
+fn load_bias(header: header::Header) -> Vaddr {
+    match header.pt2.type_().as_type() {
+        header::Type::SharedObject => ELF_ET_DYN_BASE,
+        _ => 0,
+    }
+}
+
+/// Returns how much of the stack `args` take up, see `ARGS_MAX`.
+pub fn args_size(args: &[String]) -> usize {
+    let strings: usize = args.iter().map(|arg| arg.len() + 1).sum();
+    // argc, the argv pointers and their NULL, an empty envp and the auxv.
+    let words = 1 + args.len() + 1 + 1 + 2 * (NR_AUXV + 1);
+    strings + (words * size_of::<u64>()).align_up(16)
+}
+
+/// Returns the size of the stack for a `RLIMIT_STACK` of `limit`: whole pages,
+/// at least the one the arguments go in and one more.
+fn stack_size(limit: u64) -> usize {
+    let limit = limit.min(USER_STACK_MAX as u64) as usize;
+    limit.align_down(PAGE_SIZE).max(2 * PAGE_SIZE)
+}
+
+fn parse_elf(
+    input: &[u8],
+    interpreter: Option<&[u8]>,
+    args: &[String],
+    stack_size: usize,
+    memory_space: &MemorySpace,
+    user_cpu_state: &mut UserContext,
+) {
     let header = xmas_elf::header::parse_header(input).unwrap();
+    let load_bias = load_bias(header);
+
+    // First, map each ph, and those of the interpreter
+    let phdr = map_segments(input, load_bias, memory_space);
+    let entry = load_bias + header.pt2.entry_point() as usize;
+    let (start, interpreter_base) = match interpreter {
+        Some(interpreter) => {
+            let interpreter_header = xmas_elf::header::parse_header(interpreter).unwrap();
+            map_segments(interpreter, ELF_INTERP_BASE, memory_space);
+            let interpreter_entry = interpreter_header.pt2.entry_point() as usize;
+            (ELF_INTERP_BASE + interpreter_entry, ELF_INTERP_BASE)
+        }
+        None => (entry, 0),
+    };
+
+    // Second, init the user stack with addr: 0x40_0000_0000 - 10 * PAGE_SIZE.
+    // The top page holds the arguments, so it is mapped right away. The rest
+    // is filled in as the stack grows into it, and a fault below it is fatal.
+    let stack_low = USER_STACK_TOP - stack_size;
+    memory_space
+        .add_area(VmArea::new_with_handler(
+            stack_low,
+            stack_size / PAGE_SIZE - 1,
+            PageFlags::RW,
+            Arc::new(AllocationPageFaultHandler),
+        ))
+        .unwrap();
+    let auxv: [(u64, u64); NR_AUXV] = [
+        (AT_PHDR, phdr as u64),
+        (AT_PHENT, size_of::<ProgramHeader64>() as u64),
+        (AT_PHNUM, header.pt2.ph_count() as u64),
+        (AT_PAGESZ, PAGE_SIZE as u64),
+        (AT_BASE, interpreter_base as u64),
+        (AT_ENTRY, entry as u64),
+    ];
+    let stack_pointer = push_args(memory_space, args, &auxv);
+    user_cpu_state.set_stack_pointer(stack_pointer);
+    user_cpu_state.set_instruction_pointer(start);
+    if let Some(tls_pointer) = map_tls(input, memory_space) {
+        user_cpu_state.set_tls_pointer(tls_pointer);
+    }
+
+    // Third, map the 0 address
+    memory_space.map(VmArea::new(0, 1, PageFlags::RW));
+
+    // Finally, map the code that signal handlers return to.
+    let trampoline = memory_space.map(VmArea::new(SIGRETURN_TRAMPOLINE, 1, PageFlags::RX));
+    trampoline.write_val(0, &SIGRETURN_CODE).unwrap();
+}
 
+/// Maps the loadable segments of `input`, moved up by `load_bias`.
+///
+/// Returns where the program headers ended up, or 0 if they aren't loaded.
+fn map_segments(input: &[u8], load_bias: Vaddr, memory_space: &MemorySpace) -> Vaddr {
+    let header = xmas_elf::header::parse_header(input).unwrap();
     let pt2 = header.pt2;
     let ph_count = pt2.ph_count();
+    let ph_offset = pt2.ph_offset();
+    let load_bias = load_bias as u64;
+    let mut phdr = 0;
 
-    // First, map each ph
     for index in 0..ph_count {
         let program_header = xmas_elf::program::parse_program_header(input, header, index).unwrap();
         let ph64 = match program_header {
@@ -41,10 +336,13 @@ fn parse_elf(input: &[u8], memory_space: &MemorySpace, user_cpu_state: &mut User
                 todo!("Not 64 byte executable")
             }
         };
-        if let Ok(typ) = ph64.get_type() {
-            if typ == xmas_elf::program::Type::Load {
-                let raw_start_addr = ph64.virtual_addr;
-                let raw_end_addr = ph64.virtual_addr + ph64.mem_size;
+        match ph64.get_type() {
+            Ok(xmas_elf::program::Type::Phdr) => {
+                phdr = load_bias + ph64.virtual_addr;
+            }
+            Ok(xmas_elf::program::Type::Load) => {
+                let raw_start_addr = load_bias + ph64.virtual_addr;
+                let raw_end_addr = raw_start_addr + ph64.mem_size;
 
                 let start_addr = (raw_start_addr as usize).align_down(PAGE_SIZE);
                 let end_addr = (raw_end_addr as usize).align_up(PAGE_SIZE);
@@ -74,21 +372,285 @@ fn parse_elf(input: &[u8], memory_space: &MemorySpace, user_cpu_state: &mut User
                 frames
                     .write_bytes(raw_start_addr as usize - start_addr, copy_bytes)
                     .unwrap();
+
+                // Without a `PT_PHDR`, the headers are found in the segment that
+                // loads them.
+                let file_range = ph64.offset..ph64.offset + ph64.file_size;
+                if phdr == 0 && file_range.contains(&ph_offset) {
+                    phdr = raw_start_addr + (ph_offset - ph64.offset);
+                }
             }
+            _ => {}
         }
     }
 
-    // Second, init the user stack with addr: 0x40_0000_0000 - 10 * PAGE_SIZE.
-    let stack_low = 0x40_0000_0000 - 10 * PAGE_SIZE - USER_STACK_SIZE;
-    memory_space.add_area(VmArea::new_with_handler(
-        stack_low,
-        USER_STACK_SIZE / PAGE_SIZE,
-        PageFlags::RW,
-        Arc::new(AllocationPageFaultHandler),
-    ));
-    user_cpu_state.set_stack_pointer(0x40_0000_0000 - 10 * PAGE_SIZE - 32);
-    user_cpu_state.set_instruction_pointer(header.pt2.entry_point() as usize);
+    phdr as Vaddr
+}
 
-    // Third, map the 0 address
-    memory_space.map(VmArea::new(0, 1, PageFlags::RW));
+/// Sets up the static TLS block of the main thread from the `PT_TLS` segment of
+/// `input`, if it has one: the initialized data followed by zeros.
+///
+/// Returns the thread pointer. RISC-V uses TLS variant I, so `tp` points right
+/// at the block.
+fn map_tls(input: &[u8], memory_space: &MemorySpace) -> Option<Vaddr> {
+    let header = xmas_elf::header::parse_header(input).unwrap();
+    let ph64 =
+        (0..header.pt2.ph_count()).find_map(
+            |index| match xmas_elf::program::parse_program_header(input, header, index) {
+                Ok(ProgramHeader::Ph64(ph64)) if ph64.get_type() == Ok(program::Type::Tls) => {
+                    Some(*ph64)
+                }
+                _ => None,
+            },
+        )?;
+
+    // A page-aligned block is aligned enough for any TLS segment.
+    let len = (ph64.mem_size as usize).max(1).align_up(PAGE_SIZE);
+    let base_vaddr = memory_space.find_free_region(len)?;
+    let frames = memory_space.map(VmArea::new(base_vaddr, len / PAGE_SIZE, PageFlags::RW));
+    let tdata = &input[ph64.offset as usize..(ph64.offset + ph64.file_size) as usize];
+    frames.write_bytes(0, tdata).unwrap();
+
+    Some(base_vaddr)
+}
+
+/// Maps the top page of the user stack and lays out `args` in it the way the
+/// C runtime expects to find them: argc, then the argv pointers, then an empty
+/// envp and `auxv`.
+///
+/// Returns the initial stack pointer.
+fn push_args(memory_space: &MemorySpace, args: &[String], auxv: &[(u64, u64); NR_AUXV]) -> Vaddr {
+    assert!(args_size(args) <= ARGS_MAX);
+
+    let page_base = USER_STACK_TOP - PAGE_SIZE;
+    let page = memory_space.map(VmArea::new(page_base, 1, PageFlags::RW));
+
+    let strings_size: usize = args.iter().map(|arg| arg.len() + 1).sum();
+    let mut string_vaddr = USER_STACK_TOP - strings_size;
+    let stack_pointer = (USER_STACK_TOP - args_size(args)).align_down(16);
+
+    let mut offset = stack_pointer - page_base;
+    let mut push_word = |value: u64| {
+        page.write_val(offset, &value).unwrap();
+        offset += size_of::<u64>();
+    };
+    push_word(args.len() as u64);
+    for arg in args {
+        push_word(string_vaddr as u64);
+        page.write_bytes(string_vaddr - page_base, arg.as_bytes())
+            .unwrap();
+        page.write_val(string_vaddr - page_base + arg.len(), &0u8)
+            .unwrap();
+        string_vaddr += arg.len() + 1;
+    }
+    // The argv and envp terminators.
+    push_word(0);
+    push_word(0);
+    for &(key, value) in auxv.iter().chain([&(AT_NULL, 0)]) {
+        push_word(key);
+        push_word(value);
+    }
+
+    stack_pointer
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+    use ostd::task::Task;
+    use ostd::user::UserContextApi;
+
+    use alloc::collections::btree_map::BTreeMap;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use ostd::mm::{PAGE_SIZE, Vaddr};
+    use xmas_elf::program::{ProgramHeader, ProgramHeader64, Type};
+
+    use super::{
+        AT_BASE, AT_ENTRY, AT_NULL, AT_PHDR, AT_PHNUM, ELF_ET_DYN_BASE, ELF_INTERP_BASE,
+        check_elf, check_interpreter, load_user_space,
+    };
+    use crate::error::Errno;
+    use crate::ktest_util::{new_process, prog};
+    use crate::mm::MemorySpace;
+    use crate::process::{Process, USER_STACK_SIZE};
+
+    #[ktest]
+    fn pie_is_loaded_at_bias() {
+        let binary = prog("hello_pie");
+        let header = xmas_elf::header::parse_header(binary).unwrap();
+        assert_eq!(
+            header.pt2.type_().as_type(),
+            xmas_elf::header::Type::SharedObject
+        );
+
+        let memory_space = MemorySpace::new();
+        let user_context =
+            load_user_space(binary, None, &[], &memory_space, USER_STACK_SIZE as u64);
+        let entry = ELF_ET_DYN_BASE + header.pt2.entry_point() as usize;
+        assert_eq!(user_context.instruction_pointer(), entry);
+        assert!(memory_space.is_resident(entry));
+
+        // `main` returns 42 once the program relocated itself.
+        let process = Process::new(binary);
+        process.run();
+        while !process.is_zombie() {
+            Task::yield_now();
+        }
+        assert_eq!(process.exit_code(), Some(42));
+    }
+
+    #[ktest]
+    fn main_thread_gets_tls() {
+        let binary = prog("hello_world");
+        let header = xmas_elf::header::parse_header(binary).unwrap();
+        let tls = (0..header.pt2.ph_count())
+            .find_map(|index| {
+                match xmas_elf::program::parse_program_header(binary, header, index) {
+                    Ok(ProgramHeader::Ph64(ph64)) if ph64.get_type() == Ok(Type::Tls) => {
+                        Some(*ph64)
+                    }
+                    _ => None,
+                }
+            })
+            .expect("a static glibc program has TLS");
+
+        let memory_space = MemorySpace::new();
+        let user_context =
+            load_user_space(binary, None, &[], &memory_space, USER_STACK_SIZE as u64);
+        let tls_pointer = user_context.tls_pointer();
+        assert_ne!(tls_pointer, 0);
+
+        let vm_space = memory_space.vm_space();
+        vm_space.activate();
+        let mut reader = vm_space.reader(tls_pointer, tls.mem_size as usize).unwrap();
+        let tdata = &binary[tls.offset as usize..(tls.offset + tls.file_size) as usize];
+        for &byte in tdata {
+            assert_eq!(reader.read_val::<u8>().unwrap(), byte);
+        }
+        while reader.has_remain() {
+            assert_eq!(reader.read_val::<u8>().unwrap(), 0);
+        }
+    }
+
+    #[ktest]
+    fn interpreter_starts_first() {
+        // Any position-independent program can stand in for the dynamic loader.
+        let program = prog("hello_world");
+        let interpreter = prog("hello_pie");
+        check_interpreter(interpreter, program).unwrap();
+        assert_eq!(check_interpreter(program, program).unwrap_err().code, Errno::ELIBBAD);
+
+        let memory_space = MemorySpace::new();
+        let user_context = load_user_space(
+            program,
+            Some(interpreter),
+            &[],
+            &memory_space,
+            USER_STACK_SIZE as u64,
+        );
+        let header = xmas_elf::header::parse_header(program).unwrap();
+        let interpreter_header = xmas_elf::header::parse_header(interpreter).unwrap();
+        assert_eq!(
+            user_context.instruction_pointer(),
+            ELF_INTERP_BASE + interpreter_header.pt2.entry_point() as usize
+        );
+
+        // Skip argc and the argv and envp terminators.
+        let vm_space = memory_space.vm_space();
+        vm_space.activate();
+        let mut reader = vm_space
+            .reader(user_context.stack_pointer(), PAGE_SIZE)
+            .unwrap();
+        reader.skip(3 * size_of::<u64>());
+        let mut auxv = BTreeMap::new();
+        loop {
+            let key: u64 = reader.read_val().unwrap();
+            let value: u64 = reader.read_val().unwrap();
+            if key == AT_NULL {
+                break;
+            }
+            auxv.insert(key, value);
+        }
+        assert_eq!(auxv[&AT_BASE], ELF_INTERP_BASE as u64);
+        assert_eq!(auxv[&AT_ENTRY], header.pt2.entry_point());
+        assert_eq!(auxv[&AT_PHNUM], header.pt2.ph_count() as u64);
+
+        // The program headers are where the auxv says.
+        let ph_offset = header.pt2.ph_offset() as usize;
+        let phdr: [u8; size_of::<ProgramHeader64>()] = vm_space
+            .reader(auxv[&AT_PHDR] as Vaddr, size_of::<ProgramHeader64>())
+            .unwrap()
+            .read_val()
+            .unwrap();
+        assert_eq!(
+            phdr,
+            program[ph_offset..ph_offset + size_of::<ProgramHeader64>()]
+        );
+    }
+
+    /// Copies `binary` into `buffer`, aligned the way the ELF parser wants it.
+    fn aligned_copy<'a>(buffer: &'a mut Vec<u8>, binary: &[u8]) -> &'a mut [u8] {
+        *buffer = vec![0; binary.len() + align_of::<ProgramHeader64>()];
+        let offset = buffer.as_ptr().align_offset(align_of::<ProgramHeader64>());
+        let copy = &mut buffer[offset..offset + binary.len()];
+        copy.copy_from_slice(binary);
+        copy
+    }
+
+    /// Moves the `index`th `PT_LOAD` segment of `elf` to `vaddr`.
+    fn move_segment(elf: &mut [u8], index: usize, vaddr: u64) {
+        let header = xmas_elf::header::parse_header(elf).unwrap();
+        let ph_offset = header.pt2.ph_offset() as usize;
+        let ph = (0..header.pt2.ph_count() as usize)
+            .map(|ph_index| ph_offset + ph_index * size_of::<ProgramHeader64>())
+            .filter(|&ph| elf[ph..ph + 4] == 1u32.to_le_bytes())
+            .nth(index)
+            .unwrap();
+
+        // `p_vaddr` comes after `p_type`, `p_flags` and `p_offset`.
+        elf[ph + 16..ph + 24].copy_from_slice(&vaddr.to_le_bytes());
+    }
+
+    #[ktest]
+    fn overlapping_segments_are_enoexec() {
+        let binary = prog("hello_world");
+        let mut buffer = Vec::new();
+
+        // Where the stack grows with a large enough `RLIMIT_STACK`.
+        let elf = aligned_copy(&mut buffer, binary);
+        check_elf(elf).unwrap();
+        move_segment(elf, 0, 0x3f_f000_0000);
+        assert_eq!(check_elf(elf).unwrap_err().code, Errno::ENOEXEC);
+
+        // The old image is left alone rather than torn down for a program that
+        // can't be loaded.
+        let process = new_process("hello_world");
+        let rss_pages = process.memory_space().rss_pages();
+        assert_eq!(process.exec(elf, None, &[]).unwrap_err().code, Errno::ENOEXEC);
+        assert_eq!(process.memory_space().rss_pages(), rss_pages);
+
+        // On top of another segment.
+        let header = xmas_elf::header::parse_header(binary).unwrap();
+        let first_vaddr = (0..header.pt2.ph_count())
+            .find_map(|index| {
+                match xmas_elf::program::parse_program_header(binary, header, index) {
+                    Ok(ProgramHeader::Ph64(ph64)) if ph64.get_type() == Ok(Type::Load) => {
+                        Some(ph64.virtual_addr)
+                    }
+                    _ => None,
+                }
+            })
+            .unwrap();
+        let elf = aligned_copy(&mut buffer, binary);
+        move_segment(elf, 1, first_vaddr);
+        assert_eq!(check_elf(elf).unwrap_err().code, Errno::ENOEXEC);
+
+        // Where the dynamic loader goes.
+        let elf = aligned_copy(&mut buffer, binary);
+        move_segment(elf, 0, ELF_INTERP_BASE as u64);
+        check_elf(elf).unwrap();
+        let interpreter = prog("hello_pie");
+        assert_eq!(check_interpreter(interpreter, elf).unwrap_err().code, Errno::ENOEXEC);
+    }
 }
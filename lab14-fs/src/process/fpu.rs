@@ -0,0 +1,282 @@
+//! Floating point for user tasks, turned on lazily.
+//!
+//! A user task starts with the FPU off (`sstatus.FS` is Off), so its first
+//! floating-point instruction traps as an illegal instruction. [`TaskFpu::enable`]
+//! turns the FPU on for the task, and the instruction runs again. Only tasks that
+//! did so have FP registers of their own: the scheduler saves them when such a task
+//! leaves the CPU after changing them, and loads them when it comes back, see
+//! [`switch_to`].
+//!
+//! The kernel itself does not use the FP registers, so between two task switches
+//! they hold the registers of the last user task that ran.
+//!
+//! OSTD neither switches the FP registers on RISC-V nor gives access to the user
+//! `sstatus`, so both are done here, which is why this module may use `unsafe`.
+
+#![allow(unsafe_code)]
+
+use alloc::boxed::Box;
+use core::arch::asm;
+
+use ostd::arch::cpu::context::{GeneralRegs, UserContext};
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+use ostd::task::Task;
+use ostd::user::UserContextApi;
+use riscv::register::sstatus::{self, FS};
+
+use super::UserTaskData;
+
+/// The `FS` field of `sstatus`.
+const SSTATUS_FS: usize = 0b11 << 13;
+/// `FS` set to Initial: the FPU is on, and its registers are as they were reset.
+const SSTATUS_FS_INITIAL: usize = 0b01 << 13;
+
+/// The F and D extension registers, as a task left them.
+#[derive(Clone, Copy, Default, Debug)]
+#[repr(C)]
+pub struct FpContext {
+    pub f: [u64; 32],
+    pub fcsr: u32,
+}
+
+impl FpContext {
+    /// Saves the FP registers of this CPU.
+    fn save(&mut self) {
+        let f = self.f.as_mut_ptr();
+        let fcsr = with_fpu_on(|| {
+            let fcsr: usize;
+            // SAFETY: The FPU is on, and the stores only write the 32 slots of `self.f`.
+            unsafe {
+                asm!(
+                    ".option push",
+                    ".option arch, +d",
+                    "fsd f0, 0*8({f})",
+                    "fsd f1, 1*8({f})",
+                    "fsd f2, 2*8({f})",
+                    "fsd f3, 3*8({f})",
+                    "fsd f4, 4*8({f})",
+                    "fsd f5, 5*8({f})",
+                    "fsd f6, 6*8({f})",
+                    "fsd f7, 7*8({f})",
+                    "fsd f8, 8*8({f})",
+                    "fsd f9, 9*8({f})",
+                    "fsd f10, 10*8({f})",
+                    "fsd f11, 11*8({f})",
+                    "fsd f12, 12*8({f})",
+                    "fsd f13, 13*8({f})",
+                    "fsd f14, 14*8({f})",
+                    "fsd f15, 15*8({f})",
+                    "fsd f16, 16*8({f})",
+                    "fsd f17, 17*8({f})",
+                    "fsd f18, 18*8({f})",
+                    "fsd f19, 19*8({f})",
+                    "fsd f20, 20*8({f})",
+                    "fsd f21, 21*8({f})",
+                    "fsd f22, 22*8({f})",
+                    "fsd f23, 23*8({f})",
+                    "fsd f24, 24*8({f})",
+                    "fsd f25, 25*8({f})",
+                    "fsd f26, 26*8({f})",
+                    "fsd f27, 27*8({f})",
+                    "fsd f28, 28*8({f})",
+                    "fsd f29, 29*8({f})",
+                    "fsd f30, 30*8({f})",
+                    "fsd f31, 31*8({f})",
+                    "frcsr {fcsr}",
+                    ".option pop",
+                    f = in(reg) f,
+                    fcsr = out(reg) fcsr,
+                    options(nostack),
+                );
+            }
+            fcsr
+        });
+        self.fcsr = fcsr as u32;
+    }
+
+    /// Loads the FP registers of this CPU.
+    fn load(&self) {
+        let f = self.f.as_ptr();
+        let fcsr = self.fcsr as usize;
+        with_fpu_on(|| {
+            // SAFETY: The FPU is on, and the loads only read the 32 slots of `self.f`.
+            unsafe {
+                asm!(
+                    ".option push",
+                    ".option arch, +d",
+                    "fld f0, 0*8({f})",
+                    "fld f1, 1*8({f})",
+                    "fld f2, 2*8({f})",
+                    "fld f3, 3*8({f})",
+                    "fld f4, 4*8({f})",
+                    "fld f5, 5*8({f})",
+                    "fld f6, 6*8({f})",
+                    "fld f7, 7*8({f})",
+                    "fld f8, 8*8({f})",
+                    "fld f9, 9*8({f})",
+                    "fld f10, 10*8({f})",
+                    "fld f11, 11*8({f})",
+                    "fld f12, 12*8({f})",
+                    "fld f13, 13*8({f})",
+                    "fld f14, 14*8({f})",
+                    "fld f15, 15*8({f})",
+                    "fld f16, 16*8({f})",
+                    "fld f17, 17*8({f})",
+                    "fld f18, 18*8({f})",
+                    "fld f19, 19*8({f})",
+                    "fld f20, 20*8({f})",
+                    "fld f21, 21*8({f})",
+                    "fld f22, 22*8({f})",
+                    "fld f23, 23*8({f})",
+                    "fld f24, 24*8({f})",
+                    "fld f25, 25*8({f})",
+                    "fld f26, 26*8({f})",
+                    "fld f27, 27*8({f})",
+                    "fld f28, 28*8({f})",
+                    "fld f29, 29*8({f})",
+                    "fld f30, 30*8({f})",
+                    "fld f31, 31*8({f})",
+                    "fscsr {fcsr}",
+                    ".option pop",
+                    f = in(reg) f,
+                    fcsr = in(reg) fcsr,
+                    options(nostack, readonly),
+                );
+            }
+        });
+    }
+}
+
+/// Runs `f` with the FPU on for the kernel, and marks the registers Clean after it:
+/// they now match what was saved or loaded.
+fn with_fpu_on<R>(f: impl FnOnce() -> R) -> R {
+    // SAFETY: `FS` only decides whether FP instructions may run. The kernel does not
+    // keep anything in the FP registers itself.
+    unsafe { sstatus::set_fs(FS::Clean) };
+    let res = f();
+    // SAFETY: As above.
+    unsafe { sstatus::set_fs(FS::Clean) };
+    res
+}
+
+/// The FP registers of a user task, from when it first uses the FPU.
+pub struct TaskFpu {
+    context: SpinLock<Option<Box<FpContext>>, LocalIrqDisabled>,
+}
+
+impl TaskFpu {
+    /// Starts with the FPU in the state `context`, or off.
+    pub fn new(context: Option<FpContext>) -> Self {
+        Self {
+            context: SpinLock::new(context.map(Box::new)),
+        }
+    }
+
+    /// Turns the FPU on for the current task running in `user_context`, with all its
+    /// registers zeroed.
+    ///
+    /// Nothing else is changed, so the instruction that trapped runs again.
+    pub fn enable(&self, user_context: &mut UserContext) {
+        // Locked first, so that no task switch comes between the load and the store.
+        let mut context = self.context.lock();
+        let new_context = Box::new(FpContext::default());
+        // Whatever the last task left in the registers is not this one's to see.
+        new_context.load();
+        *context = Some(new_context);
+        drop(context);
+
+        let sstatus = user_sstatus(user_context);
+        *sstatus = (*sstatus & !SSTATUS_FS) | SSTATUS_FS_INITIAL;
+    }
+
+    /// The FP registers as the current task has them now, if it has turned the FPU on.
+    pub fn snapshot(&self) -> Option<FpContext> {
+        let mut context = self.context.lock();
+        let context = context.as_mut()?;
+        if sstatus::read().fs() == FS::Dirty {
+            context.save();
+        }
+        Some(**context)
+    }
+
+    /// Turns the FPU back off, as for a new program. The next FP instruction turns it
+    /// on again.
+    pub fn reset(&self) {
+        *self.context.lock() = None;
+    }
+
+    /// Sets the FP registers of the current task to `new_context`, if it has turned the
+    /// FPU on.
+    pub fn restore(&self, new_context: &FpContext) {
+        if let Some(context) = self.context.lock().as_mut() {
+            **context = *new_context;
+            context.load();
+        }
+    }
+
+    /// Saves the registers of the current task if it changed them since they were
+    /// last loaded.
+    fn save_if_dirty(&self) {
+        if sstatus::read().fs() != FS::Dirty {
+            return;
+        }
+        if let Some(context) = self.context.lock().as_mut() {
+            context.save();
+        }
+    }
+
+    fn load(&self) {
+        if let Some(context) = self.context.lock().as_ref() {
+            context.load();
+        }
+    }
+}
+
+/// Whether the FPU is on for the task running in `user_context`.
+pub fn is_enabled(user_context: &mut UserContext) -> bool {
+    *user_sstatus(user_context) & SSTATUS_FS != 0
+}
+
+/// The FPU state of the current task, if it is a user task.
+pub fn with_current_fpu<R>(f: impl FnOnce(&TaskFpu) -> R) -> Option<R> {
+    let task = Task::current()?;
+    let data = task.data().downcast_ref::<UserTaskData>()?;
+    Some(f(&data.fpu))
+}
+
+/// Switches the FP registers over from the current task to `next`.
+///
+/// Called by the scheduler on the way to `next`, while the current task is still
+/// running. It saves only what a task that turned the FPU on has changed, and
+/// loads only for such a task.
+pub fn switch_to(next: &Task) {
+    with_current_fpu(TaskFpu::save_if_dirty);
+    if let Some(data) = next.data().downcast_ref::<UserTaskData>() {
+        data.fpu.load();
+    }
+}
+
+/// Returns the user `sstatus` in `user_context`.
+///
+/// OSTD has no accessor for it. Its RISC-V `RawUserContext`, which comes with the
+/// general registers, keeps it right after them and right before `sepc`. This
+/// checks that `sepc` is where it should be before trusting the layout.
+fn user_sstatus(user_context: &mut UserContext) -> &mut usize {
+    let regs = core::ptr::from_mut(user_context.general_regs_mut()) as usize;
+    let regs_offset = regs - core::ptr::from_ref(&*user_context) as usize;
+    let sepc = user_context.instruction_pointer();
+    let base = core::ptr::from_mut(user_context).cast::<u8>();
+    // SAFETY: The general registers and the two words after them are all inside
+    // `user_context`, see above.
+    unsafe {
+        let sstatus = base
+            .add(regs_offset + size_of::<GeneralRegs>())
+            .cast::<usize>();
+        assert_eq!(
+            *sstatus.add(1),
+            sepc,
+            "the user context has an unexpected layout"
+        );
+        &mut *sstatus
+    }
+}
@@ -1,11 +1,13 @@
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 use align_ext::AlignExt;
+use alloc::sync::Arc;
 use ostd::mm::{PAGE_SIZE, PageFlags, Vaddr};
 
+use super::Process;
+use super::rlimit::RLIMIT_DATA;
 use crate::mm::area::VmArea;
-
-use super::current_process;
+use crate::mm::fault::AllocationPageFaultHandler;
 
 #[derive(Debug)]
 pub struct UserHeap {
@@ -31,22 +33,43 @@ impl UserHeap {
         self.base
     }
 
-    pub fn brk(&self, new_end: Option<Vaddr>) -> Option<Vaddr> {
+    /// Moves the end of the heap of `process` up to `new_end`, and returns
+    /// where it ends.
+    ///
+    /// The heap never shrinks, and never grows past `RLIMIT_DATA`, into another
+    /// mapping or out of user space; the end stays put if asked to. New pages
+    /// get frames when first touched.
+    pub fn brk(&self, new_end: Option<Vaddr>, process: &Process) -> Option<Vaddr> {
         match new_end {
             Some(new_end) => {
-                let process = current_process();
                 let current_end = self.current_end.load(Ordering::Acquire);
                 if new_end <= current_end {
                     return Some(current_end);
                 }
+                let data_limit = process.rlimits().get(RLIMIT_DATA).unwrap().cur;
+                if (new_end - self.base) as u64 > data_limit {
+                    return Some(current_end);
+                }
                 let old_vaddr = current_end.align_up(PAGE_SIZE);
-                let new_vaddr = new_end.align_up(PAGE_SIZE);
+                let Some(new_vaddr) = new_end.checked_add(PAGE_SIZE - 1) else {
+                    return Some(current_end);
+                };
+                let new_vaddr = new_vaddr.align_down(PAGE_SIZE);
 
                 if new_vaddr > old_vaddr {
                     let pages = (new_vaddr - old_vaddr) / PAGE_SIZE;
-                    process
-                        .memory_space()
-                        .map(VmArea::new(old_vaddr, pages, PageFlags::RW));
+                    let memory_space = process.memory_space();
+                    let area = VmArea::new_with_handler(
+                        old_vaddr,
+                        pages,
+                        PageFlags::RW,
+                        Arc::new(AllocationPageFaultHandler),
+                    );
+                    if memory_space.add_area(area).is_err() {
+                        return Some(current_end);
+                    }
+                    // Each `brk` adds an area right after the previous one.
+                    memory_space.coalesce();
                 }
                 self.current_end.store(new_end, Ordering::Release);
                 Some(new_end)
@@ -71,3 +94,56 @@ impl Clone for UserHeap {
         }
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::mm::{PAGE_SIZE, PageFlags};
+    use ostd::prelude::ktest;
+
+    use crate::ktest_util::new_process;
+    use crate::mm::area::VmArea;
+    use crate::process::rlimit::{RLIMIT_DATA, RLimit64};
+
+    #[ktest]
+    fn brk_stops_at_rlimit_data() {
+        const LIMIT: usize = 4 * PAGE_SIZE;
+
+        let process = new_process("hello_world");
+        process
+            .rlimits()
+            .set(RLIMIT_DATA, RLimit64::new(LIMIT as u64, LIMIT as u64))
+            .unwrap();
+        let heap = process.heap();
+        let base = heap.base();
+
+        assert_eq!(heap.brk(Some(base + LIMIT), &process), Some(base + LIMIT));
+        // Past the limit, the break stays where it was.
+        assert_eq!(
+            heap.brk(Some(base + LIMIT + 1), &process),
+            Some(base + LIMIT)
+        );
+        assert_eq!(heap.brk(None, &process), Some(base + LIMIT));
+    }
+
+    #[ktest]
+    fn brk_stops_at_other_mappings() {
+        let process = new_process("hello_world");
+        let heap = process.heap();
+        let base = heap.base();
+        process
+            .memory_space()
+            .add_area(VmArea::new(base + 2 * PAGE_SIZE, 1, PageFlags::RW))
+            .unwrap();
+
+        assert_eq!(
+            heap.brk(Some(base + PAGE_SIZE), &process),
+            Some(base + PAGE_SIZE)
+        );
+        assert_eq!(
+            heap.brk(Some(base + 3 * PAGE_SIZE), &process),
+            Some(base + PAGE_SIZE)
+        );
+        // Nor does an end that would wrap around when rounded up to a page.
+        assert_eq!(heap.brk(Some(usize::MAX), &process), Some(base + PAGE_SIZE));
+    }
+}
@@ -1,29 +1,46 @@
-mod elf;
+pub mod credentials;
+pub mod elf;
+pub mod fpu;
 mod heap;
+pub mod rlimit;
+pub mod signal;
 mod status;
+mod user_access;
 
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
 use alloc::boxed::Box;
 use alloc::collections::btree_map::BTreeMap;
+use alloc::collections::btree_set::BTreeSet;
+use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use log::{debug, info};
 use ostd::arch::cpu::context::UserContext;
-use ostd::arch::qemu::{QemuExitCode, exit_qemu};
 use ostd::early_println;
-use ostd::sync::{Mutex, MutexGuard, WaitQueue};
+use ostd::mm::Vaddr;
+use ostd::sync::{Mutex, MutexGuard, SpinLock, WaitQueue, Waker};
 use ostd::task::{Task, TaskOptions};
 use ostd::user::{ReturnReason, UserContextApi, UserMode};
 use riscv::register::scause::Exception;
 use spin::Once;
 
 use crate::error::{Errno, Error, Result};
+use crate::fs::FsContext;
 use crate::fs::file_table::FileTable;
 use crate::mm::MemorySpace;
+use crate::process::credentials::Credentials;
+use crate::process::fpu::{FpContext, TaskFpu};
 use crate::process::heap::UserHeap;
+use crate::process::rlimit::{RLIMIT_NPROC, RLIMIT_STACK, ResourceLimits};
+use crate::process::signal::{NSIG, SigAction, Signal, wait_until_uninterruptible};
+pub use crate::process::status::ProcessState;
 use crate::process::status::ProcessStatus;
+use crate::syscall::SyscallRegs;
 pub const USER_STACK_SIZE: usize = 8192 * 1024; // 8MB
 
+/// The pid of the init process, which adopts orphans and whose exit ends the system.
+pub const INIT_PROCESS_ID: Pid = 1;
+
 static PROCESS_TABLE: Mutex<BTreeMap<Pid, Arc<Process>>> = Mutex::new(BTreeMap::new());
 
 #[inline]
@@ -31,23 +48,71 @@ pub fn current_process() -> Arc<Process> {
     let current = Task::current().unwrap();
     current
         .data()
-        .downcast_ref::<Weak<Process>>()
+        .downcast_ref::<UserTaskData>()
         .unwrap()
+        .process
         .upgrade()
         .unwrap()
         .clone()
 }
 
+/// Whether some live process is in the process group `pgid` of the session `sid`.
+pub fn group_in_session(pgid: Pid, sid: Pid) -> bool {
+    PROCESS_TABLE
+        .lock()
+        .values()
+        .any(|process| process.pgid() == pgid && process.sid() == sid && !process.is_zombie())
+}
+
+/// Returns the process `pid`, zombies included.
+pub fn process_by_pid(pid: Pid) -> Option<Arc<Process>> {
+    PROCESS_TABLE.lock().get(&pid).cloned()
+}
+
+/// The resources a cloned child shares with its parent.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SharedResources {
+    /// The file table (`CLONE_FILES`).
+    pub files: bool,
+    /// The working directory and umask (`CLONE_FS`).
+    pub fs: bool,
+}
+
 pub struct Process {
     // ======================== Basic info of process ===========================
     /// The id of this process.
     pid: Pid,
+    /// The id of the process group this process belongs to.
+    pgid: AtomicUsize,
+    /// The id of the session this process belongs to.
+    sid: AtomicUsize,
     /// Process state
     status: ProcessStatus,
     /// The thread of this process
     task: Once<Arc<Task>>,
-    /// File table
-    file_table: Mutex<FileTable>,
+    /// File table, shared with the processes cloned with `CLONE_FILES`.
+    file_table: Arc<Mutex<FileTable>>,
+    /// Working directory and umask, shared with the processes cloned with
+    /// `CLONE_FS`.
+    fs: Arc<Mutex<FsContext>>,
+    /// The limits on the resources the process uses.
+    rlimits: Mutex<ResourceLimits>,
+    /// The user and group ids the process runs as.
+    credentials: Mutex<Credentials>,
+    /// Signals that have been sent but not yet handled, one bit per signal.
+    pending_signals: AtomicU64,
+    /// Signals that stay pending instead of being handled, one bit per signal.
+    blocked_signals: AtomicU64,
+    /// The action taken for each signal.
+    sig_actions: Mutex<[SigAction; NSIG]>,
+    /// Wakes the process from an interruptible wait when a signal arrives.
+    signal_waker: SpinLock<Option<Arc<Waker>>>,
+    /// The argument registers of the syscall returning to user mode, if a signal
+    /// interrupted it.
+    interrupted_syscall: Mutex<Option<SyscallRegs>>,
+    /// The address set with `set_tid_address`. Nothing waits on it yet, as
+    /// there are no threads.
+    clear_child_tid: AtomicUsize,
 
     // ======================== Memory management ===============================
     memory_space: MemorySpace,
@@ -61,14 +126,24 @@ pub struct Process {
     children: Mutex<BTreeMap<Pid, Arc<Process>>>,
     /// The WaitQueue for a child process to become a zombie.
     wait_children_queue: WaitQueue,
+    /// The number of times a child has exited, stopped or continued, used by
+    /// waiters to tell a new event from one they have already looked at.
+    exited_children: AtomicUsize,
+    /// A stop or continue not yet reported to the parent.
+    job_change: SpinLock<Option<ChildEvent>>,
+    /// The WaitQueue for a stopped process to be continued.
+    continue_queue: WaitQueue,
 }
 
 impl Process {
     pub fn new(user_prog_bin: &[u8]) -> Arc<Self> {
         let (memory_space, user_context) = elf::create_user_space(user_prog_bin);
 
+        let pid = alloc_pid(&PROCESS_TABLE.lock()).expect("no pid left for a new process");
         let process = Arc::new(Process {
-            pid: alloc_pid(),
+            pid,
+            pgid: AtomicUsize::new(pid),
+            sid: AtomicUsize::new(pid),
             status: ProcessStatus::new(),
             task: Once::new(),
             memory_space,
@@ -76,10 +151,22 @@ impl Process {
             parent_process: Mutex::new(Weak::new()),
             children: Mutex::new(BTreeMap::new()),
             wait_children_queue: WaitQueue::new(),
-            file_table: Mutex::new(FileTable::new_with_standard_io()),
+            exited_children: AtomicUsize::new(0),
+            job_change: SpinLock::new(None),
+            continue_queue: WaitQueue::new(),
+            pending_signals: AtomicU64::new(0),
+            blocked_signals: AtomicU64::new(0),
+            sig_actions: Mutex::new([SigAction::Default; NSIG]),
+            signal_waker: SpinLock::new(None),
+            interrupted_syscall: Mutex::new(None),
+            clear_child_tid: AtomicUsize::new(0),
+            file_table: Arc::new(Mutex::new(FileTable::new_with_standard_io())),
+            fs: Arc::new(Mutex::new(FsContext::new())),
+            rlimits: Mutex::new(ResourceLimits::new()),
+            credentials: Mutex::new(Credentials::root()),
         });
 
-        let task = create_user_task(&process, Box::new(user_context));
+        let task = create_user_task(&process, Box::new(user_context), None);
         process.task.call_once(|| task);
         process.status.set_runnable();
         PROCESS_TABLE.lock().insert(process.pid(), process.clone());
@@ -87,7 +174,30 @@ impl Process {
         process
     }
 
-    pub fn fork(self: &Arc<Self>, user_context: &UserContext) -> Arc<Process> {
+    /// Creates a child running a copy of this process.
+    ///
+    /// Fails with `EAGAIN` if there are already as many processes as
+    /// `RLIMIT_NPROC` allows.
+    pub fn fork(self: &Arc<Self>, user_context: &UserContext) -> Result<Arc<Process>> {
+        self.clone_with(user_context, SharedResources::default())
+    }
+
+    /// Creates a child like [`Process::fork`], except that it shares the
+    /// resources in `shared` with this process rather than getting copies.
+    pub fn clone_with(
+        self: &Arc<Self>,
+        user_context: &UserContext,
+        shared: SharedResources,
+    ) -> Result<Arc<Process>> {
+        let nproc = self.rlimits().get(RLIMIT_NPROC)?.cur;
+        // Hold the table until the child is in it, so that two racing forks can't
+        // both take the last process allowed.
+        let mut process_table = PROCESS_TABLE.lock();
+        if process_table.len() as u64 >= nproc {
+            return Err(Error::new(Errno::EAGAIN));
+        }
+        let pid = alloc_pid(&process_table)?;
+
         let memory_space = self.memory_space.duplicate();
 
         let user_context = {
@@ -97,7 +207,9 @@ impl Process {
         };
 
         let child_process = Arc::new(Process {
-            pid: alloc_pid(),
+            pid,
+            pgid: AtomicUsize::new(self.pgid()),
+            sid: AtomicUsize::new(self.sid()),
             status: ProcessStatus::new(),
             task: Once::new(),
             memory_space,
@@ -105,50 +217,149 @@ impl Process {
             parent_process: Mutex::new(Arc::downgrade(self)),
             children: Mutex::new(BTreeMap::new()),
             wait_children_queue: WaitQueue::new(),
-            file_table: Mutex::new(self.file_table().duplicate()),
+            exited_children: AtomicUsize::new(0),
+            job_change: SpinLock::new(None),
+            continue_queue: WaitQueue::new(),
+            pending_signals: AtomicU64::new(0),
+            blocked_signals: AtomicU64::new(self.blocked_signals()),
+            sig_actions: Mutex::new(*self.sig_actions.lock()),
+            signal_waker: SpinLock::new(None),
+            interrupted_syscall: Mutex::new(None),
+            clear_child_tid: AtomicUsize::new(0),
+            file_table: if shared.files {
+                self.file_table.clone()
+            } else {
+                Arc::new(Mutex::new(self.file_table().duplicate()))
+            },
+            fs: if shared.fs {
+                self.fs.clone()
+            } else {
+                Arc::new(Mutex::new(self.fs().clone()))
+            },
+            rlimits: Mutex::new(self.rlimits().clone()),
+            credentials: Mutex::new(*self.credentials()),
         });
 
-        let task = create_user_task(&child_process, Box::new(user_context));
+        // The child goes on with the FP registers as they are now, like the others.
+        let fp_context = fpu::with_current_fpu(TaskFpu::snapshot).flatten();
+        let task = create_user_task(&child_process, Box::new(user_context), fp_context);
         child_process.task.call_once(|| task);
         child_process.status.set_runnable();
 
+        process_table.insert(child_process.pid(), child_process.clone());
+        drop(process_table);
         self.children
             .lock()
             .insert(child_process.pid(), child_process.clone());
-        PROCESS_TABLE
-            .lock()
-            .insert(child_process.pid(), child_process.clone());
 
-        child_process
+        Ok(child_process)
     }
 
-    pub fn exec(&self, binary: &[u8]) -> UserContext {
+    /// Replaces the program with `binary`, started with `args` as its argv.
+    ///
+    /// A dynamically-linked `binary` needs the `interpreter` it names.
+    pub fn exec(
+        &self,
+        binary: &[u8],
+        interpreter: Option<&[u8]>,
+        args: &[String],
+    ) -> Result<UserContext> {
+        // Nothing may fail once the old image is gone.
+        elf::check_elf(binary)?;
+        match (elf::interpreter_path(binary)?, interpreter) {
+            (Some(_), Some(interpreter)) => elf::check_interpreter(interpreter, binary)?,
+            (None, None) => {}
+            _ => return Err(Error::new(Errno::EINVAL)),
+        }
+        if elf::args_size(args) > elf::ARGS_MAX {
+            return Err(Error::new(Errno::E2BIG));
+        }
+
+        let stack_limit = self.rlimits().get(RLIMIT_STACK)?.cur;
+
+        self.reset_signals_for_exec();
+        self.file_table().close_files_on_exec();
+        fpu::with_current_fpu(TaskFpu::reset);
         self.memory_space.clear();
-        elf::load_user_space(binary, &self.memory_space)
+        Ok(elf::load_user_space(
+            binary,
+            interpreter,
+            args,
+            &self.memory_space,
+            stack_limit,
+        ))
     }
 
-    pub fn wait(&self, wait_pid: i32) -> Result<(Pid, u32)> {
-        let wait_pid = if wait_pid == -1 {
-            None
-        } else {
-            Some(wait_pid.abs() as Pid)
-        };
+    pub fn wait(&self, wait_pid: i32) -> Result<(Pid, ChildEvent)> {
+        self.wait_for(self.wait_target(wait_pid), WaitOptions::default())
+    }
 
-        let res = self.try_wait(wait_pid);
+    /// Returns the children that the `pid` argument of `wait4` stands for.
+    pub fn wait_target(&self, wait_pid: i32) -> WaitTarget {
+        match wait_pid {
+            -1 => WaitTarget::Any,
+            0 => WaitTarget::Group(self.pgid()),
+            pid if pid > 0 => WaitTarget::Pid(pid as Pid),
+            pgid => WaitTarget::Group(pgid.unsigned_abs() as Pid),
+        }
+    }
+
+    /// Waits for a child in `target` to have an event that `options` ask for,
+    /// and returns its pid and the event.
+    ///
+    /// The event is consumed, and an exited child reaped, unless
+    /// `options.no_wait` is set. Otherwise it stays for this or another waiter
+    /// to see again.
+    pub fn wait_for(&self, target: WaitTarget, options: WaitOptions) -> Result<(Pid, ChildEvent)> {
+        let res = self.wait_until_event(target, options);
+        if options.no_wait && res.is_ok() {
+            // We may have taken the wakeup of a waiter that would consume the
+            // event we leave behind.
+            self.wait_children_queue.wake_one();
+        }
+        res
+    }
+
+    fn wait_until_event(
+        &self,
+        wait_pid: WaitTarget,
+        options: WaitOptions,
+    ) -> Result<(Pid, ChildEvent)> {
+        let mut seen_exits = self.exited_children.load(Ordering::Acquire);
+        let res = self.try_wait(wait_pid, options);
 
         match res {
-            Ok((pid, exit_code)) => return Ok((pid as Pid, exit_code)),
+            Ok(res) => return Ok(res),
             Err(err) if err.code == Errno::EAGAIN => {}
             Err(err) => return Err(err),
         }
 
         // No child exit, waiting...
+        // Stop waiting on any error other than `EAGAIN`: once the target is no
+        // longer our child, it will never become reapable.
         let wait_queue = &self.wait_children_queue;
-        Ok(wait_queue.wait_until(|| self.try_wait(wait_pid).ok()))
+        wait_until_uninterruptible(wait_queue, || {
+            let exits = self.exited_children.load(Ordering::Acquire);
+            match self.try_wait(wait_pid, options) {
+                Err(err) if err.code == Errno::EAGAIN => {
+                    // `exit` wakes a single waiter. If a child exited since we
+                    // last looked and we still have nothing to reap, it is
+                    // meant for another waiter, so pass the wakeup along. Each
+                    // waiter does this at most once per exit, which keeps
+                    // waiters with disjoint targets from waking each other
+                    // forever.
+                    if exits != seen_exits {
+                        seen_exits = exits;
+                        wait_queue.wake_one();
+                    }
+                    None
+                }
+                res => Some(res),
+            }
+        })
     }
 
     pub fn reparent_children_to_init(&self) {
-        const INIT_PROCESS_ID: Pid = 1;
         if self.pid == INIT_PROCESS_ID || self.children.lock().is_empty() {
             return;
         }
@@ -159,11 +370,22 @@ impl Process {
             process_table.get(&INIT_PROCESS_ID).unwrap().clone()
         };
 
-        let mut init_children = init_process.children.lock();
-        let mut self_children = self.children.lock();
-        while let Some((pid, child)) = self_children.pop_first() {
-            *child.parent_process.lock() = Arc::downgrade(&init_process);
-            init_children.insert(pid, child);
+        let mut has_zombie = false;
+        {
+            let mut init_children = init_process.children.lock();
+            let mut self_children = self.children.lock();
+            while let Some((pid, child)) = self_children.pop_first() {
+                *child.parent_process.lock() = Arc::downgrade(&init_process);
+                has_zombie |= child.status.is_zombie();
+                init_children.insert(pid, child);
+            }
+        }
+
+        // The zombies' exits woke us, not init, so init would only reap them
+        // the next time it happens to wait.
+        if has_zombie {
+            init_process.exited_children.fetch_add(1, Ordering::Release);
+            init_process.wait_children_queue.wake_one();
         }
     }
 
@@ -171,12 +393,37 @@ impl Process {
         self.parent_process.lock().upgrade()
     }
 
+    /// Returns the pid of the parent process, or 0 for the init process.
+    ///
+    /// A live process always has a live parent: `exit` hands the children over
+    /// to init before the exiting process can be reaped and dropped.
+    pub fn ppid(&self) -> Pid {
+        self.parent_process().map_or(0, |parent| parent.pid())
+    }
+
     pub fn exit(&self, exit_code: u32) {
+        // Reparent before becoming a zombie. Otherwise our parent could reap and
+        // drop us while the children still point at us, and their `getppid`
+        // would observe a dangling parent.
+        self.reparent_children_to_init();
         self.status.exit(exit_code);
+        self.wake_parent();
+    }
+
+    /// Exits as killed by `signal`, which the parent sees in the wait status.
+    pub fn exit_by_signal(&self, signal: Signal) {
         self.reparent_children_to_init();
-        // Wakeup the parent process if it is waiting.
+        self.status.exit_by_signal(signal);
+        self.wake_parent();
+    }
+
+    /// Wakes up the parent process, if it is waiting, to see that we changed
+    /// state. Only one waiter can reap us, so waking them all would just send
+    /// the rest back to sleep.
+    fn wake_parent(&self) {
         if let Some(parent) = self.parent_process() {
-            parent.wait_children_queue.wake_all();
+            parent.exited_children.fetch_add(1, Ordering::Release);
+            parent.wait_children_queue.wake_one();
         }
     }
 
@@ -184,18 +431,85 @@ impl Process {
         self.file_table.lock()
     }
 
+    pub fn fs(&self) -> MutexGuard<FsContext> {
+        self.fs.lock()
+    }
+
+    pub fn rlimits(&self) -> MutexGuard<ResourceLimits> {
+        self.rlimits.lock()
+    }
+
+    pub fn credentials(&self) -> MutexGuard<Credentials> {
+        self.credentials.lock()
+    }
+
     pub fn is_zombie(&self) -> bool {
         self.status.is_zombie()
     }
 
+    pub fn state(&self) -> ProcessState {
+        self.status.state()
+    }
+
     pub fn exit_code(&self) -> Option<u32> {
         self.status.exit_code()
     }
 
+    pub fn killed_by(&self) -> Option<Signal> {
+        self.status.killed_by()
+    }
+
     pub fn pid(&self) -> Pid {
         self.pid
     }
 
+    pub fn pgid(&self) -> Pid {
+        self.pgid.load(Ordering::Relaxed)
+    }
+
+    /// Moves `pid` (this process or one of its children, 0 meaning this process)
+    /// into the process group `pgid`, where 0 means a group led by `pid` itself.
+    pub fn set_pgid(&self, pid: Pid, pgid: Pid) -> Result<()> {
+        let pid = if pid == 0 { self.pid } else { pid };
+        let pgid = if pgid == 0 { pid } else { pgid };
+
+        if pid == self.pid {
+            self.pgid.store(pgid, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let children = self.children.lock();
+        let child = children.get(&pid).ok_or(Error::new(Errno::ESRCH))?;
+        child.pgid.store(pgid, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn sid(&self) -> Pid {
+        self.sid.load(Ordering::Relaxed)
+    }
+
+    /// Makes this process the leader of a new session and of a new process
+    /// group in it, both named by its pid.
+    ///
+    /// A process group leader can't do this, as its group would end up split
+    /// across two sessions.
+    pub fn setsid(&self) -> Result<Pid> {
+        if self.pgid() == self.pid {
+            return Err(Error::new(Errno::EPERM));
+        }
+        self.sid.store(self.pid, Ordering::Relaxed);
+        self.pgid.store(self.pid, Ordering::Relaxed);
+        Ok(self.pid)
+    }
+
+    pub fn clear_child_tid(&self) -> Vaddr {
+        self.clear_child_tid.load(Ordering::Relaxed)
+    }
+
+    pub fn set_clear_child_tid(&self, tidptr: Vaddr) {
+        self.clear_child_tid.store(tidptr, Ordering::Relaxed);
+    }
+
     pub fn run(&self) {
         self.task.get().unwrap().run();
     }
@@ -208,48 +522,87 @@ impl Process {
         &self.heap
     }
 
-    fn try_wait(&self, pid: Option<Pid>) -> Result<(Pid, u32)> {
+    /// Returns an event that `options` ask for of a child in `target`, without
+    /// waiting for one.
+    pub fn try_wait(&self, target: WaitTarget, options: WaitOptions) -> Result<(Pid, ChildEvent)> {
         let mut children = self.children.lock();
         if children.is_empty() {
             return Err(Error::new(Errno::ECHILD));
         }
 
+        // Only direct children can be reaped. A pid that is not in `children`,
+        // e.g. a grandchild or an already reaped child, is `ECHILD` rather than
+        // `EAGAIN`, even if that process is a zombie.
         let mut wait_pid = None;
-        if let Some(pid) = pid {
-            if let Some(child) = children.get(&pid) {
-                if child.status.is_zombie() {
-                    wait_pid = Some(pid);
-                }
-            } else {
-                return Err(Error::new(Errno::ECHILD));
+        let mut has_target = false;
+        for (child_pid, child) in children.iter() {
+            if !target.matches(child) {
+                continue;
             }
-        } else {
-            for (child_pid, child) in children.iter() {
-                debug!(
-                    "try_wait: check child pid = {}, is zombie = {:?}",
-                    child_pid,
-                    child.status.is_zombie()
-                );
-                if child.status.is_zombie() {
-                    wait_pid = Some(*child_pid);
-                    break;
-                }
+            has_target = true;
+            debug!(
+                "try_wait: check child pid = {}, is zombie = {:?}",
+                child_pid,
+                child.status.is_zombie()
+            );
+            if let Some(event) = child.wait_event(options) {
+                wait_pid = Some((*child_pid, event));
+                break;
             }
         }
 
+        if !has_target {
+            return Err(Error::new(Errno::ECHILD));
+        }
+
         debug!("try_wait: wait_pid = {:?}", wait_pid);
 
-        if let Some(pid) = wait_pid {
-            let child = children.remove(&pid).unwrap();
-            PROCESS_TABLE.lock().remove(&pid);
-            return Ok((pid, child.status.exit_code().unwrap()));
+        if let Some((pid, event)) = wait_pid {
+            if !options.no_wait {
+                if let ChildEvent::Exited(_) | ChildEvent::Killed(_) = event {
+                    children.remove(&pid);
+                    PROCESS_TABLE.lock().remove(&pid);
+                    PID_ALLOCATOR.lock().free(pid);
+                } else {
+                    children[&pid].job_change.lock().take();
+                }
+            }
+            return Ok((pid, event));
         }
 
         Err(Error::new(crate::error::Errno::EAGAIN))
     }
+
+    /// Returns the event of this process that `options` ask its parent to
+    /// report, if there is one.
+    fn wait_event(&self, options: WaitOptions) -> Option<ChildEvent> {
+        if let Some(exit_code) = self.status.exit_code() {
+            return options.exited.then_some(ChildEvent::Exited(exit_code));
+        }
+        if let Some(signal) = self.status.killed_by() {
+            return options.exited.then_some(ChildEvent::Killed(signal));
+        }
+        match *self.job_change.lock() {
+            Some(event @ ChildEvent::Stopped(_)) if options.stopped => Some(event),
+            Some(event @ ChildEvent::Continued) if options.continued => Some(event),
+            _ => None,
+        }
+    }
+}
+
+/// What a user task carries along: the process it runs, and its FP registers.
+pub struct UserTaskData {
+    pub process: Weak<Process>,
+    pub fpu: TaskFpu,
 }
 
-fn create_user_task(process: &Arc<Process>, user_context: Box<UserContext>) -> Arc<Task> {
+/// Creates the task running `process`, starting from `user_context`, and with the
+/// FP registers in `fp_context` if the FPU is on.
+fn create_user_task(
+    process: &Arc<Process>,
+    user_context: Box<UserContext>,
+    fp_context: Option<FpContext>,
+) -> Arc<Task> {
     let entry = move |user_ctx| {
         let process = current_process();
 
@@ -263,13 +616,16 @@ fn create_user_task(process: &Arc<Process>, user_context: Box<UserContext>) -> A
             match return_reason {
                 ReturnReason::UserException => {
                     let exception = user_context.take_exception().unwrap();
-                    if exception.cpu_exception() == Exception::IllegalInstruction {
-                        // The illegal instructions can include the floating point instructions
-                        // if the FPU is not enabled. Here we just skip it.
-                        user_context
-                            .set_instruction_pointer(user_context.instruction_pointer() + 2);
+                    if exception.cpu_exception() == Exception::IllegalInstruction
+                        && !fpu::is_enabled(user_context)
+                    {
+                        // Most likely the first FP instruction of the task. The FPU
+                        // is turned on, and the instruction runs again. If it is not
+                        // an FP one, it traps again, and that is a real SIGILL.
+                        fpu::with_current_fpu(|fpu| fpu.enable(user_context));
                     } else if exception.cpu_exception() == Exception::StorePageFault
                         || exception.cpu_exception() == Exception::LoadPageFault
+                        || exception.cpu_exception() == Exception::InstructionPageFault
                     {
                         // Handle page fault in mm module
                         if let Err(_) = crate::mm::page_fault_handler(&process, &exception) {
@@ -279,7 +635,7 @@ fn create_user_task(process: &Arc<Process>, user_context: Box<UserContext>) -> A
                                 exception.page_fault_addr,
                                 user_context.instruction_pointer()
                             );
-                            exit_qemu(QemuExitCode::Success);
+                            process.force_signal(signal::SIGSEGV);
                         }
                     } else {
                         early_println!(
@@ -288,7 +644,7 @@ fn create_user_task(process: &Arc<Process>, user_context: Box<UserContext>) -> A
                             exception,
                             user_context.instruction_pointer()
                         );
-                        exit_qemu(QemuExitCode::Success);
+                        process.force_signal(signal::exception_signal(exception.cpu_exception()));
                     }
                 }
                 ReturnReason::UserSyscall => {
@@ -298,8 +654,23 @@ fn create_user_task(process: &Arc<Process>, user_context: Box<UserContext>) -> A
                     ostd::task::halt_cpu();
                 }
             }
-            if let Some(exit_code) = process.exit_code() {
-                info!("Process {} exited with code {}", process.pid(), exit_code);
+            crate::console::handle_interrupt();
+            process.handle_pending_signals(user_mode.context_mut());
+            if process.is_zombie() {
+                if let Some(exit_code) = process.exit_code() {
+                    info!("Process {} exited with code {}", process.pid(), exit_code);
+                }
+                // Nothing is left to run once init is gone. Under ktest, pid 1 is
+                // just whichever test process came first.
+                #[cfg(not(ktest))]
+                if process.pid() == INIT_PROCESS_ID {
+                    use ostd::arch::qemu::{QemuExitCode, exit_qemu};
+                    exit_qemu(if process.exit_code() == Some(0) {
+                        QemuExitCode::Success
+                    } else {
+                        QemuExitCode::Failed
+                    });
+                }
                 break;
             }
         }
@@ -309,7 +680,10 @@ fn create_user_task(process: &Arc<Process>, user_context: Box<UserContext>) -> A
 
     Arc::new(
         TaskOptions::new(user_task_func)
-            .data(Arc::downgrade(process))
+            .data(UserTaskData {
+                process: Arc::downgrade(process),
+                fpu: TaskFpu::new(fp_context),
+            })
             .build()
             .unwrap(),
     )
@@ -317,7 +691,480 @@ fn create_user_task(process: &Arc<Process>, user_context: Box<UserContext>) -> A
 
 type Pid = usize;
 
-fn alloc_pid() -> Pid {
-    static NEXT_PID: AtomicUsize = AtomicUsize::new(1);
-    NEXT_PID.fetch_add(1, Ordering::Relaxed)
+/// A change in a child that its parent can `wait` for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildEvent {
+    /// The child exited with the given exit code.
+    Exited(u32),
+    /// The child was killed by the given signal.
+    Killed(Signal),
+    /// The child was stopped by the given signal.
+    Stopped(Signal),
+    /// The child was continued by `SIGCONT`.
+    Continued,
+}
+
+impl ChildEvent {
+    /// The status `wait4` reports the event with, encoded as on Linux.
+    pub fn wait_status(&self) -> u32 {
+        match *self {
+            ChildEvent::Exited(exit_code) => (exit_code & 0xff) << 8,
+            ChildEvent::Killed(signal) => signal as u32,
+            ChildEvent::Stopped(signal) => ((signal as u32) << 8) | 0x7f,
+            ChildEvent::Continued => 0xffff,
+        }
+    }
+}
+
+/// The child events a `wait` call reports.
+#[derive(Debug, Clone, Copy)]
+pub struct WaitOptions {
+    /// Exits (`WEXITED`).
+    pub exited: bool,
+    /// Stops (`WUNTRACED`).
+    pub stopped: bool,
+    /// Continues (`WCONTINUED`).
+    pub continued: bool,
+    /// Leave the event to be reported again, and an exited child unreaped
+    /// (`WNOWAIT`).
+    pub no_wait: bool,
+}
+
+impl Default for WaitOptions {
+    fn default() -> Self {
+        Self {
+            exited: true,
+            stopped: false,
+            continued: false,
+            no_wait: false,
+        }
+    }
+}
+
+/// The children a `wait` call is allowed to reap.
+#[derive(Debug, Clone, Copy)]
+pub enum WaitTarget {
+    /// Any child process.
+    Any,
+    /// The child with the given pid.
+    Pid(Pid),
+    /// Any child in the given process group.
+    Group(Pid),
+}
+
+impl WaitTarget {
+    fn matches(&self, child: &Process) -> bool {
+        match *self {
+            WaitTarget::Any => true,
+            WaitTarget::Pid(pid) => child.pid() == pid,
+            WaitTarget::Group(pgid) => child.pgid() == pgid,
+        }
+    }
+}
+
+/// The largest pid, as Linux's default `pid_max`.
+const PID_MAX: Pid = 32768;
+
+static PID_ALLOCATOR: SpinLock<PidAllocator> = SpinLock::new(PidAllocator::new());
+
+/// Hands out pids, the ones of reaped processes first.
+///
+/// The smallest free pid is always the one picked, so pids stay bounded by
+/// the number of processes that exist at once, whatever the number created.
+struct PidAllocator {
+    /// The smallest pid never handed out.
+    next: Pid,
+    /// The pids handed out, then freed when their process was reaped.
+    free: BTreeSet<Pid>,
+}
+
+impl PidAllocator {
+    const fn new() -> Self {
+        Self {
+            next: INIT_PROCESS_ID,
+            free: BTreeSet::new(),
+        }
+    }
+
+    /// Allocates a pid, skipping the freed ones that are still `in_use`, or
+    /// returns `None` if all of them up to [`PID_MAX`] are taken.
+    fn alloc(&mut self, in_use: impl Fn(Pid) -> bool) -> Option<Pid> {
+        if let Some(&pid) = self.free.iter().find(|&&pid| !in_use(pid)) {
+            self.free.remove(&pid);
+            return Some(pid);
+        }
+        if self.next > PID_MAX {
+            return None;
+        }
+        self.next += 1;
+        Some(self.next - 1)
+    }
+
+    fn free(&mut self, pid: Pid) {
+        debug_assert!(pid < self.next);
+        self.free.insert(pid);
+    }
+}
+
+/// Allocates a pid for a new process, failing with `EAGAIN` if there is none left.
+///
+/// `process_table` is the locked [`PROCESS_TABLE`].
+fn alloc_pid(process_table: &BTreeMap<Pid, Arc<Process>>) -> Result<Pid> {
+    // A reaped leader's pid still names its process group or session until
+    // that is empty, and must not name a new process meanwhile.
+    PID_ALLOCATOR
+        .lock()
+        .alloc(|pid| {
+            process_table
+                .values()
+                .any(|process| process.pgid() == pid || process.sid() == pid)
+        })
+        .ok_or(Error::new(Errno::EAGAIN))
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::prelude::ktest;
+    use ostd::task::{Task, TaskOptions};
+
+    use ostd::mm::{VmReader, VmWriter};
+
+    use super::rlimit::{RLIMIT_NPROC, RLimit64};
+    use super::signal::{SIGINT, SIGSEGV, SigAction};
+    use super::{ChildEvent, INIT_PROCESS_ID, PID_MAX, PROCESS_TABLE, PidAllocator, Process};
+    use crate::error::Errno;
+    use crate::fs::FileLike;
+    use crate::fs::file_table::FileEntry;
+    use crate::fs::pipe::Pipe;
+    use crate::ktest_util::{new_process, prog};
+
+    #[ktest]
+    fn wait_non_child_returns_echild() {
+        let parent = new_process("hello_world");
+        let child = parent.fork(&UserContext::default()).unwrap();
+        let grandchild = child.fork(&UserContext::default()).unwrap();
+
+        // A zombie grandchild is still not ours to reap.
+        grandchild.exit(0);
+        let err = parent.wait(grandchild.pid() as i32).unwrap_err();
+        assert_eq!(err.code, Errno::ECHILD);
+
+        // Neither is a child that has already been reaped.
+        assert_eq!(
+            child.wait(-1).unwrap(),
+            (grandchild.pid(), ChildEvent::Exited(0))
+        );
+        child.exit(1);
+        assert_eq!(
+            parent.wait(child.pid() as i32).unwrap(),
+            (child.pid(), ChildEvent::Exited(1))
+        );
+        let err = parent.wait(child.pid() as i32).unwrap_err();
+        assert_eq!(err.code, Errno::ECHILD);
+    }
+
+    #[ktest]
+    fn exec_keeps_open_files() {
+        let binary = prog("hello_world");
+        let process = Process::new(binary);
+        let (pid, pgid) = (process.pid(), process.pgid());
+
+        let (reader, writer) = Pipe::new_pair();
+        writer
+            .write(VmReader::from(&b"hello world"[..]).to_fallible())
+            .unwrap();
+        let (read_fd, write_fd) = {
+            let mut file_table = process.file_table();
+            let read_fd = file_table.insert(FileEntry::new(reader.clone()));
+            let mut write_entry = FileEntry::new(writer);
+            write_entry.set_close_on_exec(true);
+            (read_fd, file_table.insert(write_entry))
+        };
+
+        let mut buf = [0u8; 6];
+        let file = process.file_table().get(read_fd).unwrap().file().clone();
+        assert_eq!(
+            file.read(VmWriter::from(&mut buf[..]).to_fallible())
+                .unwrap(),
+            6
+        );
+        assert_eq!(&buf, b"hello ");
+
+        process.exec(binary, None, &[]).unwrap();
+
+        assert_eq!((process.pid(), process.pgid()), (pid, pgid));
+        assert!(process.file_table().get(write_fd).is_none());
+
+        // The new program reads on from where the old one left off.
+        let file_table = process.file_table();
+        let file = file_table.get(read_fd).unwrap().file();
+        let reader: Arc<dyn FileLike> = reader;
+        assert!(Arc::ptr_eq(file, &reader));
+        let mut buf = [0u8; 5];
+        assert_eq!(
+            file.read(VmWriter::from(&mut buf[..]).to_fallible())
+                .unwrap(),
+            5
+        );
+        assert_eq!(&buf, b"world");
+    }
+
+    #[ktest]
+    fn exec_resets_signal_handlers() {
+        let binary = prog("hello_world");
+        let process = Process::new(binary);
+
+        const HANDLER: usize = 0x1_0000;
+        let blocked = 1 << SIGSEGV;
+        process
+            .set_sig_action(
+                SIGINT,
+                SigAction::Handler {
+                    entry: HANDLER,
+                    flags: 0,
+                    mask: 0,
+                },
+            )
+            .unwrap();
+        process.set_sig_action(SIGSEGV, SigAction::Ignore).unwrap();
+        process.set_blocked_signals(blocked);
+        process.send_signal(SIGSEGV);
+
+        process.exec(binary, None, &[]).unwrap();
+
+        assert_eq!(process.sig_action(SIGINT).unwrap(), SigAction::Default);
+        assert_eq!(process.sig_action(SIGSEGV).unwrap(), SigAction::Ignore);
+        assert_eq!(process.blocked_signals(), blocked);
+        assert_eq!(process.pending_signals.load(Ordering::Acquire), 0);
+    }
+
+    #[ktest]
+    fn child_exit_wakes_one_waiter() {
+        const NR_WAITERS: usize = 4;
+
+        let parent = new_process("hello_world");
+        let children: Vec<_> = (0..NR_WAITERS)
+            .map(|_| parent.fork(&UserContext::default()).unwrap())
+            .collect();
+
+        let reaped = Arc::new(AtomicUsize::new(0));
+        for _ in 0..NR_WAITERS {
+            let parent = parent.clone();
+            let reaped = reaped.clone();
+            TaskOptions::new(move || {
+                parent.wait(-1).unwrap();
+                reaped.fetch_add(1, Ordering::Release);
+            })
+            .data(())
+            .spawn()
+            .unwrap();
+        }
+
+        let yield_all = || {
+            for _ in 0..NR_WAITERS * 4 {
+                Task::yield_now();
+            }
+        };
+
+        // Let every waiter block, then release them one child at a time.
+        yield_all();
+        for (nr_exited, child) in children.iter().enumerate() {
+            assert_eq!(reaped.load(Ordering::Acquire), nr_exited);
+            child.exit(0);
+            yield_all();
+            assert_eq!(reaped.load(Ordering::Acquire), nr_exited + 1);
+        }
+    }
+
+    #[ktest]
+    fn orphaned_zombie_is_reaped_by_init() {
+        let binary = prog("hello_world");
+        // Init is whichever process came first, so it is not `parent`.
+        Process::new(binary);
+        let init = PROCESS_TABLE.lock().get(&INIT_PROCESS_ID).unwrap().clone();
+        let parent = Process::new(binary);
+        // A live child keeps init waiting rather than failing with `ECHILD`.
+        let _keeper = init.fork(&UserContext::default()).unwrap();
+
+        let zombie = parent.fork(&UserContext::default()).unwrap();
+        zombie.exit(0);
+
+        let reaped = Arc::new(AtomicUsize::new(0));
+        {
+            let reaped = reaped.clone();
+            let zombie_pid = zombie.pid();
+            TaskOptions::new(move || {
+                // Init may have zombies of its own to get through first.
+                while init.wait(-1).unwrap().0 != zombie_pid {}
+                reaped.store(zombie_pid, Ordering::Release);
+            })
+            .data(())
+            .spawn()
+            .unwrap();
+        }
+
+        // Let init block, then orphan the zombie.
+        for _ in 0..16 {
+            Task::yield_now();
+        }
+        assert_eq!(reaped.load(Ordering::Acquire), 0);
+        parent.exit(0);
+        for _ in 0..16 {
+            Task::yield_now();
+        }
+        assert_eq!(reaped.load(Ordering::Acquire), zombie.pid());
+    }
+
+    #[ktest]
+    fn orphan_sees_init_as_parent() {
+        // Init is whichever process came first, so it is not `parent`.
+        new_process("hello_world");
+        let init = PROCESS_TABLE.lock().get(&INIT_PROCESS_ID).unwrap().clone();
+        let parent = new_process("reparent");
+        parent.run();
+        while !parent.is_zombie() {
+            Task::yield_now();
+        }
+
+        // The child is still in the parent's group, which tells it apart from
+        // init's other children.
+        let child = init
+            .children
+            .lock()
+            .values()
+            .find(|child| child.pgid() == parent.pid())
+            .unwrap()
+            .clone();
+        while !child.is_zombie() {
+            Task::yield_now();
+        }
+        // The child saw its parent pid go straight to 1, never to 0.
+        assert_eq!(child.exit_code(), Some(0));
+        assert_eq!(
+            init.wait(child.pid() as i32).unwrap(),
+            (child.pid(), ChildEvent::Exited(0))
+        );
+    }
+
+    #[ktest]
+    fn pids_run_out_at_pid_max() {
+        let mut allocator = PidAllocator::new();
+        allocator.next = PID_MAX - 1;
+        assert_eq!(allocator.alloc(|_| false), Some(PID_MAX - 1));
+        assert_eq!(allocator.alloc(|_| false), Some(PID_MAX));
+        assert_eq!(allocator.alloc(|_| false), None);
+
+        // A pid still in use is not handed out again, a free one is.
+        allocator.free(PID_MAX - 1);
+        assert_eq!(allocator.alloc(|_| true), None);
+        assert_eq!(allocator.alloc(|_| false), Some(PID_MAX - 1));
+    }
+
+    #[ktest]
+    fn reaped_pids_are_reused() {
+        let parent = new_process("hello_world");
+
+        let mut max_pid = 0;
+        for round in 0..64 {
+            let child = parent.fork(&UserContext::default()).unwrap();
+            let pid = child.pid();
+            child.exit(0);
+            assert_eq!(
+                parent.wait(pid as i32).unwrap(),
+                (pid, ChildEvent::Exited(0))
+            );
+
+            // Each child gets the pid the last one freed, or a smaller one freed
+            // elsewhere meanwhile.
+            if round == 0 {
+                max_pid = pid;
+            }
+            assert!(pid <= max_pid);
+        }
+    }
+
+    #[ktest]
+    fn faulting_child_is_reaped() {
+        let process = new_process("fault_child");
+        process.run();
+        while !process.is_zombie() {
+            Task::yield_now();
+        }
+        // The parent saw its child killed by SIGTRAP and exited normally.
+        assert_eq!(process.exit_code(), Some(0));
+    }
+
+    #[ktest]
+    fn float_child_computes_the_right_value() {
+        let process = new_process("float_child");
+        process.run();
+        while !process.is_zombie() {
+            Task::yield_now();
+        }
+        // Both the parent and its forked child got the exact results.
+        assert_eq!(process.exit_code(), Some(0));
+    }
+
+    #[ktest]
+    fn interleaved_float_tasks_keep_their_registers() {
+        let process = new_process("float_yield");
+        process.run();
+        while !process.is_zombie() {
+            Task::yield_now();
+        }
+        // The parent and its child held different values in fs0 while yielding to
+        // each other, and each got its own back every time.
+        assert_eq!(process.exit_code(), Some(0));
+    }
+
+    #[ktest]
+    fn stack_stops_growing_at_rlimit_stack() {
+        let process = new_process("stack_limit");
+        process.run();
+        while !process.is_zombie() {
+            Task::yield_now();
+        }
+        // Recursion that fits in the default stack was killed by SIGSEGV once
+        // a child lowered its RLIMIT_STACK.
+        assert_eq!(process.exit_code(), Some(0));
+    }
+
+    #[ktest]
+    fn misaligned_atomic_raises_sigbus() {
+        let process = new_process("misaligned");
+        process.run();
+        while !process.is_zombie() {
+            Task::yield_now();
+        }
+        // The misaligned load read the right bytes, and the child doing a
+        // misaligned atomic was killed by SIGBUS.
+        assert_eq!(process.exit_code(), Some(0));
+    }
+
+    #[ktest]
+    fn fork_fails_past_rlimit_nproc() {
+        let parent = new_process("hello_world");
+        let nproc = PROCESS_TABLE.lock().len() as u64 + 3;
+        parent
+            .rlimits()
+            .set(RLIMIT_NPROC, RLimit64::new(nproc, nproc))
+            .unwrap();
+
+        let children: Vec<_> = (0..3)
+            .map(|_| parent.fork(&UserContext::default()).unwrap())
+            .collect();
+        let err = parent.fork(&UserContext::default()).err().unwrap();
+        assert_eq!(err.code, Errno::EAGAIN);
+
+        // Leave the process table as it was for the tests that count on it.
+        for child in children {
+            child.exit(0);
+            parent.wait(child.pid() as i32).unwrap();
+        }
+    }
 }
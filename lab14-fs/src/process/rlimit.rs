@@ -0,0 +1,66 @@
+use ostd::Pod;
+
+use super::USER_STACK_SIZE;
+use crate::error::{Errno, Error, Result};
+
+/// The most heap a process may have.
+pub const RLIMIT_DATA: u32 = 2;
+/// The most stack a process may have.
+pub const RLIMIT_STACK: u32 = 3;
+/// The most processes that may exist at once.
+pub const RLIMIT_NPROC: u32 = 6;
+/// One more than the highest file descriptor a process may use. Only `poll`
+/// checks it so far.
+pub const RLIMIT_NOFILE: u32 = 7;
+/// The number of resources with a limit.
+const RLIM_NLIMITS: usize = 16;
+
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+/// A limit on a resource, as `prlimit64` reads and writes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod)]
+#[repr(C)]
+pub struct RLimit64 {
+    /// The soft limit, which is the one enforced.
+    pub cur: u64,
+    /// The ceiling the soft limit may be raised to.
+    pub max: u64,
+}
+
+impl RLimit64 {
+    pub const fn new(cur: u64, max: u64) -> Self {
+        Self { cur, max }
+    }
+}
+
+/// The resource limits of a process. A forked process inherits a copy.
+#[derive(Debug, Clone)]
+pub struct ResourceLimits([RLimit64; RLIM_NLIMITS]);
+
+impl ResourceLimits {
+    pub fn new() -> Self {
+        let mut limits = [RLimit64::new(RLIM_INFINITY, RLIM_INFINITY); RLIM_NLIMITS];
+        limits[RLIMIT_STACK as usize].cur = USER_STACK_SIZE as u64;
+        limits[RLIMIT_NOFILE as usize] = RLimit64::new(1024, 4096);
+        Self(limits)
+    }
+
+    pub fn get(&self, resource: u32) -> Result<RLimit64> {
+        self.0
+            .get(resource as usize)
+            .copied()
+            .ok_or(Error::new(Errno::EINVAL))
+    }
+
+    /// Sets the limit on `resource` and returns the old one.
+    pub fn set(&mut self, resource: u32, limit: RLimit64) -> Result<RLimit64> {
+        if limit.cur > limit.max {
+            return Err(Error::new(Errno::EINVAL));
+        }
+        let old = self
+            .0
+            .get_mut(resource as usize)
+            .ok_or(Error::new(Errno::EINVAL))?;
+        Ok(core::mem::replace(old, limit))
+    }
+}
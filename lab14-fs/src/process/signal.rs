@@ -0,0 +1,780 @@
+//! Minimal signal support.
+//!
+//! A process can ignore a signal or install a handler for it. Signals are only
+//! acted upon when the process is about to return to user mode. A handler runs
+//! on the user stack, above a frame holding the interrupted registers, and
+//! returns through a trampoline that calls `rt_sigreturn` to restore them.
+//!
+//! A stop signal left to its default action stops the process until it gets
+//! `SIGCONT` or `SIGKILL`. The parent sees both the stop and the continue with
+//! `wait4`. `SIGCHLD`, `SIGURG` and `SIGWINCH` are ignored by default, and any
+//! other signal kills the process.
+//!
+//! A blocking syscall waits with [`wait_until_interruptible`], which gives up
+//! with `ERESTARTSYS` when a signal arrives. The syscall then either restarts
+//! once the signal is handled or fails with `EINTR`, if its handler lacks
+//! `SA_RESTART`.
+
+use alloc::sync::Arc;
+use core::sync::atomic::Ordering;
+
+use align_ext::AlignExt;
+use log::info;
+use ostd::Pod;
+use ostd::arch::cpu::context::{GeneralRegs, UserContext};
+use ostd::mm::Vaddr;
+use ostd::sync::{WaitQueue, Waiter};
+use ostd::task::Task;
+use ostd::user::UserContextApi;
+use riscv::register::scause::Exception;
+
+use super::elf::SIGRETURN_TRAMPOLINE;
+use super::fpu::{self, FpContext, TaskFpu};
+use super::{ChildEvent, PROCESS_TABLE, Pid, Process, UserTaskData};
+use crate::error::{Errno, Error, Result};
+use crate::syscall::SyscallRegs;
+
+pub type Signal = u8;
+
+/// The number of signals, each one a bit of a `u64` signal set.
+pub const NSIG: usize = 64;
+
+/// Interrupt from keyboard (Ctrl-C).
+pub const SIGINT: Signal = 2;
+/// Illegal instruction.
+pub const SIGILL: Signal = 4;
+/// Trace or breakpoint trap.
+pub const SIGTRAP: Signal = 5;
+/// Bus error, e.g. a misaligned access.
+pub const SIGBUS: Signal = 7;
+/// Kill, cannot be caught, blocked or ignored.
+pub const SIGKILL: Signal = 9;
+/// Invalid memory reference.
+pub const SIGSEGV: Signal = 11;
+/// Write to a pipe with no reader.
+pub const SIGPIPE: Signal = 13;
+/// Child stopped or terminated.
+pub const SIGCHLD: Signal = 17;
+/// Continue if stopped.
+pub const SIGCONT: Signal = 18;
+/// Stop process, cannot be caught, blocked or ignored.
+pub const SIGSTOP: Signal = 19;
+/// Stop typed at terminal (Ctrl-Z).
+pub const SIGTSTP: Signal = 20;
+/// Terminal input for background process.
+pub const SIGTTIN: Signal = 21;
+/// Terminal output for background process.
+pub const SIGTTOU: Signal = 22;
+/// Urgent condition on socket.
+pub const SIGURG: Signal = 23;
+/// Window resize.
+pub const SIGWINCH: Signal = 28;
+
+/// Don't block the signal while its handler runs.
+pub const SA_NODEFER: u64 = 0x4000_0000;
+/// Reset the action to the default once the handler is entered.
+pub const SA_RESETHAND: u64 = 0x8000_0000;
+/// Restart a syscall the handled signal interrupted, instead of failing it
+/// with `EINTR`.
+pub const SA_RESTART: u64 = 0x1000_0000;
+
+/// The `sa_handler` value selecting the default action.
+pub const SIG_DFL: Vaddr = 0;
+/// The `sa_handler` value selecting to ignore the signal.
+pub const SIG_IGN: Vaddr = 1;
+
+/// What a process does when it receives a signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigAction {
+    Default,
+    Ignore,
+    /// Run the user function at `entry`, with the `SA_*` `flags`, blocking the
+    /// signals in `mask` meanwhile.
+    Handler {
+        entry: Vaddr,
+        flags: u64,
+        mask: u64,
+    },
+}
+
+impl SigAction {
+    pub fn from_handler(handler: Vaddr, flags: u64, mask: u64) -> Self {
+        match handler {
+            SIG_DFL => SigAction::Default,
+            SIG_IGN => SigAction::Ignore,
+            entry => SigAction::Handler { entry, flags, mask },
+        }
+    }
+
+    pub fn handler(&self) -> Vaddr {
+        match *self {
+            SigAction::Default => SIG_DFL,
+            SigAction::Ignore => SIG_IGN,
+            SigAction::Handler { entry, .. } => entry,
+        }
+    }
+
+    pub fn flags(&self) -> u64 {
+        match *self {
+            SigAction::Handler { flags, .. } => flags,
+            _ => 0,
+        }
+    }
+
+    pub fn mask(&self) -> u64 {
+        match *self {
+            SigAction::Handler { mask, .. } => mask,
+            _ => 0,
+        }
+    }
+}
+
+/// Signals whose action and mask can't be changed.
+const UNCATCHABLE: u64 = (1 << SIGKILL) | (1 << SIGSTOP);
+/// Signals that stop the process by default.
+const STOP_SIGNALS: u64 = (1 << SIGSTOP) | (1 << SIGTSTP) | (1 << SIGTTIN) | (1 << SIGTTOU);
+/// Signals that are ignored by default.
+const IGNORED_SIGNALS: u64 = (1 << SIGCHLD) | (1 << SIGURG) | (1 << SIGWINCH);
+
+fn check_signal(signal: Signal) -> Result<()> {
+    if signal == 0 || signal as usize >= NSIG {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    Ok(())
+}
+
+impl Process {
+    /// Marks `signal` as pending for this process, interrupting the syscall it
+    /// is blocked in, if any.
+    ///
+    /// `SIGCONT` continues a stopped process right away, whatever its action,
+    /// and discards the pending stop signals; a stop signal discards a pending
+    /// `SIGCONT`. `SIGKILL` wakes a stopped process up to die.
+    pub fn send_signal(&self, signal: Signal) {
+        if signal == SIGCONT {
+            self.pending_signals
+                .fetch_and(!STOP_SIGNALS, Ordering::AcqRel);
+        } else if STOP_SIGNALS & (1 << signal) != 0 {
+            self.pending_signals
+                .fetch_and(!(1 << SIGCONT), Ordering::AcqRel);
+        }
+        self.pending_signals
+            .fetch_or(1 << signal, Ordering::Release);
+
+        match signal {
+            SIGCONT => self.resume(true),
+            SIGKILL => self.resume(false),
+            _ => {}
+        }
+        if let Some(waker) = self.signal_waker.lock().as_ref() {
+            waker.wake_up();
+        }
+    }
+
+    /// Sends `signal` for a fault in the process, which it can't go on from.
+    ///
+    /// If the signal is blocked or ignored, the faulting instruction would run
+    /// again and again. So like Linux, it is unblocked and its action reset to
+    /// the default, which kills the process.
+    pub fn force_signal(&self, signal: Signal) {
+        let blocked = self.blocked_signals() & (1 << signal) != 0;
+        let mut sig_actions = self.sig_actions.lock();
+        let action = &mut sig_actions[signal as usize];
+        if blocked || *action == SigAction::Ignore {
+            *action = SigAction::Default;
+            self.set_blocked_signals(self.blocked_signals() & !(1 << signal));
+        }
+        drop(sig_actions);
+        self.send_signal(signal);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.status.is_stopped()
+    }
+
+    /// Stops the process on `signal` until it is continued or killed.
+    fn stop(&self, signal: Signal) {
+        info!("Process {} stopped by signal {}", self.pid(), signal);
+        self.status.stop();
+        *self.job_change.lock() = Some(ChildEvent::Stopped(signal));
+        self.wake_parent();
+
+        // A `SIGCONT` or `SIGKILL` sent before we were marked stopped found
+        // nothing to continue.
+        let pending = self.pending_signals.load(Ordering::Acquire);
+        if pending & (1 << SIGCONT) != 0 {
+            self.resume(true);
+        } else if pending & (1 << SIGKILL) != 0 {
+            self.resume(false);
+        }
+
+        self.continue_queue
+            .wait_until(|| (!self.status.is_stopped()).then_some(()));
+    }
+
+    /// Continues the process if it is stopped, telling the parent if `report`
+    /// is set.
+    fn resume(&self, report: bool) {
+        if !self.status.resume() {
+            return;
+        }
+        if report {
+            *self.job_change.lock() = Some(ChildEvent::Continued);
+            self.wake_parent();
+        }
+        self.continue_queue.wake_all();
+    }
+
+    /// Whether a signal that isn't blocked is pending.
+    pub fn has_unblocked_signal(&self) -> bool {
+        self.pending_signals.load(Ordering::Acquire) & !self.blocked_signals() != 0
+    }
+
+    pub fn sig_action(&self, signal: Signal) -> Result<SigAction> {
+        check_signal(signal)?;
+        Ok(self.sig_actions.lock()[signal as usize])
+    }
+
+    /// Sets the action for `signal` and returns the previous one.
+    pub fn set_sig_action(&self, signal: Signal, action: SigAction) -> Result<SigAction> {
+        check_signal(signal)?;
+        if UNCATCHABLE & (1 << signal) != 0 {
+            return Err(Error::new(Errno::EINVAL));
+        }
+        let mut sig_actions = self.sig_actions.lock();
+        Ok(core::mem::replace(
+            &mut sig_actions[signal as usize],
+            action,
+        ))
+    }
+
+    pub fn blocked_signals(&self) -> u64 {
+        self.blocked_signals.load(Ordering::Acquire)
+    }
+
+    pub fn set_blocked_signals(&self, blocked: u64) {
+        self.blocked_signals
+            .store(blocked & !UNCATCHABLE, Ordering::Release);
+    }
+
+    /// Resets the signal state for a new program image.
+    ///
+    /// Handlers live in the old image, so they go back to the default action.
+    /// Ignored signals stay ignored and the mask is kept.
+    pub fn reset_signals_for_exec(&self) {
+        for action in self.sig_actions.lock().iter_mut() {
+            if let SigAction::Handler { .. } = action {
+                *action = SigAction::Default;
+            }
+        }
+        self.pending_signals.store(0, Ordering::Release);
+    }
+
+    /// Takes the action for the pending signals that aren't blocked, if any.
+    ///
+    /// Handlers are set up to run in `user_context`, nested if there are several.
+    pub fn handle_pending_signals(self: &Arc<Self>, user_context: &mut UserContext) {
+        // The registers of the syscall that was just interrupted, if any.
+        let mut restart = self.interrupted_syscall.lock().take();
+
+        while !self.is_zombie() {
+            let pending = self.pending_signals.load(Ordering::Acquire) & !self.blocked_signals();
+            if pending == 0 {
+                break;
+            }
+            let signal = pending.trailing_zeros() as Signal;
+            self.pending_signals
+                .fetch_and(!(1 << signal), Ordering::AcqRel);
+
+            let action = self.sig_actions.lock()[signal as usize];
+            match action {
+                SigAction::Ignore => continue,
+                // Continuing happened when `SIGCONT` was sent.
+                SigAction::Default if signal == SIGCONT => continue,
+                SigAction::Default if STOP_SIGNALS & (1 << signal) != 0 => self.stop(signal),
+                SigAction::Default if IGNORED_SIGNALS & (1 << signal) != 0 => continue,
+                SigAction::Default => {
+                    info!("Process {} killed by signal {}", self.pid(), signal);
+                    self.exit_by_signal(signal);
+                    return;
+                }
+                SigAction::Handler { .. } => {
+                    if let Some(regs) = restart.take() {
+                        if action.flags() & SA_RESTART != 0 {
+                            restart_syscall(user_context, &regs);
+                        } else {
+                            user_context.set_a0(-(Errno::EINTR as i32) as usize);
+                        }
+                    }
+                    if self.run_handler(signal, action, user_context).is_err() {
+                        info!("Process {} killed by signal {}", self.pid(), SIGSEGV);
+                        self.exit_by_signal(SIGSEGV);
+                        return;
+                    }
+                }
+            }
+        }
+
+        // No handler ran, so the syscall restarts as if nothing happened.
+        if let Some(regs) = restart {
+            restart_syscall(user_context, &regs);
+        }
+    }
+
+    /// Marks the syscall that is returning as interrupted, to be restarted with
+    /// the argument registers `regs` or failed with `EINTR` once the signal is
+    /// handled.
+    pub fn set_interrupted_syscall(&self, regs: SyscallRegs) {
+        *self.interrupted_syscall.lock() = Some(regs);
+    }
+
+    /// Pushes a signal frame saving `user_context` and the signal mask on the
+    /// user stack, and points `user_context` at the handler of `signal`.
+    fn run_handler(
+        self: &Arc<Self>,
+        signal: Signal,
+        action: SigAction,
+        user_context: &mut UserContext,
+    ) -> Result<()> {
+        let mut frame = SigFrame::new(signal, user_context, self.blocked_signals());
+        if let Some(fp_context) = fpu::with_current_fpu(TaskFpu::snapshot).flatten() {
+            frame.uc.set_fp_context(&fp_context);
+        }
+        let frame_addr = (user_context.stack_pointer() - size_of::<SigFrame>()).align_down(16);
+        self.write_user_val(frame_addr, &frame)?;
+
+        user_context.set_instruction_pointer(action.handler());
+        user_context.set_stack_pointer(frame_addr);
+        user_context.set_ra(SIGRETURN_TRAMPOLINE);
+        user_context.set_a0(signal as usize);
+        user_context.set_a1(frame_addr + core::mem::offset_of!(SigFrame, info));
+        user_context.set_a2(frame_addr + core::mem::offset_of!(SigFrame, uc));
+
+        let mut blocked = self.blocked_signals() | action.mask();
+        if action.flags() & SA_NODEFER == 0 {
+            blocked |= 1 << signal;
+        }
+        self.set_blocked_signals(blocked);
+        if action.flags() & SA_RESETHAND != 0 {
+            self.sig_actions.lock()[signal as usize] = SigAction::Default;
+        }
+        Ok(())
+    }
+
+    /// Returns from a signal handler: restores the registers and the signal
+    /// mask saved in the signal frame at the user stack pointer.
+    pub fn return_from_handler(self: &Arc<Self>, user_context: &mut UserContext) -> Result<()> {
+        let frame: SigFrame = self.read_user_val(user_context.stack_pointer())?;
+        frame.uc.restore(user_context);
+        fpu::with_current_fpu(|fpu| fpu.restore(&frame.uc.fp_context()));
+        self.set_blocked_signals(frame.uc.sigmask);
+        Ok(())
+    }
+}
+
+/// Points `user_context` back at the `ecall` that was just executed, with the
+/// argument registers it was made with.
+///
+/// `a0` has since been overwritten with `-ERESTARTSYS`, and the other
+/// registers may have been as well.
+fn restart_syscall(user_context: &mut UserContext, regs: &SyscallRegs) {
+    regs.restore(user_context);
+    user_context.set_instruction_pointer(user_context.instruction_pointer() - 4);
+}
+
+/// Waits on `queue` until `cond` returns `Some`, like [`WaitQueue::wait_until`],
+/// but gives up with `ERESTARTSYS` if the current process gets a signal that
+/// isn't blocked. The process reads as sleeping meanwhile.
+///
+/// Outside a process, in a kernel task, the wait can't be interrupted.
+pub fn wait_until_interruptible<F, R>(queue: &WaitQueue, mut cond: F) -> Result<R>
+where
+    F: FnMut() -> Option<R>,
+{
+    let Some(process) = try_current_process() else {
+        return Ok(queue.wait_until(cond));
+    };
+
+    if let Some(res) = cond() {
+        return Ok(res);
+    }
+    let (waiter, waker) = Waiter::new_pair();
+    *process.signal_waker.lock() = Some(waker);
+    process.status.sleep(true);
+    let res = waiter.wait_until_or_cancelled(
+        || {
+            queue.enqueue(waiter.waker());
+            cond()
+        },
+        || {
+            if process.has_unblocked_signal() {
+                Err(Error::new(Errno::ERESTARTSYS))
+            } else {
+                Ok(())
+            }
+        },
+    );
+    process.status.wake_up();
+    *process.signal_waker.lock() = None;
+    res
+}
+
+/// Waits on `queue` until `cond` returns `Some`, like [`WaitQueue::wait_until`],
+/// with the current process, if any, in disk-wait meanwhile: no signal cuts
+/// the wait short.
+pub fn wait_until_uninterruptible<F, R>(queue: &WaitQueue, mut cond: F) -> R
+where
+    F: FnMut() -> Option<R>,
+{
+    let Some(process) = try_current_process() else {
+        return queue.wait_until(cond);
+    };
+
+    if let Some(res) = cond() {
+        return res;
+    }
+    process.status.sleep(false);
+    let res = queue.wait_until(cond);
+    process.status.wake_up();
+    res
+}
+
+/// The process the current task runs, if it runs one.
+fn try_current_process() -> Option<Arc<Process>> {
+    Task::current().and_then(|task| {
+        task.data()
+            .downcast_ref::<UserTaskData>()?
+            .process
+            .upgrade()
+    })
+}
+
+/// Returns the signal that kills a process taking the user-mode `exception`.
+pub fn exception_signal(exception: Exception) -> Signal {
+    match exception {
+        // The FPU is on by now, so this is not an FP instruction.
+        Exception::IllegalInstruction => SIGILL,
+        Exception::Breakpoint => SIGTRAP,
+        Exception::InstructionMisaligned
+        | Exception::LoadMisaligned
+        | Exception::StoreMisaligned => SIGBUS,
+        _ => SIGSEGV,
+    }
+}
+
+/// Sends `signal` to every live process in the process group `pgid`.
+///
+/// Returns the number of processes the signal was sent to.
+pub fn kill_group(pgid: Pid, signal: Signal) -> usize {
+    let process_table = PROCESS_TABLE.lock();
+    let mut count = 0;
+    for process in process_table.values() {
+        if process.pgid() == pgid && !process.is_zombie() {
+            process.send_signal(signal);
+            count += 1;
+        }
+    }
+    count
+}
+
+/// The frame pushed on the user stack for a signal handler, laid out as on
+/// Linux: the `siginfo_t` and `ucontext_t` handed to an `SA_SIGINFO` handler.
+#[derive(Clone, Copy, Pod)]
+#[repr(C)]
+struct SigFrame {
+    info: SigInfo,
+    uc: UContext,
+}
+
+/// `siginfo_t`, of which only the signal number is filled in.
+#[derive(Clone, Copy, Pod)]
+#[repr(C)]
+struct SigInfo {
+    signo: i32,
+    errno: i32,
+    code: i32,
+    _pad: [i32; 29],
+}
+
+/// `ucontext_t` on riscv64. The floating-point state is left zeroed until the
+/// process turns the FPU on.
+#[derive(Clone, Copy, Pod)]
+#[repr(C)]
+struct UContext {
+    flags: u64,
+    link: u64,
+    stack: [u64; 3],
+    sigmask: u64,
+    _unused: [u8; 120],
+    /// Aligns `regs` to 16 bytes.
+    _pad: u64,
+    /// The pc, then `x1` to `x31`.
+    regs: [u64; 32],
+    /// The D extension state: `f0` to `f31`, then `fcsr` in the low half.
+    fp_state: [u64; 66],
+}
+
+impl SigFrame {
+    fn new(signal: Signal, user_context: &UserContext, sigmask: u64) -> Self {
+        let mut frame = Self::new_zeroed();
+        frame.info.signo = signal as i32;
+        frame.uc.sigmask = sigmask;
+
+        frame.uc.regs[0] = user_context.instruction_pointer() as u64;
+        let mut regs = *user_context.general_regs();
+        for (slot, reg) in frame.uc.regs[1..].iter_mut().zip(numbered_regs(&mut regs)) {
+            *slot = *reg as u64;
+        }
+        frame
+    }
+}
+
+impl UContext {
+    fn restore(&self, user_context: &mut UserContext) {
+        for (reg, &value) in numbered_regs(user_context.general_regs_mut())
+            .into_iter()
+            .zip(&self.regs[1..])
+        {
+            *reg = value as usize;
+        }
+        user_context.set_instruction_pointer(self.regs[0] as usize);
+    }
+
+    fn set_fp_context(&mut self, fp_context: &FpContext) {
+        self.fp_state[..32].copy_from_slice(&fp_context.f);
+        self.fp_state[32] = fp_context.fcsr as u64;
+    }
+
+    fn fp_context(&self) -> FpContext {
+        let mut fp_context = FpContext::default();
+        fp_context.f.copy_from_slice(&self.fp_state[..32]);
+        fp_context.fcsr = self.fp_state[32] as u32;
+        fp_context
+    }
+}
+
+/// Returns `x1` to `x31` of `regs`, in order.
+fn numbered_regs(regs: &mut GeneralRegs) -> [&mut usize; 31] {
+    [
+        &mut regs.ra,
+        &mut regs.sp,
+        &mut regs.gp,
+        &mut regs.tp,
+        &mut regs.t0,
+        &mut regs.t1,
+        &mut regs.t2,
+        &mut regs.s0,
+        &mut regs.s1,
+        &mut regs.a0,
+        &mut regs.a1,
+        &mut regs.a2,
+        &mut regs.a3,
+        &mut regs.a4,
+        &mut regs.a5,
+        &mut regs.a6,
+        &mut regs.a7,
+        &mut regs.s2,
+        &mut regs.s3,
+        &mut regs.s4,
+        &mut regs.s5,
+        &mut regs.s6,
+        &mut regs.s7,
+        &mut regs.s8,
+        &mut regs.s9,
+        &mut regs.s10,
+        &mut regs.s11,
+        &mut regs.t3,
+        &mut regs.t4,
+        &mut regs.t5,
+        &mut regs.t6,
+    ]
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::Ordering;
+
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::mm::{PAGE_SIZE, VmReader};
+    use ostd::prelude::ktest;
+    use ostd::task::Task;
+    use ostd::user::UserContextApi;
+
+    use super::{
+        SA_RESTART, SIGCHLD, SIGCONT, SIGINT, SIGKILL, SIGRETURN_TRAMPOLINE, SIGSEGV, SIGSTOP,
+        SIGURG, SIGWINCH, SigAction,
+    };
+    use crate::error::Errno;
+    use crate::fs::FileLike;
+    use crate::fs::file_table::FileEntry;
+    use crate::fs::pipe::Pipe;
+    use crate::ktest_util::new_process;
+    use crate::process::ProcessState;
+    use crate::syscall::SyscallRegs;
+
+    #[ktest]
+    fn sa_restart_read_completes() {
+        let process = new_process("sig_restart");
+        let (reader, writer) = Pipe::new_pair();
+        assert_eq!(process.file_table().insert(FileEntry::new(reader)), 3);
+        process.run();
+
+        let blocked_in_read = || {
+            process.signal_waker.lock().is_some()
+                && process.pending_signals.load(Ordering::Acquire) == 0
+        };
+        while !blocked_in_read() {
+            Task::yield_now();
+        }
+        process.send_signal(SIGINT);
+        // The handler runs, then the read blocks again.
+        while !blocked_in_read() {
+            Task::yield_now();
+        }
+        writer
+            .write(VmReader::from(&b"hello"[..]).to_fallible())
+            .unwrap();
+
+        while !process.is_zombie() {
+            Task::yield_now();
+        }
+        assert_eq!(process.exit_code(), Some(0));
+    }
+
+    #[ktest]
+    fn fault_signals_are_forced() {
+        for blocked in [true, false] {
+            let process = new_process("hello_world");
+            if blocked {
+                process.set_blocked_signals(1 << SIGSEGV);
+            } else {
+                process.set_sig_action(SIGSEGV, SigAction::Ignore).unwrap();
+            }
+
+            process.force_signal(SIGSEGV);
+            assert_eq!(process.sig_action(SIGSEGV).unwrap(), SigAction::Default);
+            assert_eq!(process.blocked_signals() & (1 << SIGSEGV), 0);
+            process.handle_pending_signals(&mut UserContext::default());
+            assert_eq!(process.killed_by(), Some(SIGSEGV));
+        }
+    }
+
+    #[ktest]
+    fn some_signals_are_ignored_by_default() {
+        let process = new_process("hello_world");
+        for signal in [SIGCHLD, SIGURG, SIGWINCH] {
+            process.send_signal(signal);
+        }
+        process.handle_pending_signals(&mut UserContext::default());
+        assert_eq!(process.pending_signals.load(Ordering::Acquire), 0);
+        assert!(!process.is_zombie());
+    }
+
+    #[ktest]
+    fn blocked_read_sleeps() {
+        let process = new_process("pipe_spin");
+        let (reader, writer) = Pipe::new_pair();
+        assert_eq!(process.file_table().insert(FileEntry::new(reader)), 3);
+        process.run();
+
+        while process.signal_waker.lock().is_none() {
+            Task::yield_now();
+        }
+        assert_eq!(process.state(), ProcessState::Sleeping);
+        assert_eq!(process.state().as_char(), 'S');
+
+        writer
+            .write(VmReader::from(&b"hello"[..]).to_fallible())
+            .unwrap();
+        while process.state() == ProcessState::Sleeping {
+            Task::yield_now();
+        }
+        assert_eq!(process.state(), ProcessState::Running);
+
+        process.send_signal(SIGKILL);
+        while !process.is_zombie() {
+            Task::yield_now();
+        }
+        assert_eq!(process.state(), ProcessState::Zombie);
+    }
+
+    #[ktest]
+    fn restart_restores_the_fd_argument() {
+        const ECALL_PC: usize = 0x1_0000;
+        const HANDLER: usize = 0x2_0000;
+        const SYS_READ: usize = 63;
+        const FD: usize = 3;
+
+        let process = new_process("hello_world");
+        process.memory_space().vm_space().activate();
+        let action = SigAction::Handler {
+            entry: HANDLER,
+            flags: SA_RESTART,
+            mask: 0,
+        };
+        process.set_sig_action(SIGINT, action).unwrap();
+
+        // A `read(FD, ...)` got interrupted, and returned into `a0`.
+        let mut user_context = UserContext::default();
+        user_context.set_a0(FD);
+        user_context.set_a7(SYS_READ);
+        user_context.set_stack_pointer(SIGRETURN_TRAMPOLINE - 4 * PAGE_SIZE);
+        process.set_interrupted_syscall(SyscallRegs::new(&user_context));
+        user_context.set_instruction_pointer(ECALL_PC + 4);
+        user_context.set_a0(-(Errno::ERESTARTSYS as i32) as usize);
+
+        process.send_signal(SIGINT);
+        process.handle_pending_signals(&mut user_context);
+        assert_eq!(user_context.instruction_pointer(), HANDLER);
+        assert_eq!(user_context.a0(), SIGINT as usize);
+
+        // The handler returns to the `ecall`, made again with the same fd.
+        process.return_from_handler(&mut user_context).unwrap();
+        assert_eq!(user_context.instruction_pointer(), ECALL_PC);
+        assert_eq!(user_context.a0(), FD);
+        assert_eq!(user_context.a7(), SYS_READ);
+    }
+
+    #[ktest]
+    fn sigstop_stops_until_sigcont() {
+        /// Where `spin` keeps its counter.
+        const COUNTER_ADDR: usize = 0x1000_0000;
+
+        let process = new_process("spin");
+        process.run();
+
+        let counter = || {
+            let vm_space = process.memory_space().vm_space();
+            vm_space.activate();
+            vm_space
+                .reader(COUNTER_ADDR, size_of::<u64>())
+                .ok()
+                .and_then(|mut reader| reader.read_val::<u64>().ok())
+                .unwrap_or(0)
+        };
+        while counter() == 0 {
+            Task::yield_now();
+        }
+
+        process.send_signal(SIGSTOP);
+        while !process.is_stopped() {
+            Task::yield_now();
+        }
+        let stopped_at = counter();
+        for _ in 0..100 {
+            Task::yield_now();
+        }
+        assert_eq!(counter(), stopped_at);
+
+        process.send_signal(SIGCONT);
+        assert!(!process.is_stopped());
+        while counter() == stopped_at {
+            Task::yield_now();
+        }
+
+        process.send_signal(SIGKILL);
+        while !process.is_zombie() {
+            Task::yield_now();
+        }
+    }
+}
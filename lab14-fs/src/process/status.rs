@@ -1,21 +1,56 @@
 use core::sync::atomic::{AtomicU64, Ordering};
 
+use crate::process::signal::Signal;
+
 #[derive(Debug, PartialEq, Eq)]
 #[repr(u64)]
 enum Status {
     Uninit = 0,
     Runnable = 1,
     Zombie = 2,
+    Stopped = 3,
+    Sleeping = 4,
+    DiskSleep = 5,
+}
+
+/// What a process is doing, as `ps` and the procfs `status` file show it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    /// Running, or ready to run.
+    Running,
+    /// Blocked in a wait that a signal interrupts.
+    Sleeping,
+    /// Blocked in a wait that a signal can't interrupt.
+    DiskSleep,
+    Zombie,
+    Stopped,
+}
+
+impl ProcessState {
+    /// The letter Linux shows for the state.
+    pub fn as_char(&self) -> char {
+        match self {
+            ProcessState::Running => 'R',
+            ProcessState::Sleeping => 'S',
+            ProcessState::DiskSleep => 'D',
+            ProcessState::Zombie => 'Z',
+            ProcessState::Stopped => 'T',
+        }
+    }
 }
 
 /// The status of a process.
 ///
 /// ```
-/// 0-31: Status (0: Uninit, 1: Runnable, 2: Zombie)
-/// 32-63: Exit code (if status is Zombie)
+/// 0-30: Status (0: Uninit, 1: Runnable, 2: Zombie, 3: Stopped, 4: Sleeping,
+///       5: DiskSleep)
+/// 31: Killed by a signal (if status is Zombie)
+/// 32-63: Exit code, or the signal that killed the process (if status is Zombie)
 /// ```
 pub struct ProcessStatus(AtomicU64);
 
+const KILLED: u64 = 1 << 31;
+
 impl ProcessStatus {
     pub fn new() -> Self {
         ProcessStatus(AtomicU64::new(Status::Uninit as u64))
@@ -28,29 +63,101 @@ impl ProcessStatus {
         self.0.store(value, Ordering::SeqCst);
     }
 
-    pub fn exit_code(&self) -> Option<u32> {
+    /// Exits as killed by `signal`.
+    pub fn exit_by_signal(&self, signal: Signal) {
         let status = self.get_status();
-        if status == Status::Zombie {
-            let value = self.0.load(Ordering::SeqCst);
+        assert!(status == Status::Runnable);
+        let value = (Status::Zombie as u64) | KILLED | ((signal as u64) << 32);
+        self.0.store(value, Ordering::SeqCst);
+    }
+
+    /// Returns the exit code, if the process exited rather than being killed.
+    pub fn exit_code(&self) -> Option<u32> {
+        let value = self.0.load(Ordering::SeqCst);
+        if self.get_status() == Status::Zombie && value & KILLED == 0 {
             Some((value >> 32) as u32)
         } else {
             None
         }
     }
 
+    /// Returns the signal that killed the process, if one did.
+    pub fn killed_by(&self) -> Option<Signal> {
+        let value = self.0.load(Ordering::SeqCst);
+        if self.get_status() == Status::Zombie && value & KILLED != 0 {
+            Some((value >> 32) as Signal)
+        } else {
+            None
+        }
+    }
+
     pub fn set_runnable(&self) {
         self.0.store(Status::Runnable as u64, Ordering::SeqCst);
     }
 
+    pub fn stop(&self) {
+        let status = self.get_status();
+        assert!(status == Status::Runnable);
+        self.0.store(Status::Stopped as u64, Ordering::SeqCst);
+    }
+
+    /// Makes a stopped process runnable again. Returns whether it was stopped.
+    pub fn resume(&self) -> bool {
+        self.0
+            .compare_exchange(
+                Status::Stopped as u64,
+                Status::Runnable as u64,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            )
+            .is_ok()
+    }
+
+    /// Marks a runnable process as blocked, interruptibly or not.
+    pub fn sleep(&self, interruptible: bool) {
+        let status = self.get_status();
+        assert!(status == Status::Runnable);
+        let sleeping = if interruptible {
+            Status::Sleeping
+        } else {
+            Status::DiskSleep
+        };
+        self.0.store(sleeping as u64, Ordering::SeqCst);
+    }
+
+    /// Marks a blocked process as runnable again.
+    pub fn wake_up(&self) {
+        let status = self.get_status();
+        assert!(status == Status::Sleeping || status == Status::DiskSleep);
+        self.0.store(Status::Runnable as u64, Ordering::SeqCst);
+    }
+
+    pub fn state(&self) -> ProcessState {
+        match self.get_status() {
+            Status::Uninit | Status::Runnable => ProcessState::Running,
+            Status::Sleeping => ProcessState::Sleeping,
+            Status::DiskSleep => ProcessState::DiskSleep,
+            Status::Zombie => ProcessState::Zombie,
+            Status::Stopped => ProcessState::Stopped,
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.get_status() == Status::Stopped
+    }
+
     pub fn is_zombie(&self) -> bool {
         self.get_status() == Status::Zombie
     }
 
     fn get_status(&self) -> Status {
-        match self.0.load(Ordering::SeqCst) & 0xFFFF_FFFF {
+        match self.0.load(Ordering::SeqCst) & 0x7FFF_FFFF {
             0 => Status::Uninit,
             1 => Status::Runnable,
             2 => Status::Zombie,
+            3 => Status::Stopped,
+            4 => Status::Sleeping,
+            5 => Status::DiskSleep,
             _ => panic!("Invalid process status"),
         }
     }
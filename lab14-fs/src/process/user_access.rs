@@ -0,0 +1,111 @@
+//! Copying to and from the memory of a user process.
+//!
+//! The kernel can't take a page fault while it copies, so pages that are not
+//! resident yet, like heap pages never touched, are faulted in first. A range
+//! that is not mapped, or does not allow the access, fails with `EFAULT`.
+
+use alloc::sync::Arc;
+
+use align_ext::AlignExt;
+use ostd::Pod;
+use ostd::mm::{FallibleVmRead, FallibleVmWrite, PAGE_SIZE, Vaddr, VmReader, VmWriter};
+use riscv::register::scause::Exception;
+
+use super::Process;
+use crate::error::{Errno, Error, Result};
+
+impl Process {
+    /// Returns a reader of the `len` bytes from `vaddr`.
+    pub fn user_reader(self: &Arc<Self>, vaddr: Vaddr, len: usize) -> Result<VmReader<'_>> {
+        self.fault_in(vaddr, len, Exception::LoadPageFault)?;
+        self.memory_space()
+            .vm_space()
+            .reader(vaddr, len)
+            .map_err(|_| Error::new(Errno::EFAULT))
+    }
+
+    /// Returns a writer of the `len` bytes from `vaddr`.
+    pub fn user_writer(self: &Arc<Self>, vaddr: Vaddr, len: usize) -> Result<VmWriter<'_>> {
+        self.fault_in(vaddr, len, Exception::StorePageFault)?;
+        self.memory_space()
+            .vm_space()
+            .writer(vaddr, len)
+            .map_err(|_| Error::new(Errno::EFAULT))
+    }
+
+    pub fn read_user_val<T: Pod>(self: &Arc<Self>, vaddr: Vaddr) -> Result<T> {
+        self.user_reader(vaddr, size_of::<T>())?
+            .read_val()
+            .map_err(|_| Error::new(Errno::EFAULT))
+    }
+
+    pub fn write_user_val<T: Pod>(self: &Arc<Self>, vaddr: Vaddr, val: &T) -> Result<()> {
+        self.user_writer(vaddr, size_of::<T>())?
+            .write_val(val)
+            .map_err(|_| Error::new(Errno::EFAULT))
+    }
+
+    /// Fills `bytes` from `vaddr` on.
+    pub fn read_user_bytes(self: &Arc<Self>, vaddr: Vaddr, bytes: &mut [u8]) -> Result<()> {
+        self.user_reader(vaddr, bytes.len())?
+            .read_fallible(&mut VmWriter::from(bytes).to_fallible())
+            .map_err(|_| Error::new(Errno::EFAULT))?;
+        Ok(())
+    }
+
+    /// Writes `bytes` from `vaddr` on.
+    pub fn write_user_bytes(self: &Arc<Self>, vaddr: Vaddr, bytes: &[u8]) -> Result<()> {
+        self.user_writer(vaddr, bytes.len())?
+            .write_fallible(&mut VmReader::from(bytes).to_fallible())
+            .map_err(|_| Error::new(Errno::EFAULT))?;
+        Ok(())
+    }
+
+    /// Faults in the pages of the `len` bytes from `vaddr` for an access like
+    /// `fault`.
+    fn fault_in(self: &Arc<Self>, vaddr: Vaddr, len: usize, fault: Exception) -> Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        let page_base = vaddr.align_down(PAGE_SIZE);
+        let page_end = vaddr
+            .checked_add(len)
+            .and_then(|end| end.checked_add(PAGE_SIZE - 1))
+            .ok_or(Error::new(Errno::EFAULT))?
+            .align_down(PAGE_SIZE);
+        self.memory_space()
+            .fault_in(self, page_base, page_end - page_base, fault)
+            .map_err(|_| Error::new(Errno::EFAULT))
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::mm::PAGE_SIZE;
+    use ostd::prelude::ktest;
+
+    use crate::error::Errno;
+    use crate::ktest_util::new_process;
+
+    #[ktest]
+    fn copies_fault_in_untouched_heap_pages() {
+        let process = new_process("hello_world");
+        let base = process.heap().base();
+        process.heap().brk(Some(base + 2 * PAGE_SIZE), &process);
+        process.memory_space().vm_space().activate();
+
+        // Across the page boundary, neither page touched yet.
+        let vaddr = base + PAGE_SIZE - 4;
+        assert!(!process.memory_space().is_resident(vaddr));
+        process.write_user_val(vaddr, &0x1234_5678_u64).unwrap();
+        assert_eq!(process.read_user_val::<u64>(vaddr).unwrap(), 0x1234_5678);
+        assert!(process.memory_space().is_resident(base + PAGE_SIZE));
+
+        // Past the break, nothing is mapped.
+        let err = process
+            .write_user_val(base + 2 * PAGE_SIZE, &0_u64)
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::EFAULT);
+    }
+}
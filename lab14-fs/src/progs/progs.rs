@@ -1,5 +1,13 @@
+const CLONE_TLS: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/clone_tls");
 const EXEC: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/exec");
+const FAULT_CHILD: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/fault_child");
+const FLOAT_CHILD: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/float_child");
+const FLOAT_YIELD: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/float_yield");
 const FORK: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/fork");
 const FORK_EXEC_TIME: &[u8] =
@@ -8,16 +16,22 @@ const FORK_TIME: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/fork_time");
 const FORK_TIME_LOOP: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/fork_time_loop");
+const HELLO_PIE: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/hello_pie");
 const HELLO_WORLD: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/hello_world");
 const INIT_PROC: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/init_proc");
+const MISALIGNED: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/misaligned");
 const MMAP_ANON_TEST: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/mmap_anon_test");
 const MMAP_TEST: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/mmap_test");
 const PIPE: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/pipe");
+const PIPE_SPIN: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/pipe_spin");
 const RAMFS: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/ramfs");
 const READ_EXT2_FILE: &[u8] =
@@ -30,29 +44,48 @@ const RR_TEST: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/rr_test");
 const SHELL: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/shell");
+const SIG_RESTART: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/sig_restart");
+const SPIN: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/spin");
+const STACK_LIMIT: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/stack_limit");
 const WAIT: &[u8] =
     include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/wait");
+const WAIT_PGRP: &[u8] =
+    include_bytes_aligned::include_bytes_aligned!(32, "../../target/user_prog/wait_pgrp");
 
 pub fn init() {
     super::USER_PROGS.call_once(|| {
         let mut user_progs = alloc::collections::btree_map::BTreeMap::new();
+        user_progs.insert("clone_tls", CLONE_TLS);
         user_progs.insert("exec", EXEC);
+        user_progs.insert("fault_child", FAULT_CHILD);
+        user_progs.insert("float_child", FLOAT_CHILD);
+        user_progs.insert("float_yield", FLOAT_YIELD);
         user_progs.insert("fork", FORK);
         user_progs.insert("fork_exec_time", FORK_EXEC_TIME);
         user_progs.insert("fork_time", FORK_TIME);
         user_progs.insert("fork_time_loop", FORK_TIME_LOOP);
+        user_progs.insert("hello_pie", HELLO_PIE);
         user_progs.insert("hello_world", HELLO_WORLD);
         user_progs.insert("init_proc", INIT_PROC);
+        user_progs.insert("misaligned", MISALIGNED);
         user_progs.insert("mmap_anon_test", MMAP_ANON_TEST);
         user_progs.insert("mmap_test", MMAP_TEST);
         user_progs.insert("pipe", PIPE);
+        user_progs.insert("pipe_spin", PIPE_SPIN);
         user_progs.insert("ramfs", RAMFS);
         user_progs.insert("read_ext2_file", READ_EXT2_FILE);
         user_progs.insert("read_stdin", READ_STDIN);
         user_progs.insert("reparent", REPARENT);
         user_progs.insert("rr_test", RR_TEST);
         user_progs.insert("shell", SHELL);
+        user_progs.insert("sig_restart", SIG_RESTART);
+        user_progs.insert("spin", SPIN);
+        user_progs.insert("stack_limit", STACK_LIMIT);
         user_progs.insert("wait", WAIT);
+        user_progs.insert("wait_pgrp", WAIT_PGRP);
         user_progs
     });
 }
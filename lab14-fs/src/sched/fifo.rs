@@ -8,7 +8,7 @@ use ostd::{
     },
 };
 
-use crate::process::Process;
+use crate::process::{Process, fpu};
 
 pub struct FifoScheduler {
     run_queue: SpinLock<FifoRunQueue>,
@@ -86,6 +86,11 @@ impl LocalRunQueue for FifoRunQueue {
             }
         }
 
+        // The task leaving the CPU is still the current one until the switch.
+        if let Some(ref next) = self.current {
+            fpu::switch_to(next);
+        }
+
         self.current.as_ref()
     }
 }
@@ -8,7 +8,7 @@ use ostd::{
     },
 };
 
-use crate::process::Process;
+use crate::process::{Process, fpu};
 
 pub struct RrScheduler {
     run_queue: SpinLock<RrRunQueue>,
@@ -84,6 +84,11 @@ impl LocalRunQueue for RrRunQueue {
             }
         }
 
+        // The task leaving the CPU is still the current one until the switch.
+        if let Some(ref next) = self.current {
+            fpu::switch_to(&next.task);
+        }
+
         self.current.as_ref().map(|entity| &entity.task)
     }
 }
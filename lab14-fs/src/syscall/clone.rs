@@ -1,12 +1,19 @@
 use alloc::sync::Arc;
 use log::debug;
 use ostd::arch::cpu::context::UserContext;
-use ostd::mm::Vaddr;
+use ostd::mm::{MAX_USERSPACE_VADDR, Vaddr};
 
-use crate::error::Result;
-use crate::process::Process;
+use crate::error::{Errno, Error, Result};
+use crate::process::{Process, SharedResources};
 use crate::syscall::SyscallReturn;
 
+/// Share the working directory and umask with the child.
+const CLONE_FS: u64 = 0x0000_0200;
+/// Share the file table with the child.
+const CLONE_FILES: u64 = 0x0000_0400;
+/// Set the thread pointer of the child to `tls`.
+const CLONE_SETTLS: u64 = 0x0008_0000;
+
 pub fn sys_clone(
     clone_flags: u64,
     child_stack: u64,
@@ -21,9 +28,197 @@ pub fn sys_clone(
         clone_flags, child_stack, parent_tidptr, tls, child_tidptr
     );
 
-    let child_process = current_process.fork(&user_context);
+    // The thread pointer is only ever dereferenced in user mode, but one into the
+    // kernel is a sure sign of a bug.
+    if clone_flags & CLONE_SETTLS != 0 && tls as usize >= MAX_USERSPACE_VADDR {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    let child_context = child_context(user_context, clone_flags, tls);
+    let shared = SharedResources {
+        files: clone_flags & CLONE_FILES != 0,
+        fs: clone_flags & CLONE_FS != 0,
+    };
+    let child_process = current_process.clone_with(&child_context, shared)?;
 
     child_process.run();
 
     Ok(SyscallReturn(child_process.pid() as _))
 }
+
+/// Returns the context the child starts with, which is the parent's unless
+/// `clone_flags` say otherwise.
+fn child_context(user_context: &UserContext, clone_flags: u64, tls: u64) -> UserContext {
+    let mut child_context = user_context.clone();
+    if clone_flags & CLONE_SETTLS != 0 {
+        child_context.set_tls_pointer(tls as _);
+    }
+    child_context
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::boxed::Box;
+    use alloc::string::String;
+    use alloc::sync::Arc;
+    use alloc::vec;
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::mm::{FallibleVmRead, FallibleVmWrite, PAGE_SIZE, Vaddr, VmReader, VmWriter};
+    use ostd::prelude::ktest;
+    use ostd::task::Task;
+
+    use super::{CLONE_SETTLS, child_context};
+    use crate::fs::InodeType;
+    use crate::fs::file_table::FileEntry;
+    use crate::fs::pipe::Pipe;
+    use crate::fs::ramfs::RamFS;
+    use crate::ktest_util::{MAP_PRIVATE, PROT_READ_WRITE, new_process, prog};
+    use crate::process::credentials::ROOT_ID;
+    use crate::process::{Process, SharedResources};
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::cwd::{sys_chdir, sys_getcwd};
+    use crate::syscall::mmap::{MMapFlags, sys_mmap};
+
+    #[ktest]
+    fn thread_local_storage_is_per_child() {
+        let binary = prog("hello_world");
+        let parent = Process::new(binary);
+        let parent_context = parent.exec(binary, None, &[]).unwrap();
+        let parent_tls = parent_context.tls_pointer();
+        let vm_space = parent.memory_space().vm_space();
+        vm_space.activate();
+        vm_space
+            .writer(parent_tls, 8)
+            .unwrap()
+            .write_val(&0x1234_u64)
+            .unwrap();
+
+        // The child can be given a TLS block of its own, next to the parent's.
+        let child_tls = parent_tls + 8;
+        let context = child_context(&parent_context, CLONE_SETTLS, child_tls as u64);
+        assert_eq!(context.tls_pointer(), child_tls);
+        let context = child_context(&parent_context, 0, child_tls as u64);
+        assert_eq!(context.tls_pointer(), parent_tls);
+
+        // Otherwise it uses its copy of the parent's, so its writes leave the
+        // parent's variable alone.
+        let child = parent.fork(&context).unwrap();
+        let vm_space = child.memory_space().vm_space();
+        vm_space.activate();
+        vm_space
+            .writer(context.tls_pointer(), 8)
+            .unwrap()
+            .write_val(&0x5678_u64)
+            .unwrap();
+
+        let vm_space = parent.memory_space().vm_space();
+        vm_space.activate();
+        let value: u64 = vm_space.reader(parent_tls, 8).unwrap().read_val().unwrap();
+        assert_eq!(value, 0x1234);
+    }
+
+    #[ktest]
+    fn child_reads_its_thread_pointer() {
+        let process = new_process("clone_tls");
+        process.run();
+        while !process.is_zombie() {
+            Task::yield_now();
+        }
+        assert_eq!(process.exit_code(), Some(0));
+    }
+
+    #[ktest]
+    fn clone_files_shares_the_file_table() {
+        let parent = new_process("hello_world");
+        let context = UserContext::default();
+
+        // A file the child opens shows up in the parent's table...
+        let child = parent
+            .clone_with(
+                &context,
+                SharedResources {
+                    files: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let (reader, _writer) = Pipe::new_pair();
+        let fd = child.file_table().insert(FileEntry::new(reader.clone()));
+        assert!(parent.file_table().get(fd).is_some());
+        child.file_table().close(fd).unwrap();
+        assert!(parent.file_table().get(fd).is_none());
+
+        // ...but not if the child has a copy.
+        let child = parent.fork(&context).unwrap();
+        let fd = child.file_table().insert(FileEntry::new(reader));
+        assert!(parent.file_table().get(fd).is_none());
+    }
+
+    #[ktest]
+    fn clone_fs_shares_the_working_directory() {
+        let root = crate::fs::ROOT
+            .call_once(|| Box::new(RamFS::new()))
+            .root_inode();
+        if root.lookup("clone_fs").is_err() {
+            root.create("clone_fs", InodeType::Directory, 0o755, ROOT_ID, ROOT_ID)
+                .unwrap();
+        }
+
+        let parent = new_process("hello_world");
+        let mmap_flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(buf) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            mmap_flags,
+            u64::MAX,
+            0,
+            &parent,
+        )
+        .unwrap();
+        let buf = buf as Vaddr;
+        let context = UserContext::default();
+
+        let getcwd = |process: &Arc<Process>| {
+            let vm_space = process.memory_space().vm_space();
+            vm_space.activate();
+            let SyscallReturn(len) = sys_getcwd(buf, PAGE_SIZE, process).unwrap();
+            let mut path = vec![0u8; len as usize - 1];
+            vm_space
+                .reader(buf, path.len())
+                .unwrap()
+                .read_fallible(&mut VmWriter::from(path.as_mut_slice()))
+                .unwrap();
+            String::from_utf8(path).unwrap()
+        };
+        let chdir = |process: &Arc<Process>, path: &[u8]| {
+            let vm_space = process.memory_space().vm_space();
+            vm_space.activate();
+            vm_space
+                .writer(buf, path.len())
+                .unwrap()
+                .write_fallible(&mut VmReader::from(path))
+                .unwrap();
+            sys_chdir(buf, process).unwrap();
+        };
+
+        // A chdir in the child moves the parent too...
+        let child = parent
+            .clone_with(
+                &context,
+                SharedResources {
+                    fs: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        chdir(&child, b"clone_fs\0");
+        assert_eq!(getcwd(&parent), "/clone_fs");
+
+        // ...but not if the child has a copy.
+        let child = parent.fork(&context).unwrap();
+        chdir(&child, b"..\0");
+        assert_eq!(getcwd(&child), "/");
+        assert_eq!(getcwd(&parent), "/clone_fs");
+    }
+}
@@ -0,0 +1,164 @@
+use alloc::sync::Arc;
+use log::debug;
+
+use crate::error::{Errno, Error, Result};
+use crate::process::Process;
+use crate::process::credentials::Id;
+use crate::syscall::SyscallReturn;
+
+/// The id that stands for "no id", which can't be set.
+const NO_ID: Id = Id::MAX;
+
+pub fn sys_getuid(current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_GETUID]");
+    Ok(SyscallReturn(current_process.credentials().uid() as _))
+}
+
+pub fn sys_geteuid(current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_GETEUID]");
+    Ok(SyscallReturn(current_process.credentials().euid() as _))
+}
+
+pub fn sys_getgid(current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_GETGID]");
+    Ok(SyscallReturn(current_process.credentials().gid() as _))
+}
+
+pub fn sys_getegid(current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_GETEGID]");
+    Ok(SyscallReturn(current_process.credentials().egid() as _))
+}
+
+/// Sets all the user ids as root, or else the effective one to the real or saved id.
+pub fn sys_setuid(uid: Id, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_SETUID] uid: {}", uid);
+
+    if uid == NO_ID {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    current_process.credentials().set_uid(uid)?;
+    Ok(SyscallReturn(0))
+}
+
+/// Sets all the group ids as root, or else the effective one to the real or saved id.
+pub fn sys_setgid(gid: Id, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_SETGID] gid: {}", gid);
+
+    if gid == NO_ID {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    current_process.credentials().set_gid(gid)?;
+    Ok(SyscallReturn(0))
+}
+
+/// Sets the real, effective and saved user ids; -1 leaves one as it is.
+pub fn sys_setresuid(
+    uid: Id,
+    euid: Id,
+    suid: Id,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_SETRESUID] uid: {}, euid: {}, suid: {}",
+        uid, euid, suid
+    );
+
+    current_process
+        .credentials()
+        .set_resuid(some_id(uid), some_id(euid), some_id(suid))?;
+    Ok(SyscallReturn(0))
+}
+
+/// Sets the real, effective and saved group ids; -1 leaves one as it is.
+pub fn sys_setresgid(
+    gid: Id,
+    egid: Id,
+    sgid: Id,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_SETRESGID] gid: {}, egid: {}, sgid: {}",
+        gid, egid, sgid
+    );
+
+    current_process
+        .credentials()
+        .set_resgid(some_id(gid), some_id(egid), some_id(sgid))?;
+    Ok(SyscallReturn(0))
+}
+
+fn some_id(id: Id) -> Option<Id> {
+    (id != NO_ID).then_some(id)
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::prelude::ktest;
+
+    use super::{sys_geteuid, sys_getgid, sys_getuid, sys_setgid, sys_setresuid, sys_setuid};
+    use crate::error::Errno;
+    use crate::ktest_util::new_process;
+    use crate::process::Process;
+    use crate::syscall::SyscallReturn;
+
+    #[ktest]
+    fn setuid_sets_the_ids_for_getuid() {
+        let parent = new_process("hello_world");
+        let child = parent.fork(&UserContext::default()).unwrap();
+        // Everything starts out as root.
+        assert_eq!(sys_getuid(&child).unwrap().0, 0);
+        assert_eq!(sys_geteuid(&child).unwrap().0, 0);
+
+        // The group goes first, while the process may still change it.
+        sys_setgid(100, &child).unwrap();
+        assert_eq!(sys_getgid(&child).unwrap().0, 100);
+        let SyscallReturn(ret) = sys_setuid(1000, &child).unwrap();
+        assert_eq!(ret, 0);
+        assert_eq!(sys_getuid(&child).unwrap().0, 1000);
+        assert_eq!(sys_geteuid(&child).unwrap().0, 1000);
+
+        // A fork keeps them, and the parent never had them.
+        let grandchild = child.fork(&UserContext::default()).unwrap();
+        assert_eq!(sys_getuid(&grandchild).unwrap().0, 1000);
+        assert_eq!(sys_getgid(&grandchild).unwrap().0, 100);
+        assert_eq!(sys_getuid(&parent).unwrap().0, 0);
+    }
+
+    #[ktest]
+    fn privileges_can_be_dropped_and_taken_back() {
+        let parent = new_process("hello_world");
+        let process = parent.fork(&UserContext::default()).unwrap();
+        let ids = |process: &Process| {
+            let credentials = process.credentials();
+            (credentials.uid(), credentials.euid(), credentials.suid())
+        };
+
+        // Root may set them to anything.
+        sys_setresuid(1000, 1001, 0, &process).unwrap();
+        assert_eq!(ids(&process), (1000, 1001, 0));
+        sys_setresuid(u32::MAX, 0, u32::MAX, &process).unwrap();
+        assert_eq!(ids(&process), (1000, 0, 0));
+
+        // Drop to an unprivileged effective id, keeping root as the saved one.
+        sys_setresuid(u32::MAX, 1000, u32::MAX, &process).unwrap();
+        assert_eq!(ids(&process), (1000, 1000, 0));
+
+        // Now it can only move between the ids it has.
+        let err = sys_setresuid(u32::MAX, 2000, u32::MAX, &process)
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::EPERM);
+        let err = sys_setuid(2000, &process).err().unwrap();
+        assert_eq!(err.code, Errno::EPERM);
+        assert_eq!(ids(&process), (1000, 1000, 0));
+
+        // Which includes getting root back from the saved id.
+        sys_setuid(0, &process).unwrap();
+        assert_eq!(ids(&process), (1000, 0, 0));
+        sys_setresuid(2000, 2000, 2000, &process).unwrap();
+        assert_eq!(ids(&process), (2000, 2000, 2000));
+    }
+}
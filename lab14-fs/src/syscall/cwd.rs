@@ -0,0 +1,48 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+use crate::syscall::exec::read_path;
+
+pub fn sys_getcwd(
+    buf: Vaddr,
+    size: usize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!("[SYS_GETCWD] buf: {:#x}, size: {}", buf, size);
+
+    let fs = current_process.fs();
+    let cwd_path = fs.cwd_path().as_bytes();
+    // The path goes out NUL-terminated.
+    let len = cwd_path.len() + 1;
+    if size < len {
+        return Err(Error::new(Errno::ERANGE));
+    }
+
+    current_process.write_user_bytes(buf, cwd_path)?;
+    current_process.write_user_bytes(buf + cwd_path.len(), b"\0")?;
+
+    Ok(SyscallReturn(len as _))
+}
+
+pub fn sys_chdir(path: Vaddr, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!("[SYS_CHDIR] path: {}", path);
+
+    if path.is_empty() {
+        return Err(Error::new(Errno::ENOENT));
+    }
+    let credentials = *current_process.credentials();
+    current_process.fs().chdir(&path, credentials)?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_umask(mask: u32, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_UMASK] mask: {:#o}", mask);
+
+    let old_mask = current_process.fs().set_umask(mask as u16);
+    Ok(SyscallReturn(old_mask as _))
+}
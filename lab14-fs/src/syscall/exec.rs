@@ -1,15 +1,29 @@
 use core::ffi::CStr;
 
+use align_ext::AlignExt;
+use alloc::format;
+use alloc::string::{String, ToString};
 use alloc::sync::Arc;
 use alloc::vec;
+use alloc::vec::Vec;
 use log::{debug, info};
 use ostd::arch::cpu::context::UserContext;
-use ostd::mm::{FallibleVmRead, Vaddr, VmWriter};
+use ostd::mm::{MAX_USERSPACE_VADDR, PAGE_SIZE, Vaddr, VmWriter};
 
-use crate::error::Result;
-use crate::process::Process;
+use crate::error::{Errno, Error, Result};
+use crate::fs::util::PathString;
+use crate::fs::{Inode, InodeType, Permission};
+use crate::process::{Process, elf};
 use crate::syscall::SyscallReturn;
 
+pub(super) const AT_FDCWD: i32 = -100;
+const AT_EMPTY_PATH: u32 = 0x1000;
+
+/// The most arguments `execve` takes.
+const MAX_ARGS: usize = 64;
+/// The longest `#!` line, including the `#!`.
+const MAX_SHEBANG_LENGTH: usize = 256;
+
 pub fn sys_execve(
     path: Vaddr, /* &[u8] */
     argv: Vaddr, /* &[&str] */
@@ -22,33 +36,529 @@ pub fn sys_execve(
         path, argv, envp
     );
 
-    // We ignore the argv and envp for now.
-    // The max file name: 255 bytes + 1(\0)
-    const MAX_FILENAME_LENGTH: usize = 256;
-    let mut buffer = vec![0u8; MAX_FILENAME_LENGTH];
-    current_process
-        .memory_space()
-        .vm_space()
-        .reader(path, MAX_FILENAME_LENGTH)
-        .unwrap()
-        .read_fallible(&mut VmWriter::from(&mut buffer as &mut [u8]))
-        .unwrap();
+    // We ignore the envp for now.
+    let exec_name = read_path(path, current_process)?;
+    let args = read_args(argv, current_process)?;
+    info!(
+        "[SYS_EXECVE] Execute program path: {}, args: {:?}",
+        exec_name, args
+    );
 
-    let exec_name = CStr::from_bytes_until_nul(&buffer)
-        .unwrap()
-        .to_str()
-        .unwrap();
+    let program = lookup_program(AT_FDCWD, &exec_name, current_process)?;
+    do_execve(program.bytes(), &exec_name, args, current_process, user_context)
+}
 
-    info!("[SYS_EXECVE] Execute program path: {}", exec_name);
+pub fn sys_execveat(
+    dirfd: i32,
+    path: Vaddr, /* &[u8] */
+    argv: Vaddr, /* &[&str] */
+    envp: Vaddr, /* &[&str] */
+    flags: u32,
+    current_process: &Arc<Process>,
+    user_context: &mut UserContext,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_EXECVEAT] dirfd: {}, path vaddr: {:#x?}, argv vaddr: {:#x?}, envp vaddr: {:#x?}, flags: {:#x}",
+        dirfd, path, argv, envp, flags
+    );
+
+    let exec_name = read_path(path, current_process)?;
+    let args = read_args(argv, current_process)?;
+    info!(
+        "[SYS_EXECVEAT] Execute program path: {}, args: {:?}",
+        exec_name, args
+    );
+
+    if exec_name.is_empty() {
+        if flags & AT_EMPTY_PATH == 0 {
+            return Err(Error::new(Errno::ENOENT));
+        }
+        let inode = dirfd_inode(dirfd, current_process)?;
+        inode
+            .metadata()
+            .check_permission(&current_process.credentials(), Permission::EXEC)?;
+        let binary = AlignedBinary::read_from(inode.as_ref())?;
+        let path = format!("/dev/fd/{}", dirfd);
+        return do_execve(binary.bytes(), &path, args, current_process, user_context);
+    }
+
+    let program = lookup_program(dirfd, &exec_name, current_process)?;
+    do_execve(program.bytes(), &exec_name, args, current_process, user_context)
+}
 
-    let binary = crate::progs::lookup_progs(exec_name)?;
+/// A program to execute.
+enum Program {
+    BuiltIn(&'static [u8]),
+    File(AlignedBinary),
+}
+
+impl Program {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Program::BuiltIn(binary) => binary,
+            Program::File(file) => file.bytes(),
+        }
+    }
+}
+
+/// Finds the program at `path`. Built-in programs come first, by name, and
+/// anything else is looked up in the file system, relative to `dirfd`.
+fn lookup_program(dirfd: i32, path: &str, current_process: &Arc<Process>) -> Result<Program> {
+    match crate::progs::lookup_progs(path.trim_start_matches('/')) {
+        Ok(binary) => Ok(Program::BuiltIn(binary)),
+        Err(err) if err.code == Errno::ENOENT => {
+            read_executable(dirfd, path, current_process).map(Program::File)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Executes `binary`, which was found at `path`.
+fn do_execve(
+    binary: &[u8],
+    path: &str,
+    args: Vec<String>,
+    current_process: &Arc<Process>,
+    user_context: &mut UserContext,
+) -> Result<SyscallReturn> {
+    let (binary, args) = match Shebang::parse(binary)? {
+        // The interpreter runs with the script in place of the script's own argv[0].
+        Some(shebang) => {
+            let interpreter = shebang.lookup_interpreter()?;
+            let mut interpreter_args = vec![shebang.interpreter];
+            interpreter_args.extend(shebang.arg);
+            interpreter_args.push(path.to_string());
+            interpreter_args.extend(args.into_iter().skip(1));
+            (interpreter, interpreter_args)
+        }
+        None => (binary, args),
+    };
+
+    // A dynamically-linked program starts in its loader, which lives in the file system.
+    elf::check_elf(binary)?;
+    let interpreter = match elf::interpreter_path(binary)? {
+        Some(interpreter_path) => Some(read_executable(
+            AT_FDCWD,
+            interpreter_path,
+            current_process,
+        )?),
+        None => None,
+    };
 
     // Do exec:
     // 1. Cleanup all the memory space, including heap
     // 2. Change the user context to zero
     // 3. Parse ELF and load program
 
-    *user_context = current_process.exec(binary);
+    *user_context = current_process.exec(
+        binary,
+        interpreter.as_ref().map(AlignedBinary::bytes),
+        &args,
+    )?;
 
     Ok(SyscallReturn(0 as _))
 }
+
+/// The `#!` line of a script.
+#[derive(Debug, PartialEq, Eq)]
+struct Shebang {
+    interpreter: String,
+    /// Everything after the interpreter, passed on as a single argument.
+    arg: Option<String>,
+}
+
+impl Shebang {
+    /// Parses the `#!` line of `binary`, if it is a script.
+    fn parse(binary: &[u8]) -> Result<Option<Self>> {
+        if !binary.starts_with(b"#!") {
+            return Ok(None);
+        }
+
+        let head = &binary[..binary.len().min(MAX_SHEBANG_LENGTH)];
+        let line_end = match head.iter().position(|&byte| byte == b'\n') {
+            Some(line_end) => line_end,
+            // The line may only end without a newline at the end of the file.
+            None if binary.len() <= MAX_SHEBANG_LENGTH => binary.len(),
+            None => return Err(Error::new(Errno::ENOEXEC)),
+        };
+        let line = core::str::from_utf8(&head[2..line_end])
+            .map_err(|_| Error::new(Errno::ENOEXEC))?
+            .trim();
+
+        let (interpreter, arg) = match line.split_once([' ', '\t']) {
+            Some((interpreter, arg)) => (interpreter, Some(arg.trim())),
+            None => (line, None),
+        };
+        if interpreter.is_empty() {
+            return Err(Error::new(Errno::ENOEXEC));
+        }
+
+        Ok(Some(Self {
+            interpreter: interpreter.to_string(),
+            arg: arg.filter(|arg| !arg.is_empty()).map(ToString::to_string),
+        }))
+    }
+
+    /// Interpreters are built-in programs, found by the last component of
+    /// their path. `sh` is the shell.
+    fn lookup_interpreter(&self) -> Result<&'static [u8]> {
+        let name = self.interpreter.rsplit('/').next().unwrap();
+        let name = match name {
+            "sh" => "shell",
+            name => name,
+        };
+        crate::progs::lookup_progs(name).map_err(|_| Error::new(Errno::ENOEXEC))
+    }
+}
+
+/// Reads the NULL-terminated `argv` array.
+fn read_args(argv: Vaddr, current_process: &Arc<Process>) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    if argv == 0 {
+        return Ok(args);
+    }
+
+    for index in 0..=MAX_ARGS {
+        let arg: u64 = current_process.read_user_val(argv + index * size_of::<u64>())?;
+        if arg == 0 {
+            return Ok(args);
+        }
+        args.push(read_path(arg as Vaddr, current_process)?);
+    }
+    Err(Error::new(Errno::E2BIG))
+}
+
+pub(super) fn read_path(path: Vaddr, current_process: &Arc<Process>) -> Result<String> {
+    // The max file name: 255 bytes + 1(\0)
+    const MAX_FILENAME_LENGTH: usize = 256;
+    let mut buffer = vec![0u8; MAX_FILENAME_LENGTH];
+    if path >= MAX_USERSPACE_VADDR {
+        return Err(Error::new(Errno::EFAULT));
+    }
+    // The string may end right before an unmapped page, so it is read a page at a
+    // time, and only up to the page its NUL is in.
+    let mut read_len = 0;
+    while read_len < MAX_FILENAME_LENGTH && !buffer[..read_len].contains(&0) {
+        let vaddr = path + read_len;
+        let len =
+            (vaddr.align_down(PAGE_SIZE) + PAGE_SIZE - vaddr).min(MAX_FILENAME_LENGTH - read_len);
+        current_process.read_user_bytes(vaddr, &mut buffer[read_len..read_len + len])?;
+        read_len += len;
+    }
+
+    Ok(CStr::from_bytes_until_nul(&buffer[..read_len])
+        .map_err(|_| Error::new(Errno::ENAMETOOLONG))?
+        .to_str()
+        .map_err(|_| Error::new(Errno::EINVAL))?
+        .to_string())
+}
+
+/// Reads the file at `path`, relative to `dirfd`, which `current_process` must
+/// be allowed to execute.
+fn read_executable(
+    dirfd: i32,
+    path: &str,
+    current_process: &Arc<Process>,
+) -> Result<AlignedBinary> {
+    let start = if path.starts_with('/') {
+        crate::fs::ROOT
+            .get()
+            .ok_or(Error::new(Errno::ENOENT))?
+            .root_inode()
+    } else {
+        dirfd_inode(dirfd, current_process)?
+    };
+    if start.typ() != InodeType::Directory {
+        return Err(Error::new(Errno::ENOTDIR));
+    }
+
+    let credentials = *current_process.credentials();
+    let inode = PathString::new(path.to_string())
+        .with_credentials(credentials)
+        .lookup(start.as_ref())?;
+    inode
+        .metadata()
+        .check_permission(&credentials, Permission::EXEC)?;
+    AlignedBinary::read_from(inode.as_ref())
+}
+
+pub(super) fn dirfd_inode(dirfd: i32, current_process: &Arc<Process>) -> Result<Arc<dyn Inode>> {
+    if dirfd == AT_FDCWD {
+        return Ok(current_process.fs().cwd());
+    }
+
+    let file_table = current_process.file_table();
+    let file = file_table
+        .get(dirfd)
+        .ok_or(Error::new(Errno::EBADF))?
+        .file();
+    file.as_inode().ok_or(Error::new(Errno::EACCES))
+}
+
+/// A program read from a file.
+///
+/// The ELF parser needs its input aligned like the headers in it, which a
+/// plain byte vector does not promise.
+struct AlignedBinary {
+    buffer: Vec<u8>,
+    offset: usize,
+    len: usize,
+}
+
+impl AlignedBinary {
+    const ALIGN: usize = 8;
+
+    fn read_from(inode: &dyn Inode) -> Result<Self> {
+        if inode.typ() != InodeType::File {
+            return Err(Error::new(Errno::EACCES));
+        }
+
+        let len = inode.size();
+        let mut buffer = vec![0u8; len + Self::ALIGN - 1];
+        let offset = buffer.as_ptr().align_offset(Self::ALIGN);
+        let read_len = inode.read_at(
+            0,
+            VmWriter::from(&mut buffer[offset..offset + len]).to_fallible(),
+        )?;
+
+        Ok(Self {
+            buffer,
+            offset,
+            len: read_len,
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.buffer[self.offset..self.offset + self.len]
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::boxed::Box;
+    use alloc::string::String;
+    use alloc::sync::Arc;
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::mm::{PAGE_SIZE, Vaddr, VmReader};
+    use ostd::prelude::ktest;
+    use ostd::user::UserContextApi;
+
+    use super::{
+        AT_EMPTY_PATH, AT_FDCWD, Shebang, read_args, read_path, sys_execve, sys_execveat,
+    };
+    use crate::error::Errno;
+    use crate::fs::file_table::{FileDescriptor, FileEntry};
+    use crate::fs::ramfs::RamFS;
+    use crate::fs::util::FileInode;
+    use crate::fs::{FileSystem, InodeType};
+    use crate::ktest_util::{MAP_PRIVATE, PROT_READ_WRITE, new_process, prog};
+    use crate::process::Process;
+    use crate::process::credentials::ROOT_ID;
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::mmap::{MMapFlags, sys_mmap};
+
+    /// Opens a new file holding `content`.
+    fn open_with_content(process: &Arc<Process>, content: &[u8]) -> FileDescriptor {
+        let ramfs = RamFS::new();
+        let inode = ramfs
+            .root_inode()
+            .create("file", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        inode
+            .write_at(0, VmReader::from(content).to_fallible())
+            .unwrap();
+        process
+            .file_table()
+            .insert(FileEntry::new(Arc::new(FileInode::new(inode))))
+    }
+
+    /// Returns the address of an empty string in user memory.
+    fn empty_path(process: &Arc<Process>) -> Vaddr {
+        // A fresh anonymous page reads as an empty string.
+        let flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(path) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            flags,
+            u64::MAX,
+            0,
+            process,
+        )
+        .unwrap();
+        process.memory_space().vm_space().activate();
+        path as Vaddr
+    }
+
+    fn entry_point(binary: &[u8]) -> usize {
+        let header = xmas_elf::header::parse_header(binary).unwrap();
+        header.pt2.entry_point() as usize
+    }
+
+    #[ktest]
+    fn execveat_empty_path() {
+        let binary = prog("hello_world");
+        let process = Process::new(binary);
+        let fd = open_with_content(&process, binary);
+        let path = empty_path(&process);
+
+        let mut user_context = UserContext::default();
+        sys_execveat(fd, path, 0, 0, AT_EMPTY_PATH, &process, &mut user_context).unwrap();
+
+        assert_eq!(user_context.instruction_pointer(), entry_point(binary));
+    }
+
+    #[ktest]
+    fn execveat_needs_exec_permission() {
+        let binary = prog("hello_world");
+        let process = Process::new(binary);
+        let fd = open_with_content(&process, binary);
+        let path = empty_path(&process);
+        process.credentials().set_uid(1000).unwrap();
+
+        // The file is rw-r--r--, so nobody but root may run it.
+        let mut user_context = UserContext::default();
+        let err = sys_execveat(fd, path, 0, 0, AT_EMPTY_PATH, &process, &mut user_context)
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::EACCES);
+
+        let inode = process.file_table().get(fd).unwrap().file().as_inode();
+        inode.unwrap().set_mode(0o755).unwrap();
+        sys_execveat(fd, path, 0, 0, AT_EMPTY_PATH, &process, &mut user_context).unwrap();
+        assert_eq!(user_context.instruction_pointer(), entry_point(binary));
+    }
+
+    #[ktest]
+    fn execve_needs_exec_permission() {
+        let binary = prog("hello_world");
+        let root = crate::fs::ROOT
+            .call_once(|| Box::new(RamFS::new()))
+            .root_inode();
+        let inode = root
+            .create("execve_prog", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        inode
+            .write_at(0, VmReader::from(binary).to_fallible())
+            .unwrap();
+
+        let process = Process::new(binary);
+        let path = empty_path(&process);
+        process.write_user_bytes(path, b"/execve_prog\0").unwrap();
+        process.credentials().set_uid(1000).unwrap();
+
+        // Files run by path are checked like the ones run through a descriptor.
+        let mut user_context = UserContext::default();
+        let err = sys_execve(path, 0, 0, &process, &mut user_context).err().unwrap();
+        assert_eq!(err.code, Errno::EACCES);
+
+        inode.set_mode(0o755).unwrap();
+        sys_execve(path, 0, 0, &process, &mut user_context).unwrap();
+        assert_eq!(user_context.instruction_pointer(), entry_point(binary));
+    }
+
+    #[ktest]
+    fn execveat_looks_in_the_file_system() {
+        let binary = prog("hello_world");
+        let root = crate::fs::ROOT
+            .call_once(|| Box::new(RamFS::new()))
+            .root_inode();
+        let dir = root
+            .create("execveat_dir", InodeType::Directory, 0o755, ROOT_ID, ROOT_ID)
+            .unwrap();
+        dir.create("prog", InodeType::File, 0o755, ROOT_ID, ROOT_ID)
+            .unwrap()
+            .write_at(0, VmReader::from(binary).to_fallible())
+            .unwrap();
+
+        let process = Process::new(binary);
+        let credentials = *process.credentials();
+        process.fs().chdir("/execveat_dir", credentials).unwrap();
+
+        // Relative paths start from the working directory, absolute ones from the root.
+        for name in [&b"prog\0"[..], b"/execveat_dir/prog\0"] {
+            let path = empty_path(&process);
+            process.write_user_bytes(path, name).unwrap();
+            let mut user_context = UserContext::default();
+            sys_execveat(AT_FDCWD, path, 0, 0, 0, &process, &mut user_context).unwrap();
+            assert_eq!(user_context.instruction_pointer(), entry_point(binary));
+        }
+    }
+
+    #[ktest]
+    fn script_runs_its_interpreter() {
+        let process = new_process("hello_world");
+        let fd = open_with_content(&process, b"#!/bin/sh\necho hello\n");
+        let path = empty_path(&process);
+
+        let mut user_context = UserContext::default();
+        sys_execveat(fd, path, 0, 0, AT_EMPTY_PATH, &process, &mut user_context).unwrap();
+
+        let shell = prog("shell");
+        assert_eq!(user_context.instruction_pointer(), entry_point(shell));
+
+        // The shell gets the script to run as its argument.
+        let stack_pointer = user_context.stack_pointer();
+        let argc: u64 = process
+            .memory_space()
+            .vm_space()
+            .reader(stack_pointer, size_of::<u64>())
+            .unwrap()
+            .read_val()
+            .unwrap();
+        assert_eq!(argc, 2);
+        let args = read_args(stack_pointer + size_of::<u64>(), &process).unwrap();
+        assert_eq!(args, ["/bin/sh", &alloc::format!("/dev/fd/{}", fd)]);
+        assert_eq!(read_path(path, &process).unwrap(), String::new());
+    }
+
+    #[ktest]
+    fn garbage_is_enoexec() {
+        let binary = prog("hello_world");
+        let process = Process::new(binary);
+        let path = empty_path(&process);
+        let rss_pages = process.memory_space().rss_pages();
+
+        // Some xorshift noise, then the same with a valid ELF magic.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut garbage = [0u8; 4096];
+        for byte in garbage.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *byte = state as u8;
+        }
+        let mut with_magic = garbage;
+        with_magic[..4].copy_from_slice(b"\x7fELF");
+        let truncated = &binary[..128];
+
+        for content in [&garbage[..], &with_magic[..], truncated] {
+            let fd = open_with_content(&process, content);
+            let mut user_context = UserContext::default();
+            let err = sys_execveat(fd, path, 0, 0, AT_EMPTY_PATH, &process, &mut user_context)
+                .err()
+                .unwrap();
+            assert_eq!(err.code, Errno::ENOEXEC);
+        }
+
+        // The old image is left alone.
+        assert_eq!(process.memory_space().rss_pages(), rss_pages);
+        assert_eq!(read_path(path, &process).unwrap(), String::new());
+    }
+
+    #[ktest]
+    fn bad_shebang_is_enoexec() {
+        let shebang = Shebang::parse(b"#! /bin/sh  -e \n").unwrap().unwrap();
+        assert_eq!(shebang.interpreter, "/bin/sh");
+        assert_eq!(shebang.arg.as_deref(), Some("-e"));
+        assert!(Shebang::parse(b"\x7fELF").unwrap().is_none());
+
+        let no_interpreter = Shebang::parse(b"#!   \necho hello\n").unwrap_err();
+        assert_eq!(no_interpreter.code, Errno::ENOEXEC);
+
+        let mut too_long = [b'a'; 512];
+        too_long[..3].copy_from_slice(b"#!/");
+        let too_long = Shebang::parse(&too_long).unwrap_err();
+        assert_eq!(too_long.code, Errno::ENOEXEC);
+    }
+}
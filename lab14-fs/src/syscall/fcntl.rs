@@ -0,0 +1,47 @@
+use alloc::sync::Arc;
+use log::debug;
+
+use crate::error::{Errno, Error, Result};
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+const F_GETFD: i32 = 1;
+const F_SETFD: i32 = 2;
+const F_GETFL: i32 = 3;
+const F_SETFL: i32 = 4;
+
+const FD_CLOEXEC: usize = 1;
+const O_NONBLOCK: usize = 0o4000;
+
+pub fn sys_fcntl(
+    fd: i32,
+    cmd: i32,
+    arg: usize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!("[SYS_FCNTL] fd: {}, cmd: {}, arg: {:#x}", fd, cmd, arg);
+
+    let mut file_table = current_process.file_table();
+    let entry = file_table.get_mut(fd).ok_or(Error::new(Errno::EBADF))?;
+    let file = entry.file();
+
+    match cmd {
+        F_GETFD => {
+            let flags = if entry.close_on_exec() { FD_CLOEXEC } else { 0 };
+            Ok(SyscallReturn(flags as _))
+        }
+        F_SETFD => {
+            entry.set_close_on_exec(arg & FD_CLOEXEC != 0);
+            Ok(SyscallReturn(0))
+        }
+        F_GETFL => {
+            let flags = if file.is_nonblocking() { O_NONBLOCK } else { 0 };
+            Ok(SyscallReturn(flags as _))
+        }
+        F_SETFL => {
+            file.set_nonblocking(arg & O_NONBLOCK != 0);
+            Ok(SyscallReturn(0))
+        }
+        _ => Err(Error::new(Errno::EINVAL)),
+    }
+}
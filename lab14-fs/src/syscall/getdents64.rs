@@ -0,0 +1,71 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::InodeType;
+use crate::fs::file_table::FileDescriptor;
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+const DT_LNK: u8 = 10;
+
+/// The size of `struct linux_dirent64` without its name.
+const DIRENT_HEADER_SIZE: usize = 19;
+
+pub fn sys_getdents64(
+    fd: FileDescriptor,
+    dirp: Vaddr,
+    count: usize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_GETDENTS64] fd: {}, dirp: {:#x}, count: {}",
+        fd, dirp, count
+    );
+
+    let file = current_process
+        .file_table()
+        .get(fd)
+        .ok_or(Error::new(Errno::EBADF))?
+        .file()
+        .clone();
+
+    let mut buf = Vec::new();
+    let mut too_small = false;
+    file.read_dir(&mut |entry| {
+        // The name is NUL-terminated and the record padded to 8 bytes.
+        let record_len = (DIRENT_HEADER_SIZE + entry.name.len() + 1).next_multiple_of(8);
+        if buf.len() + record_len > count {
+            too_small = buf.is_empty();
+            return false;
+        }
+        let d_type = match entry.typ {
+            InodeType::File => DT_REG,
+            InodeType::Directory => DT_DIR,
+            InodeType::SymbolLink => DT_LNK,
+        };
+
+        let d_off = buf.len() + record_len;
+        // Inodes carry no numbers here, any nonzero one marks a live entry.
+        buf.extend_from_slice(&1u64.to_ne_bytes());
+        buf.extend_from_slice(&(d_off as i64).to_ne_bytes());
+        buf.extend_from_slice(&(record_len as u16).to_ne_bytes());
+        buf.push(d_type);
+        buf.extend_from_slice(entry.name.as_bytes());
+        buf.resize(d_off, 0);
+        true
+    })?;
+
+    // Not even the next entry fits.
+    if too_small {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    current_process.write_user_bytes(dirp, &buf)?;
+
+    Ok(SyscallReturn(buf.len() as _))
+}
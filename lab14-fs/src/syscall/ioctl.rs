@@ -0,0 +1,161 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::console;
+use crate::error::{Errno, Error, Result};
+use crate::process::{self, Process};
+use crate::syscall::SyscallReturn;
+
+const TIOCSCTTY: u32 = 0x540E;
+const TIOCGPGRP: u32 = 0x540F;
+const TIOCSPGRP: u32 = 0x5410;
+
+pub fn sys_ioctl(
+    fd: i32,
+    cmd: u32,
+    arg: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!("[SYS_IOCTL] fd: {}, cmd: {:#x}, arg: {:#x}", fd, cmd, arg);
+
+    let file_table = current_process.file_table();
+    let file = file_table.get(fd).ok_or(Error::new(Errno::EBADF))?;
+    if !file.file().is_terminal() {
+        return Err(Error::new(Errno::ENOTTY));
+    }
+
+    let sid = current_process.sid();
+    match cmd {
+        TIOCSCTTY => {
+            // Only a session leader can acquire the terminal, and only if no
+            // other session has it. Stealing it with `arg` set needs
+            // privileges, which no process has.
+            if sid != current_process.pid() {
+                return Err(Error::new(Errno::EPERM));
+            }
+            match console::session() {
+                session if session == sid => {}
+                0 => console::set_session(sid),
+                _ => return Err(Error::new(Errno::EPERM)),
+            }
+        }
+        // The foreground group is only known to, and set by, the session the
+        // terminal controls.
+        _ if console::session() != sid => return Err(Error::new(Errno::ENOTTY)),
+        TIOCGPGRP => {
+            let pgid = console::foreground_pgid() as i32;
+            current_process.write_user_val(arg, &pgid)?;
+        }
+        TIOCSPGRP => {
+            let pgid: i32 = current_process.read_user_val(arg)?;
+            if pgid < 0 {
+                return Err(Error::new(Errno::EINVAL));
+            }
+            if !process::group_in_session(pgid as _, sid) {
+                return Err(Error::new(Errno::EPERM));
+            }
+            console::set_foreground_pgid(pgid as _);
+        }
+        _ => return Err(Error::new(Errno::ENOTTY)),
+    }
+
+    Ok(SyscallReturn(0))
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::mm::{PAGE_SIZE, Vaddr};
+    use ostd::prelude::ktest;
+    use ostd::task::Task;
+
+    use super::{TIOCSCTTY, TIOCSPGRP, sys_ioctl};
+    use crate::console;
+    use crate::error::Errno;
+    use crate::ktest_util::{MAP_PRIVATE, PROT_READ_WRITE, new_process};
+    use crate::process::ProcessState;
+    use crate::process::signal::SIGINT;
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::mmap::{MMapFlags, sys_mmap};
+
+    /// "End of Text", sent by Ctrl-C.
+    const ETX: u8 = 3;
+
+    #[ktest]
+    fn ctrl_c_interrupts_only_the_foreground_group() {
+        let leader = new_process("hello_world");
+        let foreground = leader.fork(&UserContext::default()).unwrap();
+        foreground.set_pgid(0, 0).unwrap();
+        let background = leader.fork(&UserContext::default()).unwrap();
+
+        let mmap_flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(pgid_addr) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            mmap_flags,
+            u64::MAX,
+            0,
+            &leader,
+        )
+        .unwrap();
+        let pgid_addr = pgid_addr as Vaddr;
+        let vm_space = leader.memory_space().vm_space();
+        vm_space.activate();
+
+        let old_session = console::session();
+        let old_foreground = console::foreground_pgid();
+        console::set_session(0);
+
+        // Only a session leader gets a controlling terminal.
+        let err = sys_ioctl(0, TIOCSCTTY, 0, &foreground).err().unwrap();
+        assert_eq!(err.code, Errno::EPERM);
+        sys_ioctl(0, TIOCSCTTY, 0, &leader).unwrap();
+        assert_eq!(console::session(), leader.sid());
+
+        let tcsetpgrp = |pgid: usize| {
+            vm_space
+                .writer(pgid_addr, size_of::<i32>())
+                .unwrap()
+                .write_val(&(pgid as i32))
+                .unwrap();
+            sys_ioctl(0, TIOCSPGRP, pgid_addr, &leader)
+        };
+        // A group in another session can't be put in the foreground.
+        let other = new_process("hello_world");
+        let err = tcsetpgrp(other.pgid()).err().unwrap();
+        assert_eq!(err.code, Errno::EPERM);
+        tcsetpgrp(foreground.pgid()).unwrap();
+
+        console::push_input(&[ETX]);
+        console::handle_interrupt();
+        assert!(foreground.has_unblocked_signal());
+        assert!(!background.has_unblocked_signal());
+        assert!(!leader.has_unblocked_signal());
+
+        console::set_session(old_session);
+        console::set_foreground_pgid(old_foreground);
+    }
+
+    #[ktest]
+    fn ctrl_c_kills_a_blocked_reader() {
+        let reader = new_process("read_stdin");
+        let old_foreground = console::foreground_pgid();
+        console::set_foreground_pgid(reader.pgid());
+        while console::try_read_byte().is_some() {}
+
+        reader.run();
+        while reader.state() != ProcessState::Sleeping {
+            Task::yield_now();
+        }
+        // No `handle_interrupt` here: the woken reader passes the Ctrl-C on.
+        console::push_input(&[ETX]);
+        while !reader.is_zombie() {
+            Task::yield_now();
+        }
+        assert_eq!(reader.killed_by(), Some(SIGINT));
+
+        console::set_foreground_pgid(old_foreground);
+    }
+}
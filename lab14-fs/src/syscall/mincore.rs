@@ -0,0 +1,39 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use log::debug;
+use ostd::mm::{PAGE_SIZE, Vaddr};
+
+use crate::error::{Errno, Error, Result};
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+pub fn sys_mincore(
+    addr: Vaddr,
+    length: usize,
+    vec_addr: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_MINCORE] addr: {:#x}, length: {:#x}, vec: {:#x}",
+        addr, length, vec_addr
+    );
+
+    if addr % PAGE_SIZE != 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    let memory_space = current_process.memory_space();
+    let pages = length.div_ceil(PAGE_SIZE);
+    let mut residency = Vec::with_capacity(pages);
+    for page in 0..pages {
+        let vaddr = addr + page * PAGE_SIZE;
+        if memory_space.is_free(vaddr, PAGE_SIZE) {
+            return Err(Error::new(Errno::ENOMEM));
+        }
+        residency.push(memory_space.is_resident(vaddr) as u8);
+    }
+
+    current_process.write_user_bytes(vec_addr, &residency)?;
+
+    Ok(SyscallReturn(0))
+}
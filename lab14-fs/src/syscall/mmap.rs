@@ -8,9 +8,9 @@ use ostd::mm::{CachePolicy, FrameAllocOptions, PAGE_SIZE, PageFlags, PagePropert
 
 use crate::error::{Errno, Error, Result};
 use crate::fs::Inode;
-use crate::mm::VmMapping;
 use crate::mm::area::VmArea;
-use crate::mm::fault::{PageFaultContext, PageFaultHandler};
+use crate::mm::fault::{AllocationPageFaultHandler, PageFaultContext, PageFaultHandler};
+use crate::mm::{MemorySpace, VmMapping};
 use crate::process::Process;
 use crate::syscall::SyscallReturn;
 
@@ -42,39 +42,90 @@ pub fn sys_mmap(
     offset: u64,
     current_process: &Arc<Process>,
 ) -> Result<SyscallReturn> {
-    // Current, we only support mmap with file
-    // MAP_PRIVATE, MAP_FIXED, no MAP_ANONYMOUS
-    assert!(vaddr != 0);
-    assert!(vaddr.align_down(PAGE_SIZE as _) == vaddr);
-    assert!(offset == 0);
-    assert!(flags & 0xf == 0x2);
-    let mmap_flags = MMapFlags::from_bits_truncate(flags as u32);
-    assert!(mmap_flags == MMapFlags::MAP_FIXED);
-
-    // Now, we can map the file
-    let page_flags = PageFlags::from_bits_truncate(perms as _);
-    let inode = current_process
-        .file_table()
-        .get(fd as _)
-        .unwrap()
-        .file()
-        .as_inode()
-        .ok_or(Error::new(Errno::EBADF))?;
-
-    let handler = Arc::new(MMapInodeFaultHandler {
-        base_vaddr: vaddr as _,
-        inode,
-    });
+    // Current, we only support MAP_PRIVATE mmap, either anonymous or with a file from
+    // its beginning. Anything else is refused rather than half done.
+    const MAP_TYPE: u32 = 0xf;
+    const MAP_PRIVATE: u32 = 0x2;
+    let supported = MMapFlags::MAP_FIXED | MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE;
+    let mmap_flags = MMapFlags::from_bits(flags & !MAP_TYPE)
+        .filter(|mmap_flags| supported.contains(*mmap_flags))
+        .ok_or(Error::new(Errno::EINVAL))?;
+    if vaddr % PAGE_SIZE as u64 != 0 || offset != 0 || flags & MAP_TYPE != MAP_PRIVATE {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    if length == 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
 
     let memory_space = current_process.memory_space();
-    memory_space.add_area(VmArea::new_with_handler(
-        vaddr as _,
-        length.align_up(PAGE_SIZE as _) as usize / PAGE_SIZE,
-        page_flags,
-        handler,
-    ));
-
-    Ok(SyscallReturn(vaddr as _))
+    let len = (length as usize)
+        .checked_add(PAGE_SIZE - 1)
+        .ok_or(Error::new(Errno::ENOMEM))?
+        .align_down(PAGE_SIZE);
+    let base_vaddr = choose_base_vaddr(memory_space, vaddr as _, len, mmap_flags)?;
+
+    let page_flags = PageFlags::from_bits_truncate(perms as _);
+    // A PROT_NONE area gets its handler too, as mprotect may open it up later. Until
+    // then any access to it is a real fault.
+    let handler: Arc<dyn PageFaultHandler> = if mmap_flags.contains(MMapFlags::MAP_ANONYMOUS) {
+        Arc::new(AllocationPageFaultHandler)
+    } else {
+        // Now, we can map the file
+        let inode = current_process
+            .file_table()
+            .get(fd as _)
+            .ok_or(Error::new(Errno::EBADF))?
+            .file()
+            .as_inode()
+            .ok_or(Error::new(Errno::EBADF))?;
+        Arc::new(MMapInodeFaultHandler { base_vaddr, inode })
+    };
+
+    if mmap_flags.contains(MMapFlags::MAP_FIXED) {
+        // Like Linux, the new mapping replaces whatever was there. Only now that
+        // nothing else can fail, so that a failed mmap leaves the old one alone.
+        memory_space.unmap(base_vaddr, len)?;
+    }
+    let area = VmArea::new_with_handler(base_vaddr, len / PAGE_SIZE, page_flags, handler);
+    // The address the user asked for is only a hint, so report where the area really is.
+    let base_vaddr = area.base_vaddr();
+    memory_space.add_area(area)?;
+
+    if mmap_flags.contains(MMapFlags::MAP_POPULATE) && !page_flags.is_empty() {
+        // Fault in every page now. If that fails, undo the whole mapping.
+        if let Err(err) = memory_space.populate(current_process, base_vaddr, len) {
+            memory_space.remove_area(base_vaddr);
+            return Err(err);
+        }
+    }
+
+    Ok(SyscallReturn(base_vaddr as _))
+}
+
+/// Picks where a mapping of `len` bytes goes.
+///
+/// With `MAP_FIXED` the address is taken as is, even if something is mapped there
+/// already. Otherwise it is only a hint, which is honored if the range is free, and
+/// a free region is picked if it is not or if there is no hint at all.
+fn choose_base_vaddr(
+    memory_space: &MemorySpace,
+    hint: Vaddr,
+    len: usize,
+    flags: MMapFlags,
+) -> Result<Vaddr> {
+    if flags.contains(MMapFlags::MAP_FIXED) {
+        if hint == 0 {
+            return Err(Error::new(Errno::EINVAL));
+        }
+        return Ok(hint);
+    }
+    if hint != 0 && memory_space.is_free(hint, len) {
+        return Ok(hint);
+    }
+
+    memory_space
+        .find_free_region(len)
+        .ok_or(Error::new(Errno::ENOMEM))
 }
 
 pub struct MMapInodeFaultHandler {
@@ -94,21 +145,39 @@ impl PageFaultHandler for MMapInodeFaultHandler {
     fn handle_page_fault<'a>(&self, context: PageFaultContext<'a>) -> Result<()> {
         let memory_space = context.process.memory_space();
         let vm_space = memory_space.vm_space();
-        let frame = FrameAllocOptions::new().alloc_frame().unwrap();
+        // The file overwrites the page right away, so only the part it doesn't cover is
+        // cleared below.
+        let frame = FrameAllocOptions::new()
+            .zeroed(false)
+            .alloc_frame()
+            .map_err(|_| Error::new(Errno::ENOMEM))?;
         let align_down_vaddr = context.vaddr.align_down(PAGE_SIZE);
 
-        // Read data from Inode
-        self.inode
+        // Read data from Inode. The fault may come from a syscall, e.g. with
+        // `MAP_POPULATE`, so a file that can't be read must not take the kernel down.
+        let read_len = self
+            .inode
             .read_at(
                 align_down_vaddr - self.base_vaddr,
                 frame.writer().to_fallible(),
             )
-            .unwrap();
+            .map_err(|_| Error::new(Errno::EIO))?;
+        if read_len < PAGE_SIZE {
+            frame
+                .writer()
+                .skip(read_len)
+                .fill_zeros(PAGE_SIZE - read_len);
+        }
 
         let guard = disable_local();
         let mut cursor_mut = vm_space
             .cursor_mut(&guard, &(align_down_vaddr..align_down_vaddr + PAGE_SIZE))
             .unwrap();
+        // The page may have been faulted in by a racing thread meanwhile, see
+        // `AllocationPageFaultHandler`.
+        if let Ok((_, Some(_))) = cursor_mut.query() {
+            return Ok(());
+        }
         cursor_mut.map(
             frame.clone().into(),
             PageProperty::new_user(context.perms, CachePolicy::Writeback),
@@ -121,3 +190,308 @@ impl PageFaultHandler for MMapInodeFaultHandler {
         Ok(())
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+    use ostd::mm::{FallibleVmRead, PAGE_SIZE, PageFlags, Vaddr, VmWriter};
+    use ostd::prelude::ktest;
+    use riscv::register::scause::Exception;
+
+    use super::{MMapFlags, choose_base_vaddr, sys_mmap};
+    use crate::error::Errno;
+    use crate::fs::file_table::FileEntry;
+    use crate::fs::ramfs::RamFS;
+    use crate::fs::util::FileInode;
+    use crate::fs::{FileSystem, InodeType};
+    use crate::ktest_util::{
+        MAP_PRIVATE, MAP_SHARED, PROT_NONE, PROT_READ, PROT_READ_WRITE, new_process,
+    };
+    use crate::process::USER_STACK_SIZE;
+    use crate::process::credentials::ROOT_ID;
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::mincore::sys_mincore;
+
+    #[ktest]
+    fn overlapping_hint_is_moved() {
+        let process = new_process("hello_world");
+        let memory_space = process.memory_space();
+
+        // Right in the middle of the user stack.
+        let hint = 0x40_0000_0000 - 10 * PAGE_SIZE - USER_STACK_SIZE / 2;
+        let len = 4 * PAGE_SIZE;
+        assert!(!memory_space.is_free(hint, len));
+
+        let base_vaddr = choose_base_vaddr(memory_space, hint, len, MMapFlags::empty()).unwrap();
+        assert_ne!(base_vaddr, hint);
+        assert!(memory_space.is_free(base_vaddr, len));
+
+        // A free hint is taken as is.
+        let free_hint = base_vaddr;
+        let base_vaddr = choose_base_vaddr(memory_space, free_hint, len, MMapFlags::empty());
+        assert_eq!(base_vaddr.unwrap(), free_hint);
+    }
+
+    #[ktest]
+    fn mmap_without_address() {
+        let process = new_process("hello_world");
+        let memory_space = process.memory_space();
+
+        let flags = MAP_PRIVATE | MMapFlags::MAP_ANONYMOUS.bits();
+        let len = 3 * PAGE_SIZE;
+        let SyscallReturn(base_vaddr) =
+            sys_mmap(0, len as _, PROT_READ_WRITE, flags, u64::MAX, 0, &process).unwrap();
+        let base_vaddr = base_vaddr as Vaddr;
+        assert_ne!(base_vaddr, 0);
+        assert_eq!(base_vaddr % PAGE_SIZE, 0);
+
+        // Fault the last page in, as the user's first store to it would.
+        let vaddr = base_vaddr + len - 8;
+        memory_space
+            .with_area_mut(vaddr, |area| {
+                area.handle_page_fault(&process, vaddr, Exception::StorePageFault)
+            })
+            .unwrap()
+            .unwrap();
+
+        let vm_space = memory_space.vm_space();
+        vm_space.activate();
+        vm_space
+            .writer(vaddr, 8)
+            .unwrap()
+            .write_val(&0xdead_beef_u64)
+            .unwrap();
+        let value: u64 = vm_space.reader(vaddr, 8).unwrap().read_val().unwrap();
+        assert_eq!(value, 0xdead_beef);
+    }
+
+    #[ktest]
+    fn anonymous_pages_are_zeroed() {
+        const PAGES: usize = 16;
+
+        let flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let len = PAGES * PAGE_SIZE;
+
+        // Dirty a bunch of frames and give them back to the allocator.
+        let process = new_process("hello_world");
+        let SyscallReturn(base_vaddr) =
+            sys_mmap(0, len as _, PROT_READ_WRITE, flags, u64::MAX, 0, &process).unwrap();
+        let vm_space = process.memory_space().vm_space();
+        vm_space.activate();
+        let mut writer = vm_space.writer(base_vaddr as Vaddr, len).unwrap();
+        while writer.has_avail() {
+            writer.write_val(&0xa5a5_a5a5_a5a5_a5a5_u64).unwrap();
+        }
+        drop(process);
+
+        let process = new_process("hello_world");
+        let SyscallReturn(base_vaddr) =
+            sys_mmap(0, len as _, PROT_READ_WRITE, flags, u64::MAX, 0, &process).unwrap();
+        let vm_space = process.memory_space().vm_space();
+        vm_space.activate();
+        let mut reader = vm_space.reader(base_vaddr as Vaddr, len).unwrap();
+        while reader.has_remain() {
+            assert_eq!(reader.read_val::<u64>().unwrap(), 0);
+        }
+    }
+
+    #[ktest]
+    fn populate_maps_every_page() {
+        let process = new_process("hello_world");
+
+        let flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let mmap = |len: usize| {
+            let SyscallReturn(base_vaddr) =
+                sys_mmap(0, len as _, PROT_READ_WRITE, flags, u64::MAX, 0, &process).unwrap();
+            base_vaddr as Vaddr
+        };
+
+        const PAGES: usize = 8;
+        let base_vaddr = mmap(PAGES * PAGE_SIZE);
+        let vec_addr = mmap(PAGE_SIZE);
+
+        process.memory_space().vm_space().activate();
+        sys_mincore(base_vaddr, PAGES * PAGE_SIZE, vec_addr, &process).unwrap();
+        let mut residency = [0u8; PAGES];
+        process
+            .memory_space()
+            .vm_space()
+            .reader(vec_addr, PAGES)
+            .unwrap()
+            .read_fallible(&mut VmWriter::from(&mut residency as &mut [u8]).to_fallible())
+            .unwrap();
+        assert_eq!(residency, [1; PAGES]);
+    }
+
+    #[ktest]
+    fn prot_none_reserves_range() {
+        let process = new_process("hello_world");
+        let memory_space = process.memory_space();
+
+        let flags = MAP_PRIVATE | MMapFlags::MAP_ANONYMOUS.bits();
+        let len = 4 * PAGE_SIZE;
+
+        let SyscallReturn(reserved) =
+            sys_mmap(0, len as _, PROT_NONE, flags, u64::MAX, 0, &process).unwrap();
+        let reserved = reserved as Vaddr;
+
+        // Touching the range is a real fault, nothing gets filled in.
+        let result = memory_space
+            .with_area_mut(reserved, |area| {
+                area.handle_page_fault(&process, reserved, Exception::LoadPageFault)
+            })
+            .unwrap();
+        assert!(result.is_err());
+        assert!(!memory_space.is_resident(reserved));
+
+        // Nothing else is placed in the range.
+        let SyscallReturn(base_vaddr) = sys_mmap(
+            reserved as _,
+            len as _,
+            PROT_READ_WRITE,
+            flags,
+            u64::MAX,
+            0,
+            &process,
+        )
+        .unwrap();
+        let base_vaddr = base_vaddr as Vaddr;
+        assert!(base_vaddr >= reserved + len || base_vaddr + len <= reserved);
+    }
+
+    #[ktest]
+    fn map_fixed_replaces_old_mapping() {
+        let process = new_process("hello_world");
+        let memory_space = process.memory_space();
+
+        let flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(base_vaddr) = sys_mmap(
+            0,
+            3 * PAGE_SIZE as u64,
+            PROT_READ_WRITE,
+            flags,
+            u64::MAX,
+            0,
+            &process,
+        )
+        .unwrap();
+        let base_vaddr = base_vaddr as Vaddr;
+
+        // Replace the middle page with a read-only one.
+        let middle = base_vaddr + PAGE_SIZE;
+        let fixed_flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_FIXED).bits();
+        let SyscallReturn(fixed) = sys_mmap(
+            middle as _,
+            PAGE_SIZE as _,
+            PROT_READ,
+            fixed_flags,
+            u64::MAX,
+            0,
+            &process,
+        )
+        .unwrap();
+        assert_eq!(fixed as Vaddr, middle);
+
+        // The old page is gone, and the pages around it are left alone.
+        assert!(!memory_space.is_resident(middle));
+        assert!(memory_space.is_resident(base_vaddr));
+        assert!(memory_space.is_resident(middle + PAGE_SIZE));
+        let perms = |vaddr| memory_space.with_area_mut(vaddr, |area| area.perms());
+        assert_eq!(perms(base_vaddr), Some(PageFlags::RW));
+        assert_eq!(perms(middle), Some(PageFlags::R));
+        assert_eq!(perms(middle + PAGE_SIZE), Some(PageFlags::RW));
+    }
+
+    #[ktest]
+    fn failed_map_fixed_keeps_old_mapping() {
+        let process = new_process("hello_world");
+        let memory_space = process.memory_space();
+
+        let flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(base_vaddr) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            flags,
+            u64::MAX,
+            0,
+            &process,
+        )
+        .unwrap();
+        let base_vaddr = base_vaddr as Vaddr;
+
+        // A file mapping from a file descriptor that is not open.
+        let err = sys_mmap(
+            base_vaddr as _,
+            PAGE_SIZE as _,
+            PROT_READ,
+            MAP_PRIVATE | MMapFlags::MAP_FIXED.bits(),
+            1000,
+            0,
+            &process,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.code, Errno::EBADF);
+        assert!(memory_space.is_resident(base_vaddr));
+        let perms = memory_space.with_area_mut(base_vaddr, |area| area.perms());
+        assert_eq!(perms, Some(PageFlags::RW));
+    }
+
+    #[ktest]
+    fn unreadable_file_fails_populate() {
+        let process = new_process("hello_world");
+        let dir = RamFS::new()
+            .root_inode()
+            .create("dir", InodeType::Dir, 0o755, ROOT_ID, ROOT_ID)
+            .unwrap();
+        let fd = process
+            .file_table()
+            .insert(FileEntry::new(Arc::new(FileInode::new(dir))));
+
+        // Reading a directory fails, which has to fail the mmap too.
+        let err = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ,
+            MAP_PRIVATE | MMapFlags::MAP_POPULATE.bits(),
+            fd as _,
+            0,
+            &process,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.code, Errno::EIO);
+    }
+
+    #[ktest]
+    fn unsupported_arguments_are_invalid() {
+        let process = new_process("hello_world");
+
+        let anonymous = MAP_PRIVATE | MMapFlags::MAP_ANONYMOUS.bits();
+        let len = PAGE_SIZE as u64;
+        let cases = [
+            // Shared, and private with flags that are not supported.
+            (0, len, MAP_SHARED | MMapFlags::MAP_ANONYMOUS.bits(), 0),
+            (0, len, anonymous | MMapFlags::MAP_NORESERVE.bits(), 0),
+            (0, len, anonymous | MMapFlags::MAP_STACK.bits(), 0),
+            (0, len, anonymous | MMapFlags::MAP_DENYWRITE.bits(), 0),
+            // An address off a page boundary, an offset, and nothing to map.
+            (PAGE_SIZE as u64 + 8, len, anonymous, 0),
+            (0, len, anonymous, PAGE_SIZE as u64),
+            (0, 0, anonymous, 0),
+        ];
+        for (vaddr, len, flags, offset) in cases {
+            let result = sys_mmap(
+                vaddr,
+                len,
+                PROT_READ_WRITE,
+                flags,
+                u64::MAX,
+                offset,
+                &process,
+            );
+            assert_eq!(result.err().unwrap().code, Errno::EINVAL);
+        }
+    }
+}
@@ -1,64 +1,167 @@
 mod brk;
 mod clone;
+mod credentials;
+mod cwd;
 mod exec;
 mod exit;
+mod fcntl;
+mod getdents64;
+mod ioctl;
+mod mincore;
 mod mmap;
-mod open;
+mod mprotect;
+pub mod open;
 mod pipe;
+mod poll;
 mod prlimit;
 mod read;
+mod readlink;
+mod set_tid_address;
+mod setpgid;
+mod signal;
+mod splice;
+mod stat;
+mod sync;
 mod time;
 mod uname;
+mod utimensat;
 mod wait4;
 mod write;
 
 use alloc::sync::Arc;
-use log::{debug, info};
+use log::{debug, info, warn};
 use ostd::arch::cpu::context::UserContext;
 use ostd::arch::qemu::exit_qemu;
 use ostd::task::Task;
+use syscall_names::syscall_name;
 
 use crate::error::{Errno, Error, Result};
 use crate::process::Process;
 use crate::syscall::brk::sys_brk;
 use crate::syscall::clone::sys_clone;
-use crate::syscall::exec::sys_execve;
+use crate::syscall::credentials::{
+    sys_getegid, sys_geteuid, sys_getgid, sys_getuid, sys_setgid, sys_setresgid, sys_setresuid,
+    sys_setuid,
+};
+use crate::syscall::cwd::{sys_chdir, sys_getcwd, sys_umask};
+use crate::syscall::exec::{sys_execve, sys_execveat};
 use crate::syscall::exit::sys_exit;
+use crate::syscall::fcntl::sys_fcntl;
+use crate::syscall::getdents64::sys_getdents64;
+use crate::syscall::ioctl::sys_ioctl;
+use crate::syscall::mincore::sys_mincore;
 use crate::syscall::mmap::sys_mmap;
+use crate::syscall::mprotect::sys_mprotect;
 use crate::syscall::pipe::sys_pipe2;
+use crate::syscall::poll::sys_ppoll;
 use crate::syscall::prlimit::sys_prlimit64;
 use crate::syscall::read::sys_read;
+use crate::syscall::readlink::sys_readlinkat;
+use crate::syscall::set_tid_address::sys_set_tid_address;
+use crate::syscall::setpgid::{sys_getpgid, sys_setpgid, sys_setsid};
+use crate::syscall::signal::{sys_rt_sigaction, sys_rt_sigprocmask, sys_rt_sigreturn};
+use crate::syscall::splice::{sys_splice, sys_tee};
+use crate::syscall::stat::sys_fstatat;
+use crate::syscall::sync::{sys_fdatasync, sys_fsync, sys_sync_file_range};
 use crate::syscall::time::sys_clock_gettime;
 use crate::syscall::uname::sys_uname;
-use crate::syscall::wait4::sys_wait4;
+use crate::syscall::utimensat::sys_utimensat;
+use crate::syscall::wait4::{sys_wait4, sys_waitid};
 use crate::syscall::write::{sys_write, sys_writev};
 
 pub struct SyscallReturn(pub isize);
 
+/// The argument registers `a0` to `a7` of a syscall, as it was made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallRegs([usize; 8]);
+
+impl SyscallRegs {
+    pub fn new(user_context: &UserContext) -> Self {
+        Self([
+            user_context.a0(),
+            user_context.a1(),
+            user_context.a2(),
+            user_context.a3(),
+            user_context.a4(),
+            user_context.a5(),
+            user_context.a6(),
+            user_context.a7(),
+        ])
+    }
+
+    pub fn restore(&self, user_context: &mut UserContext) {
+        let [a0, a1, a2, a3, a4, a5, a6, a7] = self.0;
+        user_context.set_a0(a0);
+        user_context.set_a1(a1);
+        user_context.set_a2(a2);
+        user_context.set_a3(a3);
+        user_context.set_a4(a4);
+        user_context.set_a5(a5);
+        user_context.set_a6(a6);
+        user_context.set_a7(a7);
+    }
+}
+
 pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Process>) {
+    const SYS_GETCWD: usize = 17;
+    const SYS_FCNTL: usize = 25;
+    const SYS_IOCTL: usize = 29;
+    const SYS_CHDIR: usize = 49;
     const SYS_OPENAT: usize = 56;
     const SYS_PIPE2: usize = 59;
+    const SYS_GETDENTS64: usize = 61;
 
     const SYS_READ: usize = 63;
     const SYS_WRITE: usize = 64;
     const SYS_WRITEV: usize = 66;
+    const SYS_PPOLL: usize = 73;
+    const SYS_SPLICE: usize = 76;
+    const SYS_TEE: usize = 77;
+    const SYS_READLINKAT: usize = 78;
+    const SYS_NEWFSTATAT: usize = 79;
+    const SYS_FSYNC: usize = 82;
+    const SYS_FDATASYNC: usize = 83;
+    const SYS_SYNC_FILE_RANGE: usize = 84;
+    const SYS_UTIMENSAT: usize = 88;
     const SYS_EXIT: usize = 93;
     const SYS_EXIT_GROUP: usize = 94;
+    const SYS_WAITID: usize = 95;
+    const SYS_SET_TID_ADDRESS: usize = 96;
 
     const SYS_CLOCK_GETTIME: usize = 113;
     const SYS_SCHED_YIELD: usize = 124;
+    const SYS_RT_SIGACTION: usize = 134;
+    const SYS_RT_SIGPROCMASK: usize = 135;
+    const SYS_RT_SIGRETURN: usize = 139;
     const SYS_REBOOT: usize = 142;
+    const SYS_SETGID: usize = 144;
+    const SYS_SETUID: usize = 146;
+    const SYS_SETRESUID: usize = 147;
+    const SYS_SETRESGID: usize = 149;
+    const SYS_SETPGID: usize = 154;
+    const SYS_GETPGID: usize = 155;
+    const SYS_SETSID: usize = 157;
     const SYS_NEWUNAME: usize = 160;
+    const SYS_UMASK: usize = 166;
     const SYS_GETPID: usize = 172;
     const SYS_GETPPID: usize = 173;
+    const SYS_GETUID: usize = 174;
+    const SYS_GETEUID: usize = 175;
+    const SYS_GETGID: usize = 176;
+    const SYS_GETEGID: usize = 177;
     const SYS_BRK: usize = 214;
     const SYS_CLONE: usize = 220;
     const SYS_EXECVE: usize = 221;
     const SYS_MMAP: usize = 222;
     const SYS_MPROTECT: usize = 226;
+    const SYS_MINCORE: usize = 232;
     const SYS_WAIT4: usize = 260;
     const SYS_PRLIMIT64: usize = 261;
+    const SYS_EXECVEAT: usize = 281;
 
+    // A syscall interrupted by a signal may restart, and needs its arguments
+    // back once `a0` holds the return value.
+    let regs = SyscallRegs::new(user_context);
     let args = [
         user_context.a0(),
         user_context.a1(),
@@ -77,19 +180,74 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
 
     let ret: Result<SyscallReturn> = match user_context.a7() {
         SYS_PIPE2 => sys_pipe2(args[0] as _, args[1] as _, current_process),
+        SYS_GETDENTS64 => sys_getdents64(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_SPLICE => sys_splice(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            args[4] as _,
+            args[5] as _,
+            current_process,
+        ),
+        SYS_TEE => sys_tee(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+
+        SYS_READLINKAT => sys_readlinkat(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_NEWFSTATAT => sys_fstatat(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_FSYNC => sys_fsync(args[0] as _, current_process),
+        SYS_FDATASYNC => sys_fdatasync(args[0] as _, current_process),
+        SYS_SYNC_FILE_RANGE => sys_sync_file_range(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_UTIMENSAT => sys_utimensat(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
 
         SYS_WRITEV => sys_writev(args[0] as _, args[1] as _, args[2] as _, current_process),
         SYS_NEWUNAME => sys_uname(args[0] as _, current_process),
         SYS_BRK => sys_brk(args[0] as _, current_process),
-        SYS_MPROTECT => Ok(SyscallReturn(0)),
+        SYS_MPROTECT => sys_mprotect(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_MINCORE => sys_mincore(args[0] as _, args[1] as _, args[2] as _, current_process),
         SYS_GETPID => Ok(SyscallReturn(current_process.pid() as _)),
-        SYS_GETPPID => {
-            let ppid = current_process
-                .parent_process()
-                .and_then(|p| Some(p.pid()))
-                .unwrap_or(0);
-            Ok(SyscallReturn(ppid as _))
-        }
+        SYS_SET_TID_ADDRESS => sys_set_tid_address(args[0] as _, current_process),
+        SYS_GETPPID => Ok(SyscallReturn(current_process.ppid() as _)),
+        SYS_SETPGID => sys_setpgid(args[0] as _, args[1] as _, current_process),
+        SYS_GETPGID => sys_getpgid(args[0] as _, current_process),
+        SYS_SETSID => sys_setsid(current_process),
+        SYS_GETUID => sys_getuid(current_process),
+        SYS_GETEUID => sys_geteuid(current_process),
+        SYS_GETGID => sys_getgid(current_process),
+        SYS_GETEGID => sys_getegid(current_process),
+        SYS_SETUID => sys_setuid(args[0] as _, current_process),
+        SYS_SETGID => sys_setgid(args[0] as _, current_process),
+        SYS_SETRESUID => sys_setresuid(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_SETRESGID => sys_setresgid(args[0] as _, args[1] as _, args[2] as _, current_process),
         SYS_PRLIMIT64 => sys_prlimit64(
             args[0] as _,
             args[1] as _,
@@ -114,6 +272,15 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
             current_process,
             user_context,
         ),
+        SYS_EXECVEAT => sys_execveat(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            args[4] as _,
+            current_process,
+            user_context,
+        ),
         SYS_WAIT4 => sys_wait4(
             args[0] as _,
             args[1] as _,
@@ -121,8 +288,42 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
             args[3] as _,
             current_process,
         ),
+        SYS_WAITID => sys_waitid(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
         SYS_CLOCK_GETTIME => sys_clock_gettime(args[0] as _, args[1] as _, current_process),
         SYS_REBOOT => exit_qemu(ostd::arch::qemu::QemuExitCode::Success),
+        SYS_GETCWD => sys_getcwd(args[0] as _, args[1] as _, current_process),
+        SYS_CHDIR => sys_chdir(args[0] as _, current_process),
+        SYS_UMASK => sys_umask(args[0] as _, current_process),
+        SYS_FCNTL => sys_fcntl(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_PPOLL => sys_ppoll(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_RT_SIGACTION => sys_rt_sigaction(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_RT_SIGPROCMASK => sys_rt_sigprocmask(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            args[3] as _,
+            current_process,
+        ),
+        SYS_RT_SIGRETURN => sys_rt_sigreturn(current_process, user_context),
+        SYS_IOCTL => sys_ioctl(args[0] as _, args[1] as _, args[2] as _, current_process),
         SYS_READ => sys_read(args[0] as _, args[1] as _, args[2] as _, current_process),
         SYS_SCHED_YIELD => {
             Task::yield_now();
@@ -147,12 +348,23 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
             args[5] as _,
             current_process,
         ),
-        _ => Err(Error::new(Errno::ENOSYS)),
+        _ => {
+            warn!(
+                "[pid: {}] unimplemented syscall num: {} ({})",
+                current_process.pid(),
+                user_context.a7(),
+                syscall_name(user_context.a7()).unwrap_or("unknown")
+            );
+            Err(Error::new(Errno::ENOSYS))
+        }
     };
 
     match ret {
         Ok(value) => user_context.set_a0(value.0 as usize),
         Err(e) => {
+            if e.code == Errno::ERESTARTSYS {
+                current_process.set_interrupted_syscall(regs);
+            }
             debug!(
                 "[pid: {}] Syscall num: {}, return error: {:?}",
                 current_process.pid(),
@@ -163,3 +375,27 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
         }
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::prelude::ktest;
+    use syscall_names::syscall_name;
+
+    use crate::error::Errno;
+    use crate::ktest_util::new_process;
+
+    #[ktest]
+    fn unimplemented_syscall_returns_enosys() {
+        let process = new_process("hello_world");
+
+        // Files can't be removed yet, so `unlinkat` hits the fallback arm.
+        assert_eq!(syscall_name(35), Some("unlinkat"));
+        let mut user_context = UserContext::default();
+        user_context.set_a7(35);
+        super::handle_syscall(&mut user_context, &process);
+
+        assert_eq!(user_context.a0() as isize, -(Errno::ENOSYS as isize));
+        assert!(!process.is_zombie());
+    }
+}
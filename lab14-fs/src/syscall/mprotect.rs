@@ -0,0 +1,156 @@
+use align_ext::AlignExt;
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::{PAGE_SIZE, PageFlags, Vaddr};
+
+use crate::error::{Errno, Error, Result};
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+pub fn sys_mprotect(
+    addr: Vaddr,
+    length: usize,
+    prot: u64,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_MPROTECT] addr: {:#x}, length: {:#x}, prot: {:#x}",
+        addr, length, prot
+    );
+
+    if addr % PAGE_SIZE != 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    if length == 0 {
+        return Ok(SyscallReturn(0));
+    }
+    let perms = PageFlags::from_bits(prot as _)
+        .filter(|perms| PageFlags::RWX.contains(*perms))
+        .ok_or(Error::new(Errno::EINVAL))?;
+    let len = length
+        .checked_add(PAGE_SIZE - 1)
+        .ok_or(Error::new(Errno::ENOMEM))?
+        .align_down(PAGE_SIZE);
+
+    current_process.memory_space().protect(addr, len, perms)?;
+    Ok(SyscallReturn(0))
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+    use ostd::mm::vm_space::VmQueriedItem;
+    use ostd::mm::{PAGE_SIZE, PageFlags, Vaddr, VmReader};
+    use ostd::prelude::ktest;
+    use ostd::task::disable_preempt;
+    use riscv::register::scause::Exception;
+
+    use super::sys_mprotect;
+    use crate::error::Errno;
+    use crate::fs::file_table::FileEntry;
+    use crate::fs::ramfs::RamFS;
+    use crate::fs::util::FileInode;
+    use crate::fs::{FileSystem, InodeType};
+    use crate::ktest_util::{MAP_PRIVATE, PROT_EXEC, PROT_READ, PROT_WRITE, new_process};
+    use crate::process::Process;
+    use crate::process::credentials::ROOT_ID;
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::mmap::sys_mmap;
+
+    /// Returns the permissions of the page table entry for `vaddr`, if it is mapped.
+    fn mapped_perms(process: &Arc<Process>, vaddr: Vaddr) -> Option<PageFlags> {
+        let guard = disable_preempt();
+        let vm_space = process.memory_space().vm_space();
+        let mut cursor = vm_space
+            .cursor(&guard, &(vaddr..vaddr + PAGE_SIZE))
+            .unwrap();
+        match cursor.query().unwrap() {
+            (_, Some(VmQueriedItem::MappedRam { prop, .. })) => Some(prop.flags & PageFlags::RWX),
+            _ => None,
+        }
+    }
+
+    #[ktest]
+    fn library_segments_fault_with_their_perms() {
+        let process = new_process("hello_world");
+
+        // A code, a data and a relro page, each filled with its index.
+        let ramfs = RamFS::new();
+        let inode = ramfs
+            .root_inode()
+            .create("lib", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        for page in 0..3 {
+            let content = [page as u8; PAGE_SIZE];
+            inode
+                .write_at(
+                    page * PAGE_SIZE,
+                    VmReader::from(&content as &[u8]).to_fallible(),
+                )
+                .unwrap();
+        }
+        let fd = process
+            .file_table()
+            .insert(FileEntry::new(Arc::new(FileInode::new(inode))));
+
+        // Map the whole file like a loader does, then narrow down each segment.
+        let SyscallReturn(base_vaddr) = sys_mmap(
+            0,
+            3 * PAGE_SIZE as u64,
+            PROT_READ | PROT_WRITE,
+            MAP_PRIVATE,
+            fd as _,
+            0,
+            &process,
+        )
+        .unwrap();
+        let base_vaddr = base_vaddr as Vaddr;
+        let code = base_vaddr;
+        let data = base_vaddr + PAGE_SIZE;
+        let relro = base_vaddr + 2 * PAGE_SIZE;
+        for (vaddr, prot) in [
+            (code, PROT_READ | PROT_EXEC),
+            (data, PROT_READ | PROT_WRITE),
+            (relro, PROT_READ),
+        ] {
+            sys_mprotect(vaddr, PAGE_SIZE, prot, &process).unwrap();
+        }
+
+        let memory_space = process.memory_space();
+        let fault = |vaddr: Vaddr, fault: Exception| {
+            memory_space
+                .with_area_mut(vaddr, |area| area.handle_page_fault(&process, vaddr, fault))
+                .unwrap()
+        };
+        fault(code, Exception::InstructionPageFault).unwrap();
+        fault(data, Exception::StorePageFault).unwrap();
+        fault(relro, Exception::LoadPageFault).unwrap();
+        assert_eq!(mapped_perms(&process, code), Some(PageFlags::RX));
+        assert_eq!(mapped_perms(&process, data), Some(PageFlags::RW));
+        assert_eq!(mapped_perms(&process, relro), Some(PageFlags::R));
+
+        // Each page comes from its own part of the file.
+        let vm_space = memory_space.vm_space();
+        vm_space.activate();
+        for (page, vaddr) in [code, data, relro].into_iter().enumerate() {
+            let byte: u8 = vm_space.reader(vaddr, 1).unwrap().read_val().unwrap();
+            assert_eq!(byte, page as u8);
+        }
+
+        // Writes to the read-only segments are refused rather than mapped.
+        for vaddr in [code, relro] {
+            let err = fault(vaddr, Exception::StorePageFault).err().unwrap();
+            assert_eq!(err.code, Errno::EACCES);
+        }
+    }
+
+    #[ktest]
+    fn uncovered_range_is_enomem() {
+        let process = new_process("hello_world");
+
+        let err = sys_mprotect(0x50_0000_0000, PAGE_SIZE, PROT_READ, &process)
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::ENOMEM);
+    }
+}
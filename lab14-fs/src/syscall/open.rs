@@ -1,21 +1,22 @@
-use core::ffi::CStr;
-
-use alloc::string::ToString;
 use alloc::sync::Arc;
-use alloc::vec;
 use log::debug;
-use ostd::mm::{FallibleVmRead, Vaddr, VmWriter};
+use ostd::mm::Vaddr;
 
 use crate::error::{Errno, Error, Result};
-use crate::fs::InodeType;
 use crate::fs::file_table::FileEntry;
 use crate::fs::util::PathString;
+use crate::fs::{InodeType, Permission};
 use crate::process::Process;
 use crate::syscall::SyscallReturn;
+use crate::syscall::exec::read_path;
 
 bitflags::bitflags! {
     pub struct OpenFlags: u32 {
+        const O_WRONLY = 1 << 0;
+        const O_RDWR = 1 << 1;
         const O_CREAT = 1 << 6;
+        const O_NONBLOCK = 1 << 11;
+        const O_CLOEXEC = 1 << 19;
     }
 }
 
@@ -31,39 +32,84 @@ pub fn sys_openat(
         dfd, file_name, flags, mode
     );
 
-    // The max file name: 255 bytes + 1(\0)
-    const MAX_FILENAME_LENGTH: usize = 256;
-    let mut buffer = vec![0u8; MAX_FILENAME_LENGTH];
-    current_process
-        .memory_space()
-        .vm_space()
-        .reader(file_name, MAX_FILENAME_LENGTH)
-        .unwrap()
-        .read_fallible(&mut VmWriter::from(&mut buffer as &mut [u8]))
-        .unwrap();
-
-    let file_name = CStr::from_bytes_until_nul(&buffer)
-        .unwrap()
-        .to_str()
-        .unwrap();
+    let file_name = read_path(file_name, current_process)?;
 
-    let create = OpenFlags::from_bits_truncate(flags as u32).contains(OpenFlags::O_CREAT);
-    let mut path_string = PathString::new(file_name.to_string());
-    let current_inode = crate::fs::ROOT.get().unwrap().root_inode();
+    let flags = OpenFlags::from_bits_truncate(flags as u32);
+    let create = flags.contains(OpenFlags::O_CREAT);
+    let credentials = *current_process.credentials();
+    let mut path_string = PathString::new(file_name.clone()).with_credentials(credentials);
+    let current_inode = if file_name.starts_with('/') {
+        crate::fs::ROOT.get().unwrap().root_inode()
+    } else {
+        current_process.fs().cwd()
+    };
     if path_string.is_empty() {
         return Err(Error::new(Errno::EINVAL));
     }
 
     let open_inode = if create {
-        path_string.create(current_inode.as_ref(), InodeType::File)?
+        // The umask takes away permissions from what is asked for.
+        let mode = mode as u16 & 0o7777 & !current_process.fs().umask();
+        path_string.create(current_inode.as_ref(), InodeType::File, mode)?
     } else {
-        path_string.lookup(current_inode.as_ref())?
+        let inode = path_string.lookup(current_inode.as_ref())?;
+        let wanted = if flags.contains(OpenFlags::O_RDWR) {
+            Permission::READ | Permission::WRITE
+        } else if flags.contains(OpenFlags::O_WRONLY) {
+            Permission::WRITE
+        } else {
+            Permission::READ
+        };
+        inode.metadata().check_permission(&credentials, wanted)?;
+        inode
     };
 
     let file = crate::fs::util::FileInode::new(open_inode);
-    let fd = current_process
-        .file_table()
-        .insert(FileEntry::new(Arc::new(file)));
+    let mut entry = FileEntry::new(Arc::new(file));
+    entry.set_close_on_exec(flags.contains(OpenFlags::O_CLOEXEC));
+    let fd = current_process.file_table().insert(entry);
 
     Ok(SyscallReturn(fd as _))
 }
+
+#[cfg(ktest)]
+mod test {
+    use alloc::boxed::Box;
+
+    use ostd::mm::{PAGE_SIZE, Vaddr};
+    use ostd::prelude::ktest;
+
+    use super::{OpenFlags, sys_openat};
+    use crate::fs::ramfs::RamFS;
+    use crate::ktest_util::{MAP_PRIVATE, PROT_READ_WRITE, new_process};
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::mmap::{MMapFlags, sys_mmap};
+
+    #[ktest]
+    fn create_applies_umask() {
+        let root = crate::fs::ROOT
+            .call_once(|| Box::new(RamFS::new()))
+            .root_inode();
+
+        let process = new_process("hello_world");
+        let mmap_flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(path) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            mmap_flags,
+            u64::MAX,
+            0,
+            &process,
+        )
+        .unwrap();
+        let path = path as Vaddr;
+        process.write_user_bytes(path, b"/umask_file\0").unwrap();
+
+        process.fs().set_umask(0o027);
+        let flags = OpenFlags::O_CREAT.bits() as usize;
+        sys_openat(0, path, flags, 0o666, &process).unwrap();
+        let file = root.lookup("umask_file").unwrap();
+        assert_eq!(file.metadata().mode(), 0o640);
+    }
+}
@@ -4,10 +4,12 @@ use ostd::Pod;
 use ostd::mm::Vaddr;
 
 use crate::error::Result;
+use crate::fs::FileLike;
 use crate::fs::file_table::FileEntry;
 use crate::fs::pipe::Pipe;
 use crate::process::Process;
 use crate::syscall::SyscallReturn;
+use crate::syscall::open::OpenFlags;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod)]
@@ -28,14 +30,102 @@ pub fn sys_pipe2(
 
     let (reader, writer) = Pipe::new_pair();
 
-    let mut file_table = current_process.file_table();
-    let read_fd = file_table.insert(FileEntry::new(reader));
-    let write_fd = file_table.insert(FileEntry::new(writer));
+    let flags = OpenFlags::from_bits_truncate(flags as u32);
+    if flags.contains(OpenFlags::O_NONBLOCK) {
+        reader.set_nonblocking(true);
+        writer.set_nonblocking(true);
+    }
+
+    let close_on_exec = flags.contains(OpenFlags::O_CLOEXEC);
+    let mut read_entry = FileEntry::new(reader);
+    let mut write_entry = FileEntry::new(writer);
+    read_entry.set_close_on_exec(close_on_exec);
+    write_entry.set_close_on_exec(close_on_exec);
 
-    let vm_space = current_process.memory_space().vm_space();
-    let mut writer = vm_space.writer(pipe_address, size_of::<PipeFds>()).unwrap();
+    let mut file_table = current_process.file_table();
+    let read_fd = file_table.insert(read_entry);
+    let write_fd = file_table.insert(write_entry);
 
-    writer.write_val(&PipeFds { read_fd, write_fd }).unwrap();
+    current_process.write_user_val(pipe_address, &PipeFds { read_fd, write_fd })?;
 
     Ok(SyscallReturn(0))
 }
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+    use ostd::mm::{PAGE_SIZE, Vaddr, VmReader, VmWriter};
+    use ostd::prelude::ktest;
+
+    use super::{PipeFds, sys_pipe2};
+    use crate::error::Errno;
+    use crate::ktest_util::{MAP_PRIVATE, PROT_READ_WRITE, new_process, prog};
+    use crate::process::Process;
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::mmap::{MMapFlags, sys_mmap};
+    use crate::syscall::open::OpenFlags;
+
+    /// Calls `pipe2` with `flags` and returns the new descriptors.
+    fn pipe2(process: &Arc<Process>, flags: OpenFlags) -> PipeFds {
+        let mmap_flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(fds_addr) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            mmap_flags,
+            u64::MAX,
+            0,
+            process,
+        )
+        .unwrap();
+
+        let vm_space = process.memory_space().vm_space();
+        vm_space.activate();
+        sys_pipe2(fds_addr as Vaddr, flags.bits() as _, process).unwrap();
+        vm_space
+            .reader(fds_addr as Vaddr, size_of::<PipeFds>())
+            .unwrap()
+            .read_val()
+            .unwrap()
+    }
+
+    #[ktest]
+    fn cloexec_pipe_is_closed_on_exec() {
+        let binary = prog("hello_world");
+        let process = Process::new(binary);
+
+        let fds = pipe2(&process, OpenFlags::O_CLOEXEC);
+        assert!(process.file_table().get(fds.read_fd).is_some());
+        assert!(process.file_table().get(fds.write_fd).is_some());
+
+        process.exec(binary, None, &[]).unwrap();
+        assert!(process.file_table().get(fds.read_fd).is_none());
+        assert!(process.file_table().get(fds.write_fd).is_none());
+    }
+
+    #[ktest]
+    fn nonblocking_read_of_empty_pipe() {
+        let process = new_process("hello_world");
+
+        let fds = pipe2(&process, OpenFlags::O_NONBLOCK);
+        let file_table = process.file_table();
+        let reader = file_table.get(fds.read_fd).unwrap().file();
+        let writer = file_table.get(fds.write_fd).unwrap().file();
+        assert!(reader.is_nonblocking() && writer.is_nonblocking());
+
+        let mut buf = [0u8; 8];
+        let err = reader
+            .read(VmWriter::from(&mut buf[..]).to_fallible())
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::EAGAIN);
+
+        writer
+            .write(VmReader::from(&b"hi"[..]).to_fallible())
+            .unwrap();
+        let read_len = reader
+            .read(VmWriter::from(&mut buf[..]).to_fallible())
+            .unwrap();
+        assert_eq!(&buf[..read_len], b"hi");
+    }
+}
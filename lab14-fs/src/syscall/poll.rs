@@ -0,0 +1,253 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use log::debug;
+use ostd::Pod;
+use ostd::mm::Vaddr;
+use ostd::timer::Jiffies;
+use spin::Once;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::{FileLike, POLL_QUEUE, PollEvents};
+use crate::process::Process;
+use crate::process::rlimit::RLIMIT_NOFILE;
+use crate::process::signal::wait_until_interruptible;
+use crate::syscall::SyscallReturn;
+use crate::syscall::time::timespec_t;
+
+/// The `revents` reported for a file descriptor that is not open.
+const POLLNVAL: i16 = 0x0020;
+
+/// The number of `poll`s waiting with a timeout. While there are any, the timer
+/// wakes them up on each tick to see whether their time has run out.
+static TIMED_POLLERS: AtomicUsize = AtomicUsize::new(0);
+static TIMER_CALLBACK: Once = Once::new();
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod)]
+struct PollFd {
+    fd: i32,
+    events: i16,
+    revents: i16,
+}
+
+pub fn sys_ppoll(
+    fds_addr: Vaddr,
+    nfds: usize,
+    timeout_addr: Vaddr,
+    sigmask_addr: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_PPOLL] fds: {:#x}, nfds: {}, timeout: {:#x}, sigmask: {:#x}",
+        fds_addr, nfds, timeout_addr, sigmask_addr
+    );
+
+    let fault = |_| Error::new(Errno::EFAULT);
+
+    let nofile = current_process.rlimits().get(RLIMIT_NOFILE)?.cur;
+    if nfds as u64 > nofile {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let len = nfds
+        .checked_mul(size_of::<PollFd>())
+        .ok_or(Error::new(Errno::EINVAL))?;
+    let mut reader = current_process.user_reader(fds_addr, len)?;
+    let mut poll_fds = vec![PollFd::default(); nfds];
+    for poll_fd in poll_fds.iter_mut() {
+        *poll_fd = reader.read_val().map_err(fault)?;
+    }
+
+    let timeout = if timeout_addr == 0 {
+        None
+    } else {
+        let timespec: timespec_t = current_process.read_user_val(timeout_addr)?;
+        Some(Duration::try_from(timespec)?)
+    };
+
+    // Look the files up once, so that the file table is not locked while waiting.
+    let files: Vec<_> = {
+        let file_table = current_process.file_table();
+        poll_fds
+            .iter()
+            .map(|poll_fd| {
+                file_table.get(poll_fd.fd).map(|entry| {
+                    (
+                        entry.file().clone(),
+                        PollEvents::from_bits_truncate(poll_fd.events as u16),
+                    )
+                })
+            })
+            .collect()
+    };
+
+    let mut revents = Vec::with_capacity(nfds);
+    let nr_ready = poll_files(&files, timeout, &mut revents)?;
+    for (poll_fd, revents) in poll_fds.iter_mut().zip(revents) {
+        poll_fd.revents = revents;
+    }
+
+    let mut writer = current_process.user_writer(fds_addr, len)?;
+    for poll_fd in poll_fds.iter() {
+        writer.write_val(poll_fd).map_err(fault)?;
+    }
+
+    Ok(SyscallReturn(nr_ready as _))
+}
+
+/// Waits until one of `files` is ready for its events, or until `timeout` runs out.
+///
+/// A `None` entry stands for a file descriptor that is not open. The events of
+/// each entry are stored in `revents`, and the number of entries with any event
+/// is returned. Like on Linux, a signal cuts the wait short with `EINTR`, and it
+/// is never restarted.
+fn poll_files(
+    files: &[Option<(Arc<dyn FileLike>, PollEvents)>],
+    timeout: Option<Duration>,
+    revents: &mut Vec<i16>,
+) -> Result<usize> {
+    // A timeout too long to add up is as good as none.
+    let deadline =
+        timeout.and_then(|timeout| Jiffies::elapsed().as_duration().checked_add(timeout));
+    if deadline.is_some() {
+        TIMER_CALLBACK.call_once(|| {
+            ostd::timer::register_callback_on_cpu(|| {
+                if TIMED_POLLERS.load(Ordering::Acquire) > 0 {
+                    POLL_QUEUE.wake_all();
+                }
+            });
+        });
+        TIMED_POLLERS.fetch_add(1, Ordering::AcqRel);
+    }
+
+    let nr_ready = wait_until_interruptible(&POLL_QUEUE, || {
+        revents.clear();
+        revents.extend(files.iter().map(|file| match file {
+            Some((file, events)) => file.poll(*events).bits() as i16,
+            None => POLLNVAL,
+        }));
+
+        let nr_ready = revents.iter().filter(|revents| **revents != 0).count();
+        if nr_ready > 0 {
+            return Some(nr_ready);
+        }
+        deadline
+            .is_some_and(|deadline| Jiffies::elapsed().as_duration() >= deadline)
+            .then_some(0)
+    });
+
+    if deadline.is_some() {
+        TIMED_POLLERS.fetch_sub(1, Ordering::AcqRel);
+    }
+    nr_ready.map_err(|_| Error::new(Errno::EINTR))
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::time::Duration;
+
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use ostd::mm::{VmReader, VmWriter};
+    use ostd::prelude::ktest;
+    use ostd::task::TaskOptions;
+
+    use super::{poll_files, sys_ppoll};
+    use crate::error::Errno;
+    use crate::fs::pipe::Pipe;
+    use crate::fs::{FileLike, PollEvents, Stdin};
+    use crate::ktest_util::new_process;
+    use crate::process::rlimit::RLIMIT_NOFILE;
+    use crate::syscall::time::timespec_t;
+
+    #[ktest]
+    fn poll_stdin() {
+        let stdin: Arc<dyn FileLike> = Arc::new(Stdin::new());
+        let files = [Some((stdin.clone(), PollEvents::POLLIN))];
+        let mut revents = Vec::new();
+
+        // Nothing has been typed yet.
+        stdin.set_nonblocking(true);
+        let mut buf = [0u8; 4];
+        let err = stdin
+            .read(VmWriter::from(&mut buf as &mut [u8]).to_fallible())
+            .unwrap_err();
+        assert_eq!(err.code, Errno::EAGAIN);
+        assert_eq!(
+            poll_files(&files, Some(Duration::from_millis(10)), &mut revents).unwrap(),
+            0
+        );
+        assert_eq!(revents[0], 0);
+
+        crate::console::push_input(b"\r");
+        assert_eq!(
+            poll_files(&files, Some(Duration::from_millis(10)), &mut revents).unwrap(),
+            1
+        );
+        assert_eq!(revents[0], PollEvents::POLLIN.bits() as i16);
+
+        let read_len = stdin
+            .read(VmWriter::from(&mut buf as &mut [u8]).to_fallible())
+            .unwrap();
+        assert_eq!(read_len, 1);
+    }
+
+    #[ktest]
+    fn poll_sleeps_until_pipe_is_written() {
+        let (reader, writer) = Pipe::new_pair();
+        let reader: Arc<dyn FileLike> = reader;
+        let files = [Some((reader, PollEvents::POLLIN))];
+
+        TaskOptions::new(move || {
+            writer
+                .write(VmReader::from(&b"x"[..]).to_fallible())
+                .unwrap();
+        })
+        .data(())
+        .spawn()
+        .unwrap();
+
+        // No timeout, so only the write wakes the poll up.
+        let mut revents = Vec::new();
+        assert_eq!(poll_files(&files, None, &mut revents).unwrap(), 1);
+        assert_eq!(revents[0], PollEvents::POLLIN.bits() as i16);
+    }
+
+    #[ktest]
+    fn huge_timeout_does_not_overflow() {
+        let (reader, writer) = Pipe::new_pair();
+        writer
+            .write(VmReader::from(&b"x"[..]).to_fallible())
+            .unwrap();
+        let reader: Arc<dyn FileLike> = reader;
+        let files = [Some((reader, PollEvents::POLLIN))];
+
+        let mut revents = Vec::new();
+        assert_eq!(
+            poll_files(&files, Some(Duration::MAX), &mut revents).unwrap(),
+            1
+        );
+    }
+
+    #[ktest]
+    fn invalid_timeout_is_rejected() {
+        for (sec, nsec) in [(-1, 0), (0, -1), (0, 1_000_000_000)] {
+            let err = Duration::try_from(timespec_t { sec, nsec }).unwrap_err();
+            assert_eq!(err.code, Errno::EINVAL);
+        }
+    }
+
+    #[ktest]
+    fn too_many_fds_is_invalid() {
+        let process = new_process("hello_world");
+        let nofile = process.rlimits().get(RLIMIT_NOFILE).unwrap().cur;
+
+        for nfds in [nofile as usize + 1, usize::MAX] {
+            let err = sys_ppoll(0, nfds, 0, 0, &process).err().unwrap();
+            assert_eq!(err.code, Errno::EINVAL);
+        }
+    }
+}
@@ -1,24 +1,16 @@
 use alloc::sync::Arc;
 use log::debug;
-use ostd::{Pod, mm::Vaddr};
+use ostd::mm::Vaddr;
 
 use crate::error::{Errno, Error, Result};
-use crate::process::{Process, USER_STACK_SIZE};
+use crate::process::Process;
+use crate::process::rlimit::RLimit64;
 use crate::syscall::SyscallReturn;
 
-const RLIM_INFINITY: u64 = u64::MAX;
-
-#[derive(Debug, Clone, Copy, Pod)]
-#[repr(C)]
-pub struct RLimit64 {
-    cur: u64,
-    max: u64,
-}
-
 pub fn sys_prlimit64(
     pid: i32,
-    resource: i32,
-    _new_limit: Vaddr,
+    resource: u32,
+    new_limit: Vaddr,
     old_limit: Vaddr,
     current_process: &Arc<Process>,
 ) -> Result<SyscallReturn> {
@@ -28,23 +20,18 @@ pub fn sys_prlimit64(
 
     debug!(
         "[SYS_PRLIMIT64] pid: {}, resource: {}, new_limit: {:#x}, old_limit: {:#x}",
-        pid, resource, _new_limit, old_limit
+        pid, resource, new_limit, old_limit
     );
 
-    let value: usize = USER_STACK_SIZE;
-    let rlim = RLimit64 {
-        cur: value as u64,
-        max: RLIM_INFINITY,
+    let rlim = if new_limit != 0 {
+        let limit: RLimit64 = current_process.read_user_val(new_limit)?;
+        current_process.rlimits().set(resource, limit)?
+    } else {
+        current_process.rlimits().get(resource)?
     };
 
     if old_limit != 0 {
-        current_process
-            .memory_space()
-            .vm_space()
-            .writer(old_limit, core::mem::size_of::<RLimit64>())
-            .unwrap()
-            .write_val(&rlim)
-            .unwrap();
+        current_process.write_user_val(old_limit, &rlim)?;
     }
 
     Ok(SyscallReturn(0))
@@ -20,11 +20,7 @@ pub fn sys_read(
         fd, user_buf_addr, buf_len
     );
 
-    let writer = current_process
-        .memory_space()
-        .vm_space()
-        .writer(user_buf_addr, buf_len)
-        .unwrap();
+    let writer = current_process.user_writer(user_buf_addr, buf_len)?;
 
     let file_table = current_process.file_table();
     let file = file_table.get(fd).ok_or(Error::new(Errno::EBADF))?;
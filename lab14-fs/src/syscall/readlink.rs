@@ -0,0 +1,133 @@
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::InodeType;
+use crate::fs::util::PathString;
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+use crate::syscall::exec::{dirfd_inode, read_path};
+
+pub fn sys_readlinkat(
+    dirfd: i32,
+    path: Vaddr,
+    buf: Vaddr,
+    bufsiz: isize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!(
+        "[SYS_READLINKAT] dirfd: {}, path: {}, buf: {:#x}, bufsiz: {}",
+        dirfd, path, buf, bufsiz
+    );
+
+    if bufsiz <= 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    if path.is_empty() {
+        return Err(Error::new(Errno::ENOENT));
+    }
+
+    let start = if path.starts_with('/') {
+        crate::fs::ROOT.get().unwrap().root_inode()
+    } else {
+        dirfd_inode(dirfd, current_process)?
+    };
+    // The last component is the link itself, not what it points to.
+    let inode = PathString::new(path.to_string())
+        .with_credentials(*current_process.credentials())
+        .lookup_no_follow(start.as_ref())?;
+    if inode.typ() != InodeType::SymbolLink {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    // The target goes out without a NUL, cut off at `bufsiz` bytes.
+    let target = inode.read_link()?;
+    let len = target.len().min(bufsiz as usize);
+    current_process.write_user_bytes(buf, &target.as_bytes()[..len])?;
+
+    Ok(SyscallReturn(len as _))
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+    use alloc::vec;
+
+    use ostd::mm::{FallibleVmRead, FallibleVmWrite, PAGE_SIZE, Vaddr, VmReader, VmWriter};
+    use ostd::prelude::ktest;
+
+    use super::sys_readlinkat;
+    use crate::error::{Errno, Error};
+    use crate::fs::file_table::FileEntry;
+    use crate::fs::ramfs::RamFS;
+    use crate::fs::util::FileInode;
+    use crate::fs::{FileSystem, InodeType};
+    use crate::ktest_util::{MAP_PRIVATE, PROT_READ_WRITE, new_process};
+    use crate::process::credentials::ROOT_ID;
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::mmap::{MMapFlags, sys_mmap};
+
+    #[ktest]
+    fn readlinkat_reads_and_truncates_the_target() {
+        let ramfs = RamFS::new();
+        let root = ramfs.root_inode();
+        root.create("file", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        let link = root
+            .create("link", InodeType::SymbolLink, 0o777, ROOT_ID, ROOT_ID)
+            .unwrap();
+        link.write_link("/some/target").unwrap();
+
+        let process = new_process("hello_world");
+        let dirfd = process
+            .file_table()
+            .insert(FileEntry::new(Arc::new(FileInode::new(root))));
+        let mmap_flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(buf) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            mmap_flags,
+            u64::MAX,
+            0,
+            &process,
+        )
+        .unwrap();
+        let buf = buf as Vaddr;
+        let vm_space = process.memory_space().vm_space();
+        vm_space.activate();
+        // The path, then room for the target after it.
+        vm_space
+            .writer(buf, 5)
+            .unwrap()
+            .write_fallible(&mut VmReader::from(&b"link\0"[..]))
+            .unwrap();
+        let target = buf + 8;
+
+        let readlink = |path: Vaddr, bufsiz: isize| {
+            let SyscallReturn(len) = sys_readlinkat(dirfd as _, path, target, bufsiz, &process)?;
+            let mut bytes = vec![0u8; len as usize];
+            vm_space
+                .reader(target, bytes.len())
+                .unwrap()
+                .read_fallible(&mut VmWriter::from(bytes.as_mut_slice()))
+                .unwrap();
+            Ok::<_, Error>(bytes)
+        };
+
+        assert_eq!(readlink(buf, 64).unwrap(), b"/some/target");
+        assert_eq!(readlink(buf, 5).unwrap(), b"/some");
+
+        // Anything else is not a link.
+        vm_space
+            .writer(buf, 5)
+            .unwrap()
+            .write_fallible(&mut VmReader::from(&b"file\0"[..]))
+            .unwrap();
+        let err = readlink(buf, 64).err().unwrap();
+        assert_eq!(err.code, Errno::EINVAL);
+    }
+}
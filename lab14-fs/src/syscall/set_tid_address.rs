@@ -0,0 +1,19 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::Result;
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+/// Records where the thread id is to be cleared when the thread exits.
+///
+/// RISC-V programs set `tp` themselves, so this is all a runtime asks of the
+/// kernel about its threads at startup.
+pub fn sys_set_tid_address(tidptr: Vaddr, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_SET_TID_ADDRESS] tidptr: {:#x}", tidptr);
+
+    current_process.set_clear_child_tid(tidptr);
+    // Every process is a single thread, so the thread id is the pid.
+    Ok(SyscallReturn(current_process.pid() as _))
+}
@@ -0,0 +1,85 @@
+use alloc::sync::Arc;
+use log::debug;
+
+use crate::error::{Errno, Error, Result};
+use crate::process::{Process, process_by_pid};
+use crate::syscall::SyscallReturn;
+
+pub fn sys_setpgid(pid: i32, pgid: i32, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_SETPGID] pid: {}, pgid: {}", pid, pgid);
+
+    if pid < 0 || pgid < 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    current_process.set_pgid(pid as _, pgid as _)?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_getpgid(pid: i32, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_GETPGID] pid: {}", pid);
+
+    let pgid = match pid {
+        0 => current_process.pgid(),
+        pid if pid < 0 => return Err(Error::new(Errno::ESRCH)),
+        pid => process_by_pid(pid as _)
+            .ok_or(Error::new(Errno::ESRCH))?
+            .pgid(),
+    };
+    Ok(SyscallReturn(pgid as _))
+}
+
+/// Starts a new session led by the caller.
+///
+/// The new session has no controlling terminal: the console stays with the
+/// session it controls, which the caller has left.
+pub fn sys_setsid(current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_SETSID]");
+
+    Ok(SyscallReturn(current_process.setsid()? as _))
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::prelude::ktest;
+
+    use super::{sys_getpgid, sys_setsid};
+    use crate::error::Errno;
+    use crate::ktest_util::new_process;
+    use crate::syscall::SyscallReturn;
+
+    #[ktest]
+    fn setsid_starts_a_new_session() {
+        let parent = new_process("hello_world");
+        let child = parent.fork(&UserContext::default()).unwrap();
+        assert_eq!(child.sid(), parent.sid());
+
+        let SyscallReturn(sid) = sys_setsid(&child).unwrap();
+        assert_eq!(sid as usize, child.pid());
+        assert_eq!(child.sid(), child.pid());
+        assert_eq!(child.pgid(), child.pid());
+        assert_ne!(child.sid(), parent.sid());
+
+        // Now it leads a process group, so it can't start another session.
+        let err = sys_setsid(&child).err().unwrap();
+        assert_eq!(err.code, Errno::EPERM);
+    }
+
+    #[ktest]
+    fn getpgid_looks_up_other_processes() {
+        let parent = new_process("hello_world");
+        let child = parent.fork(&UserContext::default()).unwrap();
+        parent.set_pgid(child.pid(), 0).unwrap();
+
+        let SyscallReturn(pgid) = sys_getpgid(child.pid() as _, &parent).unwrap();
+        assert_eq!(pgid as usize, child.pid());
+        let SyscallReturn(pgid) = sys_getpgid(0, &parent).unwrap();
+        assert_eq!(pgid as usize, parent.pgid());
+
+        child.exit(0);
+        parent.wait(child.pid() as _).unwrap();
+        let err = sys_getpgid(child.pid() as _, &parent).err().unwrap();
+        assert_eq!(err.code, Errno::ESRCH);
+    }
+}
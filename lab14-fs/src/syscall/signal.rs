@@ -0,0 +1,140 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::Pod;
+use ostd::arch::cpu::context::UserContext;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::process::Process;
+use crate::process::signal::{NSIG, SIGSEGV, SigAction, Signal};
+use crate::syscall::SyscallReturn;
+
+const SIG_BLOCK: u32 = 0;
+const SIG_UNBLOCK: u32 = 1;
+const SIG_SETMASK: u32 = 2;
+
+/// `struct sigaction` as the kernel sees it on riscv64.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct SigActionT {
+    handler: u64,
+    flags: u64,
+    mask: u64,
+}
+
+pub fn sys_rt_sigaction(
+    signal: usize,
+    act: Vaddr,
+    old_act: Vaddr,
+    sigset_size: usize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_RT_SIGACTION] signal: {}, act: {:#x}, old_act: {:#x}",
+        signal, act, old_act
+    );
+
+    if sigset_size != size_of::<u64>() {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    // Checked before it is narrowed, or a number past the last signal could
+    // wrap around to a valid one.
+    if !(1..=NSIG).contains(&signal) {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let signal = signal as Signal;
+
+    let old_action = if act != 0 {
+        let new_act: SigActionT = current_process.read_user_val(act)?;
+        let action = SigAction::from_handler(new_act.handler as _, new_act.flags, new_act.mask);
+        current_process.set_sig_action(signal, action)?
+    } else {
+        current_process.sig_action(signal)?
+    };
+
+    if old_act != 0 {
+        let old = SigActionT {
+            handler: old_action.handler() as _,
+            flags: old_action.flags(),
+            mask: old_action.mask(),
+        };
+        current_process.write_user_val(old_act, &old)?;
+    }
+
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_rt_sigprocmask(
+    how: u32,
+    set: Vaddr,
+    old_set: Vaddr,
+    sigset_size: usize,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_RT_SIGPROCMASK] how: {}, set: {:#x}, old_set: {:#x}",
+        how, set, old_set
+    );
+
+    if sigset_size != size_of::<u64>() {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    let blocked = current_process.blocked_signals();
+    if old_set != 0 {
+        current_process.write_user_val(old_set, &blocked)?;
+    }
+
+    if set != 0 {
+        let set: u64 = current_process.read_user_val(set)?;
+        let blocked = match how {
+            SIG_BLOCK => blocked | set,
+            SIG_UNBLOCK => blocked & !set,
+            SIG_SETMASK => set,
+            _ => return Err(Error::new(Errno::EINVAL)),
+        };
+        current_process.set_blocked_signals(blocked);
+    }
+
+    Ok(SyscallReturn(0))
+}
+
+/// Returns from a signal handler, through the trampoline the handler returns to.
+pub fn sys_rt_sigreturn(
+    current_process: &Arc<Process>,
+    user_context: &mut UserContext,
+) -> Result<SyscallReturn> {
+    debug!("[SYS_RT_SIGRETURN]");
+
+    // Like a fault, a frame that can't be read back leaves nothing to return to.
+    current_process
+        .return_from_handler(user_context)
+        .inspect_err(|_| current_process.send_signal(SIGSEGV))?;
+    // The syscall return value goes to `a0`, which must keep its restored value.
+    Ok(SyscallReturn(user_context.a0() as _))
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::sys_rt_sigaction;
+    use crate::error::Errno;
+    use crate::ktest_util::new_process;
+    use crate::process::signal::{NSIG, SIGINT};
+
+    #[ktest]
+    fn sigaction_rejects_out_of_range_signals() {
+        let process = new_process("hello_world");
+        let sigset_size = size_of::<u64>();
+
+        // 258 would be SIGINT once cut down to a byte.
+        for signal in [0, NSIG + 1, 256 + SIGINT as usize] {
+            let err = sys_rt_sigaction(signal, 0, 0, sigset_size, &process)
+                .err()
+                .unwrap();
+            assert_eq!(err.code, Errno::EINVAL);
+        }
+        sys_rt_sigaction(SIGINT as usize, 0, 0, sigset_size, &process).unwrap();
+    }
+}
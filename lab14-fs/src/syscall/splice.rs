@@ -0,0 +1,411 @@
+use alloc::sync::Arc;
+use alloc::vec;
+use log::debug;
+use ostd::mm::{Vaddr, VmReader, VmWriter};
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::FileLike;
+use crate::fs::file_table::FileDescriptor;
+use crate::fs::pipe::Pipe;
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+/// The most bytes moved by one call, which is as much as a pipe holds.
+const MAX_TRANSFER_LEN: usize = 65536;
+
+/// Fails with `EAGAIN` rather than waiting for an empty pipe, even if it blocks.
+const SPLICE_F_NONBLOCK: u32 = 0x02;
+
+/// Moves up to `len` bytes from `fd_in` to `fd_out` without passing them through
+/// user memory.
+///
+/// Between two pipes, the pages the bytes are in change hands, and are not copied at
+/// all. To or from a file, the bytes are copied once, through a kernel buffer.
+///
+/// One of the two files must be a pipe, and not the same one as the other. A file
+/// endpoint with an offset pointer is read or written at that offset, which is
+/// advanced afterwards by as much as was written. Data is only taken out of a pipe
+/// once it has been written, so a short write leaves the rest in the pipe, and a
+/// short write to a pipe leaves the rest to be read from the file again. An empty
+/// pipe is waited on, like by `read`, unless its write end is closed.
+pub fn sys_splice(
+    fd_in: FileDescriptor,
+    off_in: Vaddr,
+    fd_out: FileDescriptor,
+    off_out: Vaddr,
+    len: usize,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_SPLICE] fd_in: {}, off_in: {:#x}, fd_out: {}, off_out: {:#x}, len: {}, flags: {:#x}",
+        fd_in, off_in, fd_out, off_out, len, flags
+    );
+
+    let file_in = get_file(fd_in, current_process)?;
+    let file_out = get_file(fd_out, current_process)?;
+    let has_pipe = file_in.as_pipe().is_some() || file_out.as_pipe().is_some();
+    if !has_pipe || is_same_pipe(&file_in, &file_out) {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    let len = len.min(MAX_TRANSFER_LEN);
+    let nonblocking = flags & SPLICE_F_NONBLOCK != 0;
+    if let (Some(pipe_in), Some(pipe_out)) = (file_in.as_pipe(), file_out.as_pipe()) {
+        if off_in != 0 || off_out != 0 {
+            return Err(Error::new(Errno::ESPIPE));
+        }
+        let nonblocking = nonblocking || file_in.is_nonblocking();
+        let written = pipe_in.splice_to(pipe_out, len, nonblocking, file_out.is_nonblocking())?;
+        return Ok(SyscallReturn(written as _));
+    }
+
+    // Read before the pipe is locked, since that may fault in a user page.
+    let offset_in = if off_in != 0 {
+        Some(read_offset(off_in, current_process)?)
+    } else {
+        None
+    };
+    let offset_out = if off_out != 0 {
+        Some(read_offset(off_out, current_process)?)
+    } else {
+        None
+    };
+
+    let written = if let Some(pipe_out) = file_out.as_pipe() {
+        let mut buf = vec![0u8; len];
+        let read_len = peek_in(&file_in, offset_in, &mut buf, pipe_out)?;
+        write_out(&file_out, offset_out, &buf[..read_len])?
+    } else {
+        if offset_in.is_some() {
+            return Err(Error::new(Errno::ESPIPE));
+        }
+        // Only what was written is taken out of the pipe.
+        file_in.read_with(len, nonblocking, &mut |data| {
+            write_out(&file_out, offset_out, data)
+        })?
+    };
+
+    if let Some(offset) = offset_in {
+        write_offset(off_in, offset + written, current_process)?;
+    }
+    if let Some(offset) = offset_out {
+        write_offset(off_out, offset + written, current_process)?;
+    }
+    Ok(SyscallReturn(written as _))
+}
+
+/// Copies up to `len` bytes from the pipe `fd_in` to another pipe `fd_out`, leaving
+/// them in `fd_in` to be read again. An empty `fd_in` is waited on like by `splice`.
+///
+/// Both pipes end up sharing the pages the bytes are in, which are not copied.
+pub fn sys_tee(
+    fd_in: FileDescriptor,
+    fd_out: FileDescriptor,
+    len: usize,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_TEE] fd_in: {}, fd_out: {}, len: {}, flags: {:#x}",
+        fd_in, fd_out, len, flags
+    );
+
+    let file_in = get_file(fd_in, current_process)?;
+    let file_out = get_file(fd_out, current_process)?;
+    let (Some(pipe_in), Some(pipe_out)) = (file_in.as_pipe(), file_out.as_pipe()) else {
+        return Err(Error::new(Errno::EINVAL));
+    };
+    if is_same_pipe(&file_in, &file_out) {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    let nonblocking = flags & SPLICE_F_NONBLOCK != 0 || file_in.is_nonblocking();
+    let len = len.min(MAX_TRANSFER_LEN);
+    let written = pipe_in.tee_to(pipe_out, len, nonblocking, file_out.is_nonblocking())?;
+
+    Ok(SyscallReturn(written as _))
+}
+
+fn get_file(fd: FileDescriptor, current_process: &Arc<Process>) -> Result<Arc<dyn FileLike>> {
+    Ok(current_process
+        .file_table()
+        .get(fd)
+        .ok_or(Error::new(Errno::EBADF))?
+        .file()
+        .clone())
+}
+
+/// Whether both files are ends of the same pipe.
+fn is_same_pipe(file_in: &Arc<dyn FileLike>, file_out: &Arc<dyn FileLike>) -> bool {
+    match (file_in.as_pipe(), file_out.as_pipe()) {
+        (Some(pipe_in), Some(pipe_out)) => core::ptr::eq(pipe_in, pipe_out),
+        _ => false,
+    }
+}
+
+/// Reads from `file`, which is not a pipe, into `buf` for `pipe_out`, at `offset` if
+/// there is one, and leaves the bytes in the file, to be read again if the pipe does
+/// not take them all.
+///
+/// Files have no position, so without an `offset` they are read from the start, like
+/// by `read`. Files that are not inodes, like the console, cannot be read without
+/// taking the bytes out, so only as many are read as `pipe_out` has room for.
+fn peek_in(
+    file: &Arc<dyn FileLike>,
+    offset: Option<usize>,
+    buf: &mut [u8],
+    pipe_out: &Pipe,
+) -> Result<usize> {
+    if let Some(inode) = file.as_inode() {
+        return inode.read_at(offset.unwrap_or(0), VmWriter::from(buf).to_fallible());
+    }
+    if offset.is_some() {
+        return Err(Error::new(Errno::ESPIPE));
+    }
+    let len = buf.len().min(pipe_out.room());
+    file.read(VmWriter::from(&mut buf[..len]).to_fallible())
+}
+
+/// Writes `data` to `file`, at `offset` if there is one.
+fn write_out(file: &Arc<dyn FileLike>, offset: Option<usize>, data: &[u8]) -> Result<usize> {
+    let Some(offset) = offset else {
+        return file.write(VmReader::from(data).to_fallible());
+    };
+
+    let inode = file.as_inode().ok_or(Error::new(Errno::ESPIPE))?;
+    inode.write_at(offset, VmReader::from(data).to_fallible())
+}
+
+fn read_offset(addr: Vaddr, current_process: &Arc<Process>) -> Result<usize> {
+    let offset: i64 = current_process.read_user_val(addr)?;
+    usize::try_from(offset).map_err(|_| Error::new(Errno::EINVAL))
+}
+
+fn write_offset(addr: Vaddr, offset: usize, current_process: &Arc<Process>) -> Result<()> {
+    current_process.write_user_val(addr, &(offset as i64))
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use ostd::mm::{PAGE_SIZE, Vaddr, VmReader, VmWriter};
+    use ostd::prelude::ktest;
+    use ostd::task::TaskOptions;
+
+    use super::{SPLICE_F_NONBLOCK, sys_splice, sys_tee};
+    use crate::error::Errno;
+    use crate::fs::file_table::{FileDescriptor, FileEntry};
+    use crate::fs::pipe::Pipe;
+    use crate::fs::ramfs::RamFS;
+    use crate::fs::util::FileInode;
+    use crate::fs::{FileLike, FileSystem, InodeType};
+    use crate::ktest_util::{MAP_PRIVATE, PROT_READ_WRITE, new_process};
+    use crate::process::Process;
+    use crate::process::credentials::ROOT_ID;
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::mmap::{MMapFlags, sys_mmap};
+
+    fn insert(process: &Arc<Process>, file: Arc<dyn FileLike>) -> FileDescriptor {
+        process.file_table().insert(FileEntry::new(file))
+    }
+
+    fn read_all(process: &Arc<Process>, fd: FileDescriptor) -> Vec<u8> {
+        let file = process.file_table().get(fd).unwrap().file().clone();
+        let mut buf = [0u8; 8192];
+        let read_len = file
+            .read(VmWriter::from(&mut buf[..]).to_fallible())
+            .unwrap();
+        buf[..read_len].to_vec()
+    }
+
+    #[ktest]
+    fn splice_file_and_tee_to_two_readers() {
+        let process = new_process("hello_world");
+
+        let content: Vec<u8> = (0..5000).map(|i| i as u8).collect();
+        let ramfs = RamFS::new();
+        let inode = ramfs
+            .root_inode()
+            .create("file", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        inode
+            .write_at(0, VmReader::from(content.as_slice()).to_fallible())
+            .unwrap();
+        let file_fd = insert(&process, Arc::new(FileInode::new(inode)));
+
+        let (first_reader, first_writer) = Pipe::new_pair();
+        let (second_reader, second_writer) = Pipe::new_pair();
+        let first_read_fd = insert(&process, first_reader);
+        let first_write_fd = insert(&process, first_writer);
+        let second_read_fd = insert(&process, second_reader);
+        let second_write_fd = insert(&process, second_writer);
+
+        let len = content.len();
+        let SyscallReturn(spliced) =
+            sys_splice(file_fd, 0, first_write_fd, 0, len, 0, &process).unwrap();
+        assert_eq!(spliced as usize, len);
+        let SyscallReturn(teed) =
+            sys_tee(first_read_fd, second_write_fd, len, 0, &process).unwrap();
+        assert_eq!(teed as usize, len);
+
+        assert_eq!(read_all(&process, first_read_fd), content);
+        assert_eq!(read_all(&process, second_read_fd), content);
+    }
+
+    #[ktest]
+    fn splice_from_pipe_takes_only_what_was_written() {
+        let process = new_process("hello_world");
+
+        let (in_reader, in_writer) = Pipe::new_pair();
+        let (out_reader, out_writer) = Pipe::new_pair();
+        in_writer
+            .write(VmReader::from(&b"0123456789"[..]).to_fallible())
+            .unwrap();
+        // Room for only three more bytes.
+        let filler = [0u8; 65536 - 3];
+        out_writer
+            .write(VmReader::from(&filler[..]).to_fallible())
+            .unwrap();
+        let in_fd = insert(&process, in_reader);
+        let out_fd = insert(&process, out_writer);
+
+        let SyscallReturn(spliced) = sys_splice(in_fd, 0, out_fd, 0, 10, 0, &process).unwrap();
+        assert_eq!(spliced, 3);
+        assert_eq!(read_all(&process, in_fd), b"3456789");
+        let mut buf = [0u8; 65536];
+        let read_len = out_reader
+            .read(VmWriter::from(&mut buf[..]).to_fallible())
+            .unwrap();
+        assert_eq!(&buf[read_len - 3..read_len], b"012");
+    }
+
+    #[ktest]
+    fn splice_to_full_pipe_advances_offset_by_what_was_written() {
+        let process = new_process("hello_world");
+
+        let ramfs = RamFS::new();
+        let inode = ramfs
+            .root_inode()
+            .create("file", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        inode
+            .write_at(0, VmReader::from(&b"0123456789"[..]).to_fallible())
+            .unwrap();
+        let file_fd = insert(&process, Arc::new(FileInode::new(inode)));
+
+        let (out_reader, out_writer) = Pipe::new_pair();
+        // Room for only three more bytes.
+        let filler = [0u8; 65536 - 3];
+        out_writer
+            .write(VmReader::from(&filler[..]).to_fallible())
+            .unwrap();
+        out_writer.set_nonblocking(true);
+        let out_fd = insert(&process, out_writer);
+
+        let mmap_flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(off_in) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            mmap_flags,
+            u64::MAX,
+            0,
+            &process,
+        )
+        .unwrap();
+        let off_in = off_in as Vaddr;
+        process.memory_space().vm_space().activate();
+        process.write_user_val(off_in, &2i64).unwrap();
+
+        let SyscallReturn(spliced) =
+            sys_splice(file_fd, off_in, out_fd, 0, 8, 0, &process).unwrap();
+        assert_eq!(spliced, 3);
+        assert_eq!(process.read_user_val::<i64>(off_in).unwrap(), 5);
+
+        // Nothing fits any more, so nothing is skipped either.
+        let err = sys_splice(file_fd, off_in, out_fd, 0, 8, 0, &process)
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::EAGAIN);
+        assert_eq!(process.read_user_val::<i64>(off_in).unwrap(), 5);
+
+        let mut buf = [0u8; 65536];
+        let read_len = out_reader
+            .read(VmWriter::from(&mut buf[..]).to_fallible())
+            .unwrap();
+        assert_eq!(&buf[read_len - 3..read_len], b"234");
+    }
+
+    #[ktest]
+    fn splice_from_empty_pipe_waits_for_data() {
+        let process = new_process("hello_world");
+
+        let (in_reader, in_writer) = Pipe::new_pair();
+        let (out_reader, out_writer) = Pipe::new_pair();
+        let in_fd = insert(&process, in_reader);
+        let out_fd = insert(&process, out_writer);
+
+        // The write end is open, so this is no EOF, and there is nothing to take yet.
+        let err = sys_splice(in_fd, 0, out_fd, 0, 4, SPLICE_F_NONBLOCK, &process)
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::EAGAIN);
+        let err = sys_tee(in_fd, out_fd, 4, SPLICE_F_NONBLOCK, &process)
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::EAGAIN);
+
+        TaskOptions::new(move || {
+            in_writer
+                .write(VmReader::from(&b"data"[..]).to_fallible())
+                .unwrap();
+        })
+        .data(())
+        .spawn()
+        .unwrap();
+        let SyscallReturn(spliced) = sys_splice(in_fd, 0, out_fd, 0, 4, 0, &process).unwrap();
+        assert_eq!(spliced, 4);
+        let mut buf = [0u8; 4];
+        out_reader
+            .read(VmWriter::from(&mut buf[..]).to_fallible())
+            .unwrap();
+        assert_eq!(&buf, b"data");
+    }
+
+    #[ktest]
+    fn splice_and_tee_need_pipes() {
+        let process = new_process("hello_world");
+
+        let ramfs = RamFS::new();
+        let inode = ramfs
+            .root_inode()
+            .create("file", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        let file_fd = insert(&process, Arc::new(FileInode::new(inode.clone())));
+        let other_fd = insert(&process, Arc::new(FileInode::new(inode)));
+        let (reader, writer) = Pipe::new_pair();
+        let (other_reader, other_writer) = Pipe::new_pair();
+        other_writer
+            .write(VmReader::from(&b"x"[..]).to_fallible())
+            .unwrap();
+        let read_fd = insert(&process, reader);
+        let write_fd = insert(&process, writer);
+        let other_read_fd = insert(&process, other_reader);
+
+        let invalid = [
+            sys_splice(file_fd, 0, other_fd, 0, 1, 0, &process),
+            sys_splice(read_fd, 0, write_fd, 0, 1, 0, &process),
+            sys_tee(read_fd, file_fd, 1, 0, &process),
+            sys_tee(file_fd, write_fd, 1, 0, &process),
+            sys_tee(read_fd, write_fd, 1, 0, &process),
+        ];
+        for result in invalid {
+            assert_eq!(result.err().unwrap().code, Errno::EINVAL);
+        }
+        // Either end may be the pipe.
+        sys_splice(other_read_fd, 0, file_fd, 0, 1, 0, &process).unwrap();
+        sys_splice(file_fd, 0, write_fd, 0, 1, 0, &process).unwrap();
+    }
+}
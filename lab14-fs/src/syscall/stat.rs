@@ -0,0 +1,189 @@
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use log::debug;
+use ostd::Pod;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::util::PathString;
+use crate::fs::{Inode, InodeType};
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+use crate::syscall::exec::{dirfd_inode, read_path};
+
+const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+const AT_EMPTY_PATH: u32 = 0x1000;
+
+const S_IFLNK: u32 = 0o120000;
+const S_IFREG: u32 = 0o100000;
+const S_IFDIR: u32 = 0o040000;
+
+/// The block size `st_blksize` reports, and `st_blocks` counts in units of 512 bytes.
+const BLOCK_SIZE: usize = 4096;
+
+/// The `struct stat` of riscv64.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct Stat {
+    pub st_dev: u64,
+    pub st_ino: u64,
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: u64,
+    __pad1: u64,
+    pub st_size: i64,
+    pub st_blksize: i32,
+    __pad2: i32,
+    pub st_blocks: i64,
+    pub st_atime: i64,
+    pub st_atime_nsec: u64,
+    pub st_mtime: i64,
+    pub st_mtime_nsec: u64,
+    pub st_ctime: i64,
+    pub st_ctime_nsec: u64,
+    __unused: [u32; 2],
+}
+
+impl Stat {
+    fn new(inode: &dyn Inode) -> Self {
+        let (file_type, nlink) = match inode.typ() {
+            InodeType::File => (S_IFREG, 1),
+            InodeType::Directory => (S_IFDIR, 2),
+            InodeType::SymbolLink => (S_IFLNK, 1),
+        };
+        let size = inode.size();
+        let meta = inode.metadata();
+
+        let mut stat = Stat::new_zeroed();
+        stat.st_mode = file_type | meta.mode() as u32;
+        stat.st_nlink = nlink;
+        stat.st_uid = meta.uid();
+        stat.st_gid = meta.gid();
+        stat.st_size = size as i64;
+        stat.st_blksize = BLOCK_SIZE as i32;
+        stat.st_blocks = size.div_ceil(BLOCK_SIZE) as i64 * (BLOCK_SIZE / 512) as i64;
+        stat.st_atime = meta.atime().as_secs() as i64;
+        stat.st_atime_nsec = meta.atime().subsec_nanos() as u64;
+        stat.st_mtime = meta.mtime().as_secs() as i64;
+        stat.st_mtime_nsec = meta.mtime().subsec_nanos() as u64;
+        stat.st_ctime = meta.ctime().as_secs() as i64;
+        stat.st_ctime_nsec = meta.ctime().subsec_nanos() as u64;
+        stat
+    }
+}
+
+pub fn sys_fstatat(
+    dirfd: i32,
+    path: Vaddr,
+    statbuf: Vaddr,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    let path = read_path(path, current_process)?;
+    debug!(
+        "[SYS_FSTATAT] dirfd: {}, path: {}, statbuf: {:#x}, flags: {:#x}",
+        dirfd, path, statbuf, flags
+    );
+
+    let inode = if path.is_empty() {
+        if flags & AT_EMPTY_PATH == 0 {
+            return Err(Error::new(Errno::ENOENT));
+        }
+        dirfd_inode(dirfd, current_process)?
+    } else {
+        let start = if path.starts_with('/') {
+            crate::fs::ROOT
+                .get()
+                .ok_or(Error::new(Errno::ENOENT))?
+                .root_inode()
+        } else {
+            dirfd_inode(dirfd, current_process)?
+        };
+        let mut path_string =
+            PathString::new(path.to_string()).with_credentials(*current_process.credentials());
+        if flags & AT_SYMLINK_NOFOLLOW != 0 {
+            path_string.lookup_no_follow(start.as_ref())?
+        } else {
+            path_string.lookup(start.as_ref())?
+        }
+    };
+
+    current_process.write_user_val(statbuf, &Stat::new(inode.as_ref()))?;
+
+    Ok(SyscallReturn(0))
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+
+    use ostd::mm::{FallibleVmWrite, PAGE_SIZE, Vaddr, VmReader};
+    use ostd::prelude::ktest;
+
+    use super::{AT_SYMLINK_NOFOLLOW, S_IFLNK, S_IFREG, Stat, sys_fstatat};
+    use crate::fs::file_table::FileEntry;
+    use crate::fs::ramfs::RamFS;
+    use crate::fs::util::FileInode;
+    use crate::fs::{FileSystem, InodeType};
+    use crate::ktest_util::{MAP_PRIVATE, PROT_READ_WRITE, new_process};
+    use crate::process::credentials::ROOT_ID;
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::mmap::{MMapFlags, sys_mmap};
+
+    const S_IFMT: u32 = 0o170000;
+
+    #[ktest]
+    fn lstat_reports_the_link_itself() {
+        let ramfs = RamFS::new();
+        let root = ramfs.root_inode();
+        let file = root
+            .create("file", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+        file.write_at(0, VmReader::from(&b"hello, world"[..]).to_fallible())
+            .unwrap();
+        let link = root
+            .create("link", InodeType::SymbolLink, 0o777, ROOT_ID, ROOT_ID)
+            .unwrap();
+        link.write_link("file").unwrap();
+
+        let process = new_process("hello_world");
+        let dirfd = process
+            .file_table()
+            .insert(FileEntry::new(Arc::new(FileInode::new(root))));
+        let mmap_flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(buf) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            mmap_flags,
+            u64::MAX,
+            0,
+            &process,
+        )
+        .unwrap();
+        let buf = buf as Vaddr;
+        let vm_space = process.memory_space().vm_space();
+        vm_space.activate();
+        vm_space
+            .writer(buf, 5)
+            .unwrap()
+            .write_fallible(&mut VmReader::from(&b"link\0"[..]))
+            .unwrap();
+        let statbuf = buf + 8;
+
+        let stat = |flags: u32| {
+            sys_fstatat(dirfd as _, buf, statbuf, flags, &process).unwrap();
+            let stat: Stat = vm_space
+                .reader(statbuf, size_of::<Stat>())
+                .unwrap()
+                .read_val()
+                .unwrap();
+            (stat.st_mode & S_IFMT, stat.st_size)
+        };
+
+        assert_eq!(stat(AT_SYMLINK_NOFOLLOW), (S_IFLNK, "file".len() as i64));
+        assert_eq!(stat(0), (S_IFREG, "hello, world".len() as i64));
+    }
+}
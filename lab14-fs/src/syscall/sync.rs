@@ -0,0 +1,153 @@
+use alloc::sync::Arc;
+use log::debug;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::Inode;
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+
+const SYNC_FILE_RANGE_WAIT_BEFORE: u32 = 1;
+const SYNC_FILE_RANGE_WRITE: u32 = 2;
+const SYNC_FILE_RANGE_WAIT_AFTER: u32 = 4;
+
+pub fn sys_fsync(fd: i32, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_FSYNC] fd: {}", fd);
+    sync_inode(fd, current_process)?.sync_all()?;
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_fdatasync(fd: i32, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_FDATASYNC] fd: {}", fd);
+    sync_inode(fd, current_process)?.sync_data()?;
+    Ok(SyscallReturn(0))
+}
+
+/// The inode behind `fd`; pipes and the like have nothing to sync.
+fn sync_inode(fd: i32, current_process: &Arc<Process>) -> Result<Arc<dyn Inode>> {
+    current_process
+        .file_table()
+        .get(fd)
+        .ok_or(Error::new(Errno::EBADF))?
+        .file()
+        .as_inode()
+        .ok_or(Error::new(Errno::EINVAL))
+}
+
+pub fn sys_sync_file_range(
+    fd: i32,
+    offset: i64,
+    nbytes: i64,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_SYNC_FILE_RANGE] fd: {}, offset: {}, nbytes: {}, flags: {:#x}",
+        fd, offset, nbytes, flags
+    );
+
+    let valid_flags =
+        SYNC_FILE_RANGE_WAIT_BEFORE | SYNC_FILE_RANGE_WRITE | SYNC_FILE_RANGE_WAIT_AFTER;
+    if offset < 0 || nbytes < 0 || flags & !valid_flags != 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let start = offset as usize;
+    // Zero bytes means up to the end of the file, however long it gets.
+    let end = if nbytes == 0 {
+        usize::MAX
+    } else {
+        start
+            .checked_add(nbytes as usize)
+            .ok_or(Error::new(Errno::EINVAL))?
+    };
+
+    let inode = current_process
+        .file_table()
+        .get(fd)
+        .ok_or(Error::new(Errno::EBADF))?
+        .file()
+        .as_inode()
+        .ok_or(Error::new(Errno::ESPIPE))?;
+
+    // Writes are always waited for, so only `SYNC_FILE_RANGE_WRITE` does anything.
+    if flags & SYNC_FILE_RANGE_WRITE != 0 {
+        inode.sync_range(start..end)?;
+    }
+    Ok(SyscallReturn(0))
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+
+    use ostd::prelude::ktest;
+
+    use super::{SYNC_FILE_RANGE_WRITE, sys_fdatasync, sys_fsync, sys_sync_file_range};
+    use crate::error::Errno;
+    use crate::fs::file_table::FileEntry;
+    use crate::fs::pipe::Pipe;
+    use crate::fs::ramfs::RamFS;
+    use crate::fs::util::FileInode;
+    use crate::fs::{FileSystem, InodeType};
+    use crate::ktest_util::new_process;
+    use crate::process::credentials::ROOT_ID;
+
+    #[ktest]
+    fn sync_file_range_checks_its_arguments() {
+        let ramfs = RamFS::new();
+        let file = ramfs
+            .root_inode()
+            .create("file", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+
+        let process = new_process("hello_world");
+        let fd = process
+            .file_table()
+            .insert(FileEntry::new(Arc::new(FileInode::new(file))));
+        let (reader, _writer) = Pipe::new_pair();
+        let pipe_fd = process.file_table().insert(FileEntry::new(reader));
+
+        let sync = |fd: i32, offset: i64, nbytes: i64, flags: u32| {
+            sys_sync_file_range(fd, offset, nbytes, flags, &process)
+                .err()
+                .map(|err| err.code)
+        };
+        assert_eq!(sync(fd, 0, 4096, SYNC_FILE_RANGE_WRITE), None);
+        assert_eq!(sync(fd, 4096, 0, SYNC_FILE_RANGE_WRITE), None);
+        assert_eq!(
+            sync(fd, -1, 4096, SYNC_FILE_RANGE_WRITE),
+            Some(Errno::EINVAL)
+        );
+        assert_eq!(sync(fd, 0, 4096, 0x8), Some(Errno::EINVAL));
+        assert_eq!(
+            sync(pipe_fd, 0, 4096, SYNC_FILE_RANGE_WRITE),
+            Some(Errno::ESPIPE)
+        );
+        assert_eq!(
+            sync(fd + 100, 0, 4096, SYNC_FILE_RANGE_WRITE),
+            Some(Errno::EBADF)
+        );
+    }
+
+    #[ktest]
+    fn fsync_and_fdatasync_need_a_file() {
+        let ramfs = RamFS::new();
+        let file = ramfs
+            .root_inode()
+            .create("file", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+
+        let process = new_process("hello_world");
+        let fd = process
+            .file_table()
+            .insert(FileEntry::new(Arc::new(FileInode::new(file))));
+        let (reader, _writer) = Pipe::new_pair();
+        let pipe_fd = process.file_table().insert(FileEntry::new(reader));
+
+        for sync in [sys_fsync, sys_fdatasync] {
+            let sync = |fd: i32| sync(fd, &process).err().map(|err| err.code);
+            assert_eq!(sync(fd), None);
+            assert_eq!(sync(pipe_fd), Some(Errno::EINVAL));
+            assert_eq!(sync(fd + 100), Some(Errno::EBADF));
+        }
+    }
+}
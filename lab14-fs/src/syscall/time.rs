@@ -7,7 +7,10 @@ use ostd::{Pod, mm::Vaddr, timer::Jiffies};
 
 use super::SyscallReturn;
 
-use crate::{error::Result, process::Process};
+use crate::{
+    error::{Errno, Error, Result},
+    process::Process,
+};
 
 pub fn sys_clock_gettime(
     clockid: i32,
@@ -18,17 +21,12 @@ pub fn sys_clock_gettime(
 
     let clock = ClockId::try_from(clockid).unwrap();
 
-    let vm_space = current_process.memory_space().vm_space();
-    let mut writer = vm_space
-        .writer(timespec_addr, size_of::<timespec_t>())
-        .unwrap();
-
     match clock {
         ClockId::CLOCK_REALTIME => todo!(),
         ClockId::CLOCK_MONOTONIC => {
             // Just use the Jiffies
             let duration = Jiffies::elapsed().as_duration();
-            writer.write_val(&timespec_t::from(duration)).unwrap();
+            current_process.write_user_val(timespec_addr, &timespec_t::from(duration))?;
         }
         _ => todo!(),
     }
@@ -57,6 +55,18 @@ pub struct timespec_t {
     pub nsec: i64,
 }
 
+impl TryFrom<timespec_t> for Duration {
+    type Error = Error;
+
+    /// Fails with `EINVAL` for a negative time or nanoseconds out of `0..1_000_000_000`.
+    fn try_from(timespec: timespec_t) -> Result<Duration> {
+        if timespec.sec < 0 || !(0..1_000_000_000).contains(&timespec.nsec) {
+            return Err(Error::new(Errno::EINVAL));
+        }
+        Ok(Duration::new(timespec.sec as u64, timespec.nsec as u32))
+    }
+}
+
 impl From<Duration> for timespec_t {
     fn from(duration: Duration) -> timespec_t {
         let sec = duration.as_secs() as i64;
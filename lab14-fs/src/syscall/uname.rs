@@ -26,13 +26,7 @@ pub fn sys_uname(utsname_addr: usize, current_process: &Arc<Process>) -> Result<
     uts.version[..6].copy_from_slice(b"#1 SMP");
     uts.machine[..7].copy_from_slice(b"riscv64");
 
-    current_process
-        .memory_space()
-        .vm_space()
-        .writer(utsname_addr, core::mem::size_of::<UtsName>())
-        .unwrap()
-        .write_val(&uts)
-        .unwrap();
+    current_process.write_user_val(utsname_addr, &uts)?;
 
     Ok(SyscallReturn(0))
 }
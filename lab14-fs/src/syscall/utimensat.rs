@@ -0,0 +1,229 @@
+use core::time::Duration;
+
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::Vaddr;
+
+use crate::error::{Errno, Error, Result};
+use crate::fs::Permission;
+use crate::fs::util::PathString;
+use crate::process::Process;
+use crate::process::credentials::ROOT_ID;
+use crate::syscall::SyscallReturn;
+use crate::syscall::exec::{dirfd_inode, read_path};
+use crate::syscall::time::timespec_t;
+
+const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+
+/// A `tv_nsec` that sets the timestamp to the current time.
+const UTIME_NOW: i64 = (1 << 30) - 1;
+/// A `tv_nsec` that leaves the timestamp as it is.
+const UTIME_OMIT: i64 = (1 << 30) - 2;
+
+pub fn sys_utimensat(
+    dirfd: i32,
+    path: Vaddr,
+    times: Vaddr,
+    flags: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_UTIMENSAT] dirfd: {}, path: {:#x}, times: {:#x}, flags: {:#x}",
+        dirfd, path, times, flags
+    );
+
+    // No times at all means both are now.
+    let (atime, mtime, to_now) = if times == 0 {
+        let now = crate::fs::now();
+        (Some(now), Some(now), true)
+    } else {
+        let times: [timespec_t; 2] = current_process.read_user_val(times)?;
+        let to_now = times.iter().all(|time| time.nsec == UTIME_NOW);
+        (to_timestamp(&times[0])?, to_timestamp(&times[1])?, to_now)
+    };
+
+    // Like Linux, a null path means the file `dirfd` refers to.
+    let inode = if path == 0 {
+        dirfd_inode(dirfd, current_process)?
+    } else {
+        let path = read_path(path, current_process)?;
+        if path.is_empty() {
+            return Err(Error::new(Errno::ENOENT));
+        }
+        let start = if path.starts_with('/') {
+            crate::fs::ROOT
+                .get()
+                .ok_or(Error::new(Errno::ENOENT))?
+                .root_inode()
+        } else {
+            dirfd_inode(dirfd, current_process)?
+        };
+        let mut path_string =
+            PathString::new(path).with_credentials(*current_process.credentials());
+        if flags & AT_SYMLINK_NOFOLLOW != 0 {
+            path_string.lookup_no_follow(start.as_ref())?
+        } else {
+            path_string.lookup(start.as_ref())?
+        }
+    };
+
+    if atime.is_none() && mtime.is_none() {
+        return Ok(SyscallReturn(0));
+    }
+    // Like Linux, the owner may set the times to anything, and whoever may write
+    // the file may only set them to now.
+    let credentials = *current_process.credentials();
+    let metadata = inode.metadata();
+    if credentials.euid() != ROOT_ID && credentials.euid() != metadata.uid() {
+        if !to_now {
+            return Err(Error::new(Errno::EPERM));
+        }
+        metadata.check_permission(&credentials, Permission::WRITE)?;
+    }
+    inode.set_times(atime, mtime)?;
+    Ok(SyscallReturn(0))
+}
+
+/// Works out what a timestamp is to be set to, or `None` to leave it alone.
+fn to_timestamp(time: &timespec_t) -> Result<Option<Duration>> {
+    match time.nsec {
+        UTIME_NOW => Ok(Some(crate::fs::now())),
+        UTIME_OMIT => Ok(None),
+        _ => Duration::try_from(*time).map(Some),
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+
+    use ostd::mm::{FallibleVmWrite, PAGE_SIZE, Vaddr, VmReader};
+    use ostd::prelude::ktest;
+
+    use super::{UTIME_OMIT, sys_utimensat};
+    use crate::error::Errno;
+    use crate::fs::file_table::FileEntry;
+    use crate::fs::ramfs::RamFS;
+    use crate::fs::util::FileInode;
+    use crate::fs::{FileSystem, InodeType};
+    use crate::ktest_util::{MAP_PRIVATE, PROT_READ_WRITE, new_process};
+    use crate::process::credentials::ROOT_ID;
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::mmap::{MMapFlags, sys_mmap};
+    use crate::syscall::stat::{Stat, sys_fstatat};
+    use crate::syscall::time::timespec_t;
+
+    #[ktest]
+    fn mtime_reads_back_through_stat() {
+        let ramfs = RamFS::new();
+        let root = ramfs.root_inode();
+        root.create("file", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+
+        let process = new_process("hello_world");
+        let dirfd = process
+            .file_table()
+            .insert(FileEntry::new(Arc::new(FileInode::new(root))));
+        let mmap_flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(buf) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            mmap_flags,
+            u64::MAX,
+            0,
+            &process,
+        )
+        .unwrap();
+        let buf = buf as Vaddr;
+        let vm_space = process.memory_space().vm_space();
+        vm_space.activate();
+        let path = buf;
+        let times = buf + 8;
+        let statbuf = buf + 64;
+        vm_space
+            .writer(path, 5)
+            .unwrap()
+            .write_fallible(&mut VmReader::from(&b"file\0"[..]))
+            .unwrap();
+
+        let set_times = |atime: timespec_t, mtime: timespec_t| {
+            let mut writer = vm_space.writer(times, 2 * size_of::<timespec_t>()).unwrap();
+            writer.write_val(&atime).unwrap();
+            writer.write_val(&mtime).unwrap();
+            sys_utimensat(dirfd as _, path, times, 0, &process).unwrap();
+        };
+        let stat = || {
+            sys_fstatat(dirfd as _, path, statbuf, 0, &process).unwrap();
+            let stat: Stat = vm_space
+                .reader(statbuf, size_of::<Stat>())
+                .unwrap()
+                .read_val()
+                .unwrap();
+            (
+                (stat.st_atime, stat.st_atime_nsec),
+                (stat.st_mtime, stat.st_mtime_nsec),
+            )
+        };
+
+        set_times(
+            timespec_t { sec: 100, nsec: 0 },
+            timespec_t {
+                sec: 1_700_000_000,
+                nsec: 123,
+            },
+        );
+        assert_eq!(stat(), ((100, 0), (1_700_000_000, 123)));
+
+        // An omitted timestamp stays as it was.
+        set_times(
+            timespec_t {
+                sec: 0,
+                nsec: UTIME_OMIT,
+            },
+            timespec_t { sec: 42, nsec: 0 },
+        );
+        assert_eq!(stat(), ((100, 0), (42, 0)));
+    }
+
+    #[ktest]
+    fn only_owner_sets_other_times() {
+        let ramfs = RamFS::new();
+        let root = ramfs.root_inode();
+        let file = root
+            .create("file", InodeType::File, 0o644, ROOT_ID, ROOT_ID)
+            .unwrap();
+
+        let process = new_process("hello_world");
+        let dirfd = process
+            .file_table()
+            .insert(FileEntry::new(Arc::new(FileInode::new(root))));
+        let mmap_flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(buf) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            mmap_flags,
+            u64::MAX,
+            0,
+            &process,
+        )
+        .unwrap();
+        let path = buf as Vaddr;
+        let times = path + 8;
+        process.write_user_bytes(path, b"file\0").unwrap();
+        let explicit = [timespec_t { sec: 42, nsec: 0 }; 2];
+        process.write_user_val(times, &explicit).unwrap();
+        process.credentials().set_uid(1000).unwrap();
+        let utimensat = |times| sys_utimensat(dirfd as _, path, times, 0, &process);
+
+        // The file belongs to root and others may not write it.
+        assert_eq!(utimensat(0).err().unwrap().code, Errno::EACCES);
+        assert_eq!(utimensat(times).err().unwrap().code, Errno::EPERM);
+
+        // Being allowed to write it is enough for now, but not for other times.
+        file.set_mode(0o666).unwrap();
+        utimensat(0).unwrap();
+        assert_eq!(utimensat(times).err().unwrap().code, Errno::EPERM);
+    }
+}
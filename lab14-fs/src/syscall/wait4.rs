@@ -1,11 +1,38 @@
 use alloc::sync::Arc;
 use log::debug;
+use ostd::Pod;
 use ostd::mm::Vaddr;
 
-use crate::error::Result;
-use crate::process::Process;
+use crate::error::{Errno, Error, Result};
+use crate::process::signal::{SIGCHLD, SIGCONT};
+use crate::process::{ChildEvent, Process, WaitOptions, WaitTarget};
 use crate::syscall::SyscallReturn;
 
+/// Don't block if no child has changed state yet.
+const WNOHANG: u32 = 0x1;
+/// Report children that have stopped (`WSTOPPED` to `waitid`).
+const WUNTRACED: u32 = 0x2;
+/// Wait for children that have exited.
+const WEXITED: u32 = 0x4;
+/// Report children that have been continued.
+const WCONTINUED: u32 = 0x8;
+/// Leave the child a zombie, to be waited for again.
+const WNOWAIT: u32 = 0x0100_0000;
+
+/// `waitid` id types.
+const P_ALL: u32 = 0;
+const P_PID: u32 = 1;
+const P_PGID: u32 = 2;
+
+/// The `si_code` of a child that exited.
+const CLD_EXITED: i32 = 1;
+/// The `si_code` of a child that was killed by a signal.
+const CLD_KILLED: i32 = 2;
+/// The `si_code` of a child that stopped.
+const CLD_STOPPED: i32 = 5;
+/// The `si_code` of a child that was continued.
+const CLD_CONTINUED: i32 = 6;
+
 pub fn sys_wait4(
     wait_pid: i32,
     exit_status_ptr: Vaddr,
@@ -18,19 +45,238 @@ pub fn sys_wait4(
         wait_pid, exit_status_ptr, wait_options, rusage_addr
     );
 
-    let (pid, exit_code) = current_process.wait(wait_pid)?;
+    let options = WaitOptions {
+        exited: true,
+        stopped: wait_options & WUNTRACED != 0,
+        continued: wait_options & WCONTINUED != 0,
+        no_wait: false,
+    };
+    let target = current_process.wait_target(wait_pid);
+    let Some((pid, event)) = wait(current_process, target, options, wait_options)? else {
+        return Ok(SyscallReturn(0));
+    };
 
     // Write the exit code to the user space
     if exit_status_ptr != 0 {
         current_process.memory_space().vm_space().activate();
-        current_process
-            .memory_space()
-            .vm_space()
-            .writer(exit_status_ptr, 4)
+        current_process.write_user_val(exit_status_ptr, &event.wait_status())?;
+    }
+
+    Ok(SyscallReturn(pid as _))
+}
+
+pub fn sys_waitid(
+    id_type: u32,
+    id: u32,
+    info_addr: Vaddr,
+    options: u32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_WAITID] id_type: {}, id: {}, info_addr: {:#x}, options: {:#x}",
+        id_type, id, info_addr, options
+    );
+
+    if options & (WEXITED | WUNTRACED | WCONTINUED) == 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    let target = match id_type {
+        P_ALL => WaitTarget::Any,
+        P_PID => WaitTarget::Pid(id as _),
+        P_PGID if id == 0 => WaitTarget::Group(current_process.pgid()),
+        P_PGID => WaitTarget::Group(id as _),
+        _ => return Err(Error::new(Errno::EINVAL)),
+    };
+
+    let wait_options = WaitOptions {
+        exited: options & WEXITED != 0,
+        stopped: options & WUNTRACED != 0,
+        continued: options & WCONTINUED != 0,
+        no_wait: options & WNOWAIT != 0,
+    };
+    let info = match wait(current_process, target, wait_options, options)? {
+        Some((pid, event)) => {
+            let (code, status) = match event {
+                ChildEvent::Exited(exit_code) => (CLD_EXITED, exit_code as i32),
+                ChildEvent::Killed(signal) => (CLD_KILLED, signal as i32),
+                ChildEvent::Stopped(signal) => (CLD_STOPPED, signal as i32),
+                ChildEvent::Continued => (CLD_CONTINUED, SIGCONT as i32),
+            };
+            WaitInfo {
+                signo: SIGCHLD as _,
+                code,
+                pid: pid as _,
+                status,
+                ..WaitInfo::new_zeroed()
+            }
+        }
+        // With `WNOHANG`, no child to report is told by a zero `si_pid`.
+        None => WaitInfo::new_zeroed(),
+    };
+
+    if info_addr != 0 {
+        current_process.write_user_val(info_addr, &info)?;
+    }
+    Ok(SyscallReturn(0))
+}
+
+/// Waits for an event of a child in `target`, or returns `None` right away if
+/// there is none yet and `flags` has `WNOHANG`.
+fn wait(
+    current_process: &Arc<Process>,
+    target: WaitTarget,
+    options: WaitOptions,
+    flags: u32,
+) -> Result<Option<(usize, ChildEvent)>> {
+    if flags & WNOHANG == 0 {
+        return current_process.wait_for(target, options).map(Some);
+    }
+    match current_process.try_wait(target, options) {
+        Ok(res) => Ok(Some(res)),
+        Err(err) if err.code == Errno::EAGAIN => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// `siginfo_t` as `waitid` fills it in.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct WaitInfo {
+    signo: i32,
+    errno: i32,
+    code: i32,
+    _pad0: i32,
+    pid: i32,
+    uid: u32,
+    status: i32,
+    _pad: [i32; 25],
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+    use ostd::Pod;
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::mm::{PAGE_SIZE, Vaddr};
+    use ostd::prelude::ktest;
+    use ostd::task::Task;
+
+    use super::{CLD_EXITED, P_PID, WEXITED, WNOWAIT, WaitInfo, sys_wait4, sys_waitid};
+    use crate::error::Errno;
+    use crate::ktest_util::{MAP_PRIVATE, PROT_READ_WRITE, new_process};
+    use crate::process::Process;
+    use crate::process::signal::{SIGCHLD, SIGSEGV};
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::mmap::{MMapFlags, sys_mmap};
+
+    /// Maps a page for the kernel to write results to.
+    fn map_page(process: &Arc<Process>) -> Vaddr {
+        let mmap_flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(addr) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            mmap_flags,
+            u64::MAX,
+            0,
+            process,
+        )
+        .unwrap();
+        addr as Vaddr
+    }
+
+    #[ktest]
+    fn wait4_encodes_the_exit_code() {
+        let parent = new_process("hello_world");
+        let status_addr = map_page(&parent);
+        let vm_space = parent.memory_space().vm_space();
+        vm_space.activate();
+
+        let child = parent.fork(&UserContext::default()).unwrap();
+        let pid = child.pid();
+        child.exit(0x107);
+
+        let SyscallReturn(ret) = sys_wait4(pid as _, status_addr, 0, 0, &parent).unwrap();
+        assert_eq!(ret, pid as isize);
+        let status: u32 = vm_space
+            .reader(status_addr, size_of::<u32>())
             .unwrap()
-            .write_val(&exit_code)
+            .read_val()
             .unwrap();
+        // `WIFEXITED`, with only the low byte of the code in `WEXITSTATUS`.
+        assert_eq!(status & 0x7f, 0);
+        assert_eq!((status >> 8) & 0xff, 7);
     }
 
-    Ok(SyscallReturn(pid as _))
+    #[ktest]
+    fn wait4_reports_the_killing_signal() {
+        let parent = new_process("hello_world");
+        let status_addr = map_page(&parent);
+        let vm_space = parent.memory_space().vm_space();
+        vm_space.activate();
+
+        let child = parent.fork(&UserContext::default()).unwrap();
+        let pid = child.pid();
+        child.exit_by_signal(SIGSEGV);
+
+        sys_wait4(pid as _, status_addr, 0, 0, &parent).unwrap();
+        let status: u32 = vm_space
+            .reader(status_addr, size_of::<u32>())
+            .unwrap()
+            .read_val()
+            .unwrap();
+        // `WIFSIGNALED`, with the signal in `WTERMSIG`.
+        assert_eq!(status, SIGSEGV as u32);
+    }
+
+    #[ktest]
+    fn wnowait_leaves_the_zombie() {
+        let parent = new_process("hello_world");
+        let info_addr = map_page(&parent);
+        let vm_space = parent.memory_space().vm_space();
+        vm_space.activate();
+
+        let child = parent.fork(&UserContext::default()).unwrap();
+        let pid = child.pid();
+        child.exit(7);
+
+        let waitid = |options| {
+            vm_space
+                .writer(info_addr, size_of::<WaitInfo>())
+                .unwrap()
+                .write_val(&WaitInfo::new_zeroed())
+                .unwrap();
+            sys_waitid(P_PID, pid as _, info_addr, options, &parent).unwrap();
+            let info: WaitInfo = vm_space
+                .reader(info_addr, size_of::<WaitInfo>())
+                .unwrap()
+                .read_val()
+                .unwrap();
+            assert_eq!(info.signo, SIGCHLD as i32);
+            assert_eq!(info.code, CLD_EXITED);
+            assert_eq!(info.pid, pid as i32);
+            assert_eq!(info.status, 7);
+        };
+
+        // A peek leaves the zombie for the wait that reaps it...
+        waitid(WEXITED | WNOWAIT);
+        waitid(WEXITED);
+
+        // ...after which it is gone.
+        let err = sys_waitid(P_PID, pid as _, info_addr, WEXITED, &parent)
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Errno::ECHILD);
+    }
+
+    #[ktest]
+    fn wait_for_process_group() {
+        let process = new_process("wait_pgrp");
+        process.run();
+        while !process.is_zombie() {
+            Task::yield_now();
+        }
+        // Waiting on the group reaped both of its members, and only them.
+        assert_eq!(process.exit_code(), Some(0));
+    }
 }
@@ -2,14 +2,11 @@ use core::str;
 
 use alloc::{sync::Arc, vec};
 use log::debug;
-use ostd::{
-    Pod, early_print,
-    mm::{FallibleVmRead, Vaddr, VmWriter},
-};
+use ostd::{Pod, early_print, mm::Vaddr};
 
 use crate::{
     error::{Errno, Error, Result},
-    process::Process,
+    process::{Process, signal::SIGPIPE},
     syscall::SyscallReturn,
 };
 
@@ -34,24 +31,12 @@ pub fn sys_writev(
     let mut total_len = 0;
 
     let mut current_addr = io_vec_ptr;
-    let memory_space = current_process.memory_space();
 
     for _ in 0..io_vec_count {
-        let mut reader = memory_space
-            .vm_space()
-            .reader(current_addr, size_of::<IoVec>())
-            .unwrap();
-        let io_vec: IoVec = reader.read_val().unwrap();
+        let io_vec: IoVec = current_process.read_user_val(current_addr)?;
 
         let mut buf = vec![0u8; io_vec.len];
-
-        let mut buffer = memory_space
-            .vm_space()
-            .reader(io_vec.base, io_vec.len)
-            .unwrap();
-        buffer
-            .read_fallible(&mut VmWriter::from(&mut buf as &mut [u8]))
-            .unwrap();
+        current_process.read_user_bytes(io_vec.base, &mut buf)?;
 
         total_len += io_vec.len;
         early_print!("{}", str::from_utf8(&buf).unwrap());
@@ -72,15 +57,80 @@ pub fn sys_write(
         fd, buf, count
     );
 
-    let reader = current_process
-        .memory_space()
-        .vm_space()
-        .reader(buf, count)
-        .unwrap();
+    let reader = current_process.user_reader(buf, count)?;
 
     let file_table = current_process.file_table();
     let file = file_table.get(fd).ok_or(Error::new(Errno::EBADF))?;
-    let write_len = file.file().write(reader)?;
+    let write_len = file.file().write(reader).inspect_err(|err| {
+        // The writer learns that the reader is gone by the signal too, unless it
+        // ignores it.
+        if err.code == Errno::EPIPE {
+            current_process.send_signal(SIGPIPE);
+        }
+    })?;
 
     Ok(SyscallReturn(write_len as _))
 }
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::mm::{PAGE_SIZE, Vaddr};
+    use ostd::prelude::ktest;
+
+    use super::sys_write;
+    use crate::error::Errno;
+    use crate::fs::file_table::{FileDescriptor, FileEntry};
+    use crate::fs::pipe::Pipe;
+    use crate::ktest_util::{MAP_PRIVATE, PROT_READ_WRITE, new_process};
+    use crate::process::Process;
+    use crate::process::signal::{SIGPIPE, SigAction};
+    use crate::syscall::SyscallReturn;
+    use crate::syscall::mmap::{MMapFlags, sys_mmap};
+
+    /// Returns a process with the write end of a pipe whose read end is closed, and
+    /// a buffer to write from.
+    fn process_with_broken_pipe() -> (Arc<Process>, FileDescriptor, Vaddr) {
+        let process = new_process("hello_world");
+
+        let (reader, writer) = Pipe::new_pair();
+        drop(reader);
+        let fd = process.file_table().insert(FileEntry::new(writer));
+
+        let flags = MAP_PRIVATE | (MMapFlags::MAP_ANONYMOUS | MMapFlags::MAP_POPULATE).bits();
+        let SyscallReturn(buf) = sys_mmap(
+            0,
+            PAGE_SIZE as _,
+            PROT_READ_WRITE,
+            flags,
+            u64::MAX,
+            0,
+            &process,
+        )
+        .unwrap();
+        process.memory_space().vm_space().activate();
+        (process, fd, buf as Vaddr)
+    }
+
+    #[ktest]
+    fn broken_pipe_raises_sigpipe() {
+        let (process, fd, buf) = process_with_broken_pipe();
+
+        let err = sys_write(fd, buf, 8, &process).err().unwrap();
+        assert_eq!(err.code, Errno::EPIPE);
+        process.handle_pending_signals(&mut UserContext::default());
+        assert_eq!(process.killed_by(), Some(SIGPIPE));
+    }
+
+    #[ktest]
+    fn ignored_sigpipe_leaves_epipe() {
+        let (process, fd, buf) = process_with_broken_pipe();
+        process.set_sig_action(SIGPIPE, SigAction::Ignore).unwrap();
+
+        let err = sys_write(fd, buf, 8, &process).err().unwrap();
+        assert_eq!(err.code, Errno::EPIPE);
+        process.handle_pending_signals(&mut UserContext::default());
+        assert!(!process.is_zombie());
+    }
+}
@@ -0,0 +1,44 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdio.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static unsigned long child_tls[4];
+
+int main()
+{
+    // Disable buffer in STDOUT
+    setvbuf(stdout, NULL, _IONBF, 0);
+
+    // A raw clone, so that libc doesn't touch the thread pointer of the child.
+    long pid = syscall(SYS_clone, SIGCHLD | CLONE_SETTLS, 0, NULL, child_tls, NULL);
+    if (pid == 0)
+    {
+        unsigned long tp;
+        asm volatile("mv %0, tp" : "=r"(tp));
+        syscall(SYS_exit, tp == (unsigned long)child_tls ? 0 : 1);
+    }
+
+    int status;
+    waitpid(pid, &status, 0);
+    if (!WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("The child did not get its thread pointer\n");
+        return 1;
+    }
+
+    // Thread pointers into the kernel are refused.
+    long ret = syscall(SYS_clone, SIGCHLD | CLONE_SETTLS, 0, NULL, (void *)0xffffffc000000000UL, NULL);
+    if (ret != -1 || errno != EINVAL)
+    {
+        printf("A kernel thread pointer was accepted\n");
+        return 1;
+    }
+
+    printf("clone_tls passed\n");
+    return 0;
+}
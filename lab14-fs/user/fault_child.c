@@ -0,0 +1,35 @@
+#include <signal.h>
+#include <stdio.h>
+#include <unistd.h>
+#include <wait.h>
+
+int main(int argc, char *argv[])
+{
+    // Disable buffer in STDOUT
+    setvbuf(stdout, NULL, _IONBF, 0);
+
+    int pid = fork();
+    if (pid == 0)
+    {
+        // Child: trap into the kernel with an exception it can't handle.
+        asm volatile("ebreak");
+        printf("Child survived the breakpoint!\n");
+        return 0;
+    }
+
+    int status = 0;
+    if (waitpid(pid, &status, 0) != pid)
+    {
+        printf("waitpid failed\n");
+        return 1;
+    }
+
+    if (!WIFSIGNALED(status) || WTERMSIG(status) != SIGTRAP)
+    {
+        printf("Unexpected child status %#x\n", status);
+        return 1;
+    }
+
+    printf("Child killed by SIGTRAP, parent still running\n");
+    return 0;
+}
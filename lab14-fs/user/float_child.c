@@ -0,0 +1,59 @@
+#include <stdio.h>
+#include <unistd.h>
+#include <wait.h>
+
+// Only known at run time, so the compiler can't do the arithmetic itself.
+static volatile double half = 0.5;
+
+// 0.5 + 1.0 + ... + 50.0, which is exact in binary.
+static double sum_of_halves(void)
+{
+    double sum = 0.0;
+    for (int i = 1; i <= 100; i++)
+    {
+        sum += half * i;
+    }
+    return sum;
+}
+
+int main(int argc, char *argv[])
+{
+    // Disable buffer in STDOUT
+    setvbuf(stdout, NULL, _IONBF, 0);
+
+    // The first floating-point instruction turns the FPU on.
+    double sum = sum_of_halves();
+    if (sum != 2525.0)
+    {
+        printf("Wrong sum %d\n", (int)sum);
+        return 1;
+    }
+
+    int pid = fork();
+    if (pid == 0)
+    {
+        // Child: goes on from the parent's FP registers.
+        double product = sum * half;
+        return product == 1262.5 ? 0 : 1;
+    }
+
+    int status = 0;
+    if (waitpid(pid, &status, 0) != pid)
+    {
+        printf("waitpid failed\n");
+        return 1;
+    }
+    if (!WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("Child got a wrong product, status %#x\n", status);
+        return 1;
+    }
+    if (sum_of_halves() != sum)
+    {
+        printf("Parent got a different sum the second time\n");
+        return 1;
+    }
+
+    printf("Floating-point results are right\n");
+    return 0;
+}
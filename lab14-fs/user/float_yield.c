@@ -0,0 +1,59 @@
+#include <stdio.h>
+#include <unistd.h>
+#include <wait.h>
+
+#define ROUNDS 200
+
+// Yields the CPU with `value` in fs0, and returns what fs0 holds afterwards.
+// Whatever runs in between must not change it.
+static double yield_holding(double value)
+{
+    register double reg asm("fs0") = value;
+    // sched_yield
+    asm volatile("li a7, 124\n\tecall" : "+f"(reg) : : "a0", "a7", "memory");
+    return reg;
+}
+
+// Adds up seed, 2 * seed, ..., ROUNDS * seed, yielding between the steps.
+static int add_up(double seed)
+{
+    double sum = 0.0;
+    for (int i = 1; i <= ROUNDS; i++)
+    {
+        double held = yield_holding(seed * i);
+        if (held != seed * i)
+        {
+            return 1;
+        }
+        sum += held;
+    }
+    return sum == seed * (ROUNDS * (ROUNDS + 1) / 2) ? 0 : 1;
+}
+
+int main(int argc, char *argv[])
+{
+    // Disable buffer in STDOUT
+    setvbuf(stdout, NULL, _IONBF, 0);
+
+    int pid = fork();
+    if (pid == 0)
+    {
+        return add_up(2.5);
+    }
+    int failed = add_up(0.75);
+
+    int status = 0;
+    if (waitpid(pid, &status, 0) != pid)
+    {
+        printf("waitpid failed\n");
+        return 1;
+    }
+    if (failed || !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("FP registers changed across a task switch: parent %d, child status %#x\n", failed, status);
+        return 1;
+    }
+
+    printf("Both tasks kept their FP registers\n");
+    return 0;
+}
@@ -0,0 +1,9 @@
+#include <stdio.h>
+#include <unistd.h>
+
+// Built as a static-PIE, so the kernel has to pick where it is loaded.
+int main()
+{
+    printf("Hello from a PIE!, my pid: %d, main at: %p\n", getpid(), (void *)main);
+    return 42;
+}
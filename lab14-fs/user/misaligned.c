@@ -0,0 +1,54 @@
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <unistd.h>
+#include <wait.h>
+
+static uint8_t buffer[16] __attribute__((aligned(8)));
+
+int main(int argc, char *argv[])
+{
+    // Disable buffer in STDOUT
+    setvbuf(stdout, NULL, _IONBF, 0);
+
+    for (int i = 0; i < 16; i++)
+        buffer[i] = i;
+
+    // A plain misaligned load is done by the hardware or emulated by the SBI
+    // firmware. Either way, it must read the right bytes.
+    uint32_t value;
+    asm volatile("lw %0, 0(%1)" : "=r"(value) : "r"(buffer + 1));
+    if (value != 0x04030201)
+    {
+        printf("Misaligned load read %#x\n", value);
+        return 1;
+    }
+
+    // Nothing emulates a misaligned atomic, so it reaches the kernel.
+    int pid = fork();
+    if (pid == 0)
+    {
+        asm volatile("amoadd.w %0, %2, (%1)"
+                     : "=r"(value)
+                     : "r"(buffer + 1), "r"(1)
+                     : "memory");
+        printf("Child survived a misaligned atomic!\n");
+        return 0;
+    }
+
+    int status = 0;
+    if (waitpid(pid, &status, 0) != pid)
+    {
+        printf("waitpid failed\n");
+        return 1;
+    }
+
+    if (!WIFSIGNALED(status) || WTERMSIG(status) != SIGBUS)
+    {
+        printf("Unexpected child status %#x\n", status);
+        return 1;
+    }
+
+    printf("Misaligned atomic raised SIGBUS\n");
+    return 0;
+}
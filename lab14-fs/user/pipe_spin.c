@@ -0,0 +1,21 @@
+#include <sched.h>
+#include <unistd.h>
+
+// The kernel test passes the read end of a pipe as fd 3, and checks that the
+// process sleeps while the read below blocks.
+#define PIPE_FD 3
+
+int main()
+{
+    char buf[8];
+    if (read(PIPE_FD, buf, sizeof(buf)) < 0)
+    {
+        return 1;
+    }
+
+    // Stay runnable until the test kills us.
+    for (;;)
+    {
+        sched_yield();
+    }
+}
@@ -17,15 +17,25 @@ int main()
         return 0;
     }
 
-    printf("[Reparent] Child process, pid: %d, ppid: %d\n", getpid(), getppid());
+    pid_t original_ppid = getppid();
+    printf("[Reparent] Child process, pid: %d, ppid: %d\n", getpid(), original_ppid);
 
-    int yield_time = 2;
+    // Keep polling while the parent exits. The parent pid must switch straight
+    // from the original parent to init, and never read as 0 in between.
+    pid_t ppid = original_ppid;
     printf("[Reparent] Child process yielding, pid: %d\n", getpid());
-    for (int i = 0; i < yield_time; i++)
+    while (ppid == original_ppid)
     {
         sched_yield();
+        ppid = getppid();
     }
 
-    printf("[Reparent] Child process reparenting to init, ppid: %d\n", getppid());
+    printf("[Reparent] Child process reparenting to init, ppid: %d\n", ppid);
+    if (ppid != 1)
+    {
+        printf("[Reparent] FAILED: expected ppid 1 after reparenting\n");
+        return 1;
+    }
+    printf("[Reparent] PASSED\n");
     return 0;
-}
\ No newline at end of file
+}
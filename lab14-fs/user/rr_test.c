@@ -50,7 +50,7 @@ int main()
     {
         int status = -1;
         int pid = wait(&status);
-        printf("main: pid %d, count %d\n", pid, status);
+        printf("main: pid %d, count %d\n", pid, WEXITSTATUS(status));
     }
     printf("main: wait pids over\n");
 }
\ No newline at end of file
@@ -22,13 +22,20 @@ int execute(char *command)
     }
     else if (pid == 0)
     {
+        // Run the command as its own job, so Ctrl-C only interrupts it.
+        setpgid(0, 0);
         execl(command, NULL);
         exit(EXIT_FAILURE);
     }
     else
     {
+        // Also set it here, in case the child has not run yet.
+        setpgid(pid, pid);
+        tcsetpgrp(STDIN_FILENO, pid);
+
         int status;
         waitpid(pid, &status, 0);
+        tcsetpgrp(STDIN_FILENO, getpgrp());
     }
     return 0;
 }
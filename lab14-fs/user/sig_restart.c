@@ -0,0 +1,53 @@
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+// The kernel test passes the read end of a pipe as fd 3. It sends SIGINT while
+// the read below blocks, and only writes to the pipe once the handler has run.
+#define PIPE_FD 3
+
+static volatile sig_atomic_t handled;
+
+static void handler(int sig)
+{
+    handled = sig;
+}
+
+int main(int argc, char *argv[])
+{
+    // Disable buffer in STDOUT
+    setvbuf(stdout, NULL, _IONBF, 0);
+
+    struct sigaction sa;
+    memset(&sa, 0, sizeof(sa));
+    sa.sa_handler = handler;
+    sa.sa_flags = SA_RESTART;
+    if (sigaction(SIGINT, &sa, NULL) < 0)
+    {
+        printf("sigaction failed\n");
+        return 1;
+    }
+
+    char buf[8] = {0};
+    ssize_t n = read(PIPE_FD, buf, 5);
+    if (n < 0)
+    {
+        printf("read failed: %s\n", strerror(errno));
+        return 1;
+    }
+    if (handled != SIGINT)
+    {
+        printf("The handler did not run\n");
+        return 1;
+    }
+    if (n != 5 || strcmp(buf, "hello") != 0)
+    {
+        printf("Read %zd bytes: '%s'\n", n, buf);
+        return 1;
+    }
+
+    printf("read restarted after the handler\n");
+    return 0;
+}
@@ -0,0 +1,22 @@
+#include <sched.h>
+#include <sys/mman.h>
+
+// The kernel test watches this counter to tell whether the process is running.
+#define COUNTER_ADDR ((void *)0x10000000)
+
+int main()
+{
+    volatile unsigned long *counter = mmap(COUNTER_ADDR, 4096, PROT_READ | PROT_WRITE,
+                                           MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED, -1, 0);
+    if (counter == MAP_FAILED)
+    {
+        return 1;
+    }
+
+    // Yield on every round, so that the test gets to run in between.
+    for (;;)
+    {
+        (*counter)++;
+        sched_yield();
+    }
+}
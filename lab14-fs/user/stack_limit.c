@@ -0,0 +1,78 @@
+#include <signal.h>
+#include <stdio.h>
+#include <sys/resource.h>
+#include <unistd.h>
+#include <wait.h>
+
+// Recursing to this depth takes about 1 MiB of stack.
+#define DEPTH 1024
+
+static int recurse(int depth)
+{
+    volatile char frame[1024];
+    frame[0] = (char)depth;
+    if (depth == 0)
+    {
+        return 0;
+    }
+    return recurse(depth - 1) + frame[0] - (char)depth;
+}
+
+// Runs this program again in a child, to recurse with a stack of `limit`
+// bytes, or the default if `limit` is 0. Returns the child's wait status.
+static int run_recursion(rlim_t limit)
+{
+    int pid = fork();
+    if (pid == 0)
+    {
+        if (limit != 0)
+        {
+            struct rlimit rlim = {.rlim_cur = limit, .rlim_max = RLIM_INFINITY};
+            if (setrlimit(RLIMIT_STACK, &rlim) < 0)
+            {
+                printf("setrlimit failed\n");
+                _exit(1);
+            }
+        }
+        execl("stack_limit", "stack_limit", "recurse", NULL);
+        printf("exec failed\n");
+        _exit(1);
+    }
+
+    int status = -1;
+    if (waitpid(pid, &status, 0) != pid)
+    {
+        printf("waitpid failed\n");
+    }
+    return status;
+}
+
+int main(int argc, char *argv[])
+{
+    // Disable buffer in STDOUT
+    setvbuf(stdout, NULL, _IONBF, 0);
+
+    if (argc > 1)
+    {
+        return recurse(DEPTH);
+    }
+
+    // The default 8 MiB stack is plenty.
+    int status = run_recursion(0);
+    if (!WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("Recursion failed with the default stack: status %#x\n", status);
+        return 1;
+    }
+
+    // A 256 KiB stack is not.
+    status = run_recursion(256 * 1024);
+    if (!WIFSIGNALED(status) || WTERMSIG(status) != SIGSEGV)
+    {
+        printf("Unexpected status %#x with a 256 KiB stack\n", status);
+        return 1;
+    }
+
+    printf("Recursion killed at the stack limit\n");
+    return 0;
+}
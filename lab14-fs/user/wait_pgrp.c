@@ -0,0 +1,67 @@
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/types.h>
+#include <sys/wait.h>
+#include <stdio.h>
+
+int main()
+{
+    // Disable buffer in STDOUT
+    setvbuf(stdout, NULL, _IONBF, 0);
+
+    // The first child leads a new group, the second child joins it.
+    pid_t leader = fork();
+    if (leader == 0)
+    {
+        for (int i = 0; i < 10; i++)
+            sched_yield();
+        return 1;
+    }
+    setpgid(leader, leader);
+
+    pid_t member = fork();
+    if (member == 0)
+    {
+        return 2;
+    }
+    setpgid(member, leader);
+
+    // Not part of the group, must never be reaped by waitpid(-leader).
+    pid_t outsider = fork();
+    if (outsider == 0)
+    {
+        return 3;
+    }
+
+    printf("[Wait Pgrp] Parent process, pid: %d, group: %d\n", getpid(), leader);
+
+    int reaped = 0;
+    while (reaped < 2)
+    {
+        int status;
+        pid_t pid = waitpid(-leader, &status, 0);
+        if (pid != leader && pid != member)
+        {
+            printf("[Wait Pgrp] FAILED: waitpid(-%d) returned %d\n", leader, pid);
+            return 1;
+        }
+        printf("[Wait Pgrp] Reaped child %d, exit status: %d\n", pid, WEXITSTATUS(status));
+        reaped++;
+    }
+
+    if (waitpid(-leader, NULL, 0) != -1)
+    {
+        printf("[Wait Pgrp] FAILED: group should have no children left\n");
+        return 1;
+    }
+
+    if (waitpid(outsider, NULL, 0) != outsider)
+    {
+        printf("[Wait Pgrp] FAILED: outsider child was lost\n");
+        return 1;
+    }
+
+    printf("[Wait Pgrp] PASSED\n");
+    return 0;
+}
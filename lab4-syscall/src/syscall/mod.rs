@@ -7,12 +7,16 @@ use ostd::prelude::*;
 
 use core::str;
 
+use log::warn;
+use syscall_names::syscall_name;
+
 use crate::process::Process;
 
 pub fn handle_syscall(user_context: &mut UserContext, process: &Arc<Process>) {
     const SYS_WRITE: usize = 64;
     const SYS_EXIT: usize = 93;
     const SYS_GET_PRIORITY: usize = 1000;
+    const ENOSYS: isize = 38; // Function not implemented
 
     match user_context.a7() {
         SYS_WRITE => {
@@ -41,6 +45,35 @@ pub fn handle_syscall(user_context: &mut UserContext, process: &Arc<Process>) {
             println!("[syscall] process {} priority = {}", process.pid(), prio);
             user_context.set_a0(prio);
         }
-        _ => unimplemented!(),
+        _ => {
+            warn!(
+                "[pid: {}] unimplemented syscall num: {} ({})",
+                process.pid(),
+                user_context.a7(),
+                syscall_name(user_context.a7()).unwrap_or("unknown")
+            );
+            user_context.set_a0(-ENOSYS as usize);
+        }
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::prelude::ktest;
+
+    use crate::process::Process;
+
+    #[ktest]
+    fn unimplemented_syscall_returns_enosys() {
+        let process = Process::new(include_bytes!("../../target/hello"));
+
+        // No Linux syscall uses this number, so it must hit the fallback arm.
+        let mut user_context = UserContext::default();
+        user_context.set_a7(4095);
+        super::handle_syscall(&mut user_context, &process);
+
+        // -ENOSYS
+        assert_eq!(user_context.a0() as isize, -38);
     }
 }
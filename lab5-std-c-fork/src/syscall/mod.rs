@@ -6,8 +6,9 @@ mod uname;
 mod write;
 
 use alloc::sync::Arc;
-use log::{debug, info};
+use log::{debug, info, warn};
 use ostd::arch::cpu::context::UserContext;
+use syscall_names::syscall_name;
 
 use crate::error::{Errno, Error, Result};
 use crate::process::Process;
@@ -81,7 +82,15 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
 
         SYS_WRITE => sys_write(args[0] as _, args[1] as _, args[2] as _, current_process),
         SYS_EXIT => sys_exit(args[0] as _, current_process),
-        _ => Err(Error::new(Errno::ENOSYS)),
+        _ => {
+            warn!(
+                "[pid: {}] unimplemented syscall num: {} ({})",
+                current_process.pid(),
+                user_context.a7(),
+                syscall_name(user_context.a7()).unwrap_or("unknown")
+            );
+            Err(Error::new(Errno::ENOSYS))
+        }
     };
 
     match ret {
@@ -97,3 +106,28 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
         }
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::prelude::ktest;
+    use syscall_names::syscall_name;
+
+    use crate::error::Errno;
+    use crate::process::Process;
+
+    #[ktest]
+    fn unimplemented_syscall_returns_enosys() {
+        crate::progs::init();
+        let process = Process::new(crate::progs::lookup_progs("hello_world").unwrap());
+
+        // There is no `read` here yet, though Linux has one, so it hits the fallback arm.
+        assert_eq!(syscall_name(63), Some("read"));
+        let mut user_context = UserContext::default();
+        user_context.set_a7(63);
+        super::handle_syscall(&mut user_context, &process);
+
+        assert_eq!(user_context.a0() as isize, -(Errno::ENOSYS as isize));
+        assert!(process.exit_code().is_none());
+    }
+}
@@ -0,0 +1,11 @@
+//! What the `#[ktest]`s have in common: processes to run syscalls in.
+
+use alloc::sync::Arc;
+
+use crate::process::Process;
+
+/// A new process that would run the user program `name`.
+pub fn new_process(name: &str) -> Arc<Process> {
+    crate::progs::init();
+    Process::new(crate::progs::lookup_progs(name).unwrap())
+}
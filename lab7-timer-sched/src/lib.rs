@@ -5,6 +5,8 @@
 
 pub mod console;
 mod error;
+#[cfg(ktest)]
+mod ktest_util;
 mod logger;
 mod mm;
 pub mod process;
@@ -1,19 +1,71 @@
+use core::fmt::{self, Write};
+
 use log::{Metadata, Record};
 use ostd::early_println;
+use ostd::mm::{Fallible, FallibleVmWrite, VmReader, VmWriter};
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+use ostd::timer::{Jiffies, TIMER_FREQ};
 use owo_colors::Style;
 
+/// How many log lines the dmesg buffer keeps.
+const DMESG_LINES: usize = 256;
+/// The longest a line in the dmesg buffer gets; the rest is cut off.
+const DMESG_LINE_LEN: usize = 120;
+/// How many records one call site may log in a [`RATE_LIMIT_WINDOW`]; the
+/// rest are suppressed.
+const RATE_LIMIT_BURST: usize = 10;
+/// The length of a rate limiting window, in jiffies.
+const RATE_LIMIT_WINDOW: u64 = TIMER_FREQ;
+/// How many call sites the rate limiter keeps track of at once.
+const RATE_LIMIT_SITES: usize = 32;
+
 struct ColorLogger;
 
 static LOGGER: ColorLogger = ColorLogger;
 
+/// The most recent log lines, for user programs to read back.
+static DMESG: SpinLock<Dmesg, LocalIrqDisabled> = SpinLock::new(Dmesg::new());
+
+/// How much each call site has logged lately, to keep one in a tight loop
+/// from flooding the console.
+static RATE_LIMIT: SpinLock<RateLimit, LocalIrqDisabled> = SpinLock::new(RateLimit::new());
+
 impl log::Log for ColorLogger {
     fn enabled(&self, _metadata: &Metadata) -> bool {
         true
     }
 
     fn log(&self, record: &Record) {
+        self.log_at(record, Jiffies::elapsed().as_u64());
+    }
+
+    fn flush(&self) {}
+}
+
+impl ColorLogger {
+    /// Logs `record`, unless its call site has used up its burst in the
+    /// window around `now`.
+    ///
+    /// The first record a call site logs in a new window is preceded by how
+    /// many were suppressed in the last one.
+    fn log_at(&self, record: &Record, now: u64) {
+        // Records made up at run time have no call site to tell apart.
+        if let (Some(file), Some(line)) = (record.file_static(), record.line()) {
+            match RATE_LIMIT.lock().check(file, line, now) {
+                Verdict::Log { suppressed: 0 } => {}
+                Verdict::Log { suppressed } => self.emit(
+                    record.level(),
+                    format_args!("... ({} suppressed)", suppressed),
+                ),
+                Verdict::Suppress => return,
+            }
+        }
+        self.emit(record.level(), *record.args());
+    }
+
+    fn emit(&self, level: log::Level, args: fmt::Arguments) {
         let record_style = Style::new().default_color();
-        let level_style = match record.level() {
+        let level_style = match level {
             log::Level::Error => Style::new().red(),
             log::Level::Warn => Style::new().bright_yellow(),
             log::Level::Info => Style::new().blue(),
@@ -23,14 +75,355 @@ impl log::Log for ColorLogger {
 
         early_println!(
             "{} {}",
-            level_style.style(format_args!("[{:<5}]", record.level())),
-            record_style.style(record.args())
+            level_style.style(format_args!("[{:<5}]", level)),
+            record_style.style(args)
         );
+        DMESG.lock().push(format_args!("[{:<5}] {}", level, args));
     }
-
-    fn flush(&self) {}
 }
 
 pub(super) fn init() {
     ostd::logger::inject_logger(&LOGGER);
 }
+
+/// Copies the kernel log lines, oldest first and one per line, to `writer`.
+///
+/// Returns the number of bytes written, which stops short when `writer` is
+/// full or faults.
+pub fn read_dmesg(writer: &mut VmWriter<Fallible>) -> usize {
+    DMESG.lock().read(writer)
+}
+
+/// Like [`read_dmesg`], then forgets all the lines kept, under one lock so no
+/// line logged in between is forgotten unread.
+pub fn read_and_clear_dmesg(writer: &mut VmWriter<Fallible>) -> usize {
+    let mut dmesg = DMESG.lock();
+    let written = dmesg.read(writer);
+    dmesg.clear();
+    written
+}
+
+/// The number of bytes [`read_dmesg`] would write to a big enough buffer.
+pub fn dmesg_len() -> usize {
+    DMESG.lock().len()
+}
+
+/// Like [`read_dmesg`], but only copies the lines that no call to this has
+/// copied before, and marks them read.
+///
+/// A line cut short by a full `writer` counts as read.
+pub fn read_dmesg_unread(writer: &mut VmWriter<Fallible>) -> usize {
+    DMESG.lock().read_unread(writer)
+}
+
+/// The number of bytes [`read_dmesg_unread`] would write to a big enough buffer.
+pub fn dmesg_unread_len() -> usize {
+    DMESG.lock().unread_len()
+}
+
+/// Forgets all the log lines kept so far.
+pub fn clear_dmesg() {
+    DMESG.lock().clear();
+}
+
+/// What [`RateLimit::check`] makes of a record.
+enum Verdict {
+    /// Log it, after saying how many records were suppressed before it.
+    Log {
+        suppressed: usize,
+    },
+    Suppress,
+}
+
+/// The records one call site logged in its current window.
+#[derive(Clone, Copy)]
+struct Site {
+    file: &'static str,
+    line: u32,
+    /// The jiffies the window began at.
+    window_start: u64,
+    logged: usize,
+    suppressed: usize,
+}
+
+/// Lets each call site log [`RATE_LIMIT_BURST`] records per
+/// [`RATE_LIMIT_WINDOW`], and counts the ones it suppresses.
+///
+/// Once [`RATE_LIMIT_SITES`] call sites are tracked, a new one takes the
+/// place of the one whose window began longest ago, and what was suppressed
+/// there goes unreported.
+struct RateLimit {
+    sites: [Option<Site>; RATE_LIMIT_SITES],
+}
+
+impl RateLimit {
+    const fn new() -> Self {
+        Self {
+            sites: [None; RATE_LIMIT_SITES],
+        }
+    }
+
+    fn check(&mut self, file: &'static str, line: u32, now: u64) -> Verdict {
+        let slot = match self
+            .sites
+            .iter()
+            .position(|site| site.is_some_and(|site| site.file == file && site.line == line))
+        {
+            Some(index) => &mut self.sites[index],
+            None => {
+                let slot = self
+                    .sites
+                    .iter_mut()
+                    .min_by_key(|site| site.map(|site| site.window_start))
+                    .unwrap();
+                *slot = None;
+                slot
+            }
+        };
+
+        // `now` is read before the lock is taken, so another CPU may have
+        // started a window after it already.
+        let site = match slot {
+            Some(site) if now.saturating_sub(site.window_start) < RATE_LIMIT_WINDOW => site,
+            _ => {
+                let suppressed = slot.map_or(0, |site| site.suppressed);
+                *slot = Some(Site {
+                    file,
+                    line,
+                    window_start: now,
+                    logged: 1,
+                    suppressed: 0,
+                });
+                return Verdict::Log { suppressed };
+            }
+        };
+        if site.logged < RATE_LIMIT_BURST {
+            site.logged += 1;
+            Verdict::Log { suppressed: 0 }
+        } else {
+            site.suppressed += 1;
+            Verdict::Suppress
+        }
+    }
+}
+
+/// A log line, cut off at [`DMESG_LINE_LEN`] bytes.
+#[derive(Clone, Copy)]
+struct Line {
+    buf: [u8; DMESG_LINE_LEN],
+    len: usize,
+}
+
+impl Line {
+    const fn new() -> Self {
+        Self {
+            buf: [0; DMESG_LINE_LEN],
+            len: 0,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(DMESG_LINE_LEN - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// A ring of the last [`DMESG_LINES`] log lines; a new line overwrites the
+/// oldest one once it is full.
+struct Dmesg {
+    lines: [Line; DMESG_LINES],
+    /// The slot the next line goes to.
+    head: usize,
+    /// How many slots hold a line.
+    count: usize,
+    /// How many of the newest lines [`Dmesg::read_unread`] has not read yet.
+    unread: usize,
+}
+
+impl Dmesg {
+    const fn new() -> Self {
+        Self {
+            lines: [Line::new(); DMESG_LINES],
+            head: 0,
+            count: 0,
+            unread: 0,
+        }
+    }
+
+    fn push(&mut self, args: fmt::Arguments) {
+        let line = &mut self.lines[self.head];
+        *line = Line::new();
+        // Writing to a `Line` never fails; it truncates.
+        let _ = line.write_fmt(args);
+
+        self.head = (self.head + 1) % DMESG_LINES;
+        self.count = (self.count + 1).min(DMESG_LINES);
+        self.unread = (self.unread + 1).min(DMESG_LINES);
+    }
+
+    /// The lines from the oldest to the newest.
+    fn iter(&self) -> impl Iterator<Item = &Line> {
+        let oldest = (self.head + DMESG_LINES - self.count) % DMESG_LINES;
+        (0..self.count).map(move |i| &self.lines[(oldest + i) % DMESG_LINES])
+    }
+
+    /// The lines [`Dmesg::read_unread`] has not read yet, oldest first.
+    fn iter_unread(&self) -> impl Iterator<Item = &Line> {
+        self.iter().skip(self.count - self.unread)
+    }
+
+    fn len(&self) -> usize {
+        self.iter().map(|line| line.len + 1).sum()
+    }
+
+    fn unread_len(&self) -> usize {
+        self.iter_unread().map(|line| line.len + 1).sum()
+    }
+
+    fn read(&self, writer: &mut VmWriter<Fallible>) -> usize {
+        copy_lines(self.iter(), writer).0
+    }
+
+    fn read_unread(&mut self, writer: &mut VmWriter<Fallible>) -> usize {
+        let (written, lines) = copy_lines(self.iter_unread(), writer);
+        self.unread -= lines;
+        written
+    }
+
+    fn clear(&mut self) {
+        self.count = 0;
+        self.unread = 0;
+    }
+}
+
+/// Copies `lines` to `writer`, one per line, until it is full or faults.
+///
+/// Returns the number of bytes written, and of lines at least partly written.
+fn copy_lines<'a>(
+    lines: impl Iterator<Item = &'a Line>,
+    writer: &mut VmWriter<Fallible>,
+) -> (usize, usize) {
+    let mut written = 0;
+    let mut lines_written = 0;
+    for line in lines {
+        if writer.avail() == 0 {
+            break;
+        }
+        lines_written += 1;
+        for bytes in [line.as_bytes(), b"\n"] {
+            match writer.write_fallible(&mut VmReader::from(bytes)) {
+                Ok(n) => written += n,
+                Err((_, n)) => return (written + n, lines_written),
+            }
+        }
+    }
+    (written, lines_written)
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::vec;
+
+    use ostd::mm::VmWriter;
+    use ostd::prelude::ktest;
+
+    use super::{DMESG, DMESG_LINES, LOGGER, RATE_LIMIT_BURST, RATE_LIMIT_WINDOW};
+
+    #[ktest]
+    fn dmesg_overwrites_the_oldest_lines() {
+        let mut dmesg = DMESG.lock();
+        for i in 0..DMESG_LINES + 10 {
+            dmesg.push(format_args!("line {}", i));
+        }
+
+        let mut buf = vec![0u8; dmesg.len()];
+        let written = dmesg.read(&mut VmWriter::from(&mut buf[..]).to_fallible());
+        assert_eq!(written, buf.len());
+        let text = core::str::from_utf8(&buf).unwrap();
+        assert!(text.starts_with("line 10\nline 11\n"));
+        assert!(text.ends_with(&alloc::format!("line {}\n", DMESG_LINES + 9)));
+
+        // A short buffer gets the oldest bytes.
+        let mut short = [0u8; 4];
+        let written = dmesg.read(&mut VmWriter::from(&mut short[..]).to_fallible());
+        assert_eq!((written, &short), (4, b"line"));
+    }
+
+    #[ktest]
+    fn dmesg_reads_unread_lines_once() {
+        let mut dmesg = DMESG.lock();
+        dmesg.clear();
+        dmesg.push(format_args!("first"));
+        dmesg.push(format_args!("second"));
+
+        let mut buf = [0u8; 64];
+        let mut read_unread = |dmesg: &mut super::Dmesg| {
+            let n = dmesg.read_unread(&mut VmWriter::from(&mut buf[..]).to_fallible());
+            alloc::string::String::from_utf8(buf[..n].to_vec()).unwrap()
+        };
+        assert_eq!(read_unread(&mut dmesg), "first\nsecond\n");
+        assert_eq!((dmesg.unread_len(), read_unread(&mut dmesg).len()), (0, 0));
+
+        dmesg.push(format_args!("third"));
+        assert_eq!(dmesg.unread_len(), "third\n".len());
+        assert_eq!(read_unread(&mut dmesg), "third\n");
+        // Reading the unread lines leaves them for `read`.
+        assert_eq!(dmesg.len(), "first\nsecond\nthird\n".len());
+
+        dmesg.clear();
+        assert_eq!((dmesg.len(), dmesg.unread_len()), (0, 0));
+    }
+
+    #[ktest]
+    fn a_flood_from_one_call_site_is_cut_short() {
+        let flood = |now| {
+            LOGGER.log_at(
+                &log::Record::builder()
+                    .args(format_args!("flood"))
+                    .level(log::Level::Info)
+                    .file_static(Some("flood.rs"))
+                    .line(Some(1))
+                    .build(),
+                now,
+            )
+        };
+        let lines = || {
+            let dmesg = DMESG.lock();
+            dmesg
+                .iter()
+                .map(|line| alloc::string::String::from_utf8(line.as_bytes().to_vec()).unwrap())
+                .collect::<alloc::vec::Vec<_>>()
+        };
+
+        DMESG.lock().clear();
+        for _ in 0..1000 {
+            flood(0);
+        }
+        let logged = lines();
+        assert_eq!(logged.len(), RATE_LIMIT_BURST);
+        assert!(logged.iter().all(|line| line.ends_with("flood")));
+
+        // The next window starts with the count of what the last one dropped.
+        DMESG.lock().clear();
+        flood(RATE_LIMIT_WINDOW);
+        let summary = alloc::format!("... ({} suppressed)", 1000 - RATE_LIMIT_BURST);
+        let logged = lines();
+        assert_eq!(logged.len(), 2);
+        assert!(logged[0].ends_with(&summary), "{}", logged[0]);
+        assert!(logged[1].ends_with("flood"));
+
+        // A CPU that read the time before another one started the window
+        // counts towards that window.
+        DMESG.lock().clear();
+        flood(RATE_LIMIT_WINDOW - 1);
+        assert_eq!(lines().len(), 1);
+    }
+}
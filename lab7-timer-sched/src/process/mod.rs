@@ -22,6 +22,10 @@ use crate::mm::MemorySpace;
 use crate::process::heap::UserHeap;
 use crate::process::status::ProcessStatus;
 pub const USER_STACK_SIZE: usize = 8192 * 1024; // 8MB
+/// The priority a process starts with, the highest one.
+pub const DEFAULT_PRIORITY: usize = 0;
+/// The lowest priority a process can have.
+pub const MAX_PRIORITY: usize = 9;
 
 static PROCESS_TABLE: Mutex<BTreeMap<Pid, Arc<Process>>> = Mutex::new(BTreeMap::new());
 
@@ -41,6 +45,8 @@ pub struct Process {
     pid: Pid,
     /// Process state
     status: ProcessStatus,
+    /// The scheduling priority, where 0 is the highest.
+    priority: AtomicUsize,
     /// The thread of this process
     task: Once<Arc<Task>>,
 
@@ -65,6 +71,7 @@ impl Process {
         let process = Arc::new(Process {
             pid: alloc_pid(),
             status: ProcessStatus::new(),
+            priority: AtomicUsize::new(DEFAULT_PRIORITY),
             task: Once::new(),
             memory_space,
             heap: UserHeap::new(),
@@ -93,6 +100,7 @@ impl Process {
         let child_process = Arc::new(Process {
             pid: alloc_pid(),
             status: ProcessStatus::new(),
+            priority: AtomicUsize::new(self.priority()),
             task: Once::new(),
             memory_space,
             heap: UserHeap::new(),
@@ -185,6 +193,20 @@ impl Process {
         self.pid
     }
 
+    pub fn priority(&self) -> usize {
+        self.priority.load(Ordering::Relaxed)
+    }
+
+    /// Sets the scheduling priority, which takes effect the next time the
+    /// process is enqueued. Fails with `EINVAL` past [`MAX_PRIORITY`].
+    pub fn set_priority(&self, priority: usize) -> Result<()> {
+        if priority > MAX_PRIORITY {
+            return Err(Error::new(Errno::EINVAL));
+        }
+        self.priority.store(priority, Ordering::Relaxed);
+        Ok(())
+    }
+
     pub fn run(&self) {
         self.task.get().unwrap().run();
     }
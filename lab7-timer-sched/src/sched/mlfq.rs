@@ -0,0 +1,275 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{collections::VecDeque, sync::Arc};
+use ostd::{
+    cpu::CpuId,
+    sync::SpinLock,
+    task::{
+        Task, disable_preempt,
+        scheduler::{EnqueueFlags, LocalRunQueue, Scheduler, UpdateFlags},
+    },
+};
+
+use super::rr::{SchedStats, TimeSlice};
+use crate::process::Process;
+
+/// The number of priority queues. Queue 0 runs first.
+const NR_LEVELS: usize = 3;
+/// The time slice of a task in queue 0, in ticks. Each queue below doubles it.
+const BASE_TIME_SLICE: usize = 10;
+
+/// A multilevel feedback queue scheduler.
+///
+/// A task starts in the queue of its process's priority. One that uses up its
+/// time slice is demoted a queue, down to the last one; one that yields or
+/// sleeps before that stays where it is. A task waking from sleep starts over
+/// from its priority's queue.
+///
+/// With demotion turned off, see [`MlfqScheduler::fixed_priority`], it is a
+/// plain priority scheduler.
+pub struct MlfqScheduler {
+    run_queue: SpinLock<MlfqRunQueue>,
+    /// How many times a task has been enqueued.
+    enqueues: AtomicUsize,
+}
+
+impl MlfqScheduler {
+    /// A scheduler that never demotes a task, so the highest priority with a
+    /// task ready always runs, and tasks of one priority take turns.
+    pub fn fixed_priority() -> Self {
+        let scheduler = Self::default();
+        scheduler.run_queue.disable_irq().lock().fixed_priority = true;
+        scheduler
+    }
+
+    pub fn stats(&self) -> SchedStats {
+        let context_switches = self
+            .run_queue
+            .disable_irq()
+            .lock()
+            .context_switches
+            .load(Ordering::Relaxed);
+        SchedStats {
+            enqueues: self.enqueues.load(Ordering::Relaxed) as u64,
+            context_switches: context_switches as u64,
+        }
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn enqueue(&self, runnable: Arc<Task>, _flags: EnqueueFlags) -> Option<CpuId> {
+        self.enqueues.fetch_add(1, Ordering::Relaxed);
+        let mut run_queue = self.run_queue.disable_irq().lock();
+
+        // Kernel tasks have no process, and get the highest priority.
+        let priority = runnable
+            .data()
+            .downcast_ref::<Arc<Process>>()
+            .map(|p| p.priority())
+            .unwrap_or(0);
+
+        run_queue.push(Entity::new(runnable, priority.min(NR_LEVELS - 1)));
+        None
+    }
+
+    fn local_rq_with(&self, f: &mut dyn FnMut(&dyn LocalRunQueue<Task>)) {
+        let _guard = disable_preempt();
+        let rq = self.run_queue.disable_irq().lock();
+        f(&*rq)
+    }
+
+    fn mut_local_rq_with(&self, f: &mut dyn FnMut(&mut dyn LocalRunQueue<Task>)) {
+        let _guard = disable_preempt();
+        let mut rq = self.run_queue.disable_irq().lock();
+        f(&mut *rq)
+    }
+}
+
+impl Default for MlfqScheduler {
+    fn default() -> Self {
+        Self {
+            run_queue: SpinLock::new(MlfqRunQueue::default()),
+            enqueues: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[derive(Default)]
+struct MlfqRunQueue {
+    current: Option<Entity>,
+    queues: [VecDeque<Entity>; NR_LEVELS],
+    /// How many times another task has been picked to run.
+    context_switches: AtomicUsize,
+    /// Whether tasks stay in their queue when their time slice is up.
+    fixed_priority: bool,
+}
+
+impl MlfqRunQueue {
+    fn push(&mut self, entity: Entity) {
+        self.queues[entity.level].push_back(entity);
+    }
+
+    /// The highest queue with a task in it.
+    fn highest_level(&self) -> Option<usize> {
+        self.queues.iter().position(|queue| !queue.is_empty())
+    }
+}
+
+impl LocalRunQueue for MlfqRunQueue {
+    fn current(&self) -> Option<&Arc<Task>> {
+        self.current.as_ref().map(|entity| &entity.task)
+    }
+
+    fn update_current(&mut self, flags: UpdateFlags) -> bool {
+        let highest_level = self.highest_level();
+        match flags {
+            UpdateFlags::Tick => {
+                let Some(entity) = self.current.as_mut() else {
+                    return highest_level.is_some();
+                };
+                let expired = entity.time_slice.elapse();
+                if expired && self.fixed_priority {
+                    entity.refill();
+                } else if expired {
+                    entity.demote();
+                }
+                // A task in a higher queue, say one that just woke up, goes
+                // first even if the slice isn't up.
+                highest_level.is_some_and(|level| expired || level < entity.level)
+            }
+            UpdateFlags::Yield => {
+                // Giving up the CPU early earns a fresh slice in the same queue.
+                if let Some(entity) = self.current.as_mut() {
+                    entity.refill();
+                }
+                highest_level.is_some()
+            }
+            _ => highest_level.is_some(),
+        }
+    }
+
+    fn dequeue_current(&mut self) -> Option<Arc<Task>> {
+        self.current.take().map(|entity| entity.task)
+    }
+
+    fn try_pick_next(&mut self) -> Option<&Arc<Task>> {
+        let next = self.queues[self.highest_level()?].pop_front().unwrap();
+        if let Some(current) = self.current.replace(next) {
+            self.push(current);
+        }
+        // The task picked was queued, so it is never the one that was running.
+        self.context_switches.fetch_add(1, Ordering::Relaxed);
+
+        self.current.as_ref().map(|entity| &entity.task)
+    }
+}
+
+struct Entity {
+    task: Arc<Task>,
+    level: usize,
+    time_slice: TimeSlice,
+}
+
+impl Entity {
+    fn new(task: Arc<Task>, level: usize) -> Self {
+        Self {
+            task,
+            level,
+            time_slice: TimeSlice::new(BASE_TIME_SLICE << level),
+        }
+    }
+
+    /// Moves the task a queue down, with a new, longer time slice.
+    fn demote(&mut self) {
+        self.level = (self.level + 1).min(NR_LEVELS - 1);
+        self.refill();
+    }
+
+    /// Starts a new time slice for the queue the task is in.
+    fn refill(&mut self) {
+        self.time_slice = TimeSlice::new(BASE_TIME_SLICE << self.level);
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+
+    use ostd::prelude::ktest;
+    use ostd::task::scheduler::{EnqueueFlags, LocalRunQueue, Scheduler, UpdateFlags};
+    use ostd::task::{Task, TaskOptions};
+
+    use super::{BASE_TIME_SLICE, Entity, MlfqRunQueue, MlfqScheduler, NR_LEVELS};
+
+    #[ktest]
+    fn cpu_bound_task_sinks_to_the_lowest_queue() {
+        let cpu_bound = Arc::new(TaskOptions::new(|| {}).build().unwrap());
+        let interactive = Arc::new(TaskOptions::new(|| {}).build().unwrap());
+        let is_cpu_bound = |task: &Arc<Task>| Arc::ptr_eq(task, &cpu_bound);
+
+        let mut rq = MlfqRunQueue::default();
+        rq.push(Entity::new(cpu_bound.clone(), 0));
+        rq.push(Entity::new(interactive.clone(), 0));
+
+        for _ in 0..100 {
+            let current = rq.try_pick_next().unwrap().clone();
+            if is_cpu_bound(&current) {
+                // Runs until its time slice is up, or the other task is in a
+                // higher queue.
+                while !rq.update_current(UpdateFlags::Tick) {}
+            } else {
+                // Yields after a tick.
+                assert!(!rq.update_current(UpdateFlags::Tick));
+                assert!(rq.update_current(UpdateFlags::Yield));
+            }
+        }
+
+        let current = rq.current.as_ref().unwrap();
+        let queued = rq.queues.iter().flatten();
+        for entity in queued.chain(core::iter::once(current)) {
+            let expected = if is_cpu_bound(&entity.task) {
+                NR_LEVELS - 1
+            } else {
+                0
+            };
+            assert_eq!(entity.level, expected);
+        }
+    }
+
+    #[ktest]
+    fn fixed_priority_never_demotes() {
+        let cpu_bound = Arc::new(TaskOptions::new(|| {}).build().unwrap());
+        let mut rq = MlfqRunQueue {
+            fixed_priority: true,
+            ..Default::default()
+        };
+        rq.push(Entity::new(cpu_bound, 0));
+        rq.try_pick_next().unwrap();
+
+        for _ in 0..10 * BASE_TIME_SLICE {
+            rq.update_current(UpdateFlags::Tick);
+        }
+        assert_eq!(rq.current.as_ref().unwrap().level, 0);
+    }
+
+    #[ktest]
+    fn stats_count_enqueues_and_context_switches() {
+        let scheduler = MlfqScheduler::default();
+        for _ in 0..2 {
+            let task = Arc::new(TaskOptions::new(|| {}).build().unwrap());
+            scheduler.enqueue(task, EnqueueFlags::Spawn);
+        }
+
+        scheduler.mut_local_rq_with(&mut |rq| {
+            // The second pick puts the first task back in its queue, and the
+            // third finds it there.
+            for _ in 0..3 {
+                rq.try_pick_next().unwrap();
+            }
+        });
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.enqueues, 2);
+        assert_eq!(stats.context_switches, 3);
+    }
+}
@@ -1,21 +1,170 @@
 mod fifo;
+mod mlfq;
 mod rr;
 
 use alloc::boxed::Box;
+use alloc::sync::Arc;
 use fifo::FifoScheduler;
-use ostd::task::scheduler::inject_scheduler;
+use log::{info, warn};
+use mlfq::MlfqScheduler;
+use ostd::task::scheduler::{Scheduler, inject_scheduler};
+use ostd::task::{Task, TaskOptions};
 use rr::RrScheduler;
+use spin::Once;
 
-const USE_RR_SCHEDULER: bool = false;
+pub use rr::SchedStats;
 
-pub fn init() {
-    if USE_RR_SCHEDULER {
-        let rr_scheduler = Box::new(RrScheduler::default());
-        inject_scheduler(Box::leak(rr_scheduler));
-    } else {
-        let fifo_scheduler = Box::new(FifoScheduler::default());
-        inject_scheduler(Box::leak(fifo_scheduler));
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SchedulerKind {
+    Fifo,
+    Rr,
+    Mlfq,
+    /// The multilevel feedback queue scheduler with demotion turned off, so
+    /// every task stays at the priority of its process.
+    Prio,
+}
+
+/// The scheduler to use when the kernel command line does not name one.
+const DEFAULT_SCHEDULER: SchedulerKind = SchedulerKind::Fifo;
+
+impl SchedulerKind {
+    /// The scheduler named by `sched=fifo|rr|mlfq|prio` on the kernel command line,
+    /// e.g. from `cargo osdk run --kcmd-args="sched=mlfq"`.
+    fn from_cmdline(cmdline: &str) -> Option<Self> {
+        let name = cmdline
+            .split_whitespace()
+            .find_map(|arg| arg.strip_prefix("sched="))?;
+        match name {
+            "fifo" => Some(Self::Fifo),
+            "rr" => Some(Self::Rr),
+            "mlfq" => Some(Self::Mlfq),
+            "prio" => Some(Self::Prio),
+            _ => {
+                warn!(
+                    "unknown scheduler {:?}, using {:?}",
+                    name, DEFAULT_SCHEDULER
+                );
+                None
+            }
+        }
     }
+}
+
+/// The scheduler in use, once [`init`] has picked it.
+static SCHEDULER: Once<&'static KernelScheduler> = Once::new();
+
+pub fn init() {
+    let kind = SchedulerKind::from_cmdline(&ostd::boot::boot_info().kernel_cmdline)
+        .unwrap_or(DEFAULT_SCHEDULER);
+    info!("using the {:?} scheduler", kind);
+    let scheduler: &'static KernelScheduler = Box::leak(Box::new(new_scheduler(kind)));
+    SCHEDULER.call_once(|| scheduler);
+    inject_scheduler(scheduler.as_scheduler());
     ostd::task::scheduler::enable_preemption_on_cpu();
 }
 
+/// One of the schedulers the command line can pick.
+enum KernelScheduler {
+    Fifo(FifoScheduler),
+    Rr(RrScheduler),
+    /// With or without demotion.
+    Mlfq(MlfqScheduler),
+}
+
+impl KernelScheduler {
+    fn as_scheduler(&self) -> &dyn Scheduler {
+        match self {
+            Self::Fifo(fifo_scheduler) => fifo_scheduler,
+            Self::Rr(rr_scheduler) => rr_scheduler,
+            Self::Mlfq(mlfq_scheduler) => mlfq_scheduler,
+        }
+    }
+
+    /// The statistics that the round-robin and multilevel feedback queue
+    /// schedulers keep.
+    fn stats(&self) -> Option<SchedStats> {
+        match self {
+            Self::Fifo(_) => None,
+            Self::Rr(rr_scheduler) => Some(rr_scheduler.stats()),
+            Self::Mlfq(mlfq_scheduler) => Some(mlfq_scheduler.stats()),
+        }
+    }
+}
+
+/// Makes a scheduler of `kind`.
+fn new_scheduler(kind: SchedulerKind) -> KernelScheduler {
+    match kind {
+        SchedulerKind::Fifo => KernelScheduler::Fifo(FifoScheduler::default()),
+        SchedulerKind::Rr => {
+            let rr_scheduler = RrScheduler::default();
+            rr_scheduler.set_idle_task(idle_task());
+            KernelScheduler::Rr(rr_scheduler)
+        }
+        SchedulerKind::Mlfq => KernelScheduler::Mlfq(MlfqScheduler::default()),
+        SchedulerKind::Prio => KernelScheduler::Mlfq(MlfqScheduler::fixed_priority()),
+    }
+}
+
+/// A task that halts the CPU until an interrupt, over and over, for when
+/// there is nothing else to run.
+fn idle_task() -> Arc<Task> {
+    let idle_task = TaskOptions::new(|| {
+        loop {
+            ostd::task::halt_cpu();
+        }
+    })
+    .build()
+    .unwrap();
+    Arc::new(idle_task)
+}
+
+/// Returns the statistics of the scheduler, which the round-robin and
+/// multilevel feedback queue ones keep.
+pub fn stats() -> Option<SchedStats> {
+    SCHEDULER.get().and_then(|scheduler| scheduler.stats())
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+
+    use ostd::prelude::ktest;
+    use ostd::task::TaskOptions;
+    use ostd::task::scheduler::EnqueueFlags;
+
+    use super::{SchedulerKind, new_scheduler};
+
+    #[ktest]
+    fn scheduler_is_picked_from_the_command_line() {
+        let kind = SchedulerKind::from_cmdline;
+        assert_eq!(kind("console=ttyS0 sched=mlfq"), Some(SchedulerKind::Mlfq));
+        assert_eq!(kind("sched=rr quiet"), Some(SchedulerKind::Rr));
+        assert_eq!(kind("sched=fifo"), Some(SchedulerKind::Fifo));
+        assert_eq!(kind("sched=prio"), Some(SchedulerKind::Prio));
+        assert_eq!(kind("sched=lottery"), None);
+        assert_eq!(kind(""), None);
+    }
+
+    #[ktest]
+    fn mlfq_keeps_the_stats_once_picked() {
+        // Not the running scheduler, so tasks are only handed to it directly.
+        let kind = SchedulerKind::from_cmdline("console=ttyS0 sched=mlfq").unwrap();
+        let scheduler = new_scheduler(kind);
+        assert!(
+            scheduler.stats().is_some(),
+            "the MLFQ scheduler keeps stats"
+        );
+
+        for _ in 0..2 {
+            let task = Arc::new(TaskOptions::new(|| {}).build().unwrap());
+            scheduler.as_scheduler().enqueue(task, EnqueueFlags::Spawn);
+        }
+        scheduler.as_scheduler().mut_local_rq_with(&mut |rq| {
+            rq.try_pick_next().unwrap();
+        });
+
+        let stats = scheduler.stats().unwrap();
+        assert_eq!(stats.enqueues, 2);
+        assert_eq!(stats.context_switches, 1);
+    }
+}
@@ -1,5 +1,8 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use alloc::{collections::vec_deque::VecDeque, sync::Arc};
 use ostd::{
+    Pod,
     cpu::CpuId,
     sync::SpinLock,
     task::{
@@ -8,28 +11,67 @@ use ostd::{
     },
 };
 
-use crate::process::Process;
+use crate::process::{DEFAULT_PRIORITY, MAX_PRIORITY, Process};
+
+/// The ticks added to the time slice for each priority level.
+const TIME_SLICE_PER_PRIORITY: usize = 10;
 
 pub struct RrScheduler {
     run_queue: SpinLock<RrRunQueue>,
+    /// How many times a task has been enqueued.
+    enqueues: AtomicUsize,
+}
+
+/// What the scheduler has done so far, as `SYS_SCHED_STATS` reports it.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct SchedStats {
+    pub enqueues: u64,
+    pub context_switches: u64,
+}
+
+impl RrScheduler {
+    /// Sets the task to run when no other task is runnable.
+    ///
+    /// The idle task is never enqueued, and so never shows up in [`Self::stats`].
+    pub fn set_idle_task(&self, idle_task: Arc<Task>) {
+        self.run_queue.disable_irq().lock().idle_task = Some(idle_task);
+    }
+
+    pub fn stats(&self) -> SchedStats {
+        let context_switches = self
+            .run_queue
+            .disable_irq()
+            .lock()
+            .context_switches
+            .load(Ordering::Relaxed);
+        SchedStats {
+            enqueues: self.enqueues.load(Ordering::Relaxed) as u64,
+            context_switches: context_switches as u64,
+        }
+    }
 }
 
 impl Scheduler for RrScheduler {
     fn enqueue(&self, runnable: Arc<Task>, _flags: EnqueueFlags) -> Option<CpuId> {
+        self.enqueues.fetch_add(1, Ordering::Relaxed);
         let mut run_queue = self.run_queue.disable_irq().lock();
         
-        // Get PID from task data
-        let pid = runnable
+        // Get the priority from task data
+        let priority = runnable
             .data()
             .downcast_ref::<Arc<Process>>()
-            .map(|p| p.pid())
-            .unwrap_or(1); // Default to 1 if not a process (e.g., kernel task)
+            .map(|p| p.priority())
+            .unwrap_or(DEFAULT_PRIORITY); // Kernel tasks have no process
 
+        // Higher priorities get longer time slices.
         run_queue.entities.push_back(Entity {
             task: runnable,
-            time_slice: TimeSlice::new(pid * 10),
+            time_slice: TimeSlice::new((MAX_PRIORITY + 1 - priority) * TIME_SLICE_PER_PRIORITY),
         });
-        None
+
+        // Nothing was running but the idle task, so the new task goes now.
+        run_queue.is_idle().then(CpuId::current_racy)
     }
 
     fn local_rq_with(&self, f: &mut dyn FnMut(&dyn LocalRunQueue<Task>)) {
@@ -49,6 +91,7 @@ impl Default for RrScheduler {
     fn default() -> Self {
         Self {
             run_queue: SpinLock::new(RrRunQueue::default()),
+            enqueues: AtomicUsize::new(0),
         }
     }
 }
@@ -57,6 +100,20 @@ impl Default for RrScheduler {
 struct RrRunQueue {
     current: Option<Entity>,
     entities: VecDeque<Entity>,
+    /// How many times another task has been picked to run.
+    context_switches: AtomicUsize,
+    /// The task that runs when there is nothing else to.
+    idle_task: Option<Arc<Task>>,
+}
+
+impl RrRunQueue {
+    /// Whether the idle task is the one running.
+    fn is_idle(&self) -> bool {
+        match (&self.current, &self.idle_task) {
+            (Some(current), Some(idle_task)) => Arc::ptr_eq(&current.task, idle_task),
+            _ => false,
+        }
+    }
 }
 
 impl LocalRunQueue for RrRunQueue {
@@ -67,6 +124,9 @@ impl LocalRunQueue for RrRunQueue {
     fn update_current(&mut self, flags: ostd::task::scheduler::UpdateFlags) -> bool {
         match flags {
             ostd::task::scheduler::UpdateFlags::Tick => {
+                if self.is_idle() {
+                    return !self.entities.is_empty();
+                }
                 let Some(entity) = self.current.as_mut() else {
                     return false;
                 };
@@ -81,9 +141,29 @@ impl LocalRunQueue for RrRunQueue {
     }
 
     fn try_pick_next(&mut self) -> Option<&Arc<Task>> {
-        if let Some(current_task) = self.current.replace(self.entities.pop_front()?) {
-            self.entities.push_back(current_task);
+        let Some(next) = self.entities.pop_front() else {
+            // Rather than leave the CPU with nothing to run, fall back to the
+            // idle task. It does not count as a context switch.
+            if self.current.is_none() {
+                let idle_task = self.idle_task.clone()?;
+                self.current = Some(Entity {
+                    task: idle_task,
+                    time_slice: TimeSlice::default(),
+                });
+                return self.current.as_ref().map(|entity| &entity.task);
+            }
+            return None;
+        };
+
+        let was_idle = self.is_idle();
+        if let Some(current_task) = self.current.replace(next) {
+            // The idle task is not queued; it is there whenever the queue is empty.
+            if !was_idle {
+                self.entities.push_back(current_task);
+            }
         }
+        // The task picked was queued, so it is never the one that was running.
+        self.context_switches.fetch_add(1, Ordering::Relaxed);
 
         self.current.as_ref().map(|entity| &entity.task)
     }
@@ -95,25 +175,103 @@ struct Entity {
 }
 
 #[derive(Default)]
-struct TimeSlice {
+pub(super) struct TimeSlice {
     tick: usize,
     max_tick: usize,
 }
 
 impl TimeSlice {
-    fn new(max_tick: usize) -> Self {
+    pub(super) fn new(max_tick: usize) -> Self {
         Self {
             tick: 0,
             max_tick,
         }
     }
 
-    fn elapse(&mut self) -> bool {
+    /// Counts one tick against the slice. Returns `true` on the tick that
+    /// uses it up, after which a new slice starts.
+    ///
+    /// A slice of `max_tick == 0` never runs out.
+    pub(super) fn elapse(&mut self) -> bool {
         if self.max_tick == 0 {
             return false;
         }
-        self.tick = (self.tick + 1) % self.max_tick;
+        self.tick += 1;
+        if self.tick < self.max_tick {
+            return false;
+        }
+
+        self.tick = 0;
+        true
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::Ordering;
+
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+
+    use ostd::prelude::ktest;
+    use ostd::task::TaskOptions;
+    use ostd::task::scheduler::{LocalRunQueue, UpdateFlags};
+
+    use super::{Entity, RrRunQueue, TimeSlice};
+
+    /// Returns the ticks, counting from 1, on which `elapse` says the slice
+    /// ran out, over the first `ticks` ticks.
+    fn expiries(max_tick: usize, ticks: usize) -> Vec<usize> {
+        let mut time_slice = TimeSlice::new(max_tick);
+        (1..=ticks).filter(|_| time_slice.elapse()).collect()
+    }
+
+    #[ktest]
+    fn time_slice_of_one_tick() {
+        assert_eq!(expiries(1, 3), [1, 2, 3]);
+    }
+
+    #[ktest]
+    fn time_slice_of_two_ticks() {
+        assert_eq!(expiries(2, 6), [2, 4, 6]);
+    }
+
+    #[ktest]
+    fn time_slice_of_ten_ticks() {
+        assert_eq!(expiries(10, 25), [10, 20]);
+    }
+
+    #[ktest]
+    fn empty_queue_picks_the_idle_task() {
+        let idle_task = Arc::new(TaskOptions::new(|| {}).build().unwrap());
+        let task = Arc::new(TaskOptions::new(|| {}).build().unwrap());
+        let mut rq = RrRunQueue {
+            idle_task: Some(idle_task.clone()),
+            ..Default::default()
+        };
+
+        let next = rq.try_pick_next().unwrap();
+        assert!(Arc::ptr_eq(next, &idle_task));
+        // Nothing to preempt it for yet.
+        assert!(!rq.update_current(UpdateFlags::Tick));
+
+        // A real task takes over on the next tick, and the idle task is not
+        // left in the queue.
+        rq.entities.push_back(Entity {
+            task: task.clone(),
+            time_slice: TimeSlice::new(10),
+        });
+        assert!(rq.update_current(UpdateFlags::Tick));
+        let next = rq.try_pick_next().unwrap();
+        assert!(Arc::ptr_eq(next, &task));
+        assert!(rq.entities.is_empty());
+        assert_eq!(rq.context_switches.load(Ordering::Relaxed), 1);
 
-        self.tick == 0
+        // Once that task blocks, the idle task is back.
+        assert!(rq.update_current(UpdateFlags::Wait));
+        rq.dequeue_current();
+        let next = rq.try_pick_next().unwrap();
+        assert!(Arc::ptr_eq(next, &idle_task));
+        assert_eq!(rq.context_switches.load(Ordering::Relaxed), 1);
     }
 }
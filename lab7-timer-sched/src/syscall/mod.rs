@@ -4,16 +4,19 @@ mod exec;
 mod exit;
 mod prlimit;
 mod read;
+mod sched;
+mod syslog;
 mod time;
 mod uname;
 mod wait4;
 mod write;
 
 use alloc::sync::Arc;
-use log::{debug, info};
+use log::{debug, info, warn};
 use ostd::arch::cpu::context::UserContext;
 use ostd::arch::qemu::exit_qemu;
 use ostd::task::Task;
+use syscall_names::syscall_name;
 
 use crate::error::{Errno, Error, Result};
 use crate::process::Process;
@@ -23,6 +26,8 @@ use crate::syscall::exec::sys_execve;
 use crate::syscall::exit::sys_exit;
 use crate::syscall::prlimit::sys_prlimit64;
 use crate::syscall::read::sys_read;
+use crate::syscall::sched::{sys_sched_getparam, sys_sched_setscheduler, sys_sched_stats};
+use crate::syscall::syslog::sys_syslog;
 use crate::syscall::time::sys_clock_gettime;
 use crate::syscall::uname::sys_uname;
 use crate::syscall::wait4::sys_wait4;
@@ -37,6 +42,9 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
     const SYS_EXIT: usize = 93;
 
     const SYS_CLOCK_GETTIME: usize = 113;
+    const SYS_SYSLOG: usize = 116;
+    const SYS_SCHED_SETSCHEDULER: usize = 120;
+    const SYS_SCHED_GETPARAM: usize = 121;
     const SYS_SCHED_YIELD: usize = 124;
     const SYS_REBOOT: usize = 142;
     const SYS_NEWUNAME: usize = 160;
@@ -48,6 +56,8 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
     const SYS_MPROTECT: usize = 226;
     const SYS_WAIT4: usize = 260;
     const SYS_PRLIMIT64: usize = 261;
+    /// Not a Linux syscall: reports the scheduler statistics.
+    const SYS_SCHED_STATS: usize = 1001;
 
     let args = [
         user_context.a0(),
@@ -110,8 +120,14 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
             current_process,
         ),
         SYS_CLOCK_GETTIME => sys_clock_gettime(args[0] as _, args[1] as _, current_process),
+        SYS_SYSLOG => sys_syslog(args[0] as _, args[1] as _, args[2] as _, current_process),
         SYS_REBOOT => exit_qemu(ostd::arch::qemu::QemuExitCode::Success),
         SYS_READ => sys_read(args[0] as _, args[1] as _, args[2] as _, current_process),
+        SYS_SCHED_SETSCHEDULER => {
+            sys_sched_setscheduler(args[0] as _, args[1] as _, args[2] as _, current_process)
+        }
+        SYS_SCHED_GETPARAM => sys_sched_getparam(args[0] as _, args[1] as _, current_process),
+        SYS_SCHED_STATS => sys_sched_stats(args[0] as _, current_process),
         SYS_SCHED_YIELD => {
             Task::yield_now();
             Ok(SyscallReturn(0))
@@ -119,7 +135,15 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
 
         SYS_WRITE => sys_write(args[0] as _, args[1] as _, args[2] as _, current_process),
         SYS_EXIT => sys_exit(args[0] as _, current_process),
-        _ => Err(Error::new(Errno::ENOSYS)),
+        _ => {
+            warn!(
+                "[pid: {}] unimplemented syscall num: {} ({})",
+                current_process.pid(),
+                user_context.a7(),
+                syscall_name(user_context.a7()).unwrap_or("unknown")
+            );
+            Err(Error::new(Errno::ENOSYS))
+        }
     };
 
     match ret {
@@ -135,3 +159,27 @@ pub fn handle_syscall(user_context: &mut UserContext, current_process: &Arc<Proc
         }
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::arch::cpu::context::UserContext;
+    use ostd::prelude::ktest;
+    use syscall_names::syscall_name;
+
+    use crate::error::Errno;
+    use crate::ktest_util::new_process;
+
+    #[ktest]
+    fn unimplemented_syscall_returns_enosys() {
+        let process = new_process("hello_world");
+
+        // There are no files here yet, so `openat` hits the fallback arm.
+        assert_eq!(syscall_name(56), Some("openat"));
+        let mut user_context = UserContext::default();
+        user_context.set_a7(56);
+        super::handle_syscall(&mut user_context, &process);
+
+        assert_eq!(user_context.a0() as isize, -(Errno::ENOSYS as isize));
+        assert!(process.exit_code().is_none());
+    }
+}
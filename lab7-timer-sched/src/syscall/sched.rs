@@ -0,0 +1,100 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::{Pod, mm::Vaddr};
+
+use super::SyscallReturn;
+use crate::error::{Errno, Error, Result};
+use crate::process::Process;
+use crate::sched;
+
+/// The scheduling policies a process may ask for. All of them are scheduled
+/// the same way, by priority alone.
+const SCHED_OTHER: i32 = 0;
+const SCHED_FIFO: i32 = 1;
+const SCHED_RR: i32 = 2;
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct SchedParam {
+    sched_priority: i32,
+}
+
+pub fn sys_sched_setscheduler(
+    pid: i32,
+    policy: i32,
+    param_addr: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_SCHED_SETSCHEDULER] pid: {}, policy: {}, param_addr: {:#x}",
+        pid, policy, param_addr
+    );
+
+    check_pid(pid, current_process)?;
+    if !matches!(policy, SCHED_OTHER | SCHED_FIFO | SCHED_RR) {
+        return Err(Error::new(Errno::EINVAL));
+    }
+
+    let param = current_process
+        .memory_space()
+        .vm_space()
+        .reader(param_addr, size_of::<SchedParam>())
+        .and_then(|mut reader| reader.read_val::<SchedParam>())
+        .map_err(|_| Error::new(Errno::EFAULT))?;
+    let priority = usize::try_from(param.sched_priority).map_err(|_| Error::new(Errno::EINVAL))?;
+    current_process.set_priority(priority)?;
+
+    Ok(SyscallReturn(0))
+}
+
+pub fn sys_sched_getparam(
+    pid: i32,
+    param_addr: Vaddr,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_SCHED_GETPARAM] pid: {}, param_addr: {:#x}",
+        pid, param_addr
+    );
+
+    check_pid(pid, current_process)?;
+    let param = SchedParam {
+        sched_priority: current_process.priority() as i32,
+    };
+    current_process
+        .memory_space()
+        .vm_space()
+        .writer(param_addr, size_of::<SchedParam>())
+        .and_then(|mut writer| writer.write_val(&param))
+        .map_err(|_| Error::new(Errno::EFAULT))?;
+
+    Ok(SyscallReturn(0))
+}
+
+/// Writes the statistics of the scheduler to `stats_addr`, as a `SchedStats`.
+/// Fails with `ENOSYS` if the scheduler in use keeps none.
+pub fn sys_sched_stats(stats_addr: Vaddr, current_process: &Arc<Process>) -> Result<SyscallReturn> {
+    debug!("[SYS_SCHED_STATS] stats_addr: {:#x}", stats_addr);
+
+    let stats = sched::stats().ok_or(Error::new(Errno::ENOSYS))?;
+    current_process
+        .memory_space()
+        .vm_space()
+        .writer(stats_addr, size_of::<sched::SchedStats>())
+        .and_then(|mut writer| writer.write_val(&stats))
+        .map_err(|_| Error::new(Errno::EFAULT))?;
+
+    Ok(SyscallReturn(0))
+}
+
+/// Only a process's own scheduling parameters can be looked at or changed,
+/// by pid 0 or its own pid.
+fn check_pid(pid: i32, current_process: &Arc<Process>) -> Result<()> {
+    if pid < 0 {
+        return Err(Error::new(Errno::EINVAL));
+    }
+    if pid != 0 && pid as usize != current_process.pid() {
+        return Err(Error::new(Errno::EPERM));
+    }
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use alloc::sync::Arc;
+use log::debug;
+use ostd::mm::{Fallible, Vaddr, VmWriter};
+
+use super::SyscallReturn;
+use crate::error::{Errno, Error, Result};
+use crate::logger::{
+    clear_dmesg, dmesg_len, dmesg_unread_len, read_and_clear_dmesg, read_dmesg, read_dmesg_unread,
+};
+use crate::process::Process;
+
+/// Reads the kernel log lines that no `SYSLOG_ACTION_READ` has read yet.
+const SYSLOG_ACTION_READ: i32 = 2;
+/// Reads all the kernel log lines kept, leaving them in place.
+const SYSLOG_ACTION_READ_ALL: i32 = 3;
+/// Reads all the kernel log lines kept, then forgets them.
+const SYSLOG_ACTION_READ_CLEAR: i32 = 4;
+/// Forgets all the kernel log lines kept.
+const SYSLOG_ACTION_CLEAR: i32 = 5;
+/// Returns the size of what `SYSLOG_ACTION_READ` would read.
+const SYSLOG_ACTION_SIZE_UNREAD: i32 = 9;
+/// Returns the size of what `SYSLOG_ACTION_READ_ALL` would read.
+const SYSLOG_ACTION_SIZE_BUFFER: i32 = 10;
+
+pub fn sys_syslog(
+    action: i32,
+    buf_addr: Vaddr,
+    len: i32,
+    current_process: &Arc<Process>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[SYS_SYSLOG] action: {}, buf_addr: {:#x}, len: {}",
+        action, buf_addr, len
+    );
+
+    let read = |read_lines: fn(&mut VmWriter<Fallible>) -> usize| {
+        if len < 0 {
+            return Err(Error::new(Errno::EINVAL));
+        }
+        if len == 0 {
+            return Ok(SyscallReturn(0));
+        }
+        let mut writer = current_process
+            .memory_space()
+            .vm_space()
+            .writer(buf_addr, len as usize)
+            .map_err(|_| Error::new(Errno::EFAULT))?;
+        Ok(SyscallReturn(read_lines(&mut writer) as _))
+    };
+
+    match action {
+        // Nothing to read returns at once; Linux would wait for a new line.
+        SYSLOG_ACTION_READ => read(read_dmesg_unread),
+        SYSLOG_ACTION_READ_ALL => read(read_dmesg),
+        SYSLOG_ACTION_READ_CLEAR => read(read_and_clear_dmesg),
+        SYSLOG_ACTION_CLEAR => {
+            clear_dmesg();
+            Ok(SyscallReturn(0))
+        }
+        SYSLOG_ACTION_SIZE_UNREAD => Ok(SyscallReturn(dmesg_unread_len() as _)),
+        SYSLOG_ACTION_SIZE_BUFFER => Ok(SyscallReturn(dmesg_len() as _)),
+        _ => Err(Error::new(Errno::EINVAL)),
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::vec;
+
+    use log::{Level, Record};
+    use ostd::mm::{FallibleVmRead, PAGE_SIZE, PageFlags, VmWriter};
+    use ostd::prelude::ktest;
+
+    use super::{
+        SYSLOG_ACTION_READ, SYSLOG_ACTION_READ_ALL, SYSLOG_ACTION_READ_CLEAR,
+        SYSLOG_ACTION_SIZE_BUFFER, SYSLOG_ACTION_SIZE_UNREAD, sys_syslog,
+    };
+    use crate::ktest_util::new_process;
+    use crate::mm::VmMapping;
+    use crate::syscall::SyscallReturn;
+
+    #[ktest]
+    fn syslog_reads_the_log_into_a_user_buffer() {
+        crate::logger::init();
+        let process = new_process("hello_world");
+        let buf = 0x1000_0000;
+        process
+            .memory_space()
+            .map(VmMapping::new(buf, 1, PageFlags::RW));
+        let vm_space = process.memory_space().vm_space();
+        vm_space.activate();
+
+        let syslog = |action: i32| {
+            let SyscallReturn(ret) = sys_syslog(action, buf, PAGE_SIZE as _, &process).unwrap();
+            ret as usize
+        };
+        let read = |action: i32| {
+            let mut bytes = vec![0u8; syslog(action)];
+            vm_space
+                .reader(buf, bytes.len())
+                .unwrap()
+                .read_fallible(&mut VmWriter::from(bytes.as_mut_slice()))
+                .unwrap();
+            alloc::string::String::from_utf8(bytes).unwrap()
+        };
+
+        // Start from an empty log.
+        read(SYSLOG_ACTION_READ_CLEAR);
+        for i in 0..3 {
+            // Straight to the logger, past the level filter of the `log` macros.
+            log::logger().log(
+                &Record::builder()
+                    .args(format_args!("syslog test {}", i))
+                    .level(Level::Info)
+                    .build(),
+            );
+        }
+
+        let size = syslog(SYSLOG_ACTION_SIZE_UNREAD);
+        let text = read(SYSLOG_ACTION_READ);
+        assert_eq!(text.len(), size);
+        let lines: alloc::vec::Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        for (line, i) in lines.iter().zip(0..) {
+            assert!(
+                line.ends_with(&alloc::format!("syslog test {}", i)),
+                "{}",
+                line
+            );
+        }
+
+        assert_eq!(syslog(SYSLOG_ACTION_SIZE_UNREAD), 0);
+        assert_eq!(syslog(SYSLOG_ACTION_SIZE_BUFFER), size);
+        assert_eq!(read(SYSLOG_ACTION_READ_ALL), text);
+        assert_eq!(read(SYSLOG_ACTION_READ_CLEAR), text);
+        assert_eq!(syslog(SYSLOG_ACTION_SIZE_BUFFER), 0);
+    }
+}
@@ -4,8 +4,19 @@
 #include <sys/types.h>
 #include <sys/wait.h>
 #include <sys/reboot.h>
+#include <sys/syscall.h>
 #include <stdio.h>
 
+// Not a Linux syscall: fills in a `struct sched_stats`, if the scheduler keeps
+// statistics.
+#define SYS_SCHED_STATS 1001
+
+struct sched_stats
+{
+    unsigned long enqueues;
+    unsigned long context_switches;
+};
+
 int main()
 {
     // Disable buffer in STDOUT
@@ -29,6 +40,12 @@ int main()
         }
         if (child_pid == pid)
         {
+            struct sched_stats stats;
+            if (syscall(SYS_SCHED_STATS, &stats) == 0)
+            {
+                printf("[INIT] Enqueues: %lu, context switches: %lu\n",
+                       stats.enqueues, stats.context_switches);
+            }
             printf("[INIT] Shell process exited, exiting system...\n");
             reboot(RB_POWER_OFF);
         }
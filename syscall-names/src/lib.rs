@@ -0,0 +1,86 @@
+//! The Linux names of the riscv64 syscall numbers, shared by the labs'
+//! dispatchers to report syscalls that they do not implement.
+
+#![no_std]
+
+/// Returns the Linux name of a riscv64 syscall number, if it has one.
+pub fn syscall_name(syscall_num: usize) -> Option<&'static str> {
+    let name = match syscall_num {
+        17 => "getcwd",
+        23 => "dup",
+        24 => "dup3",
+        25 => "fcntl",
+        29 => "ioctl",
+        34 => "mkdirat",
+        35 => "unlinkat",
+        37 => "linkat",
+        38 => "renameat",
+        46 => "ftruncate",
+        48 => "faccessat",
+        49 => "chdir",
+        53 => "fchmodat",
+        56 => "openat",
+        57 => "close",
+        59 => "pipe2",
+        61 => "getdents64",
+        62 => "lseek",
+        63 => "read",
+        64 => "write",
+        65 => "readv",
+        66 => "writev",
+        67 => "pread64",
+        68 => "pwrite64",
+        72 => "pselect6",
+        73 => "ppoll",
+        78 => "readlinkat",
+        79 => "newfstatat",
+        80 => "fstat",
+        82 => "fsync",
+        83 => "fdatasync",
+        88 => "utimensat",
+        93 => "exit",
+        94 => "exit_group",
+        96 => "set_tid_address",
+        98 => "futex",
+        99 => "set_robust_list",
+        101 => "nanosleep",
+        113 => "clock_gettime",
+        116 => "syslog",
+        120 => "sched_setscheduler",
+        121 => "sched_getparam",
+        124 => "sched_yield",
+        129 => "kill",
+        131 => "tgkill",
+        134 => "rt_sigaction",
+        135 => "rt_sigprocmask",
+        139 => "rt_sigreturn",
+        142 => "reboot",
+        154 => "setpgid",
+        155 => "getpgid",
+        157 => "setsid",
+        160 => "uname",
+        166 => "umask",
+        167 => "prctl",
+        172 => "getpid",
+        173 => "getppid",
+        174 => "getuid",
+        175 => "geteuid",
+        176 => "getgid",
+        177 => "getegid",
+        178 => "gettid",
+        179 => "sysinfo",
+        214 => "brk",
+        215 => "munmap",
+        220 => "clone",
+        221 => "execve",
+        222 => "mmap",
+        226 => "mprotect",
+        233 => "madvise",
+        260 => "wait4",
+        261 => "prlimit64",
+        278 => "getrandom",
+        291 => "statx",
+        _ => return None,
+    };
+    Some(name)
+}